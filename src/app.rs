@@ -1,7 +1,534 @@
-use crate::render;
+use crate::render::{self, RunStep, RunExit, RunEvent, EventLoop, EventSender, screen::Screen, snapshot, draw::Layoutable, space::{AbsoluteSpace, Size2D}, drawing::{lobby::LobbyView, game::Game, map::{self, Map, MapTarget}, chat::ChatLine}};
+use crate::net::{self, client::{Client, MessageSink}, protocol::*, server::ServerConfig, save};
+use crate::logic::{lobby::{GameOptions, GameSetup}, session::*, game::{GameEvent, GameView, Phase}, board::Board, model::GameModel};
+use crate::keymap::{Command, Keymap};
+use crate::replay;
 
-// for now this just starts the render loop
-pub fn start(enable_logger: bool) -> Result<(), std::io::Error> {
-    // TODO: fill in map struct here
-    render::run(enable_logger, None)
-}
\ No newline at end of file
+use std::{io, net::{SocketAddr, ToSocketAddrs}, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use rand::{SeedableRng, rngs::StdRng};
+use tui::style::Color;
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/* how the app was asked to start */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    /* made up players building all over a random board, nothing to play */
+    Demo,
+    /* load is a saved game to continue instead of starting a new one, bind is the address other players reach the game on */
+    Host { bind: String, port: u16, name: String, server: ServerConfig, load: Option<String> },
+    /* spectators join without a seat, god view spectators see every hand */
+    Join { addr: String, name: String, token: Option<SessionToken>, spectate: bool, god_view: bool },
+    /* print the board the seed generates instead of playing, ansi keeps the colors */
+    Snapshot { seed: u64, ansi: bool },
+    /* write the board the seed generates to an svg or html file */
+    Export { seed: u64, path: String },
+    /* step through a recorded game, speed is how many times faster than an action a second it plays */
+    Replay { path: String, speed: f32 }
+}
+
+impl Mode {
+    /* 
+     * parse the command line arguments (without the program name) into a mode, returns an error message on bad input
+     * without a command the demo runs, nothing is opened to the network unless asked for
+     */
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Mode, String> {
+        let command = args.next().unwrap_or_else(|| String::from("demo"));
+
+        let mut positional = Vec::new();
+        let mut name = String::from("Player");
+        let mut server = ServerConfig::default();
+        let mut token = None;
+        let mut spectate = false;
+        let mut god_view = false;
+        let mut ansi = false;
+        let mut out = None;
+        let mut load = None;
+        let mut bind = String::from(net::LOCAL_ADDR);
+        let mut speed = 1.0;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--name" => name = args.next().ok_or("--name needs a value")?,
+                "--grace" => server.sessions.grace_period = Self::parse_seconds(&arg, args.next())?,
+                "--bot-after" => server.sessions.bot_takeover = Some(Self::parse_seconds(&arg, args.next())?),
+                "--god-delay" => server.god_view_delay = Some(Self::parse_seconds(&arg, args.next())?),
+                "--spectate" => spectate = true,
+                "--ansi" => ansi = true,
+                "--out" => out = Some(args.next().ok_or("--out needs a file")?),
+                "--bind" => bind = args.next().ok_or("--bind needs an address")?,
+                "--load" => load = Some(args.next().ok_or("--load needs a saved game")?),
+                "--record" => server.record = Some(PathBuf::from(args.next().ok_or("--record needs a file")?)),
+                "--speed" => {
+                    let value = args.next().ok_or("--speed needs a number")?;
+                    speed = match value.parse::<f32>() {
+                        Ok(speed) if (replay::MIN_SPEED..=replay::MAX_SPEED).contains(&speed) => speed,
+                        _ => return Err(format!("bad speed {:?}, it goes from {} to {}", value, replay::MIN_SPEED, replay::MAX_SPEED))
+                    };
+                },
+                "--god-view" => {
+                    spectate = true;
+                    god_view = true;
+                },
+                "--token" => {
+                    let value = args.next().ok_or("--token needs a value")?;
+                    token = Some(value.parse().map_err(|_| format!("bad token {:?}", value))?);
+                },
+                _ => positional.push(arg)
+            }
+        }
+
+        match command.as_str() {
+            "host" => Ok(Mode::Host {
+                bind,
+                port: match positional.first() {
+                    Some(port) => port.parse().map_err(|_| format!("bad port {:?}", port))?,
+                    None => net::DEFAULT_PORT
+                },
+                name,
+                server,
+                load
+            }),
+            "join" if spectate && token.is_some() => Err(String::from("spectators don't have a seat to take back")),
+            "join" => Ok(Mode::Join {
+                addr: positional.first().cloned().ok_or("join needs an address to connect to")?,
+                name,
+                token,
+                spectate,
+                god_view
+            }),
+            "snapshot" => Ok(Mode::Snapshot { seed: Self::parse_seed(positional.first())?, ansi }),
+            "export" => Ok(Mode::Export { seed: Self::parse_seed(positional.first())?, path: out.unwrap_or_else(|| String::from("board.svg")) }),
+            "replay" => Ok(Mode::Replay { path: positional.first().cloned().ok_or("replay needs a recorded game")?, speed }),
+            "demo" => Ok(Mode::Demo),
+            other => Err(format!("unknown command {:?}, expected host, join, snapshot, export, replay or demo", other))
+        }
+    }
+
+    /* a map seed, any seed when none is given */
+    fn parse_seed(seed: Option<&String>) -> Result<u64, String> {
+        match seed {
+            Some(seed) => seed.parse().map_err(|_| format!("bad seed {:?}", seed)),
+            None => Ok(rand::random())
+        }
+    }
+
+    fn parse_seconds(flag: &str, value: Option<String>) -> Result<Duration, String> {
+        let value = value.ok_or_else(|| format!("{} needs a number of seconds", flag))?;
+        value.parse().map(Duration::from_secs).map_err(|_| format!("bad number of seconds {:?} for {}", value, flag))
+    }
+}
+
+/* what wakes the app up besides input */
+#[derive(Debug)]
+enum AppEvent {
+    /* a message from the server or None once the connection closed, connections are numbered so ones that were replaced can be ignored */
+    Server(u32, Option<ServerMessage>)
+}
+
+/* hands what the numbered connection receives to the event loop */
+fn forward_to(sender: &EventSender<AppEvent>, connection: u32) -> impl MessageSink {
+    let sender = sender.clone();
+    move |message| sender.send(AppEvent::Server(connection, message))
+}
+
+pub fn start(enable_logger: bool, fps: u32, mode: Mode, keymap: Keymap) -> Result<(), io::Error> {
+    let events = EventLoop::new(fps);
+    let sink = forward_to(&events.get_sender(), 0);
+    match mode {
+        Mode::Demo => render::run_demo(enable_logger, fps, keymap),
+        Mode::Host { bind, port, name, server, load } => {
+            let saved = load.map(|path| save::load_game(Path::new(&path))).transpose()?;
+            let addr = net::server::spawn((bind.as_str(), port), GameOptions::new(rand::random()), server, saved)?;
+            log::info!("hosting on {}", addr);
+
+            /* listening on every address means the host can still reach it locally */
+            let local = if addr.ip().is_unspecified() { SocketAddr::new(net::LOCAL_ADDR.parse().unwrap(), addr.port()) } else { addr };
+            play_online(enable_logger, &keymap, events, Client::connect(local, &name, sink)?, None)
+        },
+        Mode::Join { addr, name, token, spectate, god_view } => {
+            let addr = if addr.contains(':') { addr } else { format!("{}:{}", addr, net::DEFAULT_PORT) };
+            if spectate {
+                let client = Client::watch(addr, &name, god_view, RECONNECT_TIMEOUT, sink)?;
+                return play_online(enable_logger, &keymap, events, client, Some(Rejoin::Watch { name, god_view }))
+            }
+
+            let client = match token {
+                Some(token) => {
+                    let addr = addr
+                        .to_socket_addrs()?
+                        .next()
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("could not resolve {}", addr)))?;
+                    Client::resume(&addr, token, RECONNECT_TIMEOUT, sink)?
+                },
+                None => Client::connect(addr, &name, sink)?
+            };
+            play_online(enable_logger, &keymap, events, client, None)
+        },
+        Mode::Snapshot { seed, ansi } => {
+            print!("{}", snapshot_board(seed, ansi));
+            Ok(())
+        },
+        Mode::Export { seed, path } => {
+            map::export_board(&Map::generate(&mut StdRng::seed_from_u64(seed)), Path::new(&path))?;
+            eprintln!("wrote the board of seed {} to {}", seed, path);
+            Ok(())
+        },
+        Mode::Replay { path, speed } => replay::run(enable_logger, fps, &keymap, Path::new(&path), speed)
+    }
+}
+
+/* draw the board the seed generates off screen, laid out like it is once the reveal is over */
+fn snapshot_board(seed: u64, ansi: bool) -> String {
+    let mut screen = Screen::new(Map::generate(&mut StdRng::seed_from_u64(seed)));
+    screen.root.settle(&mut screen.service.animation);
+
+    let size = screen.root.to_absolute_layout_space(AbsoluteSpace::default()).size;
+    let buf = screen.render_to_buffer(Size2D::new(size.x, size.y), &mut ());
+    if ansi { snapshot::to_ansi(&buf) } else { snapshot::to_text(&buf) }
+}
+
+/* sit in the lobby until the server starts the game and then play it */
+fn play_online(enable_logger: bool, keymap: &Keymap, events: EventLoop<AppEvent>, client: Client, rejoin: Option<Rejoin>) -> Result<(), io::Error> {
+    let server_addr = client.get_server_addr()?;
+    let mut terminal = render::setup_terminal()?;
+    let result = run_online(&mut terminal, enable_logger, keymap, events, client, rejoin);
+    render::restore_terminal(&mut terminal)?;
+
+    /* the seat is held for a while so tell the player how to get back into the game they left */
+    if let Ok(Some(token)) = result {
+        eprintln!("left the game, to take your seat back run: catarminal join {} --token {}", server_addr, token);
+    }
+
+    result.map(|_| ())
+}
+
+/* how to get back into the game after the connection drops */
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rejoin {
+    Resume(SessionToken),
+    Watch { name: String, god_view: bool }
+}
+
+/* how the game client is connected to the server */
+#[derive(Debug)]
+enum Connection {
+    Connected(Client),
+    /* reconnected and waiting for the server to send the game back */
+    Resuming(Client),
+    Lost { retry_at: Instant },
+    GaveUp
+}
+
+/* runs the lobby and then the game, returns the session token if the player quit while the game was still going */
+fn run_online(terminal: &mut render::AppTerminal, enable_logger: bool, keymap: &Keymap, mut events: EventLoop<AppEvent>, mut client: Client, mut rejoin: Option<Rejoin>) -> Result<Option<SessionToken>, io::Error> {
+    let mut lobby_screen = Screen::new(LobbyView::new());
+    let mut snapshot: Option<GameSnapshot> = None;
+    let mut error: Option<io::Error> = None;
+
+    let exit = render::run_screen(terminal, enable_logger, &mut events, &mut lobby_screen, |screen, event| {
+        if matches!(event, RunEvent::Key(key) if keymap.is_bound_to(key, Command::Quit)) {
+            return RunStep::Quit
+        }
+
+        for message in screen.root.take_outbox() {
+            if let Err(err) = client.send(&message) {
+                error = Some(err);
+                return RunStep::Finish
+            }
+        }
+
+        let message = match event {
+            RunEvent::Message(AppEvent::Server(_, Some(message))) => message,
+            RunEvent::Message(AppEvent::Server(_, None)) => {
+                /* a client turned away before it got in only has the reason it was rejected to show for it */
+                let err = io::Error::new(io::ErrorKind::ConnectionAborted, "lost connection to the server");
+                error = Some(match (screen.root.client, &rejoin) {
+                    (Some(_), _) | (_, Some(Rejoin::Watch { .. })) => err,
+                    _ => io::Error::new(io::ErrorKind::ConnectionRefused, screen.root.status.clone())
+                });
+                return RunStep::Finish
+            },
+            _ => return RunStep::Continue
+        };
+
+        match message {
+            ServerMessage::Welcome(id, session_token) => {
+                screen.root.client = Some(id);
+                screen.root.status = String::from("joined the lobby");
+                rejoin = Some(Rejoin::Resume(session_token));
+            },
+            ServerMessage::Watching(delay) => {
+                screen.root.status = match delay {
+                    Some(delay) => format!("watching with god view, the game shows up {} seconds behind", delay),
+                    None => String::from("watching")
+                };
+            },
+            ServerMessage::Lobby(lobby) => screen.root.lobby = lobby,
+            ServerMessage::Observers(names) => screen.root.observers = names,
+            ServerMessage::Rejected(reason) => screen.root.status = reason,
+            ServerMessage::Kicked => {
+                error = Some(io::Error::new(io::ErrorKind::ConnectionAborted, "kicked from the lobby"));
+                return RunStep::Finish
+            },
+            ServerMessage::Snapshot(game_snapshot) => {
+                snapshot = Some(game_snapshot);
+                return RunStep::Finish
+            },
+            ServerMessage::Update(_, _, _) | ServerMessage::Presence(_, _) | ServerMessage::Chat(_, _) => ()
+        }
+        RunStep::Rerender
+    })?;
+
+    if let Some(err) = error {
+        return Err(err)
+    }
+
+    let (snapshot, rejoin) = match (exit, snapshot, rejoin) {
+        (RunExit::Finished, Some(snapshot), Some(rejoin)) => (snapshot, rejoin),
+        _ => return Ok(None)
+    };
+
+    let server_addr = client.get_server_addr()?;
+    let mut game_screen = Screen::new(create_game(&snapshot.setup));
+    game_screen.root.set_keymap(keymap.clone());
+    game_screen.root.observers = lobby_screen.root.observers.clone();
+    apply_snapshot(&mut game_screen, &snapshot);
+    /* only a new game drops its board in, one that was loaded or is being come back to shows up as it stands */
+    if is_new_game(&snapshot.view) {
+        game_screen.root.map_dragger.drawing.reveal(&mut game_screen.service.animation);
+    } else {
+        game_screen.root.sync(&mut game_screen.service.animation);
+        game_screen.root.map_dragger.drawing.settle(&mut game_screen.service.animation);
+    }
+    if snapshot.seat.is_none() {
+        game_screen.root.chat.push(ChatLine::system(String::from("you are watching, only other observers can read your messages")));
+    }
+
+    let sender = events.get_sender();
+    let mut connection = Connection::Connected(client);
+    let mut connection_number = 0;
+    let mut next_clock_tick = None;
+    arm_clock_tick(&game_screen, &sender, &mut next_clock_tick);
+    let exit = render::run_screen(terminal, enable_logger, &mut events, &mut game_screen, |screen, event| {
+        if matches!(event, RunEvent::Key(key) if keymap.is_bound_to(key, Command::Quit)) {
+            return RunStep::Quit
+        }
+
+        let mut step = send_outbox(&mut connection, screen.root.take_outbox(), screen);
+        /* the running clock ticked off a second so the countdown has to be drawn again */
+        if matches!(event, RunEvent::Tick) && next_clock_tick.is_some_and(|at| Instant::now() >= at) {
+            next_clock_tick = None;
+            step = RunStep::Rerender;
+        }
+
+        let next = match event {
+            RunEvent::Message(AppEvent::Server(number, message)) if number == connection_number => {
+                let (next, received_step) = receive(std::mem::replace(&mut connection, Connection::GaveUp), message, screen);
+                step = received_step;
+                next
+            },
+            /* the reconnect timer went off, the new connection gets a new number so the old one can't be heard from anymore */
+            RunEvent::Tick if matches!(connection, Connection::Lost { retry_at } if Instant::now() >= retry_at) => {
+                connection_number += 1;
+                step = RunStep::Rerender;
+                reconnect(&server_addr, &rejoin, forward_to(&sender, connection_number), screen)
+            },
+            _ => {
+                arm_clock_tick(screen, &sender, &mut next_clock_tick);
+                return step
+            }
+        };
+
+        if let Connection::Lost { retry_at } = next {
+            sender.set_timer(retry_at);
+        }
+        connection = next;
+        arm_clock_tick(screen, &sender, &mut next_clock_tick);
+        step
+    })?;
+
+    /* the seat is only worth coming back to if the server still holds it */
+    Ok(match (exit, connection, rejoin) {
+        (RunExit::Quit, Connection::GaveUp, _) => None,
+        (RunExit::Quit, _, Rejoin::Resume(token)) => Some(token),
+        _ => None
+    })
+}
+
+/* keep a timer set for when the running clock ticks off its next second */
+fn arm_clock_tick(screen: &Screen<Game>, sender: &EventSender<AppEvent>, next_tick: &mut Option<Instant>) {
+    if next_tick.is_none() {
+        *next_tick = screen.root.model.get_next_clock_tick(Instant::now());
+        if let Some(at) = *next_tick {
+            sender.set_timer(at);
+        }
+    }
+}
+
+fn create_game(setup: &GameSetup) -> Game {
+    /* every client generates the same board from the seed the host picked */
+    let board = Board::generate(&mut StdRng::seed_from_u64(setup.options.map_seed));
+    Game::new(
+        GameModel::new(board, setup.players.len()),
+        setup.players
+            .iter()
+            .map(|seat| (seat.name.clone(), seat.color.get_color()))
+            .collect()
+    )
+}
+
+fn is_new_game(view: &GameView) -> bool {
+    view.phase == Phase::Setup { step: 0, settlement: None }
+}
+
+/* catch the game up on everything in the snapshot */
+fn apply_snapshot(screen: &mut Screen<Game>, snapshot: &GameSnapshot) {
+    let model = &mut screen.root.model;
+    model.seat = snapshot.seat;
+    for (player, &presence) in snapshot.presence.iter().enumerate() {
+        model.set_presence(player, presence);
+    }
+    model.apply_view(&snapshot.view);
+    model.apply_clocks(snapshot.clocks.as_ref(), Instant::now());
+}
+
+/* the model has what the events did, only the tiles that produced on a roll are left to point out */
+pub fn apply_events(screen: &mut Screen<Game>, events: &[GameEvent]) {
+    for event in events {
+        if let GameEvent::Rolled { dice: (a, b), .. } = *event {
+            let tiles: Vec<MapTarget> = screen.root.model.get_producing_tiles(a + b).into_iter().map(MapTarget::Tile).collect();
+            if !tiles.is_empty() {
+                screen.root.map_dragger.drawing.highlight_all(tiles.clone(), &mut screen.service.animation);
+                screen.root.show_on_map(&tiles, &mut screen.service.animation);
+            }
+        }
+    }
+}
+
+/* send what the player did, anything typed while the connection is down is dropped */
+fn send_outbox(connection: &mut Connection, outbox: Vec<ClientMessage>, screen: &mut Screen<Game>) -> RunStep {
+    match connection {
+        Connection::Connected(client) => {
+            for message in outbox {
+                if let Err(err) = client.send(&message) {
+                    log::warn!("failed to send {:?}: {}", message, err);
+                }
+            }
+            RunStep::Continue
+        },
+        _ if !outbox.is_empty() => {
+            screen.root.chat.push(ChatLine::system(String::from("not connected to the server, nothing was sent")));
+            RunStep::Rerender
+        },
+        _ => RunStep::Continue
+    }
+}
+
+/* handle what the server sent, None means the connection broke and getting back into the game should be tried right away */
+fn receive(connection: Connection, message: Option<ServerMessage>, screen: &mut Screen<Game>) -> (Connection, RunStep) {
+    let (client, mut is_resuming) = match connection {
+        Connection::Connected(client) => (client, false),
+        Connection::Resuming(client) => (client, true),
+        connection => return (connection, RunStep::Continue)
+    };
+
+    let message = match message {
+        Some(message) => message,
+        None => {
+            log::warn!("lost connection to the server");
+            screen.root.banner = Some(String::from("connection lost, reconnecting..."));
+            return (Connection::Lost { retry_at: Instant::now() }, RunStep::Rerender)
+        }
+    };
+
+    match message {
+        ServerMessage::Snapshot(snapshot) => {
+            apply_snapshot(screen, &snapshot);
+            screen.root.banner = None;
+            is_resuming = false;
+        },
+        ServerMessage::Update(events, view, clocks) => {
+            screen.root.model.apply_update(&events, &view);
+            screen.root.model.apply_clocks(clocks.as_ref(), Instant::now());
+            apply_events(screen, &events);
+            for event in events.iter() {
+                screen.root.push_event(event);
+            }
+        },
+        ServerMessage::Presence(seat, presence) => screen.root.model.set_presence(seat, presence),
+        ServerMessage::Observers(names) => screen.root.set_observers(names),
+        ServerMessage::Chat(sender, text) => {
+            let line = match sender {
+                ChatSender::Player(seat) if seat < screen.root.model.players.len() => Some(ChatLine::from(
+                    screen.root.get_player_name(seat).to_owned(),
+                    screen.root.get_player_color(seat),
+                    text
+                )),
+                ChatSender::Player(_) => None,
+                ChatSender::Observer(name) => Some(ChatLine::from(format!("{} (observer)", name), Color::DarkGray, text)),
+                ChatSender::System => Some(ChatLine::system(text))
+            };
+            if let Some(line) = line {
+                screen.root.chat.push(line);
+            }
+        },
+        ServerMessage::Rejected(reason) if is_resuming => {
+            screen.root.banner = Some(format!("could not get back into the game: {}", reason));
+            return (Connection::GaveUp, RunStep::Rerender)
+        },
+        ServerMessage::Rejected(reason) => {
+            screen.root.chat.push(ChatLine::system(reason));
+            screen.root.reprompt();
+        },
+        _ => ()
+    }
+
+    (if is_resuming { Connection::Resuming(client) } else { Connection::Connected(client) }, RunStep::Rerender)
+}
+
+/* try to get back into the game, waiting a while to try again if the server can't be reached */
+fn reconnect(server_addr: &SocketAddr, rejoin: &Rejoin, sink: impl MessageSink, screen: &mut Screen<Game>) -> Connection {
+    let client = match rejoin {
+        Rejoin::Resume(token) => Client::resume(server_addr, *token, RECONNECT_TIMEOUT, sink),
+        Rejoin::Watch { name, god_view } => Client::watch(server_addr, name, *god_view, RECONNECT_TIMEOUT, sink)
+    };
+
+    match client {
+        Ok(client) => {
+            screen.root.banner = Some(String::from("reconnecting..."));
+            Connection::Resuming(client)
+        },
+        Err(err) => {
+            log::warn!("failed to reconnect: {}", err);
+            screen.root.banner = Some(format!("connection lost, retrying every {} seconds...", RECONNECT_INTERVAL.as_secs()));
+            Connection::Lost { retry_at: Instant::now() + RECONNECT_INTERVAL }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Mode, String> {
+        Mode::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    /* nothing listens on the network unless hosting was asked for */
+    #[test]
+    fn hosting_is_asked_for() {
+        assert_eq!(parse(&[]), Ok(Mode::Demo));
+
+        match parse(&["host"]) {
+            Ok(Mode::Host { bind, port, .. }) => assert_eq!((bind.as_str(), port), (net::LOCAL_ADDR, net::DEFAULT_PORT)),
+            other => panic!("expected a hosted game, got {:?}", other)
+        }
+        match parse(&["host", "4000", "--bind", "0.0.0.0"]) {
+            Ok(Mode::Host { bind, port, .. }) => assert_eq!((bind.as_str(), port), ("0.0.0.0", 4000)),
+            other => panic!("expected a hosted game, got {:?}", other)
+        }
+        assert!(parse(&["host", "--bind"]).is_err());
+    }
+}
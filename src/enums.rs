@@ -24,6 +24,11 @@ const LUMBER_RESOURCE_SYMBOL: &'static str = "🌲";
 const DESERT_RESOURCE_SYMBOL: &'static str = "🌵";
 const ANY_RESOURCE_SYMBOL: &'static str = "??";
 
+const PLAYER_RED_COLOR: Color = Color::Red;
+const PLAYER_GREEN_COLOR: Color = Color::Green;
+const PLAYER_BLUE_COLOR: Color = Color::Blue;
+const PLAYER_YELLOW_COLOR: Color = Color::Yellow;
+
 const PORT_SPECIFIC_RESOURCE_TRADING_RATIO: (u32, u32) = (2, 1);
 const PORT_ANY_RESOURCE_TRADING_RATIO: (u32, u32) = (3, 1);
 
@@ -40,7 +45,27 @@ pub enum Resource {
 }
 
 impl Resource {
-    const NUM_TYPES: usize = 5;
+    pub const NUM_TYPES: usize = 5;
+    pub const ALL: [Resource; Self::NUM_TYPES] = [Self::Ore, Self::Wool, Self::Wheat, Self::Brick, Self::Lumber];
+
+    /* where the resource goes in arrays indexed by resource (like hands) */
+    pub fn get_index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Ore => "ore",
+            Self::Wool => "wool",
+            Self::Wheat => "wheat",
+            Self::Brick => "brick",
+            Self::Lumber => "lumber"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Resource> {
+        Self::ALL.iter().copied().find(|resource| resource.get_name() == name)
+    }
 
     pub fn get_color(&self) -> Color {
         match self {
@@ -108,7 +133,7 @@ impl Distribution<PortResource> for PortResource {
         if rng.gen_range(0..Resource::NUM_TYPES as usize + 1) == 0 {
             Self::OfAnyKind
         } else {
-            Self::Of(rand::random::<Resource>())
+            Self::Of(rng.gen::<Resource>())
         }
     }
 }
@@ -143,7 +168,7 @@ impl Distribution<TileResource> for Standard {
         if rng.gen_range(0..Resource::NUM_TYPES as usize + 1) == 0 {
             TileResource::OfDesert
         } else {
-            TileResource::Of(rand::random::<Resource>())
+            TileResource::Of(rng.gen::<Resource>())
         }
     }
 }
@@ -163,4 +188,74 @@ impl Distribution<Building> for Building {
             _ => Building::City
         }
     }
-}
\ No newline at end of file
+}
+
+/* cards bought from the development deck */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DevelopmentCard {
+    Knight,
+    RoadBuilding,
+    YearOfPlenty,
+    Monopoly,
+    VictoryPoint
+}
+
+impl DevelopmentCard {
+    pub const ALL: [DevelopmentCard; 5] = [Self::Knight, Self::RoadBuilding, Self::YearOfPlenty, Self::Monopoly, Self::VictoryPoint];
+
+    /* how many of each card the development deck starts with */
+    pub fn get_deck_count(&self) -> usize {
+        match self {
+            Self::Knight => 14,
+            Self::RoadBuilding => 2,
+            Self::YearOfPlenty => 2,
+            Self::Monopoly => 2,
+            Self::VictoryPoint => 5
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Knight => "knight",
+            Self::RoadBuilding => "road building",
+            Self::YearOfPlenty => "year of plenty",
+            Self::Monopoly => "monopoly",
+            Self::VictoryPoint => "victory point"
+        }
+    }
+}
+
+/* the colors a player can pick for themselves in the lobby, this is what the PlayerFrame draws their name with */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayerColor {
+    Red,
+    Green,
+    Blue,
+    Yellow
+}
+
+impl PlayerColor {
+    pub const ALL: [PlayerColor; 4] = [Self::Red, Self::Green, Self::Blue, Self::Yellow];
+
+    pub fn get_color(&self) -> Color {
+        match self {
+            Self::Red => PLAYER_RED_COLOR,
+            Self::Green => PLAYER_GREEN_COLOR,
+            Self::Blue => PLAYER_BLUE_COLOR,
+            Self::Yellow => PLAYER_YELLOW_COLOR
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Red => "red",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Yellow => "yellow"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PlayerColor> {
+        Self::ALL.iter().copied().find(|color| color.get_name() == name)
+    }
+}
@@ -0,0 +1,480 @@
+/*
+ * keymap.rs
+ * which key chords run which game commands
+ *
+ * the defaults can be changed with a keys file, one binding per line like "roll = f, ctrl+r"
+ * a line replaces every default chord of its command, "none" unbinds it and # starts a comment
+ * keys the panes handle themselves (like tab, the arrows and enter) can't be given to other commands, see RESERVED_CHORDS
+ * the file is looked for at $CATARMINAL_KEYS, then $XDG_CONFIG_HOME/catarminal/keys, then ~/.config/catarminal/keys
+ */
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use std::{fmt, fs, io, path::{Path, PathBuf}};
+
+/* things the player can do with a key press no matter what has focus */
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Command {
+    Cancel,
+    Quit,
+    Roll,
+    EndTurn,
+    BuildRoad,
+    BuildSettlement,
+    BuildCity,
+    BuyDevelopment,
+    PlayDevelopment,
+    Undo,
+    ExportBoard,
+    SaveGame,
+    OpenTrade,
+    OpenChat,
+    OpenCommandLine,
+    ToggleStats,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    /* only used while watching a replay */
+    StepForward,
+    StepBack,
+    NextTurn,
+    PreviousTurn,
+    GoToTurn,
+    TogglePlayback,
+    PlayFaster,
+    PlaySlower
+}
+
+impl Command {
+    pub const ALL: [Command; 30] = [
+        Command::Cancel,
+        Command::Quit,
+        Command::Roll,
+        Command::EndTurn,
+        Command::BuildRoad,
+        Command::BuildSettlement,
+        Command::BuildCity,
+        Command::BuyDevelopment,
+        Command::PlayDevelopment,
+        Command::Undo,
+        Command::ExportBoard,
+        Command::SaveGame,
+        Command::OpenTrade,
+        Command::OpenChat,
+        Command::OpenCommandLine,
+        Command::ToggleStats,
+        Command::PanUp,
+        Command::PanDown,
+        Command::PanLeft,
+        Command::PanRight,
+        Command::ZoomIn,
+        Command::ZoomOut,
+        Command::StepForward,
+        Command::StepBack,
+        Command::NextTurn,
+        Command::PreviousTurn,
+        Command::GoToTurn,
+        Command::TogglePlayback,
+        Command::PlayFaster,
+        Command::PlaySlower
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Command::Cancel => "cancel",
+            Command::Quit => "quit",
+            Command::Roll => "roll",
+            Command::EndTurn => "end-turn",
+            Command::BuildRoad => "build-road",
+            Command::BuildSettlement => "build-settlement",
+            Command::BuildCity => "build-city",
+            Command::BuyDevelopment => "buy-development",
+            Command::PlayDevelopment => "play-development",
+            Command::Undo => "undo",
+            Command::ExportBoard => "export-board",
+            Command::SaveGame => "save-game",
+            Command::OpenTrade => "open-trade",
+            Command::OpenChat => "open-chat",
+            Command::OpenCommandLine => "open-command-line",
+            Command::ToggleStats => "toggle-stats",
+            Command::PanUp => "pan-up",
+            Command::PanDown => "pan-down",
+            Command::PanLeft => "pan-left",
+            Command::PanRight => "pan-right",
+            Command::ZoomIn => "zoom-in",
+            Command::ZoomOut => "zoom-out",
+            Command::StepForward => "step-forward",
+            Command::StepBack => "step-back",
+            Command::NextTurn => "next-turn",
+            Command::PreviousTurn => "previous-turn",
+            Command::GoToTurn => "go-to-turn",
+            Command::TogglePlayback => "toggle-playback",
+            Command::PlayFaster => "play-faster",
+            Command::PlaySlower => "play-slower"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::ALL.iter().copied().find(|command| command.get_name() == name)
+    }
+
+    fn get_default_chords(&self) -> &'static [&'static str] {
+        match self {
+            Command::Cancel => &["esc"],
+            Command::Quit => &["esc"],
+            Command::Roll => &["d"],
+            Command::EndTurn => &["e"],
+            Command::BuildRoad => &["r"],
+            Command::BuildSettlement => &["s"],
+            Command::BuildCity => &["c"],
+            Command::BuyDevelopment => &["b"],
+            Command::PlayDevelopment => &["p"],
+            Command::Undo => &["u", "ctrl+z"],
+            Command::ExportBoard => &["x"],
+            Command::SaveGame => &["ctrl+s"],
+            Command::OpenTrade => &["t"],
+            Command::OpenChat => &["enter"],
+            Command::OpenCommandLine => &[":"],
+            Command::ToggleStats => &["i"],
+            Command::PanUp => &["shift+up"],
+            Command::PanDown => &["shift+down"],
+            Command::PanLeft => &["shift+left"],
+            Command::PanRight => &["shift+right"],
+            Command::ZoomIn => &["+", "="],
+            Command::ZoomOut => &["-"],
+            Command::StepForward => &["."],
+            Command::StepBack => &[","],
+            Command::NextTurn => &["]"],
+            Command::PreviousTurn => &["["],
+            Command::GoToTurn => &["g"],
+            Command::TogglePlayback => &["space"],
+            Command::PlayFaster => &[">"],
+            Command::PlaySlower => &["<"]
+        }
+    }
+}
+
+/* commands that are allowed to share a chord, cancel goes first and quit only happens when there is nothing to cancel */
+const SHARED_CHORDS: [(Command, Command); 1] = [(Command::Cancel, Command::Quit)];
+
+/* 
+ * keys the panes handle themselves and what for, the keymap only gets them when the focused pane doesn't want them
+ * so a keys file can only bind them to commands they run by default, anything else would only work some of the time
+ */
+const RESERVED_CHORDS: [(&str, &str); 21] = [
+    ("tab", "moving focus"),
+    ("shift+tab", "moving focus"),
+    ("esc", "leaving the focused pane"),
+    ("up", "moving focus and the map cursor"),
+    ("down", "moving focus and the map cursor"),
+    ("left", "moving focus and the map cursor"),
+    ("right", "moving focus and the map cursor"),
+    ("enter", "picking what the focused pane has selected"),
+    ("space", "cycling what the map cursor is on"),
+    ("pageup", "scrolling the chat and events"),
+    ("pagedown", "scrolling the chat and events"),
+    ("0", "typing a turn to go to in replays"),
+    ("1", "typing a turn to go to in replays"),
+    ("2", "typing a turn to go to in replays"),
+    ("3", "typing a turn to go to in replays"),
+    ("4", "typing a turn to go to in replays"),
+    ("5", "typing a turn to go to in replays"),
+    ("6", "typing a turn to go to in replays"),
+    ("7", "typing a turn to go to in replays"),
+    ("8", "typing a turn to go to in replays"),
+    ("9", "typing a turn to go to in replays")
+];
+
+/* a key with the modifiers held down with it */
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers
+}
+
+impl KeyChord {
+    /* parse chords like "r", "ctrl+r", "shift+up" or "f5" */
+    pub fn parse(text: &str) -> Result<KeyChord, KeymapError> {
+        let text = text.trim().to_lowercase();
+        let mut parts: Vec<&str> = text.split('+').collect();
+        /* "+" and "ctrl++" bind the plus key */
+        if text.ends_with("++") || text == "+" {
+            parts.pop();
+            parts.pop();
+            parts.push("+");
+        }
+
+        let key = parts.pop().filter(|key| !key.is_empty()).ok_or_else(|| KeymapError(format!("no key in chord {:?}", text)))?;
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in parts {
+            modifiers |= match modifier {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => return Err(KeymapError(format!("unknown modifier {:?} in chord {:?}", other, text)))
+            };
+        }
+
+        let code = match key {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap()),
+            _ if key.starts_with('f') => match key[1..].parse() {
+                Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(KeymapError(format!("unknown key {:?} in chord {:?}", key, text)))
+            },
+            _ => return Err(KeymapError(format!("unknown key {:?} in chord {:?}", key, text)))
+        };
+
+        Ok(KeyChord::from_key(KeyEvent::new(code, modifiers)))
+    }
+
+    /*
+     * terminals disagree on whether shifted letters come with shift held, the uppercase letter or both
+     * so letters are kept lowercase with shift meaning uppercase, and other characters already say if they were shifted
+     */
+    fn from_key(key: KeyEvent) -> KeyChord {
+        match key.code {
+            KeyCode::Char(c) if c.is_alphabetic() => KeyChord {
+                code: KeyCode::Char(c.to_lowercase().next().unwrap_or(c)),
+                modifiers: if c.is_uppercase() { key.modifiers | KeyModifiers::SHIFT } else { key.modifiers }
+            },
+            KeyCode::Char(c) => KeyChord { code: KeyCode::Char(c), modifiers: key.modifiers - KeyModifiers::SHIFT },
+            KeyCode::BackTab => KeyChord { code: KeyCode::Tab, modifiers: key.modifiers | KeyModifiers::SHIFT },
+            code => KeyChord { code, modifiers: key.modifiers }
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [(KeyModifiers::CONTROL, "ctrl+"), (KeyModifiers::ALT, "alt+"), (KeyModifiers::SHIFT, "shift+")] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+
+        match self.code {
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Insert => f.write_str("insert"),
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            code => write!(f, "{:?}", code)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeymapError(pub String);
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key bindings: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyChord, Command)>
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::parse("").unwrap()
+    }
+}
+
+impl Keymap {
+    /* the defaults with the bindings in the text laid over them */
+    pub fn parse(text: &str) -> Result<Keymap, KeymapError> {
+        let mut chords: Vec<(Command, Vec<KeyChord>)> = Command::ALL
+            .iter()
+            .map(|&command| (command, command.get_default_chords().iter().map(|chord| KeyChord::parse(chord).unwrap()).collect()))
+            .collect();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue
+            }
+
+            let at_line = |err: KeymapError| KeymapError(format!("line {}: {}", i + 1, err.0));
+            let (name, value) = line.split_once('=').ok_or_else(|| at_line(KeymapError(format!("expected \"command = chord\", got {:?}", line))))?;
+            let command = Command::from_name(name.trim()).ok_or_else(|| at_line(KeymapError(format!("unknown command {:?}", name.trim()))))?;
+            let value = value.trim();
+            let bound: Vec<KeyChord> = if value == "none" {
+                Vec::new()
+            } else {
+                value.split(',').map(KeyChord::parse).collect::<Result<_, _>>().map_err(at_line)?
+            };
+
+            for chord in bound.iter() {
+                let is_default = command.get_default_chords().iter().any(|default| KeyChord::parse(default).unwrap() == *chord);
+                if let Some((_, taken_by)) = RESERVED_CHORDS.iter().find(|(reserved, _)| KeyChord::parse(reserved).unwrap() == *chord).filter(|_| !is_default) {
+                    return Err(at_line(KeymapError(format!("{} is taken by {}", chord, taken_by))))
+                }
+            }
+            chords.iter_mut().find(|(other, _)| *other == command).unwrap().1 = bound;
+        }
+
+        let bindings: Vec<(KeyChord, Command)> = chords
+            .into_iter()
+            .flat_map(|(command, chords)| chords.into_iter().map(move |chord| (chord, command)))
+            .collect();
+
+        /* one chord can only run one command, apart from the pairs that are meant to share */
+        for (i, &(chord, command)) in bindings.iter().enumerate() {
+            for &(other_chord, other_command) in bindings[..i].iter() {
+                let is_shared = SHARED_CHORDS.contains(&(other_command, command)) || SHARED_CHORDS.contains(&(command, other_command));
+                if chord == other_chord && command != other_command && !is_shared {
+                    return Err(KeymapError(format!("{} is bound to both {} and {}", chord, other_command.get_name(), command.get_name())))
+                }
+            }
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    /* read the keys file if there is one, the defaults are used without it */
+    pub fn load() -> Result<Keymap, KeymapError> {
+        let path = match Self::get_path() {
+            Some(path) => path,
+            None => return Ok(Keymap::default())
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Keymap::parse(&text).map_err(|err| KeymapError(format!("{}: {}", path.display(), err.0))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Keymap::default()),
+            Err(err) => Err(KeymapError(format!("could not read {}: {}", path.display(), err)))
+        }
+    }
+
+    fn get_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("CATARMINAL_KEYS") {
+            return Some(PathBuf::from(path))
+        }
+
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|config| config.join("catarminal").join("keys"))
+    }
+
+    /* every command the key press runs, in the order they should be tried */
+    pub fn get_commands(&self, key: KeyEvent) -> impl Iterator<Item = Command> + '_ {
+        let chord = KeyChord::from_key(key);
+        self.bindings
+            .iter()
+            .filter(move |&&(bound, _)| bound == chord)
+            .map(|&(_, command)| command)
+    }
+
+    pub fn is_bound_to(&self, key: KeyEvent, command: Command) -> bool {
+        self.get_commands(key).any(|bound| bound == command)
+    }
+
+    /* the first chord of the command for hints, None if it is unbound */
+    pub fn get_chord(&self, command: Command) -> Option<KeyChord> {
+        self.bindings
+            .iter()
+            .find(|&&(_, bound)| bound == command)
+            .map(|&(chord, _)| chord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn defaults_and_overrides() {
+        let keymap = Keymap::default();
+        assert!(keymap.is_bound_to(key(KeyCode::Char('d')), Command::Roll));
+        assert_eq!(keymap.get_commands(key(KeyCode::Esc)).collect::<Vec<_>>(), vec![Command::Cancel, Command::Quit]);
+
+        let keymap = Keymap::parse("roll = f, ctrl+r  # rolling\n\nend-turn = none").unwrap();
+        assert!(keymap.is_bound_to(key(KeyCode::Char('f')), Command::Roll));
+        assert!(keymap.is_bound_to(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL), Command::Roll));
+        assert!(!keymap.is_bound_to(key(KeyCode::Char('d')), Command::Roll));
+        assert_eq!(keymap.get_chord(Command::EndTurn), None);
+        assert!(!keymap.is_bound_to(KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT), Command::BuildRoad));
+    }
+
+    /* a chord can't run two commands, even when one of them is a default the file didn't touch */
+    #[test]
+    fn conflicting_keys_are_refused() {
+        assert!(Keymap::parse("roll = e").is_err());
+        assert!(Keymap::parse("roll = z\nend-turn = z").is_err());
+        assert!(Keymap::parse("quit = q\ncancel = q\nroll = q").is_err());
+        assert!(Keymap::parse("roll = e\nend-turn = none").is_ok());
+    }
+
+    /* cancel and quit are meant to share a chord, cancel is tried first */
+    #[test]
+    fn shared_chords_are_allowed() {
+        let keymap = Keymap::parse("quit = q\ncancel = q").unwrap();
+        assert_eq!(keymap.get_commands(key(KeyCode::Char('q'))).collect::<Vec<_>>(), vec![Command::Cancel, Command::Quit]);
+        assert!(Keymap::parse("cancel = ctrl+c, esc").is_ok());
+    }
+
+    #[test]
+    fn unknown_names_are_refused() {
+        for text in ["jump = j", "roll = hyper+r", "roll = f13", "roll = spacebar", "roll", "roll = ctrl+"] {
+            let err = Keymap::parse(text).unwrap_err();
+            assert!(err.0.starts_with("line 1: "), "{:?} gave {:?}", text, err);
+        }
+    }
+
+    /* keys the panes take for themselves only fall through to the commands they run by default */
+    #[test]
+    fn reserved_keys_are_refused() {
+        for text in ["roll = enter", "end-turn = space", "roll = up", "roll = shift+tab", "undo = pgup", "build-road = 1", "quit = esc\ncancel = q\nroll = x, tab"] {
+            let err = Keymap::parse(text).unwrap_err();
+            assert!(err.0.contains("is taken by"), "{:?} gave {:?}", text, err);
+        }
+
+        assert!(Keymap::parse("open-chat = ctrl+t, enter\ntoggle-playback = space").is_ok());
+        assert!(Keymap::parse("roll = shift+up\npan-up = ctrl+up").is_ok());
+    }
+
+    #[test]
+    fn every_command_is_listed_once() {
+        for (i, command) in Command::ALL.iter().enumerate() {
+            assert_eq!(Command::from_name(command.get_name()), Some(*command));
+            assert!(!Command::ALL[..i].contains(command));
+        }
+    }
+}
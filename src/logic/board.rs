@@ -0,0 +1,59 @@
+/*
+ * board.rs
+ * the tiles and ports a game is played on
+ * the board is generated from a seed so the server and every client end up with the same one without sending it around
+ */
+
+use crate::enums::{Resource, TileResource, PortResource};
+use crate::render::drawing::map::{MAP_TILE_CAPACITY, MAP_PORT_CAPACITY};
+
+use rand::{prelude::Distribution, distributions::Uniform, Rng};
+
+pub const DESERT_ROLL: u8 = 7;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BoardTile {
+    pub roll: u8,
+    pub resource: TileResource
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    pub tiles: Vec<BoardTile>,
+    pub ports: Vec<PortResource>
+}
+
+impl Board {
+    /* generate a random board, the same rng state always generates the same board */
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        let mut tiles: Vec<BoardTile> = Uniform::from(2..12) // from a uniform distribution from [2, 12)
+            .sample_iter(&mut *rng) // create an iterator that samples from it
+            .take(*MAP_TILE_CAPACITY - 1) // sample as many times equal to the map capacity for tiles (minus 1 for desert tile)
+            .collect::<Vec<u8>>()
+            .into_iter()
+            .map(|roll| BoardTile {
+                roll: if roll == DESERT_ROLL { 12 } else { roll }, // 7 becomes 12 because 7 is not on a tile its a robber round
+                resource: TileResource::Of(rng.gen::<Resource>())
+            })
+            .collect();
+
+        // insert desert tile at a random location in the tiles vector
+        let desert_tile_index = rng.gen_range(0..=tiles.len());
+        tiles.insert(desert_tile_index, BoardTile { roll: DESERT_ROLL, resource: TileResource::OfDesert });
+
+        let ports = PortResource::OfAnyKind // sample_iter takes a self type so I need to do PortResource::OfAnyKind instead of just PortResource
+            .sample_iter(&mut *rng) // create an iterator that takes samples of PortResource
+            .take(*MAP_PORT_CAPACITY) // sample as many times equal to the map capacity for ports
+            .collect();
+
+        Board { tiles, ports }
+    }
+
+    /* the robber starts on the desert */
+    pub fn get_desert(&self) -> usize {
+        self.tiles
+            .iter()
+            .position(|tile| tile.resource == TileResource::OfDesert)
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * bot.rs
+ * a simple bot for seats nobody is sitting in
+ * it picks from the legal actions with a few rules of thumb, it is not meant to be good just to keep games moving
+ */
+
+use super::{game::*, hand::*};
+
+use crate::enums::{Resource, Building, TileResource};
+use crate::render::drawing::map::MAP_GRAPH;
+
+use rand::{Rng, seq::SliceRandom};
+
+/* chance the bot keeps building roads toward new settlement spots instead of ending its turn */
+const ROAD_CHANCE: f64 = 0.5;
+
+/* pick the next action for the player, None if the game is not waiting on them */
+pub fn choose_action<R: Rng>(game: &GameState, player: PlayerId, rng: &mut R) -> Option<Action> {
+    if let Phase::Discard { amounts } = &game.phase {
+        return match amounts.get(player).copied().unwrap_or(0) {
+            0 => None,
+            amount => Some(Action::Discard(choose_discard(&game.players[player].hand, amount)))
+        }
+    }
+
+    let legal = game.get_legal_actions(player);
+    if legal.is_empty() {
+        return None
+    }
+
+    /* offers are taken when they don't cost the bot more cards than it gets */
+    if player != game.turn {
+        let offer = game.offer.as_ref()?;
+        let is_worth_it = offer.give.total() >= offer.get.total() && legal.contains(&Action::AcceptTrade);
+        return Some(if is_worth_it { Action::AcceptTrade } else { Action::DeclineTrade })
+    }
+
+    let has = |action: Action| legal.contains(&action);
+
+    /* settlements and cities are always worth it */
+    let best_plot = legal
+        .iter()
+        .filter_map(|action| match action {
+            Action::BuildSettlement(plot) | Action::BuildCity(plot) => Some((*action, get_plot_score(game, *plot))),
+            _ => None
+        })
+        .max_by_key(|&(action, score)| (matches!(action, Action::BuildCity(_)), score));
+    if let Some((action, _)) = best_plot {
+        return Some(action)
+    }
+
+    match game.phase {
+        Phase::Roll => {
+            if has(Action::PlayKnight) && is_robbed(game, player) {
+                Some(Action::PlayKnight)
+            } else {
+                Some(Action::Roll)
+            }
+        },
+        Phase::MoveRobber { .. } => legal
+            .iter()
+            .copied()
+            .max_by_key(|action| match action {
+                Action::MoveRobber(tile) => get_robber_score(game, player, *tile),
+                _ => i32::MIN
+            }),
+        Phase::Steal { .. } => legal
+            .iter()
+            .copied()
+            .max_by_key(|action| match action {
+                Action::Steal(victim) => game.players[*victim].hand.total() as i32,
+                _ => i32::MIN
+            }),
+        Phase::Setup { .. } | Phase::RoadBuilding { .. } => choose_road(game, &legal, rng),
+        Phase::Main => {
+            if has(Action::PlayKnight) && is_robbed(game, player) {
+                return Some(Action::PlayKnight)
+            }
+
+            if has(Action::PlayRoadBuilding) {
+                return Some(Action::PlayRoadBuilding)
+            }
+
+            /* year of plenty goes toward a city */
+            let hand = &game.players[player].hand;
+            let mut missing = CITY_COST;
+            for resource in Resource::ALL {
+                missing.remove(resource, missing.get(resource).min(hand.get(resource)));
+            }
+            let wanted: Vec<Resource> = missing.iter().flat_map(|(resource, count)| std::iter::repeat_n(resource, count as usize)).collect();
+            if let [a, b, ..] = wanted.as_slice() {
+                if has(Action::PlayYearOfPlenty(*a, *b)) {
+                    return Some(Action::PlayYearOfPlenty(*a, *b))
+                }
+            }
+
+            if has(Action::BuyDevelopment) && !hand.contains(&CITY_COST) {
+                return Some(Action::BuyDevelopment)
+            }
+
+            if game.players[player].settlements_left > 0 && rng.gen_bool(ROAD_CHANCE) {
+                if let Some(road) = choose_road(game, &legal, rng) {
+                    return Some(road)
+                }
+            }
+
+            Some(Action::EndTurn)
+        },
+        _ => legal.choose(rng).copied()
+    }
+}
+
+/* how much a plot is expected to produce */
+fn get_plot_score(game: &GameState, plot: usize) -> i32 {
+    MAP_GRAPH.plot_tiles[plot]
+        .iter()
+        .map(|&tile| get_tile_pips(game, tile))
+        .sum()
+}
+
+/* how likely a tile is to be rolled out of 36 */
+fn get_tile_pips(game: &GameState, tile: usize) -> i32 {
+    let tile = &game.board.tiles[tile];
+    match tile.resource {
+        TileResource::OfDesert => 0,
+        TileResource::Of(_) => 6 - (7 - tile.roll as i32).abs()
+    }
+}
+
+/* the robber is best on a tile that hurts others and not the bot */
+fn get_robber_score(game: &GameState, player: PlayerId, tile: usize) -> i32 {
+    let weight: i32 = MAP_GRAPH.tile_plots[tile]
+        .iter()
+        .filter_map(|&plot| game.buildings[plot])
+        .map(|(building, owner)| {
+            let count = if building == Building::City { 2 } else { 1 };
+            if owner == player { -3*count } else { count }
+        })
+        .sum();
+    weight*get_tile_pips(game, tile)
+}
+
+fn is_robbed(game: &GameState, player: PlayerId) -> bool {
+    MAP_GRAPH.tile_plots[game.robber]
+        .iter()
+        .any(|&plot| matches!(game.buildings[plot], Some((_, owner)) if owner == player))
+}
+
+/* roads that lead to a free plot come first */
+fn choose_road<R: Rng>(game: &GameState, legal: &[Action], rng: &mut R) -> Option<Action> {
+    let roads: Vec<(Action, bool)> = legal
+        .iter()
+        .filter_map(|&action| match action {
+            Action::BuildRoad(a, b) => Some((action, game.is_plot_free(a) || game.is_plot_free(b))),
+            _ => None
+        })
+        .collect();
+
+    let promising: Vec<Action> = roads.iter().filter(|(_, is_promising)| *is_promising).map(|(action, _)| *action).collect();
+    promising
+        .choose(rng)
+        .or_else(|| roads.choose(rng).map(|(action, _)| action))
+        .copied()
+}
+
+/* throw away whatever there is the most of */
+fn choose_discard(hand: &Hand, amount: u8) -> Hand {
+    let mut left = *hand;
+    let mut discard = Hand::default();
+    for _ in 0..amount {
+        let most = Resource::ALL.iter().copied().max_by_key(|&resource| left.get(resource)).unwrap();
+        left.remove(most, 1);
+        discard.add(most, 1);
+    }
+    discard
+}
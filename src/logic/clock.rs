@@ -0,0 +1,395 @@
+/*
+ * clock.rs
+ * time controls so nobody can hold a game up by walking away from it
+ *
+ * a turn clock gives every turn the same amount of time and a game clock gives every player a bank of time for the whole game,
+ * the bank only runs during the player's own turns and the increment is added to it after each one, either or both can be on
+ * the turn clock stops while anyone discards after a 7, every seat that has to discard gets a clock of its own for it instead
+ * that is as long as a turn and comes out of the seat's bank like a turn does, without the increment
+ *
+ * a turn runs out when the first of the two does, the server then plays the rest of it for the player:
+ * the dice are rolled, anything else the game waits on is picked at random and the turn is ended
+ * a seat that runs out of time discarding has its cards picked at random
+ */
+
+use super::{game::*, hand::Hand};
+
+use crate::enums::Resource;
+
+use rand::{Rng, seq::SliceRandom};
+
+use std::time::{Duration, Instant};
+
+/* the steps the lobby goes through, in seconds */
+pub const TURN_TIMES: [Option<u16>; 7] = [None, Some(30), Some(60), Some(90), Some(120), Some(180), Some(300)];
+pub const GAME_TIMES: [Option<u16>; 8] = [None, Some(300), Some(600), Some(900), Some(1200), Some(1800), Some(2700), Some(3600)];
+pub const INCREMENTS: [u16; 6] = [0, 5, 10, 15, 30, 60];
+
+/* the clocks the host picked for the game, all in seconds */
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub turn_time: Option<u16>,
+    pub game_time: Option<u16>,
+    /* added to the player's game clock after each of their turns */
+    pub increment: u16
+}
+
+impl TimeControl {
+    pub fn is_on(&self) -> bool {
+        self.turn_time.is_some() || self.game_time.is_some()
+    }
+
+    /* only the steps the lobby offers are allowed */
+    pub fn is_valid(&self) -> bool {
+        TURN_TIMES.contains(&self.turn_time) && GAME_TIMES.contains(&self.game_time) && INCREMENTS.contains(&self.increment)
+    }
+
+    /* the time control with one of its settings moved up or down a step */
+    pub fn step(&self, setting: TimeSetting, is_up: bool) -> TimeControl {
+        fn step<T: Copy + PartialEq>(steps: &[T], value: T, is_up: bool) -> T {
+            let i = steps.iter().position(|&step| step == value).unwrap_or(0);
+            steps[if is_up { (i + 1).min(steps.len() - 1) } else { i.saturating_sub(1) }]
+        }
+
+        let mut control = *self;
+        match setting {
+            TimeSetting::Turn => control.turn_time = step(&TURN_TIMES, self.turn_time, is_up),
+            TimeSetting::Game => control.game_time = step(&GAME_TIMES, self.game_time, is_up),
+            TimeSetting::Increment => control.increment = step(&INCREMENTS, self.increment, is_up)
+        }
+        control
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeSetting {
+    Turn,
+    Game,
+    Increment
+}
+
+/* like 1:30, hours are left as minutes */
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds/60, seconds % 60)
+}
+
+/* the clocks as clients see them, times are in milliseconds as of when this was sent */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockView {
+    /* whose clock is running, None once the game is over or while it is stopped for discards */
+    pub player: Option<PlayerId>,
+    /* how long the player had for the turn when it started and how much of it is left */
+    pub turn_length: u32,
+    pub turn_left: u32,
+    /* what was on every player's game clock when the turn started, None without a game clock */
+    pub banks: Option<Vec<u32>>
+}
+
+/* the server's clocks, they only exist when the time control is on */
+#[derive(Debug)]
+pub struct Clocks {
+    control: TimeControl,
+    banks: Vec<Duration>,
+    /* whose turn is being timed and when it started */
+    player: Option<PlayerId>,
+    turn_started: Instant,
+    /* the server has said the player ran out of time */
+    is_timed_out: bool,
+    /* when the turn clock stopped for discards and which seats are still discarding */
+    paused: Option<Instant>,
+    discarding: Vec<bool>
+}
+
+impl Clocks {
+    /* banks start full unless a loaded game says what was left on them */
+    pub fn new(control: TimeControl, player_count: usize, player: PlayerId, banks: Option<Vec<Duration>>, now: Instant) -> Self {
+        Clocks {
+            control,
+            banks: banks
+                .filter(|banks| banks.len() == player_count)
+                .unwrap_or_else(|| vec![Duration::from_secs(control.game_time.unwrap_or(0) as u64); player_count]),
+            player: Some(player),
+            turn_started: now,
+            is_timed_out: false,
+            paused: None,
+            discarding: vec![false; player_count]
+        }
+    }
+
+    pub fn start_turn(&mut self, player: PlayerId, now: Instant) {
+        self.stop(now);
+        self.player = Some(player);
+        self.turn_started = now;
+        self.is_timed_out = false;
+    }
+
+    /* the time the turn took comes out of the player's bank and the increment goes in */
+    pub fn stop(&mut self, now: Instant) {
+        if let (Some(player), Some(_)) = (self.player.take(), self.control.game_time) {
+            let bank = &mut self.banks[player];
+            *bank = bank.saturating_sub(now - self.turn_started) + Duration::from_secs(self.control.increment as u64);
+        }
+    }
+
+    /* 
+     * stop the turn clock when discards start and start it again once they are done, seats that are done discarding pay for it
+     * called after anything happens in the game
+     */
+    pub fn sync_discards(&mut self, phase: &Phase, now: Instant) {
+        let amounts = match phase {
+            Phase::Discard { amounts } => Some(amounts),
+            _ => None
+        };
+        if let (Some(amounts), None) = (amounts, self.paused) {
+            self.paused = Some(now);
+            self.discarding = amounts.iter().map(|&amount| amount > 0).collect();
+        }
+
+        let paused = match self.paused {
+            Some(paused) => paused,
+            None => return
+        };
+        for player in 0..self.discarding.len() {
+            if self.discarding[player] && amounts.is_none_or(|amounts| amounts.get(player).is_none_or(|&amount| amount == 0)) {
+                self.stop_discarding(player, paused, now);
+            }
+        }
+        if amounts.is_none() {
+            self.turn_started += now - paused;
+            self.paused = None;
+        }
+    }
+
+    fn stop_discarding(&mut self, player: PlayerId, paused: Instant, now: Instant) {
+        self.discarding[player] = false;
+        if self.control.game_time.is_some() {
+            self.banks[player] = self.banks[player].saturating_sub(now - paused);
+        }
+    }
+
+    /* the discarding seats whose own clock ran out, each is only given once */
+    pub fn take_discard_timeouts(&mut self, now: Instant) -> Vec<PlayerId> {
+        let paused = match self.paused {
+            Some(paused) => paused,
+            None => return Vec::new()
+        };
+        let timed_out: Vec<PlayerId> = (0..self.discarding.len())
+            .filter(|&player| self.discarding[player] && now - paused >= self.get_turn_length(player))
+            .collect();
+        for &player in timed_out.iter() {
+            self.stop_discarding(player, paused, now);
+        }
+        timed_out
+    }
+
+    /* how long the running player has for the whole turn, or a seat for discarding */
+    fn get_turn_length(&self, player: PlayerId) -> Duration {
+        let turn = self.control.turn_time.map_or(Duration::MAX, |time| Duration::from_secs(time as u64));
+        let bank = if self.control.game_time.is_some() { self.banks[player] } else { Duration::MAX };
+        turn.min(bank)
+    }
+
+    /* what is on every game clock right now with the running turn and discards taken off, for saves, None without a game clock */
+    pub fn get_banks(&self, now: Instant) -> Option<Vec<Duration>> {
+        self.control.game_time?;
+        let mut banks = self.banks.clone();
+        if let Some(player) = self.player {
+            banks[player] = banks[player].saturating_sub(self.paused.unwrap_or(now) - self.turn_started);
+        }
+        if let Some(paused) = self.paused {
+            for (bank, _) in banks.iter_mut().zip(self.discarding.iter()).filter(|(_, &is_discarding)| is_discarding) {
+                *bank = bank.saturating_sub(now - paused);
+            }
+        }
+        Some(banks)
+    }
+
+    /* the turn time doesn't go down while the clock is stopped for discards */
+    pub fn get_time_left(&self, now: Instant) -> Option<Duration> {
+        let now = self.paused.unwrap_or(now);
+        self.player.map(|player| self.get_turn_length(player).saturating_sub(now - self.turn_started))
+    }
+
+    /* the player whose time ran out, the first time it is asked for this turn */
+    pub fn take_timeout(&mut self, now: Instant) -> Option<PlayerId> {
+        if self.is_timed_out || self.paused.is_some() || self.get_time_left(now) != Some(Duration::ZERO) {
+            return None
+        }
+        self.is_timed_out = true;
+        self.player
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.is_timed_out
+    }
+
+    pub fn view(&self, now: Instant) -> ClockView {
+        let millis = |time: Duration| time.as_millis().min(u32::MAX as u128) as u32;
+        let player = self.player.filter(|_| self.paused.is_none());
+        ClockView {
+            player,
+            turn_length: player.map_or(0, |player| millis(self.get_turn_length(player))),
+            turn_left: player.and(self.get_time_left(now)).map_or(0, millis),
+            banks: self.control.game_time.map(|_| self.banks.iter().map(|&bank| millis(bank)).collect())
+        }
+    }
+}
+
+/* random cards for a seat that ran out of time discarding, None if it doesn't have to discard */
+pub fn get_timeout_discard<R: Rng>(game: &GameState, player: PlayerId, rng: &mut R) -> Option<Action> {
+    let amount = match &game.phase {
+        Phase::Discard { amounts } => amounts.get(player).copied().filter(|&amount| amount > 0)?,
+        _ => return None
+    };
+    let mut cards: Vec<Resource> = game.players[player].hand
+        .iter()
+        .flat_map(|(resource, count)| std::iter::repeat_n(resource, count as usize))
+        .collect();
+    cards.shuffle(rng);
+
+    let mut discard = Hand::default();
+    for &resource in cards.iter().take(amount as usize) {
+        discard.add(resource, 1);
+    }
+    Some(Action::Discard(discard))
+}
+
+/* what the server plays for a player who is out of time, None once there is nothing left to do or while others discard on their own clocks */
+pub fn get_timeout_action<R: Rng>(game: &GameState, rng: &mut R) -> Option<(PlayerId, Action)> {
+    match &game.phase {
+        Phase::Finished { .. } | Phase::Discard { .. } => None,
+        Phase::Roll => Some((game.turn, Action::Roll)),
+        Phase::Main => Some((game.turn, Action::EndTurn)),
+        /* setup placements, the robber and road building can't be skipped so any legal move will do */
+        _ => {
+            let legal: Vec<Action> = game.get_legal_actions(game.turn).into_iter().filter(|&action| action != Action::Undo).collect();
+            legal.choose(rng).map(|&action| (game.turn, action))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::board::Board;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const SEED: u64 = 7;
+    const CONTROL: TimeControl = TimeControl { turn_time: Some(60), game_time: Some(300), increment: 5 };
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    fn get_game(phase: Phase) -> GameState {
+        let mut game = GameState::new(Board::generate(&mut StdRng::seed_from_u64(SEED)), 4, 10, SEED);
+        game.phase = phase;
+        game
+    }
+
+    #[test]
+    fn settings_step_through_what_the_lobby_offers() {
+        let control = TimeControl::default();
+        assert!(!control.is_on() && control.is_valid());
+        assert_eq!(control.step(TimeSetting::Turn, false), control);
+        assert_eq!(control.step(TimeSetting::Turn, true).turn_time, Some(30));
+        assert_eq!(CONTROL.step(TimeSetting::Game, false).game_time, None);
+        assert_eq!(CONTROL.step(TimeSetting::Increment, true).increment, 10);
+        assert_eq!(TimeControl { increment: 60, ..CONTROL }.step(TimeSetting::Increment, true).increment, 60);
+        assert!(CONTROL.is_on() && CONTROL.is_valid());
+        assert!(!TimeControl { turn_time: Some(45), ..CONTROL }.is_valid());
+        assert_eq!(format_time(Duration::from_millis(3_725_900)), "62:05");
+    }
+
+    /* a turn is as long as the turn clock or what is left in the bank, whichever is shorter */
+    #[test]
+    fn turns_come_out_of_the_bank() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(CONTROL, 2, 0, Some(vec![secs(300), secs(20)]), start);
+        assert_eq!(clocks.get_time_left(start + secs(10)), Some(secs(50)));
+
+        clocks.start_turn(1, start + secs(10));
+        assert_eq!(clocks.get_banks(start + secs(10)), Some(vec![secs(295), secs(20)]));
+        assert_eq!(clocks.view(start + secs(15)), ClockView { player: Some(1), turn_length: 20_000, turn_left: 15_000, banks: Some(vec![295_000, 20_000]) });
+
+        assert_eq!(clocks.take_timeout(start + secs(29)), None);
+        assert_eq!(clocks.take_timeout(start + secs(30)), Some(1));
+        assert_eq!(clocks.take_timeout(start + secs(31)), None);
+        assert!(clocks.is_timed_out());
+
+        clocks.start_turn(0, start + secs(40));
+        assert!(!clocks.is_timed_out());
+        assert_eq!(clocks.get_banks(start + secs(40)), Some(vec![secs(295), secs(5)]));
+
+        /* without a game clock there are no banks and only the turn clock counts */
+        let clocks = Clocks::new(TimeControl { game_time: None, ..CONTROL }, 2, 0, None, start);
+        assert_eq!(clocks.get_banks(start), None);
+        assert_eq!(clocks.view(start).banks, None);
+        assert_eq!(clocks.get_time_left(start + secs(100)), Some(Duration::ZERO));
+    }
+
+    /* the turn clock stops while seats discard, each on a clock of its own paid from its bank */
+    #[test]
+    fn discards_stop_the_turn_clock() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(CONTROL, 3, 0, None, start);
+        clocks.sync_discards(&Phase::Discard { amounts: vec![0, 4, 2] }, start + secs(10));
+        assert_eq!(clocks.get_time_left(start + secs(30)), Some(secs(50)));
+        assert_eq!(clocks.take_timeout(start + secs(100)), None);
+        assert_eq!(clocks.view(start + secs(30)).player, None);
+
+        clocks.sync_discards(&Phase::Discard { amounts: vec![0, 0, 2] }, start + secs(30));
+        assert_eq!(clocks.get_banks(start + secs(40)), Some(vec![secs(290), secs(280), secs(270)]));
+        assert!(clocks.take_discard_timeouts(start + secs(69)).is_empty());
+        assert_eq!(clocks.take_discard_timeouts(start + secs(70)), vec![2]);
+        assert!(clocks.take_discard_timeouts(start + secs(71)).is_empty());
+
+        clocks.sync_discards(&Phase::Main, start + secs(80));
+        assert_eq!(clocks.get_time_left(start + secs(80)), Some(secs(50)));
+        assert_eq!(clocks.get_banks(start + secs(80)), Some(vec![secs(290), secs(280), secs(240)]));
+    }
+
+    /* what a save keeps of the banks starts the clocks off where they were */
+    #[test]
+    fn saved_banks_resume() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(CONTROL, 2, 0, None, start);
+        clocks.start_turn(1, start + secs(100));
+        let banks = clocks.get_banks(start + secs(130)).unwrap();
+        assert_eq!(banks, vec![secs(205), secs(270)]);
+
+        let resumed = Clocks::new(CONTROL, 2, 1, Some(banks.clone()), start);
+        assert_eq!(resumed.get_banks(start), Some(banks));
+        assert_eq!(resumed.get_time_left(start), Some(secs(60)));
+
+        /* banks for another number of seats are no good, they start full */
+        let resumed = Clocks::new(CONTROL, 3, 0, Some(vec![secs(1)]), start);
+        assert_eq!(resumed.get_banks(start), Some(vec![secs(300); 3]));
+    }
+
+    #[test]
+    fn timeouts_finish_the_turn() {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let game = get_game(Phase::Roll);
+        assert_eq!(get_timeout_action(&game, &mut rng), Some((game.turn, Action::Roll)));
+        assert_eq!(get_timeout_action(&get_game(Phase::Main), &mut rng), Some((game.turn, Action::EndTurn)));
+        assert_eq!(get_timeout_action(&get_game(Phase::Finished { winner: 0 }), &mut rng), None);
+
+        let game = get_game(Phase::Setup { step: 0, settlement: None });
+        let (player, action) = get_timeout_action(&game, &mut rng).unwrap();
+        assert!(player == game.turn && game.get_legal_actions(player).contains(&action));
+
+        /* discards are picked at random out of the seat's own hand */
+        let mut game = get_game(Phase::Discard { amounts: vec![0, 3, 0, 0] });
+        game.players[1].hand = Hand::of(Resource::Ore, 2);
+        game.players[1].hand.add(Resource::Wool, 4);
+        assert_eq!(get_timeout_action(&game, &mut rng), None);
+        assert_eq!(get_timeout_discard(&game, 0, &mut rng), None);
+        match get_timeout_discard(&game, 1, &mut rng) {
+            Some(Action::Discard(discard)) => assert!(discard.total() == 3 && game.players[1].hand.contains(&discard)),
+            action => panic!("expected a discard, got {:?}", action)
+        }
+    }
+}
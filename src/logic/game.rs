@@ -0,0 +1,1265 @@
+/*
+ * game.rs
+ * the rules of the game
+ *
+ * GameState is the authoritative state of a game, only the server holds one and players change it by applying actions
+ * every action that goes through produces events describing what happened, events and views are all clients ever see
+ * views and events are redacted for whoever is looking so nobody learns what is in someone else's hand
+ *
+ * the player whose turn it is can take back the builds, bank trades and robber moves they made this turn as long as nothing came after them
+ * that can't be taken back, which is anything that showed someone something hidden (dice, stolen cards, development cards bought or played),
+ * anything the other players had a part in (discards and trades) and ending the turn
+ */
+
+use super::{board::*, hand::*};
+
+use crate::enums::{Resource, Building, TileResource, PortResource, DevelopmentCard};
+use crate::render::drawing::map::MAP_GRAPH;
+
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha12Rng;
+
+use std::{fmt, collections::BTreeMap};
+
+/* players are identified by their seat (which is also their turn order) */
+pub type PlayerId = usize;
+
+pub const MAX_ROADS: u8 = 15;
+pub const MAX_SETTLEMENTS: u8 = 5;
+pub const MAX_CITIES: u8 = 4;
+
+const BANK_RESOURCE_COUNT: u8 = 19;
+const DISCARD_LIMIT: u8 = 7;
+const ROBBER_ROLL: u8 = 7;
+const LONGEST_ROAD_MIN: u8 = 5;
+const LARGEST_ARMY_MIN: u8 = 3;
+const ROAD_BUILDING_ROADS: u8 = 2;
+const AWARD_VICTORY_POINTS: u8 = 2;
+const BANK_TRADE_RATIO: u8 = 4;
+
+/* what the game is waiting on */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /* initial placement goes around the table and back, step counts the settlement + road pairs placed so far */
+    Setup { step: usize, settlement: Option<usize> },
+    Roll,
+    /* how many cards each player still has to discard after a 7 */
+    Discard { amounts: Vec<u8> },
+    /* rolled is false when the robber came from a knight played before rolling */
+    MoveRobber { rolled: bool },
+    Steal { rolled: bool },
+    Main,
+    RoadBuilding { roads: u8, rolled: bool },
+    Finished { winner: PlayerId }
+}
+
+/* things that can be built on the board */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Road(usize, usize),
+    Settlement(usize),
+    City(usize)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Roll,
+    Discard(Hand),
+    MoveRobber(usize),
+    Steal(PlayerId),
+    BuildRoad(usize, usize),
+    BuildSettlement(usize),
+    BuildCity(usize),
+    BuyDevelopment,
+    PlayKnight,
+    PlayRoadBuilding,
+    PlayYearOfPlenty(Resource, Resource),
+    PlayMonopoly(Resource),
+    /* what the player gives and what they get, every resource given has to come in lots of the player's ratio for it */
+    TradeWithBank(Hand, Hand),
+    /* put a trade on the table for one player or for anyone when None, what the player gives and what they want back */
+    OfferTrade(Option<PlayerId>, Hand, Hand),
+    AcceptTrade,
+    DeclineTrade,
+    CancelTrade,
+    EndTurn,
+    /* take back the last action the player can still take back this turn */
+    Undo
+}
+
+/* a trade the current player put on the table */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeOffer {
+    pub from: PlayerId,
+    /* None when anyone can take it */
+    pub to: Option<PlayerId>,
+    pub give: Hand,
+    pub get: Hand,
+    /* players who turned it down, an offer everyone turned down is taken off the table */
+    pub declined: Vec<PlayerId>
+}
+
+/* what happened, hidden parts are None for anyone who is not allowed to know them */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    TurnStarted { player: PlayerId },
+    Rolled { player: PlayerId, dice: (u8, u8) },
+    Produced { player: PlayerId, resources: Hand },
+    Received { player: PlayerId, resources: Hand },
+    Discarded { player: PlayerId, resources: Hand },
+    RobberMoved { player: PlayerId, tile: usize },
+    Stole { thief: PlayerId, victim: PlayerId, resource: Option<Resource> },
+    Built { player: PlayerId, piece: Piece },
+    BoughtDevelopment { player: PlayerId, card: Option<DevelopmentCard> },
+    PlayedDevelopment { player: PlayerId, card: DevelopmentCard },
+    Monopolized { player: PlayerId, resource: Resource, count: u8 },
+    TradedWithBank { player: PlayerId, gave: Hand, got: Hand },
+    TradeOffered { player: PlayerId, to: Option<PlayerId>, give: Hand, get: Hand },
+    TradeDeclined { player: PlayerId },
+    /* the offer is off the table without anyone taking it */
+    TradeCanceled { player: PlayerId },
+    Traded { from: PlayerId, to: PlayerId, gave: Hand, got: Hand },
+    LongestRoad { player: Option<PlayerId> },
+    LargestArmy { player: Option<PlayerId> },
+    Won { player: PlayerId },
+    /* the action was taken back and the game is back to how it was before it */
+    Undone { player: PlayerId, action: Action }
+}
+
+/* who is looking at the game */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Viewer {
+    Player(PlayerId),
+    /* spectators without god view */
+    Public,
+    /* spectators with god view see everything */
+    God
+}
+
+impl Viewer {
+    fn can_see(&self, player: PlayerId) -> bool {
+        match self {
+            Self::Player(viewer) => *viewer == player,
+            Self::Public => false,
+            Self::God => true
+        }
+    }
+}
+
+impl GameEvent {
+    pub fn redact_for(&self, viewer: Viewer) -> GameEvent {
+        match *self {
+            Self::Stole { thief, victim, .. } if !viewer.can_see(thief) && !viewer.can_see(victim) =>
+                Self::Stole { thief, victim, resource: None },
+            Self::BoughtDevelopment { player, .. } if !viewer.can_see(player) =>
+                Self::BoughtDevelopment { player, card: None },
+            _ => self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameError {
+    GameOver,
+    NotYourTurn,
+    WrongPhase,
+    CannotAfford,
+    IllegalPlacement,
+    NoPiecesLeft,
+    NoSuchCard,
+    AlreadyPlayedDevelopment,
+    InvalidDiscard,
+    InvalidVictim,
+    InvalidTile,
+    EmptyDeck,
+    EmptyBank,
+    InvalidTrade,
+    NoOffer,
+    NothingToUndo
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::GameOver => "the game is over",
+            Self::NotYourTurn => "it is not your turn",
+            Self::WrongPhase => "you can't do that right now",
+            Self::CannotAfford => "you can't afford that",
+            Self::IllegalPlacement => "you can't build there",
+            Self::NoPiecesLeft => "you have no pieces of that kind left",
+            Self::NoSuchCard => "you don't have a playable card of that kind",
+            Self::AlreadyPlayedDevelopment => "you already played a development card this turn",
+            Self::InvalidDiscard => "that is not the right number of cards to discard",
+            Self::InvalidVictim => "you can't steal from that player",
+            Self::InvalidTile => "the robber can't go there",
+            Self::EmptyDeck => "the development deck is empty",
+            Self::EmptyBank => "the bank doesn't have that",
+            Self::InvalidTrade => "that trade doesn't add up",
+            Self::NoOffer => "there is no trade offer like that",
+            Self::NothingToUndo => "there is nothing you can take back"
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerState {
+    pub hand: Hand,
+    /* cards that can be played, cards bought this turn wait in new_development_cards until the turn ends */
+    pub development_cards: Vec<DevelopmentCard>,
+    pub new_development_cards: Vec<DevelopmentCard>,
+    pub knights_played: u8,
+    pub road_length: u8,
+    pub roads_left: u8,
+    pub settlements_left: u8,
+    pub cities_left: u8
+}
+
+impl PlayerState {
+    fn new() -> Self {
+        PlayerState {
+            hand: Hand::default(),
+            development_cards: Vec::new(),
+            new_development_cards: Vec::new(),
+            knights_played: 0,
+            road_length: 0,
+            roads_left: MAX_ROADS,
+            settlements_left: MAX_SETTLEMENTS,
+            cities_left: MAX_CITIES
+        }
+    }
+
+    fn development_count(&self) -> usize {
+        self.development_cards.len() + self.new_development_cards.len()
+    }
+}
+
+/* what one player looks like to a viewer, hands are None when the viewer can't see them */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerView {
+    pub resource_count: u8,
+    pub hand: Option<Hand>,
+    pub development_count: u8,
+    pub development_cards: Option<Vec<DevelopmentCard>>,
+    pub knights_played: u8,
+    pub road_length: u8,
+    /* hidden victory point cards are only counted for viewers who can see them */
+    pub victory_points: u8
+}
+
+/* everything a viewer is allowed to know about a game */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameView {
+    pub players: Vec<PlayerView>,
+    pub roads: Vec<(usize, usize, PlayerId)>,
+    pub buildings: Vec<(usize, Building, PlayerId)>,
+    pub robber: usize,
+    pub bank: Hand,
+    pub development_deck: u8,
+    pub turn: PlayerId,
+    pub phase: Phase,
+    pub dice: Option<(u8, u8)>,
+    pub longest_road: Option<PlayerId>,
+    pub largest_army: Option<PlayerId>,
+    pub offer: Option<TradeOffer>,
+    /* what the viewer can do right now, only players get any */
+    pub legal_actions: Vec<Action>
+}
+
+/* the parts of the game an action that can be taken back changes, as they were before it */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub action: Action,
+    players: Vec<PlayerState>,
+    roads: BTreeMap<(usize, usize), PlayerId>,
+    buildings: Vec<Option<(Building, PlayerId)>>,
+    robber: usize,
+    bank: Hand,
+    phase: Phase,
+    longest_road: Option<PlayerId>,
+    largest_army: Option<PlayerId>,
+    has_played_development: bool,
+    /* spending what an offer gives cancels it, taking that back brings the offer back */
+    offer: Option<TradeOffer>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+    pub board: Board,
+    pub victory_points_to_win: u8,
+    pub players: Vec<PlayerState>,
+    /* roads are keyed by their plots with the smaller plot first */
+    pub roads: BTreeMap<(usize, usize), PlayerId>,
+    pub buildings: Vec<Option<(Building, PlayerId)>>,
+    pub robber: usize,
+    pub bank: Hand,
+    pub development_deck: Vec<DevelopmentCard>,
+    pub turn: PlayerId,
+    pub phase: Phase,
+    pub dice: Option<(u8, u8)>,
+    pub longest_road: Option<PlayerId>,
+    pub largest_army: Option<PlayerId>,
+    pub has_played_development: bool,
+    pub offer: Option<TradeOffer>,
+    /* dice, steals and the deck shuffle all draw from here, it is a known generator so saves can store where it is at */
+    pub rng: ChaCha12Rng,
+    /* what the current player can take back with the last action on top, cleared by anything that can't be taken back */
+    pub checkpoints: Vec<Checkpoint>
+}
+
+impl GameState {
+    pub fn new(board: Board, player_count: usize, victory_points_to_win: u8, seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let mut development_deck: Vec<DevelopmentCard> = DevelopmentCard::ALL
+            .iter()
+            .flat_map(|&card| std::iter::repeat_n(card, card.get_deck_count()))
+            .collect();
+        development_deck.shuffle(&mut rng);
+
+        GameState {
+            robber: board.get_desert(),
+            board,
+            victory_points_to_win,
+            players: (0..player_count).map(|_| PlayerState::new()).collect(),
+            roads: BTreeMap::new(),
+            buildings: vec![None; MAP_GRAPH.plot_points.len()],
+            bank: Hand([BANK_RESOURCE_COUNT; Resource::NUM_TYPES]),
+            development_deck,
+            turn: 0,
+            phase: Phase::Setup { step: 0, settlement: None },
+            dice: None,
+            longest_road: None,
+            largest_army: None,
+            has_played_development: false,
+            offer: None,
+            rng,
+            checkpoints: Vec::new()
+        }
+    }
+
+    /* the players the game is waiting on */
+    pub fn get_actors(&self) -> Vec<PlayerId> {
+        match &self.phase {
+            Phase::Finished { .. } => Vec::new(),
+            Phase::Discard { amounts } => (0..amounts.len()).filter(|&player| amounts[player] > 0).collect(),
+            _ => vec![self.turn]
+        }
+    }
+
+    pub fn get_victory_points(&self, player: PlayerId) -> u8 {
+        let hidden = self.players[player].development_cards
+            .iter()
+            .chain(self.players[player].new_development_cards.iter())
+            .filter(|&&card| card == DevelopmentCard::VictoryPoint)
+            .count() as u8;
+        self.get_public_victory_points(player) + hidden
+    }
+
+    /* victory points everyone can count from the board and the awards */
+    pub fn get_public_victory_points(&self, player: PlayerId) -> u8 {
+        let buildings: u8 = self.buildings
+            .iter()
+            .flatten()
+            .filter(|(_, owner)| *owner == player)
+            .map(|(building, _)| if *building == Building::City { 2 } else { 1 })
+            .sum();
+        let awards = [self.longest_road, self.largest_army].iter().filter(|&&holder| holder == Some(player)).count() as u8;
+        buildings + AWARD_VICTORY_POINTS*awards
+    }
+
+    /* the players who still have to answer the trade offer, nobody has to but bots do */
+    pub fn get_trade_responders(&self) -> Vec<PlayerId> {
+        match &self.offer {
+            Some(offer) => (0..self.players.len())
+                .filter(|&player| player != offer.from && offer.to.unwrap_or(player) == player && !offer.declined.contains(&player))
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    /* how many of a resource the player gives the bank for one card, ports next to their buildings make it cheaper */
+    pub fn get_trade_ratio(&self, player: PlayerId, resource: Resource) -> u8 {
+        self.buildings
+            .iter()
+            .enumerate()
+            .filter(|(_, building)| matches!(building, Some((_, owner)) if *owner == player))
+            .filter_map(|(plot, _)| MAP_GRAPH.plot_ports[plot])
+            .map(|port| self.board.ports[port])
+            .filter(|port| match port {
+                PortResource::Of(port_resource) => *port_resource == resource,
+                PortResource::OfAnyKind => true
+            })
+            .map(|port| port.get_ratio().0 as u8)
+            .min()
+            .unwrap_or(BANK_TRADE_RATIO)
+    }
+
+    /* players the current player can steal from after moving the robber */
+    pub fn get_steal_victims(&self) -> Vec<PlayerId> {
+        let mut victims: Vec<PlayerId> = MAP_GRAPH.tile_plots[self.robber]
+            .iter()
+            .filter_map(|&plot| self.buildings[plot].map(|(_, owner)| owner))
+            .filter(|&owner| owner != self.turn && !self.players[owner].hand.is_empty())
+            .collect();
+        victims.sort_unstable();
+        victims.dedup();
+        victims
+    }
+
+    /*
+     * every action the player could take right now
+     * discards and trade offers are left out since there are too many of them, players pick their own cards for those
+     * bank trades are only listed one card at a time
+     */
+    pub fn get_legal_actions(&self, player: PlayerId) -> Vec<Action> {
+        let mut candidates = vec![
+            Action::Roll,
+            Action::EndTurn,
+            Action::BuyDevelopment,
+            Action::PlayKnight,
+            Action::PlayRoadBuilding,
+            Action::AcceptTrade,
+            Action::DeclineTrade,
+            Action::CancelTrade,
+            Action::Undo
+        ];
+        candidates.extend((0..self.board.tiles.len()).map(Action::MoveRobber));
+        candidates.extend((0..self.players.len()).map(Action::Steal));
+        candidates.extend(self.get_edges().map(|(a, b)| Action::BuildRoad(a, b)));
+        candidates.extend((0..self.buildings.len()).map(Action::BuildSettlement));
+        candidates.extend((0..self.buildings.len()).map(Action::BuildCity));
+        candidates.extend(Resource::ALL.iter().map(|&resource| Action::PlayMonopoly(resource)));
+        for (i, &a) in Resource::ALL.iter().enumerate() {
+            candidates.extend(Resource::ALL[i..].iter().map(|&b| Action::PlayYearOfPlenty(a, b)));
+        }
+        for give in Resource::ALL {
+            let ratio = self.get_trade_ratio(player, give);
+            candidates.extend(Resource::ALL.iter().filter(|&&get| get != give).map(|&get| Action::TradeWithBank(Hand::of(give, ratio), Hand::of(get, 1))));
+        }
+
+        candidates.into_iter().filter(|action| self.validate(player, action).is_ok()).collect()
+    }
+
+    /* make sure the action can be applied without changing anything */
+    pub fn validate(&self, player: PlayerId, action: &Action) -> Result<(), GameError> {
+        if let Phase::Finished { .. } = self.phase {
+            return Err(GameError::GameOver)
+        }
+
+        if let Phase::Discard { amounts } = &self.phase {
+            return match action {
+                Action::Discard(hand) => {
+                    let amount = amounts.get(player).copied().unwrap_or(0);
+                    if amount == 0 {
+                        Err(GameError::WrongPhase)
+                    } else if hand.total() != amount || !self.players[player].hand.contains(hand) {
+                        Err(GameError::InvalidDiscard)
+                    } else {
+                        Ok(())
+                    }
+                },
+                _ => Err(GameError::WrongPhase)
+            }
+        }
+
+        /* anyone the offer is for answers it, not just the current player */
+        if let Action::AcceptTrade | Action::DeclineTrade = action {
+            let offer = self.offer.as_ref().filter(|_| self.get_trade_responders().contains(&player)).ok_or(GameError::NoOffer)?;
+            if *action == Action::AcceptTrade && !self.players[player].hand.contains(&offer.get) {
+                return Err(GameError::CannotAfford)
+            }
+            return Ok(())
+        }
+
+        if player != self.turn {
+            return Err(GameError::NotYourTurn)
+        }
+
+        let state = &self.players[player];
+        match (&self.phase, *action) {
+            (_, Action::Undo) => {
+                if self.checkpoints.is_empty() { return Err(GameError::NothingToUndo) }
+            },
+            (Phase::Setup { settlement: None, .. }, Action::BuildSettlement(plot)) => {
+                if !self.is_plot_free(plot) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Setup { settlement: Some(settlement), .. }, Action::BuildRoad(a, b)) => {
+                if !self.is_edge_free(a, b) || (a != *settlement && b != *settlement) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Roll, Action::Roll) => (),
+            (Phase::MoveRobber { .. }, Action::MoveRobber(tile)) => {
+                if tile >= self.board.tiles.len() || tile == self.robber { return Err(GameError::InvalidTile) }
+            },
+            (Phase::Steal { .. }, Action::Steal(victim)) => {
+                if !self.get_steal_victims().contains(&victim) { return Err(GameError::InvalidVictim) }
+            },
+            (Phase::Main, Action::BuildRoad(a, b)) | (Phase::RoadBuilding { .. }, Action::BuildRoad(a, b)) => {
+                if state.roads_left == 0 { return Err(GameError::NoPiecesLeft) }
+                if self.phase == Phase::Main && !state.hand.contains(&ROAD_COST) { return Err(GameError::CannotAfford) }
+                if !self.can_build_road(player, a, b) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Main, Action::BuildSettlement(plot)) => {
+                if state.settlements_left == 0 { return Err(GameError::NoPiecesLeft) }
+                if !state.hand.contains(&SETTLEMENT_COST) { return Err(GameError::CannotAfford) }
+                if !self.is_plot_free(plot) || !self.touches_own_road(player, plot) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Main, Action::BuildCity(plot)) => {
+                if state.cities_left == 0 { return Err(GameError::NoPiecesLeft) }
+                if !state.hand.contains(&CITY_COST) { return Err(GameError::CannotAfford) }
+                if self.buildings.get(plot).copied().flatten() != Some((Building::Settlement, player)) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Main, Action::BuyDevelopment) => {
+                if !state.hand.contains(&DEVELOPMENT_COST) { return Err(GameError::CannotAfford) }
+                if self.development_deck.is_empty() { return Err(GameError::EmptyDeck) }
+            },
+            (Phase::Main, Action::TradeWithBank(give, get)) => {
+                let mut lots = 0;
+                for resource in Resource::ALL {
+                    let count = give.get(resource);
+                    if count == 0 {
+                        continue
+                    }
+                    let ratio = self.get_trade_ratio(player, resource);
+                    if count % ratio != 0 || get.get(resource) > 0 { return Err(GameError::InvalidTrade) }
+                    lots += count/ratio;
+                }
+                if lots == 0 || lots != get.total() { return Err(GameError::InvalidTrade) }
+                if !state.hand.contains(&give) { return Err(GameError::CannotAfford) }
+                if !self.bank.contains(&get) { return Err(GameError::EmptyBank) }
+            },
+            (Phase::Main, Action::OfferTrade(to, give, get)) => {
+                let overlaps = Resource::ALL.iter().any(|&resource| give.get(resource) > 0 && get.get(resource) > 0);
+                if give.is_empty() || get.is_empty() || overlaps { return Err(GameError::InvalidTrade) }
+                if to.is_some_and(|to| to == player || to >= self.players.len()) { return Err(GameError::InvalidTrade) }
+                if !state.hand.contains(&give) { return Err(GameError::CannotAfford) }
+            },
+            (Phase::Main, Action::CancelTrade) => {
+                if self.offer.is_none() { return Err(GameError::NoOffer) }
+            },
+            (Phase::Main, Action::EndTurn) => (),
+            (Phase::Roll | Phase::Main, Action::PlayKnight) => self.check_playable(player, DevelopmentCard::Knight)?,
+            (Phase::Roll | Phase::Main, Action::PlayRoadBuilding) => {
+                self.check_playable(player, DevelopmentCard::RoadBuilding)?;
+                if state.roads_left == 0 { return Err(GameError::NoPiecesLeft) }
+                if !self.get_edges().any(|(a, b)| self.can_build_road(player, a, b)) { return Err(GameError::IllegalPlacement) }
+            },
+            (Phase::Roll | Phase::Main, Action::PlayYearOfPlenty(a, b)) => {
+                self.check_playable(player, DevelopmentCard::YearOfPlenty)?;
+                let mut wanted = Hand::of(a, 1);
+                wanted.add(b, 1);
+                if !self.bank.contains(&wanted) { return Err(GameError::EmptyBank) }
+            },
+            (Phase::Roll | Phase::Main, Action::PlayMonopoly(_)) => self.check_playable(player, DevelopmentCard::Monopoly)?,
+            _ => return Err(GameError::WrongPhase)
+        }
+
+        Ok(())
+    }
+
+    /* apply the action for the player, returns what happened */
+    pub fn apply(&mut self, player: PlayerId, action: Action) -> Result<Vec<GameEvent>, GameError> {
+        self.validate(player, &action)?;
+
+        let checkpoint = if self.can_take_back(&action) { Some(self.get_checkpoint(action)) } else { None };
+        let mut events = Vec::new();
+        match (self.phase.clone(), action) {
+            (_, Action::Undo) => {
+                let checkpoint = self.checkpoints.pop().unwrap();
+                self.restore(&checkpoint);
+                events.push(GameEvent::Undone { player, action: checkpoint.action });
+            },
+            (Phase::Setup { step, .. }, Action::BuildSettlement(plot)) => {
+                self.place(player, Piece::Settlement(plot), &mut events);
+                /* the second settlement collects from the tiles around it */
+                if step >= self.players.len() {
+                    let mut resources = Hand::default();
+                    for &tile in MAP_GRAPH.plot_tiles[plot].iter() {
+                        if let TileResource::Of(resource) = self.board.tiles[tile].resource {
+                            if self.bank.get(resource) > 0 {
+                                resources.add(resource, 1);
+                            }
+                        }
+                    }
+                    self.give(player, &resources);
+                    if !resources.is_empty() {
+                        events.push(GameEvent::Received { player, resources });
+                    }
+                }
+                self.phase = Phase::Setup { step, settlement: Some(plot) };
+            },
+            (Phase::Setup { step, .. }, Action::BuildRoad(a, b)) => {
+                self.place(player, Piece::Road(a, b), &mut events);
+                let step = step + 1;
+                let player_count = self.players.len();
+                if step == 2*player_count {
+                    self.phase = Phase::Roll;
+                    self.start_turn(0, &mut events);
+                } else {
+                    self.phase = Phase::Setup { step, settlement: None };
+                    let next = if step < player_count { step } else { 2*player_count - 1 - step };
+                    self.start_turn(next, &mut events);
+                }
+            },
+            (_, Action::Roll) => {
+                let dice = (self.rng.gen_range(1..=6), self.rng.gen_range(1..=6));
+                self.dice = Some(dice);
+                events.push(GameEvent::Rolled { player, dice });
+
+                if dice.0 + dice.1 == ROBBER_ROLL {
+                    let amounts: Vec<u8> = self.players
+                        .iter()
+                        .map(|state| if state.hand.total() > DISCARD_LIMIT { state.hand.total()/2 } else { 0 })
+                        .collect();
+                    self.phase = if amounts.iter().any(|&amount| amount > 0) {
+                        Phase::Discard { amounts }
+                    } else {
+                        Phase::MoveRobber { rolled: true }
+                    };
+                } else {
+                    self.produce(dice.0 + dice.1, &mut events);
+                    self.phase = Phase::Main;
+                }
+            },
+            (Phase::Discard { mut amounts }, Action::Discard(resources)) => {
+                self.players[player].hand.remove_hand(&resources);
+                self.bank.add_hand(&resources);
+                amounts[player] = 0;
+                events.push(GameEvent::Discarded { player, resources });
+                self.phase = if amounts.iter().all(|&amount| amount == 0) {
+                    Phase::MoveRobber { rolled: true }
+                } else {
+                    Phase::Discard { amounts }
+                };
+            },
+            (Phase::MoveRobber { rolled }, Action::MoveRobber(tile)) => {
+                self.robber = tile;
+                events.push(GameEvent::RobberMoved { player, tile });
+                self.phase = if self.get_steal_victims().is_empty() {
+                    Self::after_robber(rolled)
+                } else {
+                    Phase::Steal { rolled }
+                };
+            },
+            (Phase::Steal { rolled }, Action::Steal(victim)) => {
+                let hand = &self.players[victim].hand;
+                let cards: Vec<Resource> = hand.iter().flat_map(|(resource, count)| std::iter::repeat_n(resource, count as usize)).collect();
+                let resource = *cards.choose(&mut self.rng).unwrap();
+                self.players[victim].hand.remove(resource, 1);
+                self.players[player].hand.add(resource, 1);
+                events.push(GameEvent::Stole { thief: player, victim, resource: Some(resource) });
+                self.phase = Self::after_robber(rolled);
+            },
+            (phase, Action::BuildRoad(a, b)) => {
+                if let Phase::RoadBuilding { roads, rolled } = phase {
+                    self.place(player, Piece::Road(a, b), &mut events);
+                    let state = &self.players[player];
+                    let can_continue = roads > 1 && state.roads_left > 0 && self.get_edges().any(|(a, b)| self.can_build_road(player, a, b));
+                    self.phase = if can_continue { Phase::RoadBuilding { roads: roads - 1, rolled } } else { Self::after_robber(rolled) };
+                } else {
+                    self.pay(player, &ROAD_COST);
+                    self.place(player, Piece::Road(a, b), &mut events);
+                }
+            },
+            (_, Action::BuildSettlement(plot)) => {
+                self.pay(player, &SETTLEMENT_COST);
+                self.place(player, Piece::Settlement(plot), &mut events);
+            },
+            (_, Action::BuildCity(plot)) => {
+                self.pay(player, &CITY_COST);
+                self.place(player, Piece::City(plot), &mut events);
+            },
+            (_, Action::BuyDevelopment) => {
+                self.pay(player, &DEVELOPMENT_COST);
+                let card = self.development_deck.pop().unwrap();
+                self.players[player].new_development_cards.push(card);
+                events.push(GameEvent::BoughtDevelopment { player, card: Some(card) });
+            },
+            (phase, Action::PlayKnight) => {
+                self.play(player, DevelopmentCard::Knight, &mut events);
+                self.players[player].knights_played += 1;
+                self.update_largest_army(&mut events);
+                self.phase = Phase::MoveRobber { rolled: phase == Phase::Main };
+            },
+            (phase, Action::PlayRoadBuilding) => {
+                self.play(player, DevelopmentCard::RoadBuilding, &mut events);
+                self.phase = Phase::RoadBuilding { roads: ROAD_BUILDING_ROADS, rolled: phase == Phase::Main };
+            },
+            (_, Action::PlayYearOfPlenty(a, b)) => {
+                self.play(player, DevelopmentCard::YearOfPlenty, &mut events);
+                let mut resources = Hand::of(a, 1);
+                resources.add(b, 1);
+                self.give(player, &resources);
+                events.push(GameEvent::Received { player, resources });
+            },
+            (_, Action::PlayMonopoly(resource)) => {
+                self.play(player, DevelopmentCard::Monopoly, &mut events);
+                let mut count = 0;
+                for other in 0..self.players.len() {
+                    if other != player {
+                        let taken = self.players[other].hand.get(resource);
+                        self.players[other].hand.remove(resource, taken);
+                        count += taken;
+                    }
+                }
+                self.players[player].hand.add(resource, count);
+                events.push(GameEvent::Monopolized { player, resource, count });
+            },
+            (_, Action::TradeWithBank(give, get)) => {
+                self.pay(player, &give);
+                self.give(player, &get);
+                events.push(GameEvent::TradedWithBank { player, gave: give, got: get });
+            },
+            (_, Action::OfferTrade(to, give, get)) => {
+                self.offer = Some(TradeOffer { from: player, to, give, get, declined: Vec::new() });
+                events.push(GameEvent::TradeOffered { player, to, give, get });
+            },
+            (_, Action::AcceptTrade) => {
+                let offer = self.offer.take().unwrap();
+                self.players[offer.from].hand.remove_hand(&offer.give);
+                self.players[offer.from].hand.add_hand(&offer.get);
+                self.players[player].hand.remove_hand(&offer.get);
+                self.players[player].hand.add_hand(&offer.give);
+                events.push(GameEvent::Traded { from: offer.from, to: player, gave: offer.give, got: offer.get });
+            },
+            (_, Action::DeclineTrade) => {
+                self.offer.as_mut().unwrap().declined.push(player);
+                events.push(GameEvent::TradeDeclined { player });
+                if self.get_trade_responders().is_empty() {
+                    self.offer = None;
+                }
+            },
+            (_, Action::CancelTrade) => {
+                self.offer = None;
+                events.push(GameEvent::TradeCanceled { player });
+            },
+            (_, Action::EndTurn) => {
+                let state = &mut self.players[player];
+                let bought = std::mem::take(&mut state.new_development_cards);
+                state.development_cards.extend(bought);
+                self.phase = Phase::Roll;
+                self.start_turn((player + 1) % self.players.len(), &mut events);
+            },
+            _ => unreachable!("validate let through {:?} in {:?}", action, self.phase)
+        }
+
+        match checkpoint {
+            Some(checkpoint) => self.checkpoints.push(checkpoint),
+            None if action != Action::Undo => self.checkpoints.clear(),
+            None => ()
+        }
+
+        /* offers only stand while their player is in the main phase of their turn and can still pay */
+        if let Some(offer) = &self.offer {
+            if self.phase != Phase::Main || self.turn != offer.from || !self.players[offer.from].hand.contains(&offer.give) {
+                events.push(GameEvent::TradeCanceled { player: offer.from });
+                self.offer = None;
+            }
+        }
+
+        /* players can only win on their own turn */
+        if self.get_victory_points(self.turn) >= self.victory_points_to_win && !matches!(self.phase, Phase::Setup { .. }) {
+            self.phase = Phase::Finished { winner: self.turn };
+            events.push(GameEvent::Won { player: self.turn });
+        }
+
+        Ok(events)
+    }
+
+    /* what the game looks like to the viewer */
+    pub fn view_for(&self, viewer: Viewer) -> GameView {
+        GameView {
+            players: self.players
+                .iter()
+                .enumerate()
+                .map(|(player, state)| {
+                    let can_see = viewer.can_see(player);
+                    PlayerView {
+                        resource_count: state.hand.total(),
+                        hand: if can_see { Some(state.hand) } else { None },
+                        development_count: state.development_count() as u8,
+                        development_cards: if can_see {
+                            Some(state.development_cards.iter().chain(state.new_development_cards.iter()).copied().collect())
+                        } else {
+                            None
+                        },
+                        knights_played: state.knights_played,
+                        road_length: state.road_length,
+                        victory_points: if can_see { self.get_victory_points(player) } else { self.get_public_victory_points(player) }
+                    }
+                })
+                .collect(),
+            roads: self.roads.iter().map(|(&(a, b), &player)| (a, b, player)).collect(),
+            buildings: self.buildings
+                .iter()
+                .enumerate()
+                .filter_map(|(plot, building)| building.map(|(kind, player)| (plot, kind, player)))
+                .collect(),
+            robber: self.robber,
+            bank: self.bank,
+            development_deck: self.development_deck.len() as u8,
+            turn: self.turn,
+            phase: self.phase.clone(),
+            dice: self.dice,
+            longest_road: self.longest_road,
+            largest_army: self.largest_army,
+            offer: self.offer.clone(),
+            legal_actions: match viewer {
+                Viewer::Player(player) => self.get_legal_actions(player),
+                _ => Vec::new()
+            }
+        }
+    }
+
+    /* every road space on the board with the smaller plot first */
+    pub fn get_edges(&self) -> impl Iterator<Item = (usize, usize)> {
+        MAP_GRAPH.plot_edges
+            .iter()
+            .enumerate()
+            .flat_map(|(a, edges)| edges.iter().filter(move |&&b| a < b).map(move |&b| (a, b)))
+    }
+
+    /* plots have to be empty and not next to any other building */
+    pub fn is_plot_free(&self, plot: usize) -> bool {
+        plot < self.buildings.len()
+            && self.buildings[plot].is_none()
+            && MAP_GRAPH.plot_edges[plot].iter().all(|&neighbor| self.buildings[neighbor].is_none())
+    }
+
+    pub fn is_edge_free(&self, a: usize, b: usize) -> bool {
+        a < MAP_GRAPH.plot_edges.len()
+            && MAP_GRAPH.plot_edges[a].contains(&b)
+            && !self.roads.contains_key(&(a.min(b), a.max(b)))
+    }
+
+    /* roads have to connect to one of the player's buildings or to one of their roads not cut off by someone else's building */
+    pub fn can_build_road(&self, player: PlayerId, a: usize, b: usize) -> bool {
+        self.is_edge_free(a, b) && [a, b].iter().any(|&plot| {
+            match self.buildings[plot] {
+                Some((_, owner)) => owner == player,
+                None => self.touches_own_road(player, plot)
+            }
+        })
+    }
+
+    fn touches_own_road(&self, player: PlayerId, plot: usize) -> bool {
+        MAP_GRAPH.plot_edges[plot]
+            .iter()
+            .any(|&neighbor| self.roads.get(&(plot.min(neighbor), plot.max(neighbor))) == Some(&player))
+    }
+
+    fn check_playable(&self, player: PlayerId, card: DevelopmentCard) -> Result<(), GameError> {
+        if self.has_played_development {
+            Err(GameError::AlreadyPlayedDevelopment)
+        } else if !self.players[player].development_cards.contains(&card) {
+            Err(GameError::NoSuchCard)
+        } else {
+            Ok(())
+        }
+    }
+
+    /*
+     * whether the player can take the action back once it goes through, nothing hidden comes out of these and nobody else is part of them
+     * the road that ends a setup turn is left out since it starts the next player's turn
+     */
+    fn can_take_back(&self, action: &Action) -> bool {
+        match action {
+            Action::BuildRoad(..) => !matches!(self.phase, Phase::Setup { .. }),
+            Action::BuildSettlement(_)
+                | Action::BuildCity(_)
+                | Action::MoveRobber(_)
+                | Action::TradeWithBank(..) => true,
+            _ => false
+        }
+    }
+
+    fn get_checkpoint(&self, action: Action) -> Checkpoint {
+        Checkpoint {
+            action,
+            players: self.players.clone(),
+            roads: self.roads.clone(),
+            buildings: self.buildings.clone(),
+            robber: self.robber,
+            bank: self.bank,
+            phase: self.phase.clone(),
+            longest_road: self.longest_road,
+            largest_army: self.largest_army,
+            has_played_development: self.has_played_development,
+            offer: self.offer.clone()
+        }
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.players = checkpoint.players.clone();
+        self.roads = checkpoint.roads.clone();
+        self.buildings = checkpoint.buildings.clone();
+        self.robber = checkpoint.robber;
+        self.bank = checkpoint.bank;
+        self.phase = checkpoint.phase.clone();
+        self.longest_road = checkpoint.longest_road;
+        self.largest_army = checkpoint.largest_army;
+        self.has_played_development = checkpoint.has_played_development;
+        self.offer = checkpoint.offer.clone();
+    }
+
+    fn after_robber(rolled: bool) -> Phase {
+        if rolled { Phase::Main } else { Phase::Roll }
+    }
+
+    fn start_turn(&mut self, player: PlayerId, events: &mut Vec<GameEvent>) {
+        self.turn = player;
+        self.dice = None;
+        self.has_played_development = false;
+        events.push(GameEvent::TurnStarted { player });
+    }
+
+    fn pay(&mut self, player: PlayerId, cost: &Hand) {
+        self.players[player].hand.remove_hand(cost);
+        self.bank.add_hand(cost);
+    }
+
+    fn give(&mut self, player: PlayerId, resources: &Hand) {
+        self.bank.remove_hand(resources);
+        self.players[player].hand.add_hand(resources);
+    }
+
+    fn play(&mut self, player: PlayerId, card: DevelopmentCard, events: &mut Vec<GameEvent>) {
+        let cards = &mut self.players[player].development_cards;
+        let index = cards.iter().position(|&other| other == card).unwrap();
+        cards.remove(index);
+        self.has_played_development = true;
+        events.push(GameEvent::PlayedDevelopment { player, card });
+    }
+
+    fn place(&mut self, player: PlayerId, piece: Piece, events: &mut Vec<GameEvent>) {
+        let state = &mut self.players[player];
+        match piece {
+            Piece::Road(a, b) => {
+                state.roads_left -= 1;
+                self.roads.insert((a.min(b), a.max(b)), player);
+            },
+            Piece::Settlement(plot) => {
+                state.settlements_left -= 1;
+                self.buildings[plot] = Some((Building::Settlement, player));
+            },
+            Piece::City(plot) => {
+                state.cities_left -= 1;
+                state.settlements_left += 1;
+                self.buildings[plot] = Some((Building::City, player));
+            }
+        }
+
+        events.push(GameEvent::Built { player, piece });
+
+        /* settlements can cut roads so every road is measured again */
+        if !matches!(piece, Piece::City(_)) {
+            self.update_longest_road(events);
+        }
+    }
+
+    /* hand out resources for a roll, a resource the bank can't cover for everyone goes to nobody unless only one player wanted it */
+    fn produce(&mut self, roll: u8, events: &mut Vec<GameEvent>) {
+        let mut production = vec![Hand::default(); self.players.len()];
+        for (tile, board_tile) in self.board.tiles.iter().enumerate() {
+            let resource = match board_tile.resource {
+                TileResource::Of(resource) if board_tile.roll == roll && tile != self.robber => resource,
+                _ => continue
+            };
+
+            for &plot in MAP_GRAPH.tile_plots[tile].iter() {
+                if let Some((building, owner)) = self.buildings[plot] {
+                    production[owner].add(resource, if building == Building::City { 2 } else { 1 });
+                }
+            }
+        }
+
+        for resource in Resource::ALL {
+            let wanted: u8 = production.iter().map(|hand| hand.get(resource)).sum();
+            let takers = production.iter().filter(|hand| hand.get(resource) > 0).count();
+            if wanted > self.bank.get(resource) {
+                for hand in production.iter_mut().filter(|hand| hand.get(resource) > 0) {
+                    let count = hand.get(resource);
+                    hand.remove(resource, if takers == 1 { count - self.bank.get(resource) } else { count });
+                }
+            }
+        }
+
+        for (player, resources) in production.into_iter().enumerate() {
+            if !resources.is_empty() {
+                self.give(player, &resources);
+                events.push(GameEvent::Produced { player, resources });
+            }
+        }
+    }
+
+    fn update_longest_road(&mut self, events: &mut Vec<GameEvent>) {
+        for player in 0..self.players.len() {
+            self.players[player].road_length = self.measure_longest_road(player);
+        }
+
+        let lengths: Vec<u8> = self.players.iter().map(|state| state.road_length).collect();
+        let holder = Self::award_holder(self.longest_road, &lengths, LONGEST_ROAD_MIN);
+        if holder != self.longest_road {
+            self.longest_road = holder;
+            events.push(GameEvent::LongestRoad { player: holder });
+        }
+    }
+
+    fn update_largest_army(&mut self, events: &mut Vec<GameEvent>) {
+        let armies: Vec<u8> = self.players.iter().map(|state| state.knights_played).collect();
+        let holder = Self::award_holder(self.largest_army, &armies, LARGEST_ARMY_MIN);
+        if holder != self.largest_army {
+            self.largest_army = holder;
+            events.push(GameEvent::LargestArmy { player: holder });
+        }
+    }
+
+    /* the holder keeps an award until someone beats them, when nobody clearly leads (like the holder's road got cut) nobody has it */
+    fn award_holder(holder: Option<PlayerId>, scores: &[u8], minimum: u8) -> Option<PlayerId> {
+        let best = scores.iter().copied().max().unwrap_or(0);
+        if best < minimum {
+            return None
+        }
+
+        if let Some(holder) = holder {
+            if scores[holder] == best {
+                return Some(holder)
+            }
+        }
+
+        let leaders: Vec<PlayerId> = (0..scores.len()).filter(|&player| scores[player] == best).collect();
+        if leaders.len() == 1 { Some(leaders[0]) } else { None }
+    }
+
+    fn measure_longest_road(&self, player: PlayerId) -> u8 {
+        let mut used = Vec::new();
+        self.roads
+            .iter()
+            .filter(|(_, &owner)| owner == player)
+            .flat_map(|(&(a, b), _)| [a, b])
+            .map(|plot| self.measure_trail(player, plot, &mut used))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /* longest trail of the player's roads leaving the plot without reusing a road, other players' buildings cut the trail */
+    fn measure_trail(&self, player: PlayerId, plot: usize, used: &mut Vec<(usize, usize)>) -> u8 {
+        if !used.is_empty() && matches!(self.buildings[plot], Some((_, owner)) if owner != player) {
+            return 0
+        }
+
+        let mut longest = 0;
+        for &next in MAP_GRAPH.plot_edges[plot].iter() {
+            let edge = (plot.min(next), plot.max(next));
+            if self.roads.get(&edge) == Some(&player) && !used.contains(&edge) {
+                used.push(edge);
+                longest = longest.max(1 + self.measure_trail(player, next, used));
+                used.pop();
+            }
+        }
+        longest
+    }
+}
+
+/* taking actions back, the game has to come back exactly as it was and only for what shows nobody anything */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::bot;
+
+    use rand::rngs::StdRng;
+
+    const SEED: u64 = 7;
+    const PLENTY: Hand = Hand([10; Resource::NUM_TYPES]);
+
+    /* bots play the setup and the first roll, then the current player gets plenty to build with */
+    fn start_main() -> GameState {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut game = GameState::new(Board::generate(&mut rng), 4, 10, SEED);
+        while game.phase != Phase::Main {
+            let player = game.get_actors()[0];
+            let action = bot::choose_action(&game, player, &mut rng).unwrap();
+            game.apply(player, action).unwrap();
+        }
+        game.players[game.turn].hand = PLENTY;
+        game
+    }
+
+    /* apply the first legal action the filter picks and keep the game as it was before it */
+    fn take(game: &mut GameState, before: &mut Vec<GameState>, filter: impl Fn(&Action) -> bool) -> Action {
+        let action = game.get_legal_actions(game.turn).into_iter().find(filter).unwrap();
+        before.push(game.clone());
+        game.apply(game.turn, action).unwrap();
+        action
+    }
+
+    fn assert_refused(game: &GameState) {
+        assert!(game.validate(game.turn, &Action::Undo).is_err());
+        assert!(!game.get_legal_actions(game.turn).contains(&Action::Undo));
+    }
+
+    #[test]
+    fn undo_builds_and_trades() {
+        let mut game = start_main();
+        let player = game.turn;
+        let mut before = Vec::new();
+        for _ in 0..5 {
+            take(&mut game, &mut before, |action| matches!(action, Action::BuildRoad(..)));
+        }
+        assert_eq!(game.longest_road, Some(player));
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildSettlement(_)));
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildCity(_)));
+        take(&mut game, &mut before, |action| matches!(action, Action::TradeWithBank(..)));
+
+        while let Some(state) = before.pop() {
+            let action = state.checkpoints.last().map(|checkpoint| checkpoint.action);
+            let events = game.apply(player, Action::Undo).unwrap();
+            assert!(matches!(events[..], [GameEvent::Undone { player: undone, .. }] if undone == player));
+            assert_eq!(game, state);
+            assert_eq!(game.checkpoints.last().map(|checkpoint| checkpoint.action), action);
+        }
+        assert_eq!(game.apply(player, Action::Undo), Err(GameError::NothingToUndo));
+    }
+
+    /* the knight can't be taken back but moving the robber it brought can */
+    #[test]
+    fn undo_robber_move() {
+        let mut game = start_main();
+        let player = game.turn;
+        game.players[player].development_cards.push(DevelopmentCard::Knight);
+        game.apply(player, Action::PlayKnight).unwrap();
+        assert_refused(&game);
+
+        let mut before = Vec::new();
+        take(&mut game, &mut before, |action| matches!(action, Action::MoveRobber(_)));
+        game.apply(player, Action::Undo).unwrap();
+        assert_eq!(game, before[0]);
+        assert_eq!(game.phase, Phase::MoveRobber { rolled: true });
+    }
+
+    /* players see their own hand, public spectators see nobody's and god view sees everybody's */
+    #[test]
+    fn views_hide_what_the_viewer_can_not_see() {
+        let mut game = start_main();
+        let player = game.turn;
+        let other = (player + 1) % game.players.len();
+        game.players[player].development_cards.push(DevelopmentCard::VictoryPoint);
+
+        let own = game.view_for(Viewer::Player(player));
+        let others = game.view_for(Viewer::Player(other));
+        assert_eq!(own.players[player].hand, Some(PLENTY));
+        assert_eq!(others.players[player].hand, None);
+        assert_eq!(others.players[player].development_cards, None);
+        assert_eq!(others.players[player].resource_count, PLENTY.total());
+        assert_eq!(others.players[player].development_count, 1);
+        assert_eq!(own.players[player].victory_points, others.players[player].victory_points + 1);
+        assert!(!own.legal_actions.is_empty());
+
+        let public = game.view_for(Viewer::Public);
+        assert!(public.players.iter().all(|view| view.hand.is_none() && view.development_cards.is_none()));
+        assert!(public.legal_actions.is_empty());
+
+        let god = game.view_for(Viewer::God);
+        assert!(god.players.iter().zip(game.players.iter()).all(|(view, state)| view.hand == Some(state.hand)));
+        assert_eq!(god.players[player].victory_points, own.players[player].victory_points);
+        assert!(god.legal_actions.is_empty());
+    }
+
+    #[test]
+    fn events_hide_what_the_viewer_can_not_see() {
+        let stole = GameEvent::Stole { thief: 0, victim: 1, resource: Some(Resource::Ore) };
+        let bought = GameEvent::BoughtDevelopment { player: 0, card: Some(DevelopmentCard::Knight) };
+        for viewer in [Viewer::Player(0), Viewer::God] {
+            assert_eq!(stole.redact_for(viewer), stole);
+            assert_eq!(bought.redact_for(viewer), bought);
+        }
+        assert_eq!(stole.redact_for(Viewer::Player(1)), stole);
+        for viewer in [Viewer::Player(2), Viewer::Public] {
+            assert_eq!(stole.redact_for(viewer), GameEvent::Stole { thief: 0, victim: 1, resource: None });
+            assert_eq!(bought.redact_for(viewer), GameEvent::BoughtDevelopment { player: 0, card: None });
+        }
+    }
+
+    /* the build that spent what was offered cancels the offer, undoing it puts the offer back up */
+    #[test]
+    fn undo_brings_back_offer() {
+        let mut game = start_main();
+        let player = game.turn;
+        let other = (player + 1) % game.players.len();
+        game.players[player].hand = Hand::of(Resource::Lumber, 1);
+        game.players[player].hand.add(Resource::Brick, 1);
+        game.players[other].hand = PLENTY;
+        game.apply(player, Action::OfferTrade(Some(other), Hand::of(Resource::Lumber, 1), Hand::of(Resource::Ore, 1))).unwrap();
+
+        let mut before = Vec::new();
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildRoad(..)));
+        assert_eq!(game.offer, None);
+
+        let events = game.apply(player, Action::Undo).unwrap();
+        assert!(matches!(events[..], [GameEvent::Undone { .. }]));
+        assert_eq!(game, before[0]);
+        assert!(game.offer.is_some());
+        game.apply(other, Action::AcceptTrade).unwrap();
+    }
+
+    #[test]
+    fn undo_refused_after_roll() {
+        let mut game = start_main();
+        game.apply(game.turn, Action::EndTurn).unwrap();
+        assert_eq!(game.apply(game.turn, Action::Undo), Err(GameError::NothingToUndo));
+        game.apply(game.turn, Action::Roll).unwrap();
+        assert_refused(&game);
+    }
+
+    #[test]
+    fn undo_refused_after_steal() {
+        let mut game = start_main();
+        let player = game.turn;
+        let mut before = Vec::new();
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildRoad(..)));
+        game.players[player].development_cards.push(DevelopmentCard::Knight);
+        game.apply(player, Action::PlayKnight).unwrap();
+
+        /* anywhere next to someone else's building, they are all given something to steal */
+        for other in (0..game.players.len()).filter(|&other| other != player) {
+            game.players[other].hand = PLENTY;
+        }
+        let tile = (0..game.board.tiles.len())
+            .find(|&tile| tile != game.robber && MAP_GRAPH.tile_plots[tile].iter().any(|&plot| matches!(game.buildings[plot], Some((_, owner)) if owner != player)))
+            .unwrap();
+        game.apply(player, Action::MoveRobber(tile)).unwrap();
+        assert!(game.validate(player, &Action::Undo).is_ok());
+
+        let victim = game.get_steal_victims()[0];
+        game.apply(player, Action::Steal(victim)).unwrap();
+        assert_refused(&game);
+    }
+
+    #[test]
+    fn undo_refused_after_trade() {
+        let mut game = start_main();
+        let player = game.turn;
+        let other = (player + 1) % game.players.len();
+        game.players[other].hand = PLENTY;
+        let mut before = Vec::new();
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildRoad(..)));
+
+        game.apply(player, Action::OfferTrade(Some(other), Hand::of(Resource::Lumber, 1), Hand::of(Resource::Ore, 1))).unwrap();
+        game.apply(other, Action::AcceptTrade).unwrap();
+        assert_refused(&game);
+    }
+
+    #[test]
+    fn undo_refused_after_buying_development() {
+        let mut game = start_main();
+        let mut before = Vec::new();
+        take(&mut game, &mut before, |action| matches!(action, Action::BuildRoad(..)));
+        game.apply(game.turn, Action::BuyDevelopment).unwrap();
+        assert_refused(&game);
+    }
+
+    #[test]
+    fn undo_refused_with_nothing_to_take_back() {
+        let game = start_main();
+        assert!(game.checkpoints.is_empty());
+        assert_eq!(game.validate(game.turn, &Action::Undo), Err(GameError::NothingToUndo));
+    }
+}
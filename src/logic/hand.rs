@@ -0,0 +1,89 @@
+/*
+ * hand.rs
+ * a count of every kind of resource, used for hands, the bank, costs and anything else that moves resources around
+ */
+
+use crate::enums::Resource;
+
+use std::fmt;
+
+pub const ROAD_COST: Hand = Hand([0, 0, 0, 1, 1]);
+pub const SETTLEMENT_COST: Hand = Hand([0, 1, 1, 1, 1]);
+pub const CITY_COST: Hand = Hand([3, 0, 2, 0, 0]);
+pub const DEVELOPMENT_COST: Hand = Hand([1, 1, 1, 0, 0]);
+
+/* counts are in the same order as Resource::ALL */
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Hand(pub [u8; Resource::NUM_TYPES]);
+
+impl Hand {
+    pub fn of(resource: Resource, count: u8) -> Self {
+        let mut hand = Hand::default();
+        hand.add(resource, count);
+        hand
+    }
+
+    pub fn get(&self, resource: Resource) -> u8 {
+        self.0[resource.get_index()]
+    }
+
+    pub fn add(&mut self, resource: Resource, count: u8) {
+        self.0[resource.get_index()] += count;
+    }
+
+    /* only call this after making sure the hand has enough */
+    pub fn remove(&mut self, resource: Resource, count: u8) {
+        self.0[resource.get_index()] -= count;
+    }
+
+    pub fn add_hand(&mut self, other: &Hand) {
+        for resource in Resource::ALL {
+            self.add(resource, other.get(resource));
+        }
+    }
+
+    /* only call this after making sure the hand contains the other hand */
+    pub fn remove_hand(&mut self, other: &Hand) {
+        for resource in Resource::ALL {
+            self.remove(resource, other.get(resource));
+        }
+    }
+
+    pub fn contains(&self, other: &Hand) -> bool {
+        Resource::ALL.iter().all(|&resource| self.get(resource) >= other.get(resource))
+    }
+
+    pub fn total(&self) -> u8 {
+        self.0.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /* every resource with a nonzero count */
+    pub fn iter(&self) -> impl Iterator<Item = (Resource, u8)> + '_ {
+        Resource::ALL
+            .iter()
+            .map(|&resource| (resource, self.get(resource)))
+            .filter(|&(_, count)| count > 0)
+    }
+}
+
+/* reads like "2 ore, 1 wheat" */
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("nothing")
+        }
+
+        for (i, (resource, count)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}", count, resource.get_name())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * history.rs
+ * a game as the actions that were played in it
+ *
+ * a history starts from some state of the game (a new game or one loaded from a save) and lists every action that went through after it
+ * together with the events it caused, which is where the random outcomes are (dice, stolen cards and development cards drawn)
+ * the rng is part of the starting state so applying the actions again plays out exactly like the recorded events say,
+ * a history that doesn't is refused since it was recorded under different rules
+ */
+
+use super::game::*;
+
+/* one action that went through and what came of it */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub player: PlayerId,
+    pub action: Action,
+    pub events: Vec<GameEvent>
+}
+
+#[derive(Debug)]
+pub struct History {
+    /* the state before every record and the one after the last */
+    states: Vec<GameState>,
+    records: Vec<Record>,
+    /* how many records were played when each turn started, the first turn is whatever the history starts in */
+    turns: Vec<usize>
+}
+
+impl History {
+    /* play every record out from the starting state, the error says which record came out different */
+    pub fn new(start: GameState, records: Vec<Record>) -> Result<History, String> {
+        let mut states = Vec::with_capacity(records.len() + 1);
+        let mut turns = vec![0];
+        let mut state = start;
+        for (i, record) in records.iter().enumerate() {
+            let mut next = state.clone();
+            match next.apply(record.player, record.action) {
+                Ok(events) if events == record.events => (),
+                Ok(_) => return Err(format!("action {} didn't play out like it was recorded", i + 1)),
+                Err(err) => return Err(format!("action {} can't be played: {}", i + 1, err))
+            }
+            states.push(std::mem::replace(&mut state, next));
+
+            if record.events.iter().any(|event| matches!(event, GameEvent::TurnStarted { .. })) {
+                turns.push(i + 1);
+            }
+        }
+        states.push(state);
+
+        Ok(History { states, records, turns })
+    }
+
+    /* positions go from 0 (before any record) to the record count (after all of them) */
+    pub fn get_record_count(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn get_state(&self, position: usize) -> &GameState {
+        &self.states[position.min(self.records.len())]
+    }
+
+    /* the records played to get from one position to the other */
+    pub fn get_records(&self, from: usize, to: usize) -> &[Record] {
+        &self.records[from.min(to)..to.min(self.records.len())]
+    }
+
+    pub fn get_turn_count(&self) -> usize {
+        self.turns.len()
+    }
+
+    /* where the turn started, turns past the last one go to the end */
+    pub fn get_turn_start(&self, turn: usize) -> usize {
+        self.turns.get(turn).copied().unwrap_or(self.records.len())
+    }
+
+    /* the turn being played at the position */
+    pub fn get_turn_at(&self, position: usize) -> usize {
+        self.turns.iter().rposition(|&start| start <= position).unwrap_or(0)
+    }
+}
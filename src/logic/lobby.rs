@@ -0,0 +1,340 @@
+/*
+ * lobby.rs
+ * the pre-game lobby where players take a seat, pick a name and color, and mark themselves ready
+ * the host (first client to join) picks the map and options, can kick players and can fill seats with bots
+ */
+
+use super::clock::TimeControl;
+
+use crate::enums::PlayerColor;
+
+use std::fmt;
+
+/* identifier the server hands out to every connection */
+pub type ClientId = u64;
+
+pub const MAX_SEATS: usize = 4;
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_NAME_LENGTH: usize = 20;
+
+const DEFAULT_VICTORY_POINTS: u8 = 10;
+pub const MIN_VICTORY_POINTS: u8 = 3;
+pub const MAX_VICTORY_POINTS: u8 = 20;
+
+/* who is sitting in a seat */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Occupant {
+    Client(ClientId),
+    Bot
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seat {
+    pub name: String,
+    pub color: PlayerColor,
+    pub is_ready: bool,
+    pub occupant: Occupant
+}
+
+/* options the host picks before the game starts, the map is generated from the seed so every client builds the same board */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GameOptions {
+    pub map_seed: u64,
+    pub victory_points: u8,
+    pub time_control: TimeControl
+}
+
+impl GameOptions {
+    pub fn new(map_seed: u64) -> Self {
+        GameOptions {
+            map_seed,
+            victory_points: DEFAULT_VICTORY_POINTS,
+            time_control: TimeControl::default()
+        }
+    }
+}
+
+/* everything needed to start a game once the lobby is ready (players are in turn order) */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSetup {
+    pub players: Vec<Seat>,
+    pub options: GameOptions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyError {
+    Full,
+    NotHost,
+    NotSeated,
+    NoSuchSeat,
+    ColorTaken,
+    InvalidName,
+    InvalidOptions,
+    CannotKickHost
+}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Full => "the lobby is full",
+            Self::NotHost => "only the host can do that",
+            Self::NotSeated => "you are not seated",
+            Self::NoSuchSeat => "that seat is empty",
+            Self::ColorTaken => "that color is taken",
+            Self::InvalidName => "names must be 1 to 20 printable characters",
+            Self::InvalidOptions => "those options are not allowed",
+            Self::CannotKickHost => "the host cannot be kicked"
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lobby {
+    pub seats: Vec<Option<Seat>>,
+    pub options: GameOptions,
+    pub host: Option<ClientId>
+}
+
+impl Lobby {
+    pub fn new(options: GameOptions) -> Self {
+        Lobby {
+            seats: vec![None; MAX_SEATS],
+            options,
+            host: None
+        }
+    }
+
+    pub fn seat_of(&self, client: ClientId) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|seat| matches!(seat, Some(Seat { occupant: Occupant::Client(id), .. }) if *id == client))
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.seats.iter().filter(|seat| seat.is_some()).count()
+    }
+
+    /* the lobby is ready to start when enough players are seated, someone of them isn't a bot and every one of them is ready */
+    pub fn is_ready(&self) -> bool {
+        self.player_count() >= MIN_PLAYERS
+            && self.seats.iter().flatten().any(|seat| seat.occupant != Occupant::Bot)
+            && self.seats.iter().flatten().all(|seat| seat.is_ready)
+    }
+
+    /* seat a client in the first free seat with the first free color, the first client to join becomes the host */
+    pub fn join(&mut self, client: ClientId, name: &str) -> Result<usize, LobbyError> {
+        let name = Self::validate_name(name)?;
+        let index = self.seats.iter().position(|seat| seat.is_none()).ok_or(LobbyError::Full)?;
+        let color = self.free_color().ok_or(LobbyError::Full)?;
+
+        self.seats[index] = Some(Seat {
+            name,
+            color,
+            is_ready: false,
+            occupant: Occupant::Client(client)
+        });
+
+        if self.host.is_none() {
+            self.host = Some(client);
+        }
+
+        Ok(index)
+    }
+
+    /* free the seat of a client, if they were the host then hosting passes on to the next seated client */
+    pub fn leave(&mut self, client: ClientId) {
+        if let Some(index) = self.seat_of(client) {
+            self.seats[index] = None;
+        }
+
+        if self.host == Some(client) {
+            self.host = self.seats
+                .iter()
+                .flatten()
+                .find_map(|seat| if let Occupant::Client(id) = seat.occupant { Some(id) } else { None });
+        }
+    }
+
+    pub fn set_name(&mut self, client: ClientId, name: &str) -> Result<(), LobbyError> {
+        let name = Self::validate_name(name)?;
+        self.seat_mut(client)?.name = name;
+        Ok(())
+    }
+
+    pub fn set_color(&mut self, client: ClientId, color: PlayerColor) -> Result<(), LobbyError> {
+        let index = self.seat_of(client).ok_or(LobbyError::NotSeated)?;
+        if self.seats
+            .iter()
+            .enumerate()
+            .any(|(i, seat)| i != index && matches!(seat, Some(seat) if seat.color == color))
+        {
+            return Err(LobbyError::ColorTaken)
+        }
+
+        self.seats[index].as_mut().unwrap().color = color;
+        Ok(())
+    }
+
+    pub fn set_ready(&mut self, client: ClientId, is_ready: bool) -> Result<(), LobbyError> {
+        self.seat_mut(client)?.is_ready = is_ready;
+        Ok(())
+    }
+
+    /* host only: empty a seat, returns the kicked client if the seat was not a bot */
+    pub fn kick(&mut self, by: ClientId, index: usize) -> Result<Option<ClientId>, LobbyError> {
+        self.check_host(by)?;
+        let seat = self.seats.get(index).cloned().flatten().ok_or(LobbyError::NoSuchSeat)?;
+        match seat.occupant {
+            Occupant::Client(client) if client == by => Err(LobbyError::CannotKickHost),
+            Occupant::Client(client) => {
+                self.leave(client);
+                Ok(Some(client))
+            },
+            Occupant::Bot => {
+                self.seats[index] = None;
+                Ok(None)
+            }
+        }
+    }
+
+    /* host only: fill the first free seat with a bot, bots are always ready */
+    pub fn add_bot(&mut self, by: ClientId) -> Result<usize, LobbyError> {
+        self.check_host(by)?;
+        let index = self.seats.iter().position(|seat| seat.is_none()).ok_or(LobbyError::Full)?;
+        let color = self.free_color().ok_or(LobbyError::Full)?;
+        let bot_number = 1 + self.seats.iter().flatten().filter(|seat| seat.occupant == Occupant::Bot).count();
+
+        self.seats[index] = Some(Seat {
+            name: format!("Bot {}", bot_number),
+            color,
+            is_ready: true,
+            occupant: Occupant::Bot
+        });
+
+        Ok(index)
+    }
+
+    /* host only: change the game options, everyone has to ready up again since they agreed to different options */
+    pub fn set_options(&mut self, by: ClientId, options: GameOptions) -> Result<(), LobbyError> {
+        self.check_host(by)?;
+        if !(MIN_VICTORY_POINTS..=MAX_VICTORY_POINTS).contains(&options.victory_points) || !options.time_control.is_valid() {
+            return Err(LobbyError::InvalidOptions)
+        }
+
+        self.options = options;
+        for seat in self.seats.iter_mut().flatten() {
+            if let Occupant::Client(_) = seat.occupant {
+                seat.is_ready = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /* the players of the game in seat order */
+    pub fn to_setup(&self) -> GameSetup {
+        GameSetup {
+            players: self.seats.iter().flatten().cloned().collect(),
+            options: self.options
+        }
+    }
+
+    fn free_color(&self) -> Option<PlayerColor> {
+        PlayerColor::ALL
+            .iter()
+            .copied()
+            .find(|&color| !self.seats.iter().flatten().any(|seat| seat.color == color))
+    }
+
+    fn seat_mut(&mut self, client: ClientId) -> Result<&mut Seat, LobbyError> {
+        let index = self.seat_of(client).ok_or(LobbyError::NotSeated)?;
+        Ok(self.seats[index].as_mut().unwrap())
+    }
+
+    fn check_host(&self, client: ClientId) -> Result<(), LobbyError> {
+        if self.host == Some(client) { Ok(()) } else { Err(LobbyError::NotHost) }
+    }
+
+    fn validate_name(name: &str) -> Result<String, LobbyError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH || name.chars().any(|c| c.is_control()) {
+            Err(LobbyError::InvalidName)
+        } else {
+            Ok(name.to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosting_passes_on() {
+        let mut lobby = Lobby::new(GameOptions::new(0));
+        assert_eq!(lobby.join(1, "Ann"), Ok(0));
+        assert_eq!(lobby.join(2, " Bob "), Ok(1));
+        assert_eq!(lobby.host, Some(1));
+        assert_eq!(lobby.seats[1].as_ref().unwrap().name, "Bob");
+        assert_ne!(lobby.seats[0].as_ref().unwrap().color, lobby.seats[1].as_ref().unwrap().color);
+
+        assert_eq!(lobby.kick(2, 0), Err(LobbyError::NotHost));
+        assert_eq!(lobby.kick(1, 0), Err(LobbyError::CannotKickHost));
+        lobby.leave(1);
+        assert_eq!(lobby.host, Some(2));
+        assert_eq!(lobby.join(3, "Cy"), Ok(0));
+        assert_eq!(lobby.kick(2, 0), Ok(Some(3)));
+        assert_eq!(lobby.player_count(), 1);
+    }
+
+    #[test]
+    fn everyone_has_to_be_ready() {
+        let mut lobby = Lobby::new(GameOptions::new(0));
+        lobby.join(1, "Ann").unwrap();
+        lobby.set_ready(1, true).unwrap();
+        assert!(!lobby.is_ready());
+
+        lobby.add_bot(1).unwrap();
+        lobby.join(2, "Bob").unwrap();
+        assert!(!lobby.is_ready());
+
+        /* the one holding the game up leaving lets it start */
+        lobby.leave(2);
+        assert!(lobby.is_ready());
+
+        /* agreeing to other options has to happen again */
+        let options = GameOptions { victory_points: 12, ..lobby.options };
+        lobby.set_options(1, options).unwrap();
+        assert!(!lobby.is_ready());
+        assert_eq!(lobby.set_options(1, GameOptions { victory_points: MAX_VICTORY_POINTS + 1, ..options }), Err(LobbyError::InvalidOptions));
+    }
+
+    /* bots are always ready but nobody would be there to play with them */
+    #[test]
+    fn bots_alone_never_start() {
+        let mut lobby = Lobby::new(GameOptions::new(0));
+        lobby.join(1, "Ann").unwrap();
+        lobby.add_bot(1).unwrap();
+        lobby.add_bot(1).unwrap();
+        lobby.leave(1);
+        assert_eq!(lobby.player_count(), 2);
+        assert!(!lobby.is_ready());
+    }
+
+    #[test]
+    fn names_and_colors() {
+        let mut lobby = Lobby::new(GameOptions::new(0));
+        lobby.join(1, "Ann").unwrap();
+        lobby.join(2, "Bob").unwrap();
+        assert_eq!(lobby.join(3, ""), Err(LobbyError::InvalidName));
+        assert_eq!(lobby.set_name(1, &"a".repeat(MAX_NAME_LENGTH + 1)), Err(LobbyError::InvalidName));
+        assert_eq!(lobby.set_name(3, "Cy"), Err(LobbyError::NotSeated));
+
+        let taken = lobby.seats[1].as_ref().unwrap().color;
+        assert_eq!(lobby.set_color(1, taken), Err(LobbyError::ColorTaken));
+
+        lobby.join(3, "Cy").unwrap();
+        lobby.join(4, "Di").unwrap();
+        assert_eq!(lobby.join(5, "Ed"), Err(LobbyError::Full));
+    }
+}
@@ -0,0 +1,15 @@
+// logic
+// this module should contain everything related to starting/running the game loop
+
+pub mod lobby;
+pub mod session;
+pub mod board;
+pub mod hand;
+pub mod game;
+pub mod bot;
+pub mod notation;
+pub mod model;
+pub mod history;
+pub mod clock;
+pub mod stats;
+
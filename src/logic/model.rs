@@ -0,0 +1,346 @@
+/*
+ * model.rs
+ * the game as a client knows it
+ *
+ * the model is the one place a client keeps the state of the game, it is filled in from what the server sends and knows nothing about drawing
+ * drawings derive what they show from it by comparing it with what they showed last and animating the differences (see Map::sync and PlayerList::sync)
+ */
+
+use super::{board::Board, game::*, hand::Hand, session::Presence, clock::ClockView, stats::GameStats};
+
+use crate::enums::{Building, TileResource};
+
+use std::{collections::BTreeMap, time::{Duration, Instant}};
+
+/* the pieces on the board, roads are keyed with the smaller plot first */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pieces {
+    pub roads: BTreeMap<(usize, usize), PlayerId>,
+    pub buildings: BTreeMap<usize, (Building, PlayerId)>,
+    pub robber: usize
+}
+
+/* how one set of pieces turns into another */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PieceChange {
+    Placed(Piece, PlayerId),
+    Removed(Piece),
+    RobberMoved(usize)
+}
+
+impl Pieces {
+    /* an empty board with the robber on the tile */
+    pub fn new(robber: usize) -> Self {
+        Pieces { roads: BTreeMap::new(), buildings: BTreeMap::new(), robber }
+    }
+
+    pub fn place(&mut self, player: PlayerId, piece: Piece) {
+        match piece {
+            Piece::Road(a, b) => { self.roads.insert((a.min(b), a.max(b)), player); },
+            Piece::Settlement(plot) => { self.buildings.insert(plot, (Building::Settlement, player)); },
+            Piece::City(plot) => { self.buildings.insert(plot, (Building::City, player)); }
+        }
+    }
+
+    /* what has to change for these pieces to become the next ones, upgrades to a building count as placing it again */
+    pub fn diff(&self, next: &Pieces) -> Vec<PieceChange> {
+        let mut changes = Vec::new();
+        for (&(a, b), &player) in next.roads.iter() {
+            if self.roads.get(&(a, b)) != Some(&player) {
+                changes.push(PieceChange::Placed(Piece::Road(a, b), player));
+            }
+        }
+        for &(a, b) in self.roads.keys().filter(|edge| !next.roads.contains_key(edge)) {
+            changes.push(PieceChange::Removed(Piece::Road(a, b)));
+        }
+
+        for (&plot, &(building, player)) in next.buildings.iter() {
+            if self.buildings.get(&plot) != Some(&(building, player)) {
+                changes.push(PieceChange::Placed(get_building_piece(plot, building), player));
+            }
+        }
+        for (&plot, &(building, _)) in self.buildings.iter().filter(|(plot, _)| !next.buildings.contains_key(plot)) {
+            changes.push(PieceChange::Removed(get_building_piece(plot, building)));
+        }
+
+        if self.robber != next.robber {
+            changes.push(PieceChange::RobberMoved(next.robber));
+        }
+        changes
+    }
+}
+
+fn get_building_piece(plot: usize, building: Building) -> Piece {
+    match building {
+        Building::Settlement => Piece::Settlement(plot),
+        Building::City => Piece::City(plot)
+    }
+}
+
+/* one seat as the client knows it */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PlayerModel {
+    pub victory_points: u8,
+    pub resource_count: u8,
+    pub development_count: u8,
+    pub knights_played: u8,
+    pub road_length: u8,
+    pub presence: Presence,
+    /* only known for our own seat, or every seat with god view */
+    pub hand: Option<Hand>,
+    /* None when the game has no clocks */
+    pub clock: Option<PlayerClock>
+}
+
+/* one seat's clocks as of the last update */
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct PlayerClock {
+    /* when the turn runs out and how long it was, only while it is the player's clock that is running */
+    pub turn: Option<(Instant, Duration)>,
+    /* what was on the player's game clock when their turn started, None without a game clock */
+    pub bank: Option<Duration>
+}
+
+impl PlayerClock {
+    /* what is left of the turn and how much of it that is, from 0 to 1 */
+    pub fn get_turn_left(&self, now: Instant) -> Option<(Duration, f32)> {
+        self.turn.map(|(deadline, length)| {
+            let left = deadline.saturating_duration_since(now);
+            (left, if length.is_zero() { 0.0 } else { left.as_secs_f32()/length.as_secs_f32() })
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameModel {
+    pub board: Board,
+    pub pieces: Pieces,
+    pub players: Vec<PlayerModel>,
+    /* the seat being played from, None when spectating */
+    pub seat: Option<PlayerId>,
+    /* what the server says the seat can do right now */
+    pub legal_actions: Vec<Action>,
+    /* how many cards the seat has to discard and the trade on the table */
+    pub discard_count: u8,
+    pub offer: Option<TradeOffer>,
+    pub phase: Phase,
+    /* counted from every update since the model was made */
+    pub stats: GameStats
+}
+
+impl GameModel {
+    /* a game on the board nobody has built anything in yet */
+    pub fn new(board: Board, player_count: usize) -> Self {
+        GameModel {
+            pieces: Pieces::new(board.get_desert()),
+            stats: GameStats::new(player_count, board.tiles.len()),
+            board,
+            players: vec![PlayerModel::default(); player_count],
+            seat: None,
+            legal_actions: Vec::new(),
+            discard_count: 0,
+            offer: None,
+            phase: Phase::Setup { step: 0, settlement: None }
+        }
+    }
+
+    /* take on everything the view lets us see, presence isn't part of it */
+    pub fn apply_view(&mut self, view: &GameView) {
+        let mut pieces = Pieces::new(view.robber);
+        for &(a, b, player) in view.roads.iter() {
+            pieces.place(player, Piece::Road(a, b));
+        }
+        for &(plot, building, player) in view.buildings.iter() {
+            pieces.place(player, get_building_piece(plot, building));
+        }
+        self.pieces = pieces;
+
+        for (model, player) in self.players.iter_mut().zip(view.players.iter()) {
+            model.victory_points = player.victory_points;
+            model.resource_count = player.resource_count;
+            model.development_count = player.development_count;
+            model.knights_played = player.knights_played;
+            model.road_length = player.road_length;
+            model.hand = player.hand;
+        }
+
+        self.legal_actions = view.legal_actions.clone();
+        self.discard_count = match (&view.phase, self.seat) {
+            (Phase::Discard { amounts }, Some(seat)) => amounts.get(seat).copied().unwrap_or(0),
+            _ => 0
+        };
+        self.offer = view.offer.clone();
+        self.phase = view.phase.clone();
+    }
+
+    /* take on the view an action left behind, the stats count its events against the game like it was before them */
+    pub fn apply_update(&mut self, events: &[GameEvent], view: &GameView) {
+        /* victory points are charted from where the game stood when the first update came in */
+        if self.stats.get_victory_points().is_empty() {
+            self.stats.record_victory_points(self.get_victory_points());
+        }
+        self.stats.record(events, &self.board, &self.pieces, &self.phase);
+        self.apply_view(view);
+        if events.iter().any(|event| matches!(event, GameEvent::TurnStarted { .. } | GameEvent::Won { .. })) {
+            self.stats.record_victory_points(self.get_victory_points());
+        }
+    }
+
+    fn get_victory_points(&self) -> Vec<u8> {
+        self.players.iter().map(|player| player.victory_points).collect()
+    }
+
+    /* the clocks count down from when they were received */
+    pub fn apply_clocks(&mut self, clocks: Option<&ClockView>, received: Instant) {
+        for (player, model) in self.players.iter_mut().enumerate() {
+            model.clock = clocks.map(|clocks| PlayerClock {
+                turn: clocks.player.filter(|&running| running == player).map(|_| (
+                    received + Duration::from_millis(clocks.turn_left as u64),
+                    Duration::from_millis(clocks.turn_length as u64)
+                )),
+                bank: clocks.banks.as_ref().and_then(|banks| banks.get(player)).map(|&bank| Duration::from_millis(bank as u64))
+            });
+        }
+    }
+
+    /* just after the next second ticks off the running clock, None if no clock is running */
+    pub fn get_next_clock_tick(&self, now: Instant) -> Option<Instant> {
+        self.players
+            .iter()
+            .find_map(|player| player.clock.and_then(|clock| clock.turn))
+            .map(|(deadline, _)| deadline.saturating_duration_since(now))
+            .filter(|left| !left.is_zero())
+            .map(|left| now + Duration::from_nanos((left.as_nanos() % 1_000_000_000) as u64) + Duration::from_millis(1))
+    }
+
+    pub fn set_presence(&mut self, player: PlayerId, presence: Presence) {
+        if let Some(model) = self.players.get_mut(player) {
+            model.presence = presence;
+        }
+    }
+
+    /* the tiles that produce when the number is rolled, the robber's tile doesn't */
+    pub fn get_producing_tiles(&self, roll: u8) -> Vec<usize> {
+        self.board.tiles
+            .iter()
+            .enumerate()
+            .filter(|&(i, tile)| tile.roll == roll && tile.resource != TileResource::OfDesert && i != self.pieces.robber)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::bot;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const SEED: u64 = 7;
+
+    fn get_pieces(roads: &[(usize, usize, PlayerId)], buildings: &[(Piece, PlayerId)], robber: usize) -> Pieces {
+        let mut pieces = Pieces::new(robber);
+        for &(a, b, player) in roads {
+            pieces.place(player, Piece::Road(a, b));
+        }
+        for &(piece, player) in buildings {
+            pieces.place(player, piece);
+        }
+        pieces
+    }
+
+    /* bots play the setup so there is something on the board */
+    fn start_main() -> GameState {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut game = GameState::new(Board::generate(&mut rng), 4, 10, SEED);
+        while game.phase != Phase::Main {
+            let player = game.get_actors()[0];
+            let action = bot::choose_action(&game, player, &mut rng).unwrap();
+            game.apply(player, action).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn diff_places_removes_and_moves() {
+        let last = get_pieces(&[(3, 1, 0), (4, 5, 1)], &[(Piece::Settlement(5), 0), (Piece::Settlement(8), 1)], 2);
+        let next = get_pieces(&[(1, 3, 0), (7, 6, 2)], &[(Piece::City(5), 0)], 9);
+        assert_eq!(last.roads.keys().next(), Some(&(1, 3)));
+        assert!(last.diff(&last).is_empty());
+
+        /* roads first, then buildings, then the robber */
+        assert_eq!(last.diff(&next), vec![
+            PieceChange::Placed(Piece::Road(6, 7), 2),
+            PieceChange::Removed(Piece::Road(4, 5)),
+            PieceChange::Placed(Piece::City(5), 0),
+            PieceChange::Removed(Piece::Settlement(8)),
+            PieceChange::RobberMoved(9)
+        ]);
+        /* going back a city is a settlement placed again */
+        assert_eq!(next.diff(&last), vec![
+            PieceChange::Placed(Piece::Road(4, 5), 1),
+            PieceChange::Removed(Piece::Road(6, 7)),
+            PieceChange::Placed(Piece::Settlement(5), 0),
+            PieceChange::Placed(Piece::Settlement(8), 1),
+            PieceChange::RobberMoved(2)
+        ]);
+
+        /* a road that changes hands is placed again without being removed */
+        let taken = get_pieces(&[(1, 3, 2), (4, 5, 1)], &[(Piece::Settlement(5), 0), (Piece::Settlement(8), 1)], 2);
+        assert_eq!(last.diff(&taken), vec![PieceChange::Placed(Piece::Road(1, 3), 2)]);
+    }
+
+    #[test]
+    fn views_fill_in_the_model() {
+        let mut game = start_main();
+        let player = game.turn;
+        let other = (player + 1) % game.players.len();
+        let mut model = GameModel::new(game.board.clone(), game.players.len());
+        model.seat = Some(player);
+        model.set_presence(other, Presence::Covered);
+        model.apply_view(&game.view_for(Viewer::Player(player)));
+
+        assert_eq!(model.pieces.roads, game.roads);
+        assert_eq!(model.pieces.buildings.len(), game.buildings.iter().flatten().count());
+        assert!(model.pieces.buildings.iter().all(|(&plot, &building)| game.buildings[plot] == Some(building)));
+        assert_eq!(model.pieces.robber, game.robber);
+        assert_eq!(model.players[player].hand, Some(game.players[player].hand));
+        assert_eq!(model.players[other].hand, None);
+        assert_eq!(model.players[other].resource_count, game.players[other].hand.total());
+        assert_eq!(model.legal_actions, game.get_legal_actions(player));
+        assert_eq!(model.phase, Phase::Main);
+        /* presence isn't in the view so it is kept */
+        assert_eq!(model.players[other].presence, Presence::Covered);
+
+        /* only the seat's own discard is counted */
+        game.phase = Phase::Discard { amounts: (0..game.players.len() as u8).collect() };
+        model.apply_view(&game.view_for(Viewer::Player(player)));
+        assert_eq!(model.discard_count, player as u8);
+        model.seat = None;
+        model.apply_view(&game.view_for(Viewer::Public));
+        assert_eq!(model.discard_count, 0);
+        assert!(model.legal_actions.is_empty());
+        assert!(model.players.iter().all(|player| player.hand.is_none()));
+    }
+
+    /* only the running clock counts down and it ticks just after each second */
+    #[test]
+    fn clocks_count_from_when_they_came() {
+        let mut model = GameModel::new(Board::generate(&mut StdRng::seed_from_u64(SEED)), 4);
+        let received = Instant::now();
+        model.apply_clocks(Some(&ClockView { player: Some(1), turn_length: 60_000, turn_left: 15_500, banks: Some(vec![1000, 2000, 3000, 4000]) }), received);
+
+        assert_eq!(model.players[1].clock.unwrap().get_turn_left(received), Some((Duration::from_millis(15_500), 15.5/60.0)));
+        assert_eq!(model.players[0].clock.unwrap().get_turn_left(received), None);
+        assert_eq!(model.players[2].clock.unwrap().bank, Some(Duration::from_secs(3)));
+        assert_eq!(model.get_next_clock_tick(received), Some(received + Duration::from_millis(501)));
+        assert_eq!(model.get_next_clock_tick(received + Duration::from_secs(20)), None);
+
+        model.apply_clocks(Some(&ClockView { player: None, turn_length: 0, turn_left: 0, banks: None }), received);
+        assert!(model.players.iter().all(|player| player.clock == Some(PlayerClock::default())));
+        assert_eq!(model.get_next_clock_tick(received), None);
+        model.apply_clocks(None, received);
+        assert!(model.players.iter().all(|player| player.clock.is_none()));
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * notation.rs
+ * actions written out as text for the command line, like "build settlement 23" or "trade 4 ore for 1 wheat"
+ *
+ * plots, roads and tiles go by their number on the map graph and players go by @ and their name without spaces
+ * cards are written as counts and resources like "2 ore, 1 wool", commas and "and" are optional and a count of 1 can be left out
+ * every action formats to text that parses back into the same action
+ */
+
+use super::{game::*, hand::Hand};
+
+use crate::enums::Resource;
+
+/* the text to type for the action, names are the players' names by seat */
+pub fn format(action: &Action, names: &[String]) -> String {
+    match *action {
+        Action::Roll => String::from("roll"),
+        Action::Discard(hand) => format!("discard {}", hand),
+        Action::MoveRobber(tile) => format!("robber {}", tile),
+        Action::Steal(victim) => format!("steal {}", format_player(victim, names)),
+        Action::BuildRoad(a, b) => format!("build road {} {}", a, b),
+        Action::BuildSettlement(plot) => format!("build settlement {}", plot),
+        Action::BuildCity(plot) => format!("build city {}", plot),
+        Action::BuyDevelopment => String::from("buy development"),
+        Action::PlayKnight => String::from("play knight"),
+        Action::PlayRoadBuilding => String::from("play road building"),
+        Action::PlayYearOfPlenty(a, b) => format!("play year of plenty {} {}", a.get_name(), b.get_name()),
+        Action::PlayMonopoly(resource) => format!("play monopoly {}", resource.get_name()),
+        Action::TradeWithBank(give, get) => format!("trade {} for {}", give, get),
+        Action::OfferTrade(Some(to), give, get) => format!("offer {} {} for {}", format_player(to, names), give, get),
+        Action::OfferTrade(None, give, get) => format!("offer {} for {}", give, get),
+        Action::AcceptTrade => String::from("accept"),
+        Action::DeclineTrade => String::from("decline"),
+        Action::CancelTrade => String::from("cancel"),
+        Action::EndTurn => String::from("end turn"),
+        Action::Undo => String::from("undo")
+    }
+}
+
+/* read typed text into an action, the error says what was wrong with it */
+pub fn parse(text: &str, names: &[String]) -> Result<Action, String> {
+    let text = text.to_lowercase().replace(',', " ");
+    let words: Vec<&str> = text.split_whitespace().filter(|&word| word != "and").collect();
+
+    match words.as_slice() {
+        ["roll"] => Ok(Action::Roll),
+        ["end"] | ["end", "turn"] => Ok(Action::EndTurn),
+        ["buy"] | ["buy", "development"] => Ok(Action::BuyDevelopment),
+        ["discard", cards @ ..] => Ok(Action::Discard(parse_hand(cards)?)),
+        ["robber", tile] | ["move", "robber", tile] => Ok(Action::MoveRobber(parse_number(tile)?)),
+        ["steal", player] => Ok(Action::Steal(parse_player(player, names)?)),
+        ["build", "road", a, b] => Ok(Action::BuildRoad(parse_number(a)?, parse_number(b)?)),
+        ["build", "settlement", plot] => Ok(Action::BuildSettlement(parse_number(plot)?)),
+        ["build", "city", plot] => Ok(Action::BuildCity(parse_number(plot)?)),
+        ["build", ..] => Err(String::from("expected \"build road <plot> <plot>\", \"build settlement <plot>\" or \"build city <plot>\"")),
+        ["play", "knight"] => Ok(Action::PlayKnight),
+        ["play", "road", "building"] => Ok(Action::PlayRoadBuilding),
+        ["play", "year", "of", "plenty", a, b] => Ok(Action::PlayYearOfPlenty(parse_resource(a)?, parse_resource(b)?)),
+        ["play", "monopoly", resource] => Ok(Action::PlayMonopoly(parse_resource(resource)?)),
+        ["play", ..] => Err(String::from("expected \"play knight\", \"play road building\", \"play year of plenty <resource> <resource>\" or \"play monopoly <resource>\"")),
+        ["trade", cards @ ..] => {
+            let (give, get) = parse_exchange(cards).ok_or("expected \"trade <cards> for <cards>\"")?;
+            Ok(Action::TradeWithBank(parse_hand(give)?, parse_hand(get)?))
+        },
+        ["offer", player, cards @ ..] if player.starts_with('@') => {
+            let (give, get) = parse_exchange(cards).ok_or("expected \"offer @player <cards> for <cards>\"")?;
+            Ok(Action::OfferTrade(Some(parse_player(player, names)?), parse_hand(give)?, parse_hand(get)?))
+        },
+        ["offer", cards @ ..] => {
+            let (give, get) = parse_exchange(cards).ok_or("expected \"offer <cards> for <cards>\"")?;
+            Ok(Action::OfferTrade(None, parse_hand(give)?, parse_hand(get)?))
+        },
+        ["accept"] => Ok(Action::AcceptTrade),
+        ["decline"] => Ok(Action::DeclineTrade),
+        ["cancel"] => Ok(Action::CancelTrade),
+        ["undo"] => Ok(Action::Undo),
+        [] => Err(String::from("type a command like \"roll\" or \"build settlement 23\"")),
+        [word, ..] => Err(format!("unknown command {:?}", word))
+    }
+}
+
+fn format_player(player: PlayerId, names: &[String]) -> String {
+    match names.get(player) {
+        Some(name) => format!("@{}", name.split_whitespace().collect::<String>()),
+        None => format!("@{}", player + 1)
+    }
+}
+
+/* players by name or by seat number counting from 1 */
+fn parse_player(word: &str, names: &[String]) -> Result<PlayerId, String> {
+    let name = word.strip_prefix('@').ok_or_else(|| format!("expected a player like @name but got {:?}", word))?;
+    names
+        .iter()
+        .position(|other| other.split_whitespace().collect::<String>().to_lowercase() == name)
+        .or_else(|| name.parse::<usize>().ok().filter(|&seat| seat >= 1 && seat <= names.len()).map(|seat| seat - 1))
+        .ok_or_else(|| format!("nobody is called @{}", name))
+}
+
+fn parse_number(word: &str) -> Result<usize, String> {
+    word.parse().map_err(|_| format!("expected a number but got {:?}", word))
+}
+
+/* plurals are fine, "2 bricks" reads better than "2 brick" */
+fn parse_resource(word: &str) -> Result<Resource, String> {
+    Resource::from_name(word)
+        .or_else(|| word.strip_suffix('s').and_then(Resource::from_name))
+        .ok_or_else(|| format!("unknown resource {:?}", word))
+}
+
+/* cards like "2 ore 1 wool" or just "wool" for one */
+fn parse_hand(words: &[&str]) -> Result<Hand, String> {
+    let mut hand = Hand::default();
+    let mut words = words.iter();
+    while let Some(&word) = words.next() {
+        let (count, resource) = match word.parse::<u8>() {
+            Ok(count) => (count, words.next().ok_or_else(|| format!("expected a resource after {}", count))?),
+            Err(_) => (1, &word)
+        };
+        let resource = parse_resource(resource)?;
+        if hand.get(resource).checked_add(count).is_none() {
+            return Err(format!("that is too much {}", resource.get_name()))
+        }
+        hand.add(resource, count);
+    }
+
+    if hand.is_empty() {
+        return Err(String::from("expected some cards like \"2 ore\""))
+    }
+    Ok(hand)
+}
+
+/* split "<cards> for <cards>" around the for */
+fn parse_exchange<'a, 'b>(words: &'a [&'b str]) -> Option<(&'a [&'b str], &'a [&'b str])> {
+    let i = words.iter().position(|&word| word == "for")?;
+    Some((&words[..i], &words[i + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_names() -> Vec<String> {
+        vec![String::from("Andre"), String::from("Unbreakable Patches"), String::from("Seigward")]
+    }
+
+    fn hand(cards: &[(Resource, u8)]) -> Hand {
+        let mut hand = Hand::default();
+        for &(resource, count) in cards {
+            hand.add(resource, count);
+        }
+        hand
+    }
+
+    #[test]
+    fn every_action_parses_back() {
+        let names = get_names();
+        let give = hand(&[(Resource::Ore, 2), (Resource::Wool, 1)]);
+        let get = hand(&[(Resource::Lumber, 1)]);
+        let actions = [
+            Action::Roll,
+            Action::Discard(hand(&[(Resource::Brick, 3), (Resource::Wheat, 1)])),
+            Action::MoveRobber(7),
+            Action::Steal(1),
+            Action::BuildRoad(3, 4),
+            Action::BuildSettlement(23),
+            Action::BuildCity(0),
+            Action::BuyDevelopment,
+            Action::PlayKnight,
+            Action::PlayRoadBuilding,
+            Action::PlayYearOfPlenty(Resource::Wheat, Resource::Ore),
+            Action::PlayMonopoly(Resource::Wool),
+            Action::TradeWithBank(hand(&[(Resource::Ore, 4)]), get),
+            Action::OfferTrade(Some(2), give, get),
+            Action::OfferTrade(None, give, get),
+            Action::AcceptTrade,
+            Action::DeclineTrade,
+            Action::CancelTrade,
+            Action::EndTurn,
+            Action::Undo
+        ];
+        for action in actions.iter() {
+            let text = format(action, &names);
+            assert_eq!(parse(&text, &names).as_ref(), Ok(action), "{:?} formatted to {:?}", action, text);
+        }
+    }
+
+    #[test]
+    fn parses_loosely_written_actions() {
+        let names = get_names();
+        assert_eq!(parse("End", &names), Ok(Action::EndTurn));
+        assert_eq!(parse("move robber 3", &names), Ok(Action::MoveRobber(3)));
+        assert_eq!(parse("trade 4 bricks for wool", &names), Ok(Action::TradeWithBank(hand(&[(Resource::Brick, 4)]), hand(&[(Resource::Wool, 1)]))));
+        assert_eq!(parse("discard ore, ore and 1 wheat", &names), Ok(Action::Discard(hand(&[(Resource::Ore, 2), (Resource::Wheat, 1)]))));
+    }
+
+    /* names match without their spaces and in any case, seat numbers count from 1 */
+    #[test]
+    fn offers_go_to_players_by_name() {
+        let names = get_names();
+        let offer = |to| Ok(Action::OfferTrade(Some(to), hand(&[(Resource::Ore, 1)]), hand(&[(Resource::Wheat, 1)])));
+        assert_eq!(parse("offer @unbreakablepatches ore for wheat", &names), offer(1));
+        assert_eq!(parse("offer @UnbreakablePatches ore for wheat", &names), offer(1));
+        assert_eq!(parse("offer @seigward ore for wheat", &names), offer(2));
+        assert_eq!(parse("offer @1 ore for wheat", &names), offer(0));
+        assert!(parse("offer @unbreakable ore for wheat", &names).is_err());
+        assert!(parse("offer @4 ore for wheat", &names).is_err());
+        assert_eq!(format(&Action::Steal(1), &names), "steal @UnbreakablePatches");
+    }
+
+    #[test]
+    fn refuses_what_it_cant_read() {
+        let names = get_names();
+        for text in [
+            "",
+            "jump",
+            "build",
+            "build road 3",
+            "build city x",
+            "play",
+            "play monopoly gold",
+            "robber",
+            "steal andre",
+            "trade 4 ore",
+            "trade for wool",
+            "discard 2",
+            "discard 200 ore 100 ore",
+            "offer @nobody ore for wheat"
+        ] {
+            assert!(parse(text, &names).is_err(), "{:?} parsed", text);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * session.rs
+ * holding seats for players whose connection dropped mid-game
+ *
+ * every client gets a session token when they take a seat, reconnecting with it puts them back in that seat
+ * a seat is held for its player during the grace period and a bot plays it for good once the grace period runs out
+ * optionally a bot fills in sooner while the player is away and hands the seat back when they return
+ */
+
+use super::{lobby::*, game::GameView, clock::ClockView};
+
+use std::time::{Duration, Instant};
+
+/* secret handed to a client so it can prove which seat is theirs after reconnecting */
+pub type SessionToken = u64;
+
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SessionConfig {
+    /* how long a disconnected player's seat is kept for them */
+    pub grace_period: Duration,
+    /* how long a disconnected player can be away before a bot plays for them, if at all */
+    pub bot_takeover: Option<Duration>
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            grace_period: DEFAULT_GRACE_PERIOD,
+            bot_takeover: None
+        }
+    }
+}
+
+/* how a seat is being played right now */
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Presence {
+    #[default]
+    Connected,
+    /* disconnected, the game waits for them */
+    Away,
+    /* disconnected, a bot plays until they return */
+    Covered,
+    /* played by a bot for good */
+    Bot
+}
+
+impl Presence {
+    pub fn is_bot_controlled(&self) -> bool {
+        matches!(self, Self::Covered | Self::Bot)
+    }
+
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            Self::Connected => "",
+            Self::Away => "away",
+            Self::Covered => "away (bot)",
+            Self::Bot => "bot"
+        }
+    }
+}
+
+/* everything a client needs to pick a game back up, spectators have no seat */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSnapshot {
+    pub setup: GameSetup,
+    pub seat: Option<usize>,
+    pub presence: Vec<Presence>,
+    pub view: GameView,
+    pub clocks: Option<ClockView>
+}
+
+#[derive(Debug)]
+struct Session {
+    token: SessionToken,
+    client: Option<ClientId>,
+    disconnected_at: Option<Instant>,
+    presence: Presence
+}
+
+/* the sessions of every seat in a started game */
+#[derive(Debug)]
+pub struct Sessions {
+    seats: Vec<Session>,
+    config: SessionConfig
+}
+
+impl Sessions {
+    /* token_of is asked for the token of every seated client, seats without a token start out as bots */
+    pub fn new<F: Fn(ClientId) -> Option<SessionToken>>(setup: &GameSetup, config: SessionConfig, token_of: F) -> Self {
+        Sessions {
+            seats: setup.players
+                .iter()
+                .map(|seat| {
+                    match seat.occupant {
+                        Occupant::Client(client) => match token_of(client) {
+                            Some(token) => Session { token, client: Some(client), disconnected_at: None, presence: Presence::Connected },
+                            None => Session { token: 0, client: None, disconnected_at: None, presence: Presence::Bot }
+                        },
+                        Occupant::Bot => Session { token: 0, client: None, disconnected_at: None, presence: Presence::Bot }
+                    }
+                })
+                .collect(),
+            config
+        }
+    }
+
+    pub fn seat_of(&self, client: ClientId) -> Option<usize> {
+        self.seats.iter().position(|session| session.client == Some(client))
+    }
+
+    pub fn get_client(&self, seat: usize) -> Option<ClientId> {
+        self.seats[seat].client
+    }
+
+    pub fn get_presence(&self, seat: usize) -> Presence {
+        self.seats[seat].presence
+    }
+
+    pub fn get_presences(&self) -> Vec<Presence> {
+        self.seats.iter().map(|session| session.presence).collect()
+    }
+
+    /* the client's connection dropped, returns the seat they left behind */
+    pub fn disconnect(&mut self, client: ClientId, now: Instant) -> Option<usize> {
+        let seat = self.seat_of(client)?;
+        let session = &mut self.seats[seat];
+        session.client = None;
+        session.disconnected_at = Some(now);
+        session.presence = Presence::Away;
+        Some(seat)
+    }
+
+    /* 
+     * put a reconnecting client back in the seat their token belongs to
+     * returns the seat and the client that held it if its old connection has not noticed it is dead yet
+     */
+    pub fn resume(&mut self, token: SessionToken, client: ClientId) -> Option<(usize, Option<ClientId>)> {
+        let seat = self.seats
+            .iter()
+            .position(|session| session.presence != Presence::Bot && session.token == token)?;
+
+        let session = &mut self.seats[seat];
+        let replaced = session.client.replace(client);
+        session.disconnected_at = None;
+        session.presence = Presence::Connected;
+        Some((seat, replaced))
+    }
+
+    /* move away players along as time passes, returns the seats whose presence changed */
+    pub fn tick(&mut self, now: Instant) -> Vec<(usize, Presence)> {
+        let mut changes = Vec::new();
+        for (seat, session) in self.seats.iter_mut().enumerate() {
+            let away_for = match session.disconnected_at {
+                Some(disconnected_at) => now.saturating_duration_since(disconnected_at),
+                None => continue
+            };
+
+            let presence = if away_for >= self.config.grace_period {
+                Presence::Bot
+            } else if matches!(self.config.bot_takeover, Some(timeout) if away_for >= timeout) {
+                Presence::Covered
+            } else {
+                Presence::Away
+            };
+
+            if presence != session.presence {
+                session.presence = presence;
+                if presence == Presence::Bot {
+                    session.disconnected_at = None;
+                }
+                changes.push((seat, presence));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::PlayerColor;
+
+    const GRACE: Duration = Duration::from_secs(60);
+    const TAKEOVER: Duration = Duration::from_secs(10);
+
+    /* clients 1 and 2 hold the first two seats with tokens 11 and 12, the third seat is a bot */
+    fn get_sessions(bot_takeover: Option<Duration>) -> Sessions {
+        let seat = |occupant, color| Seat { name: String::from("player"), color, is_ready: true, occupant };
+        let setup = GameSetup {
+            players: vec![
+                seat(Occupant::Client(1), PlayerColor::ALL[0]),
+                seat(Occupant::Client(2), PlayerColor::ALL[1]),
+                seat(Occupant::Bot, PlayerColor::ALL[2])
+            ],
+            options: GameOptions::new(0)
+        };
+        Sessions::new(&setup, SessionConfig { grace_period: GRACE, bot_takeover }, |client| Some(10 + client))
+    }
+
+    #[test]
+    fn seats_are_held_and_resumed() {
+        let mut sessions = get_sessions(None);
+        let start = Instant::now();
+        assert_eq!(sessions.get_presences(), vec![Presence::Connected, Presence::Connected, Presence::Bot]);
+
+        assert_eq!(sessions.disconnect(2, start), Some(1));
+        assert_eq!(sessions.disconnect(2, start), None);
+        assert_eq!(sessions.seat_of(2), None);
+        assert_eq!(sessions.get_presence(1), Presence::Away);
+        assert!(sessions.tick(start + GRACE/2).is_empty());
+
+        /* only the token of the seat takes it back, from a new connection */
+        assert_eq!(sessions.resume(99, 3), None);
+        assert_eq!(sessions.resume(12, 3), Some((1, None)));
+        assert_eq!(sessions.seat_of(3), Some(1));
+        assert_eq!(sessions.get_presence(1), Presence::Connected);
+
+        /* the clock starts over every time the connection drops */
+        sessions.disconnect(3, start + GRACE/2);
+        assert!(sessions.tick(start + GRACE).is_empty());
+        assert_eq!(sessions.tick(start + GRACE/2 + GRACE), vec![(1, Presence::Bot)]);
+    }
+
+    /* a connection that hasn't noticed it is dead is handed back so the server can close it */
+    #[test]
+    fn resuming_replaces_stale_connections() {
+        let mut sessions = get_sessions(None);
+        assert_eq!(sessions.resume(11, 5), Some((0, Some(1))));
+        assert_eq!(sessions.seat_of(1), None);
+        assert_eq!(sessions.get_client(0), Some(5));
+    }
+
+    #[test]
+    fn bots_take_over() {
+        let mut sessions = get_sessions(Some(TAKEOVER));
+        let start = Instant::now();
+        sessions.disconnect(1, start);
+        assert!(sessions.tick(start + TAKEOVER/2).is_empty());
+        assert_eq!(sessions.tick(start + TAKEOVER), vec![(0, Presence::Covered)]);
+        assert!(sessions.get_presence(0).is_bot_controlled());
+        assert!(sessions.tick(start + TAKEOVER*2).is_empty());
+
+        /* the bot only covers until they are back, after the grace period the seat is the bot's for good */
+        assert_eq!(sessions.resume(11, 3), Some((0, None)));
+        assert!(!sessions.get_presence(0).is_bot_controlled());
+        sessions.disconnect(3, start);
+        assert_eq!(sessions.tick(start + GRACE), vec![(0, Presence::Bot)]);
+        assert_eq!(sessions.resume(11, 4), None);
+        assert!(sessions.tick(start + GRACE*2).is_empty());
+    }
+
+    /* seated clients the lobby never handed a token to can't ever come back so bots play them from the start */
+    #[test]
+    fn seats_without_tokens_are_bots() {
+        let setup = GameSetup {
+            players: vec![Seat { name: String::from("player"), color: PlayerColor::ALL[0], is_ready: true, occupant: Occupant::Client(1) }],
+            options: GameOptions::new(0)
+        };
+        let sessions = Sessions::new(&setup, SessionConfig::default(), |_| None);
+        assert_eq!(sessions.get_presences(), vec![Presence::Bot]);
+        assert_eq!(sessions.seat_of(1), None);
+    }
+}
@@ -0,0 +1,322 @@
+/*
+ * stats.rs
+ * numbers for looking back on a game, like whether the dice really had it in for someone
+ *
+ * stats are counted on the client from the events it is sent, so they only cover the game since the client started watching it
+ * a card stolen between two other players still counts as a card, the client just never learns which one it was
+ * an action that is taken back takes back what it counted too
+ */
+
+use super::{board::Board, game::*, hand::*, model::Pieces};
+
+use crate::enums::{Building, TileResource};
+use crate::render::drawing::map::MAP_GRAPH;
+
+/* the dice add up to anywhere from 2 to 12 */
+pub const MIN_ROLL: u8 = 2;
+pub const MAX_ROLL: u8 = 12;
+
+/* how many of the 36 ways two dice can land add up to the roll */
+pub fn get_roll_ways(roll: u8) -> u32 {
+    6 - (roll as i32 - 7).unsigned_abs()
+}
+
+/* where one player's cards came from and went, counted in cards */
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ResourceFlow {
+    /* from production, the bank (the second settlement and year of plenty), stealing and monopolies */
+    pub gained: u32,
+    /* discarded on a 7 or stolen */
+    pub lost_to_robber: u32,
+    /* given away in trades with the bank or other players and what came back for them */
+    pub traded_away: u32,
+    pub traded_for: u32,
+    /* on pieces and development cards */
+    pub spent: u32
+}
+
+/* everything an action can count, kept so taking the action back can put it back */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Counts {
+    rolls: Vec<u32>,
+    flows: Vec<ResourceFlow>,
+    production: Vec<u32>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameStats {
+    counts: Counts,
+    /* every player's victory points as each turn started and when the game was won */
+    victory_points: Vec<Vec<u8>>,
+    /* the counts before every action of the turn so far */
+    checkpoints: Vec<Counts>
+}
+
+impl GameStats {
+    pub fn new(player_count: usize, tile_count: usize) -> Self {
+        GameStats {
+            counts: Counts {
+                rolls: vec![0; (MAX_ROLL - MIN_ROLL + 1) as usize],
+                flows: vec![ResourceFlow::default(); player_count],
+                production: vec![0; tile_count]
+            },
+            victory_points: Vec::new(),
+            checkpoints: Vec::new()
+        }
+    }
+
+    /*
+     * count the events of one action, with the board, pieces and phase as they were before it
+     * roads only cost something in the main phase and production is worked out tile by tile from the buildings around them
+     */
+    pub fn record(&mut self, events: &[GameEvent], board: &Board, pieces: &Pieces, phase: &Phase) {
+        if events.iter().any(|event| matches!(event, GameEvent::Undone { .. })) {
+            if let Some(counts) = self.checkpoints.pop() {
+                self.counts = counts;
+            }
+            return
+        }
+        self.checkpoints.push(self.counts.clone());
+
+        let flows = &mut self.counts.flows;
+        for event in events {
+            match *event {
+                GameEvent::Rolled { dice: (a, b), .. } => {
+                    self.counts.rolls[(a + b - MIN_ROLL) as usize] += 1;
+                    let produced: Vec<(PlayerId, Hand)> = events
+                        .iter()
+                        .filter_map(|event| match *event {
+                            GameEvent::Produced { player, resources } => Some((player, resources)),
+                            _ => None
+                        })
+                        .collect();
+                    Self::count_production(&mut self.counts.production, a + b, &produced, board, pieces);
+                },
+                GameEvent::Produced { player, resources } | GameEvent::Received { player, resources } =>
+                    flows[player].gained += resources.total() as u32,
+                GameEvent::Discarded { player, resources } => flows[player].lost_to_robber += resources.total() as u32,
+                GameEvent::Stole { thief, victim, .. } => {
+                    flows[thief].gained += 1;
+                    flows[victim].lost_to_robber += 1;
+                },
+                GameEvent::Monopolized { player, count, .. } => flows[player].gained += count as u32,
+                GameEvent::Built { player, piece } => {
+                    let cost = match piece {
+                        Piece::Road(..) if *phase == Phase::Main => ROAD_COST,
+                        Piece::Settlement(_) if *phase == Phase::Main => SETTLEMENT_COST,
+                        Piece::City(_) => CITY_COST,
+                        _ => Hand::default()
+                    };
+                    flows[player].spent += cost.total() as u32;
+                },
+                GameEvent::BoughtDevelopment { player, .. } => flows[player].spent += DEVELOPMENT_COST.total() as u32,
+                GameEvent::TradedWithBank { player, gave, got } => {
+                    flows[player].traded_away += gave.total() as u32;
+                    flows[player].traded_for += got.total() as u32;
+                },
+                GameEvent::Traded { from, to, gave, got } => {
+                    flows[from].traded_away += gave.total() as u32;
+                    flows[from].traded_for += got.total() as u32;
+                    flows[to].traded_away += got.total() as u32;
+                    flows[to].traded_for += gave.total() as u32;
+                },
+                GameEvent::TurnStarted { .. } => self.checkpoints.clear(),
+                _ => ()
+            }
+        }
+    }
+
+    /*
+     * what every tile that matched the roll handed out, a resource the bank ran short of goes to the tiles in order
+     * until what the player actually got runs out, so the tiles add up to the production events
+     */
+    fn count_production(production: &mut [u32], roll: u8, produced: &[(PlayerId, Hand)], board: &Board, pieces: &Pieces) {
+        let mut left: Vec<(PlayerId, Hand)> = produced.to_vec();
+        for (tile, board_tile) in board.tiles.iter().enumerate() {
+            let resource = match board_tile.resource {
+                TileResource::Of(resource) if board_tile.roll == roll && tile != pieces.robber => resource,
+                _ => continue
+            };
+
+            for plot in MAP_GRAPH.tile_plots[tile].iter() {
+                let (building, owner) = match pieces.buildings.get(plot) {
+                    Some(&building) => building,
+                    None => continue
+                };
+                if let Some((_, hand)) = left.iter_mut().find(|(player, _)| *player == owner) {
+                    let count = (if building == Building::City { 2 } else { 1 }).min(hand.get(resource));
+                    hand.remove(resource, count);
+                    production[tile] += count as u32;
+                }
+            }
+        }
+    }
+
+    /* note down where everyone stands, the view has to be applied before so the points are current */
+    pub fn record_victory_points(&mut self, victory_points: Vec<u8>) {
+        self.victory_points.push(victory_points);
+    }
+
+    pub fn get_roll_count(&self, roll: u8) -> u32 {
+        self.counts.rolls[(roll - MIN_ROLL) as usize]
+    }
+
+    pub fn get_total_rolls(&self) -> u32 {
+        self.counts.rolls.iter().sum()
+    }
+
+    /* how many times the roll should have come up by now */
+    pub fn get_expected_rolls(&self, roll: u8) -> f64 {
+        self.get_total_rolls() as f64*get_roll_ways(roll) as f64/36.0
+    }
+
+    pub fn get_flow(&self, player: PlayerId) -> &ResourceFlow {
+        &self.counts.flows[player]
+    }
+
+    /* how many cards the tile has handed out */
+    pub fn get_production(&self, tile: usize) -> u32 {
+        self.counts.production[tile]
+    }
+
+    pub fn get_victory_points(&self) -> &[Vec<u8>] {
+        &self.victory_points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::enums::Resource;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const SEED: u64 = 7;
+
+    fn get_board() -> Board {
+        Board::generate(&mut StdRng::seed_from_u64(SEED))
+    }
+
+    fn get_stats(board: &Board) -> GameStats {
+        GameStats::new(3, board.tiles.len())
+    }
+
+    fn record(stats: &mut GameStats, board: &Board, events: &[GameEvent], phase: Phase) {
+        stats.record(events, board, &Pieces::new(board.get_desert()), &phase);
+    }
+
+    #[test]
+    fn rolls_are_counted_against_the_odds() {
+        assert_eq!((get_roll_ways(2), get_roll_ways(7), get_roll_ways(12)), (1, 6, 1));
+        assert_eq!((MIN_ROLL..=MAX_ROLL).map(get_roll_ways).sum::<u32>(), 36);
+
+        let board = get_board();
+        let mut stats = get_stats(&board);
+        for dice in [(3, 4), (1, 1), (6, 1)] {
+            record(&mut stats, &board, &[GameEvent::Rolled { player: 0, dice }], Phase::Roll);
+        }
+        assert_eq!((stats.get_roll_count(7), stats.get_roll_count(2), stats.get_roll_count(12)), (2, 1, 0));
+        assert_eq!(stats.get_total_rolls(), 3);
+        assert_eq!(stats.get_expected_rolls(7), 0.5);
+    }
+
+    #[test]
+    fn cards_are_counted_where_they_go() {
+        let board = get_board();
+        let mut stats = get_stats(&board);
+        let (two, three) = (Hand::of(Resource::Ore, 2), Hand::of(Resource::Wool, 3));
+        record(&mut stats, &board, &[
+            GameEvent::Received { player: 0, resources: three },
+            GameEvent::Discarded { player: 1, resources: three },
+            GameEvent::Stole { thief: 0, victim: 1, resource: None },
+            GameEvent::Monopolized { player: 2, resource: Resource::Ore, count: 4 },
+            GameEvent::TradedWithBank { player: 0, gave: Hand::of(Resource::Ore, 4), got: Hand::of(Resource::Wool, 1) },
+            GameEvent::Traded { from: 1, to: 2, gave: two, got: three }
+        ], Phase::Main);
+
+        assert_eq!(*stats.get_flow(0), ResourceFlow { gained: 4, traded_away: 4, traded_for: 1, ..ResourceFlow::default() });
+        assert_eq!(*stats.get_flow(1), ResourceFlow { lost_to_robber: 4, traded_away: 2, traded_for: 3, ..ResourceFlow::default() });
+        assert_eq!(*stats.get_flow(2), ResourceFlow { gained: 4, traded_away: 3, traded_for: 2, ..ResourceFlow::default() });
+    }
+
+    /* pieces placed during setup are free, cities and development cards always cost */
+    #[test]
+    fn spending_depends_on_the_phase() {
+        let board = get_board();
+        let mut stats = get_stats(&board);
+        let setup = Phase::Setup { step: 0, settlement: None };
+        record(&mut stats, &board, &[GameEvent::Built { player: 0, piece: Piece::Settlement(0) }, GameEvent::Built { player: 0, piece: Piece::Road(0, 1) }], setup);
+        assert_eq!(stats.get_flow(0).spent, 0);
+
+        record(&mut stats, &board, &[
+            GameEvent::Built { player: 0, piece: Piece::Road(1, 2) },
+            GameEvent::Built { player: 0, piece: Piece::Settlement(2) },
+            GameEvent::Built { player: 0, piece: Piece::City(2) },
+            GameEvent::BoughtDevelopment { player: 0, card: None }
+        ], Phase::Main);
+        assert_eq!(stats.get_flow(0).spent, 2 + 4 + 5 + 3);
+    }
+
+    /* taking an action back takes back what it counted, but only for actions of the turn */
+    #[test]
+    fn undo_takes_back_counts() {
+        let board = get_board();
+        let mut stats = get_stats(&board);
+        let built = [GameEvent::Built { player: 0, piece: Piece::City(3) }];
+        let undone = [GameEvent::Undone { player: 0, action: Action::BuildCity(3) }];
+        record(&mut stats, &board, &built, Phase::Main);
+        let before = stats.clone();
+        record(&mut stats, &board, &built, Phase::Main);
+        record(&mut stats, &board, &undone, Phase::Main);
+        assert_eq!(stats, before);
+        record(&mut stats, &board, &undone, Phase::Main);
+        assert_eq!(stats.get_flow(0).spent, 0);
+
+        record(&mut stats, &board, &built, Phase::Main);
+        record(&mut stats, &board, &[GameEvent::TurnStarted { player: 1 }], Phase::Main);
+        record(&mut stats, &board, &undone, Phase::Main);
+        assert_eq!(stats.get_flow(0).spent, 5);
+    }
+
+    /* a tile is credited with what the buildings around it got, up to what the bank could pay */
+    #[test]
+    fn production_is_counted_per_tile() {
+        let mut board = get_board();
+        let tile = (0..board.tiles.len()).find(|&tile| matches!(board.tiles[tile].resource, TileResource::Of(_))).unwrap();
+        let (roll, resource) = match board.tiles[tile].resource {
+            TileResource::Of(resource) => (board.tiles[tile].roll, resource),
+            _ => unreachable!()
+        };
+        /* no other tile shares the roll so the count is all on this one */
+        for (i, board_tile) in board.tiles.iter_mut().enumerate() {
+            if i != tile && board_tile.roll == roll {
+                board_tile.roll = 0;
+            }
+        }
+
+        let plots = &MAP_GRAPH.tile_plots[tile];
+        let mut pieces = Pieces::new(board.get_desert());
+        pieces.place(0, Piece::Settlement(plots[0]));
+        pieces.place(1, Piece::City(plots[3]));
+        let dice = (roll/2, roll - roll/2);
+        let rolled = |one: u8| [
+            GameEvent::Rolled { player: 0, dice },
+            GameEvent::Produced { player: 0, resources: Hand::of(resource, 1) },
+            GameEvent::Produced { player: 1, resources: Hand::of(resource, one) }
+        ];
+
+        let mut stats = get_stats(&board);
+        stats.record(&rolled(2), &board, &pieces, &Phase::Roll);
+        assert_eq!(stats.get_production(tile), 3);
+        assert_eq!((stats.get_flow(0).gained, stats.get_flow(1).gained), (1, 2));
+        stats.record(&rolled(1), &board, &pieces, &Phase::Roll);
+        assert_eq!(stats.get_production(tile), 5);
+
+        /* nothing comes from under the robber */
+        pieces.robber = tile;
+        stats.record(&[GameEvent::Rolled { player: 0, dice }], &board, &pieces, &Phase::Roll);
+        assert_eq!(stats.get_production(tile), 5);
+        assert_eq!((0..board.tiles.len()).map(|tile| stats.get_production(tile)).sum::<u32>(), 5);
+    }
+}
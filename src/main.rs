@@ -6,12 +6,48 @@ mod render;
 mod enums;
 mod app;
 mod logic;
+mod net;
+mod keymap;
+mod replay;
 
 fn main() -> Result<(), std::io::Error> {
     // setup logger
     tui_logger::init_logger(log::LevelFilter::Trace).unwrap();
     tui_logger::set_default_level(log::LevelFilter::Trace);
 
+    // "log" can come before the other arguments to show the logger
+    let mut args = std::env::args().skip(1).peekable();
+    let enable_logger = args.next_if(|arg| arg == "log").is_some();
+
+    // then "--fps N" for how often to draw while something is animating
+    let fps = match args.next_if(|arg| arg == "--fps") {
+        Some(_) => match args.next().and_then(|fps| fps.parse::<u32>().ok()) {
+            Some(fps) if fps > 0 => fps,
+            _ => {
+                eprintln!("--fps needs a number of frames per second above 0");
+                std::process::exit(2)
+            }
+        },
+        None => render::DEFAULT_FPS
+    };
+
+    let mode = match app::Mode::parse(args) {
+        Ok(mode) => mode,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: catarminal [log] [--fps N] [host [PORT] [--bind ADDRESS] [--name NAME] [--load FILE] [--record FILE] [--grace SECONDS] [--bot-after SECONDS] [--god-delay SECONDS] | join ADDRESS[:PORT] [--name NAME] [--token TOKEN | --spectate | --god-view] | snapshot [SEED] [--ansi] | export [SEED] [--out FILE.svg|FILE.html] | replay FILE [--speed X] | demo]");
+            std::process::exit(2)
+        }
+    };
+
+    let keymap = match keymap::Keymap::load() {
+        Ok(keymap) => keymap,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2)
+        }
+    };
+
     // run the app
-    app::start(std::env::args().nth(1) == Some(String::from("log")))
+    app::start(enable_logger, fps, mode, keymap)
 }
@@ -0,0 +1,99 @@
+/*
+ * client.rs
+ * a connection to a server
+ * a reader thread decodes incoming lines and hands every message to the given sink the moment it arrives
+ * so whoever listens (the event loop of the app) never has to poll for them
+ */
+
+use super::protocol::*;
+
+use crate::logic::session::SessionToken;
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+    thread
+};
+
+/* 
+ * what the reader thread hands messages to, it gets None once the connection is closed and every message was handed over
+ * returning false stops the reader thread
+ */
+pub trait MessageSink: FnMut(Option<ServerMessage>) -> bool + Send + 'static {}
+impl<F: FnMut(Option<ServerMessage>) -> bool + Send + 'static> MessageSink for F {}
+
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream
+}
+
+impl Client {
+    /* connect to the server and introduce ourselves with the given name */
+    pub fn connect<A: ToSocketAddrs>(addr: A, name: &str, sink: impl MessageSink) -> io::Result<Client> {
+        let mut client = Client::open(TcpStream::connect(addr)?, sink)?;
+        client.send(&ClientMessage::Hello { version: PROTOCOL_VERSION, name: name.to_owned() })?;
+        Ok(client)
+    }
+
+    /* connect to the server as a spectator, god view is only granted if the host allows it */
+    pub fn watch<A: ToSocketAddrs>(addr: A, name: &str, god_view: bool, timeout: Duration, sink: impl MessageSink) -> io::Result<Client> {
+        let mut client = Client::open(Client::connect_any(addr, timeout)?, sink)?;
+        client.send(&ClientMessage::Watch { version: PROTOCOL_VERSION, name: name.to_owned(), god_view })?;
+        Ok(client)
+    }
+
+    /* connect to the server and ask for the seat the token belongs to back, gives up if the server can't be reached in time */
+    pub fn resume(addr: &SocketAddr, token: SessionToken, timeout: Duration, sink: impl MessageSink) -> io::Result<Client> {
+        let mut client = Client::open(TcpStream::connect_timeout(addr, timeout)?, sink)?;
+        client.send(&ClientMessage::Resume { version: PROTOCOL_VERSION, token })?;
+        Ok(client)
+    }
+
+    /* the address of the server we are connected to */
+    pub fn get_server_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    fn connect_any<A: ToSocketAddrs>(addr: A, timeout: Duration) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "address did not resolve");
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = err
+            }
+        }
+        Err(last_err)
+    }
+
+    fn open(stream: TcpStream, mut sink: impl MessageSink) -> io::Result<Client> {
+        let reader = stream.try_clone()?;
+
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break
+                };
+                match decode::<ServerMessage>(&line) {
+                    Ok(message) => if !sink(Some(message)) { return },
+                    Err(err) => log::warn!("server sent a bad message: {}", err)
+                }
+            }
+            sink(None);
+        });
+
+        Ok(Client { stream })
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+        writeln!(self.stream, "{}", encode(message))
+    }
+}
+
+/* the reader thread holds its own handle to the stream so it has to be shut down for the server to notice we left */
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.stream.shutdown(std::net::Shutdown::Both).ok();
+    }
+}
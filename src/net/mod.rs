@@ -0,0 +1,15 @@
+/*
+ * net
+ * everything needed to play over the network: the line protocol, the hosting server, the client connection and saving hosted games
+ */
+
+pub mod protocol;
+pub mod server;
+pub mod client;
+pub mod save;
+
+pub const DEFAULT_PORT: u16 = 7373;
+
+
+/* hosted games only listen here unless another address is asked for */
+pub const LOCAL_ADDR: &str = "127.0.0.1";
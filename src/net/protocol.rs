@@ -0,0 +1,773 @@
+/*
+ * protocol.rs
+ * the line protocol spoken between the server and its clients
+ *
+ * every message is one line of space separated fields where the first field names the message
+ * strings are escaped so they never contain a space or a newline (\s for space, \n for newline, \\ for backslash)
+ * fields are written and read in order through the Wire trait so nested structs are just their fields one after another
+ */
+
+use crate::enums::{PlayerColor, Resource, Building, DevelopmentCard};
+use crate::logic::{lobby::*, session::*, game::*, hand::Hand, clock::{TimeControl, ClockView}};
+
+use std::fmt;
+
+/* bumped whenever a message changes shape, clients with a different version are turned away */
+pub const PROTOCOL_VERSION: u32 = 9;
+
+/* longer chat messages are cut off */
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolError(pub String);
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "protocol error: {}", self.0)
+    }
+}
+
+/* messages a client sends to the server */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    Hello { version: u32, name: String },
+    /* take back a seat in a started game after the connection dropped */
+    Resume { version: u32, token: SessionToken },
+    /* join as a spectator, god view shows every hand but only after a delay */
+    Watch { version: u32, name: String, god_view: bool },
+    Act(Action),
+    Chat(String),
+    SetName(String),
+    SetColor(PlayerColor),
+    SetReady(bool),
+
+    // host only
+    Kick(usize),
+    AddBot,
+    SetOptions(GameOptions),
+    /* write the game to a file on the host's machine so it can be continued later */
+    SaveGame
+}
+
+/* messages the server sends to a client */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    Welcome(ClientId, SessionToken),
+    Lobby(Lobby),
+    Rejected(String),
+    Kicked,
+    /* answer to Watch, the delay in seconds if god view was granted */
+    Watching(Option<u64>),
+    Observers(Vec<String>),
+    Snapshot(GameSnapshot),
+    /* the clocks come with every update when the game has them */
+    Update(Vec<GameEvent>, GameView, Option<ClockView>),
+    Presence(usize, Presence),
+    Chat(ChatSender, String)
+}
+
+/* who a chat message came from */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatSender {
+    Player(usize),
+    Observer(String),
+    /* the server itself, like when someone loses their connection */
+    System
+}
+
+/* types that can be written into and read out of a message line */
+pub trait Wire: Sized {
+    fn write(&self, line: &mut LineWriter);
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError>;
+}
+
+/* builds a message line out of fields */
+#[derive(Debug, Default)]
+pub struct LineWriter {
+    line: String
+}
+
+impl LineWriter {
+    pub fn field(&mut self, field: &str) -> &mut Self {
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        for c in field.chars() {
+            match c {
+                '\\' => self.line.push_str("\\\\"),
+                ' ' => self.line.push_str("\\s"),
+                '\n' => self.line.push_str("\\n"),
+                '\r' => self.line.push_str("\\r"),
+                _ => self.line.push(c)
+            }
+        }
+        /* empty strings still need to take up a field */
+        if field.is_empty() {
+            self.line.push_str("\\e");
+        }
+        self
+    }
+
+    pub fn put<T: Wire>(&mut self, value: &T) -> &mut Self {
+        value.write(self);
+        self
+    }
+
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.line)
+    }
+}
+
+/* reads fields out of a message line in order */
+#[derive(Debug)]
+pub struct LineReader<'a> {
+    fields: std::str::Split<'a, char>
+}
+
+impl<'a> LineReader<'a> {
+    pub fn new(line: &'a str) -> Self {
+        LineReader { fields: line.trim_end_matches(['\r', '\n']).split(' ') }
+    }
+
+    pub fn field(&mut self) -> Result<String, ProtocolError> {
+        let raw = self.fields.next().ok_or_else(|| ProtocolError(String::from("message ended early")))?;
+        let mut field = String::with_capacity(raw.len());
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                field.push(c);
+                continue
+            }
+            match chars.next() {
+                Some('\\') => field.push('\\'),
+                Some('s') => field.push(' '),
+                Some('n') => field.push('\n'),
+                Some('r') => field.push('\r'),
+                Some('e') => (),
+                _ => return Err(ProtocolError(format!("bad escape in field {:?}", raw)))
+            }
+        }
+        Ok(field)
+    }
+
+    pub fn get<T: Wire>(&mut self) -> Result<T, ProtocolError> {
+        T::read(self)
+    }
+
+    /* makes sure nothing trails the message */
+    pub fn finish(mut self) -> Result<(), ProtocolError> {
+        match self.fields.next() {
+            None => Ok(()),
+            Some(extra) => Err(ProtocolError(format!("unexpected field {:?}", extra)))
+        }
+    }
+}
+
+/* encode and decode whole messages */
+pub fn encode<T: Wire>(message: &T) -> String {
+    LineWriter::default().put(message).finish()
+}
+
+pub fn decode<T: Wire>(line: &str) -> Result<T, ProtocolError> {
+    let mut reader = LineReader::new(line);
+    let message = reader.get()?;
+    reader.finish()?;
+    Ok(message)
+}
+
+/* Wire impls for primitives */
+
+macro_rules! impl_wire_for_number {
+    ($($t:ty),*) => {
+        $(
+            impl Wire for $t {
+                fn write(&self, line: &mut LineWriter) {
+                    line.field(&self.to_string());
+                }
+                fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+                    let field = line.field()?;
+                    field.parse().map_err(|_| ProtocolError(format!("expected {} but got {:?}", stringify!($t), field)))
+                }
+            }
+        )*
+    };
+}
+
+impl_wire_for_number!(u8, u16, u32, u64, u128, usize, i8, i16, i32);
+
+impl Wire for bool {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(if *self { "1" } else { "0" });
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            other => Err(ProtocolError(format!("expected bool but got {:?}", other)))
+        }
+    }
+}
+
+impl Wire for String {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(self);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        line.field()
+    }
+}
+
+/* options are written as a "-" or a "+" followed by the value */
+impl<T: Wire> Wire for Option<T> {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            None => { line.field("-"); },
+            Some(value) => { line.field("+").put(value); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "-" => Ok(None),
+            "+" => Ok(Some(line.get()?)),
+            other => Err(ProtocolError(format!("expected option but got {:?}", other)))
+        }
+    }
+}
+
+/* vecs are written as their length followed by their elements */
+impl<T: Wire> Wire for Vec<T> {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.len());
+        for value in self {
+            line.put(value);
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let len: usize = line.get()?;
+        (0..len).map(|_| line.get()).collect()
+    }
+}
+
+impl<A: Wire, B: Wire> Wire for (A, B) {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.0).put(&self.1);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok((line.get()?, line.get()?))
+    }
+}
+
+impl<A: Wire, B: Wire, C: Wire> Wire for (A, B, C) {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.0).put(&self.1).put(&self.2);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok((line.get()?, line.get()?, line.get()?))
+    }
+}
+
+/* Wire impls for game types */
+
+impl Wire for Resource {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(self.get_name());
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        Resource::from_name(&field).ok_or_else(|| ProtocolError(format!("unknown resource {:?}", field)))
+    }
+}
+
+impl Wire for DevelopmentCard {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(self.get_name());
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        DevelopmentCard::ALL
+            .iter()
+            .copied()
+            .find(|card| card.get_name() == field)
+            .ok_or_else(|| ProtocolError(format!("unknown development card {:?}", field)))
+    }
+}
+
+impl Wire for Building {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(match self {
+            Self::Settlement => "settlement",
+            Self::City => "city"
+        });
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "settlement" => Ok(Self::Settlement),
+            "city" => Ok(Self::City),
+            other => Err(ProtocolError(format!("unknown building {:?}", other)))
+        }
+    }
+}
+
+impl Wire for Hand {
+    fn write(&self, line: &mut LineWriter) {
+        for count in self.0.iter() {
+            line.put(count);
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let mut hand = Hand::default();
+        for count in hand.0.iter_mut() {
+            *count = line.get()?;
+        }
+        Ok(hand)
+    }
+}
+
+impl Wire for Piece {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Road(a, b) => { line.field("road").put(a).put(b); },
+            Self::Settlement(plot) => { line.field("settlement").put(plot); },
+            Self::City(plot) => { line.field("city").put(plot); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "road" => Ok(Self::Road(line.get()?, line.get()?)),
+            "settlement" => Ok(Self::Settlement(line.get()?)),
+            "city" => Ok(Self::City(line.get()?)),
+            other => Err(ProtocolError(format!("unknown piece {:?}", other)))
+        }
+    }
+}
+
+impl Wire for Phase {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Setup { step, settlement } => { line.field("setup").put(step).put(settlement); },
+            Self::Roll => { line.field("roll"); },
+            Self::Discard { amounts } => { line.field("discard").put(amounts); },
+            Self::MoveRobber { rolled } => { line.field("robber").put(rolled); },
+            Self::Steal { rolled } => { line.field("steal").put(rolled); },
+            Self::Main => { line.field("main"); },
+            Self::RoadBuilding { roads, rolled } => { line.field("road_building").put(roads).put(rolled); },
+            Self::Finished { winner } => { line.field("finished").put(winner); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "setup" => Ok(Self::Setup { step: line.get()?, settlement: line.get()? }),
+            "roll" => Ok(Self::Roll),
+            "discard" => Ok(Self::Discard { amounts: line.get()? }),
+            "robber" => Ok(Self::MoveRobber { rolled: line.get()? }),
+            "steal" => Ok(Self::Steal { rolled: line.get()? }),
+            "main" => Ok(Self::Main),
+            "road_building" => Ok(Self::RoadBuilding { roads: line.get()?, rolled: line.get()? }),
+            "finished" => Ok(Self::Finished { winner: line.get()? }),
+            other => Err(ProtocolError(format!("unknown phase {:?}", other)))
+        }
+    }
+}
+
+impl Wire for Action {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Roll => { line.field("roll"); },
+            Self::Discard(hand) => { line.field("discard").put(hand); },
+            Self::MoveRobber(tile) => { line.field("robber").put(tile); },
+            Self::Steal(victim) => { line.field("steal").put(victim); },
+            Self::BuildRoad(a, b) => { line.field("road").put(a).put(b); },
+            Self::BuildSettlement(plot) => { line.field("settlement").put(plot); },
+            Self::BuildCity(plot) => { line.field("city").put(plot); },
+            Self::BuyDevelopment => { line.field("buy"); },
+            Self::PlayKnight => { line.field("knight"); },
+            Self::PlayRoadBuilding => { line.field("road_building"); },
+            Self::PlayYearOfPlenty(a, b) => { line.field("year_of_plenty").put(a).put(b); },
+            Self::PlayMonopoly(resource) => { line.field("monopoly").put(resource); },
+            Self::TradeWithBank(give, get) => { line.field("bank_trade").put(give).put(get); },
+            Self::OfferTrade(to, give, get) => { line.field("offer").put(to).put(give).put(get); },
+            Self::AcceptTrade => { line.field("accept"); },
+            Self::DeclineTrade => { line.field("decline"); },
+            Self::CancelTrade => { line.field("cancel"); },
+            Self::EndTurn => { line.field("end"); },
+            Self::Undo => { line.field("undo"); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "roll" => Ok(Self::Roll),
+            "discard" => Ok(Self::Discard(line.get()?)),
+            "robber" => Ok(Self::MoveRobber(line.get()?)),
+            "steal" => Ok(Self::Steal(line.get()?)),
+            "road" => Ok(Self::BuildRoad(line.get()?, line.get()?)),
+            "settlement" => Ok(Self::BuildSettlement(line.get()?)),
+            "city" => Ok(Self::BuildCity(line.get()?)),
+            "buy" => Ok(Self::BuyDevelopment),
+            "knight" => Ok(Self::PlayKnight),
+            "road_building" => Ok(Self::PlayRoadBuilding),
+            "year_of_plenty" => Ok(Self::PlayYearOfPlenty(line.get()?, line.get()?)),
+            "monopoly" => Ok(Self::PlayMonopoly(line.get()?)),
+            "bank_trade" => Ok(Self::TradeWithBank(line.get()?, line.get()?)),
+            "offer" => Ok(Self::OfferTrade(line.get()?, line.get()?, line.get()?)),
+            "accept" => Ok(Self::AcceptTrade),
+            "decline" => Ok(Self::DeclineTrade),
+            "cancel" => Ok(Self::CancelTrade),
+            "end" => Ok(Self::EndTurn),
+            "undo" => Ok(Self::Undo),
+            other => Err(ProtocolError(format!("unknown action {:?}", other)))
+        }
+    }
+}
+
+impl Wire for GameEvent {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::TurnStarted { player } => { line.field("turn").put(player); },
+            Self::Rolled { player, dice } => { line.field("rolled").put(player).put(dice); },
+            Self::Produced { player, resources } => { line.field("produced").put(player).put(resources); },
+            Self::Received { player, resources } => { line.field("received").put(player).put(resources); },
+            Self::Discarded { player, resources } => { line.field("discarded").put(player).put(resources); },
+            Self::RobberMoved { player, tile } => { line.field("robber").put(player).put(tile); },
+            Self::Stole { thief, victim, resource } => { line.field("stole").put(thief).put(victim).put(resource); },
+            Self::Built { player, piece } => { line.field("built").put(player).put(piece); },
+            Self::BoughtDevelopment { player, card } => { line.field("bought").put(player).put(card); },
+            Self::PlayedDevelopment { player, card } => { line.field("played").put(player).put(card); },
+            Self::Monopolized { player, resource, count } => { line.field("monopolized").put(player).put(resource).put(count); },
+            Self::TradedWithBank { player, gave, got } => { line.field("bank_traded").put(player).put(gave).put(got); },
+            Self::TradeOffered { player, to, give, get } => { line.field("offered").put(player).put(to).put(give).put(get); },
+            Self::TradeDeclined { player } => { line.field("declined").put(player); },
+            Self::TradeCanceled { player } => { line.field("canceled").put(player); },
+            Self::Traded { from, to, gave, got } => { line.field("traded").put(from).put(to).put(gave).put(got); },
+            Self::LongestRoad { player } => { line.field("longest_road").put(player); },
+            Self::LargestArmy { player } => { line.field("largest_army").put(player); },
+            Self::Won { player } => { line.field("won").put(player); },
+            Self::Undone { player, action } => { line.field("undone").put(player).put(action); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "turn" => Ok(Self::TurnStarted { player: line.get()? }),
+            "rolled" => Ok(Self::Rolled { player: line.get()?, dice: line.get()? }),
+            "produced" => Ok(Self::Produced { player: line.get()?, resources: line.get()? }),
+            "received" => Ok(Self::Received { player: line.get()?, resources: line.get()? }),
+            "discarded" => Ok(Self::Discarded { player: line.get()?, resources: line.get()? }),
+            "robber" => Ok(Self::RobberMoved { player: line.get()?, tile: line.get()? }),
+            "stole" => Ok(Self::Stole { thief: line.get()?, victim: line.get()?, resource: line.get()? }),
+            "built" => Ok(Self::Built { player: line.get()?, piece: line.get()? }),
+            "bought" => Ok(Self::BoughtDevelopment { player: line.get()?, card: line.get()? }),
+            "played" => Ok(Self::PlayedDevelopment { player: line.get()?, card: line.get()? }),
+            "monopolized" => Ok(Self::Monopolized { player: line.get()?, resource: line.get()?, count: line.get()? }),
+            "bank_traded" => Ok(Self::TradedWithBank { player: line.get()?, gave: line.get()?, got: line.get()? }),
+            "offered" => Ok(Self::TradeOffered { player: line.get()?, to: line.get()?, give: line.get()?, get: line.get()? }),
+            "declined" => Ok(Self::TradeDeclined { player: line.get()? }),
+            "canceled" => Ok(Self::TradeCanceled { player: line.get()? }),
+            "traded" => Ok(Self::Traded { from: line.get()?, to: line.get()?, gave: line.get()?, got: line.get()? }),
+            "longest_road" => Ok(Self::LongestRoad { player: line.get()? }),
+            "largest_army" => Ok(Self::LargestArmy { player: line.get()? }),
+            "won" => Ok(Self::Won { player: line.get()? }),
+            "undone" => Ok(Self::Undone { player: line.get()?, action: line.get()? }),
+            other => Err(ProtocolError(format!("unknown event {:?}", other)))
+        }
+    }
+}
+
+impl Wire for TradeOffer {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.from).put(&self.to).put(&self.give).put(&self.get).put(&self.declined);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(TradeOffer {
+            from: line.get()?,
+            to: line.get()?,
+            give: line.get()?,
+            get: line.get()?,
+            declined: line.get()?
+        })
+    }
+}
+
+impl Wire for PlayerView {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.resource_count)
+            .put(&self.hand)
+            .put(&self.development_count)
+            .put(&self.development_cards)
+            .put(&self.knights_played)
+            .put(&self.road_length)
+            .put(&self.victory_points);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(PlayerView {
+            resource_count: line.get()?,
+            hand: line.get()?,
+            development_count: line.get()?,
+            development_cards: line.get()?,
+            knights_played: line.get()?,
+            road_length: line.get()?,
+            victory_points: line.get()?
+        })
+    }
+}
+
+impl Wire for GameView {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.players)
+            .put(&self.roads)
+            .put(&self.buildings)
+            .put(&self.robber)
+            .put(&self.bank)
+            .put(&self.development_deck)
+            .put(&self.turn)
+            .put(&self.phase)
+            .put(&self.dice)
+            .put(&self.longest_road)
+            .put(&self.largest_army)
+            .put(&self.offer)
+            .put(&self.legal_actions);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(GameView {
+            players: line.get()?,
+            roads: line.get()?,
+            buildings: line.get()?,
+            robber: line.get()?,
+            bank: line.get()?,
+            development_deck: line.get()?,
+            turn: line.get()?,
+            phase: line.get()?,
+            dice: line.get()?,
+            longest_road: line.get()?,
+            largest_army: line.get()?,
+            offer: line.get()?,
+            legal_actions: line.get()?
+        })
+    }
+}
+
+impl Wire for PlayerColor {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(self.get_name());
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        PlayerColor::from_name(&field).ok_or_else(|| ProtocolError(format!("unknown color {:?}", field)))
+    }
+}
+
+impl Wire for Occupant {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Client(id) => { line.field("client").put(id); },
+            Self::Bot => { line.field("bot"); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "client" => Ok(Self::Client(line.get()?)),
+            "bot" => Ok(Self::Bot),
+            other => Err(ProtocolError(format!("unknown occupant {:?}", other)))
+        }
+    }
+}
+
+impl Wire for Seat {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.name).put(&self.color).put(&self.is_ready).put(&self.occupant);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(Seat {
+            name: line.get()?,
+            color: line.get()?,
+            is_ready: line.get()?,
+            occupant: line.get()?
+        })
+    }
+}
+
+impl Wire for GameOptions {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.map_seed).put(&self.victory_points).put(&self.time_control);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(GameOptions {
+            map_seed: line.get()?,
+            victory_points: line.get()?,
+            time_control: line.get()?
+        })
+    }
+}
+
+impl Wire for TimeControl {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.turn_time).put(&self.game_time).put(&self.increment);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(TimeControl {
+            turn_time: line.get()?,
+            game_time: line.get()?,
+            increment: line.get()?
+        })
+    }
+}
+
+impl Wire for Lobby {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.seats).put(&self.options).put(&self.host);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(Lobby {
+            seats: line.get()?,
+            options: line.get()?,
+            host: line.get()?
+        })
+    }
+}
+
+impl Wire for GameSetup {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.players).put(&self.options);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(GameSetup {
+            players: line.get()?,
+            options: line.get()?
+        })
+    }
+}
+
+impl Wire for Presence {
+    fn write(&self, line: &mut LineWriter) {
+        line.field(match self {
+            Self::Connected => "connected",
+            Self::Away => "away",
+            Self::Covered => "covered",
+            Self::Bot => "bot"
+        });
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "connected" => Ok(Self::Connected),
+            "away" => Ok(Self::Away),
+            "covered" => Ok(Self::Covered),
+            "bot" => Ok(Self::Bot),
+            other => Err(ProtocolError(format!("unknown presence {:?}", other)))
+        }
+    }
+}
+
+impl Wire for GameSnapshot {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.setup).put(&self.seat).put(&self.presence).put(&self.view).put(&self.clocks);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(GameSnapshot {
+            setup: line.get()?,
+            seat: line.get()?,
+            presence: line.get()?,
+            view: line.get()?,
+            clocks: line.get()?
+        })
+    }
+}
+
+impl Wire for ClockView {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.player).put(&self.turn_length).put(&self.turn_left).put(&self.banks);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(ClockView {
+            player: line.get()?,
+            turn_length: line.get()?,
+            turn_left: line.get()?,
+            banks: line.get()?
+        })
+    }
+}
+
+/* Wire impls for messages */
+
+impl Wire for ChatSender {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Player(seat) => { line.field("player").put(seat); },
+            Self::Observer(name) => { line.field("observer").put(name); },
+            Self::System => { line.field("system"); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "player" => Ok(Self::Player(line.get()?)),
+            "observer" => Ok(Self::Observer(line.get()?)),
+            "system" => Ok(Self::System),
+            other => Err(ProtocolError(format!("unknown chat sender {:?}", other)))
+        }
+    }
+}
+
+impl Wire for ClientMessage {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Hello { version, name } => { line.field("hello").put(version).put(name); },
+            Self::Resume { version, token } => { line.field("resume").put(version).put(token); },
+            Self::Watch { version, name, god_view } => { line.field("watch").put(version).put(name).put(god_view); },
+            Self::Act(action) => { line.field("act").put(action); },
+            Self::Chat(text) => { line.field("chat").put(text); },
+            Self::SetName(name) => { line.field("name").put(name); },
+            Self::SetColor(color) => { line.field("color").put(color); },
+            Self::SetReady(is_ready) => { line.field("ready").put(is_ready); },
+            Self::Kick(seat) => { line.field("kick").put(seat); },
+            Self::AddBot => { line.field("bot"); },
+            Self::SetOptions(options) => { line.field("options").put(options); },
+            Self::SaveGame => { line.field("save"); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "hello" => Ok(Self::Hello { version: line.get()?, name: line.get()? }),
+            "resume" => Ok(Self::Resume { version: line.get()?, token: line.get()? }),
+            "watch" => Ok(Self::Watch { version: line.get()?, name: line.get()?, god_view: line.get()? }),
+            "act" => Ok(Self::Act(line.get()?)),
+            "chat" => Ok(Self::Chat(line.get()?)),
+            "name" => Ok(Self::SetName(line.get()?)),
+            "color" => Ok(Self::SetColor(line.get()?)),
+            "ready" => Ok(Self::SetReady(line.get()?)),
+            "kick" => Ok(Self::Kick(line.get()?)),
+            "bot" => Ok(Self::AddBot),
+            "options" => Ok(Self::SetOptions(line.get()?)),
+            "save" => Ok(Self::SaveGame),
+            other => Err(ProtocolError(format!("unknown client message {:?}", other)))
+        }
+    }
+}
+
+impl Wire for ServerMessage {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::Welcome(client, token) => { line.field("welcome").put(client).put(token); },
+            Self::Lobby(lobby) => { line.field("lobby").put(lobby); },
+            Self::Rejected(reason) => { line.field("rejected").put(reason); },
+            Self::Kicked => { line.field("kicked"); },
+            Self::Watching(delay) => { line.field("watching").put(delay); },
+            Self::Observers(names) => { line.field("observers").put(names); },
+            Self::Snapshot(snapshot) => { line.field("snapshot").put(snapshot); },
+            Self::Update(events, view, clocks) => { line.field("update").put(events).put(view).put(clocks); },
+            Self::Presence(seat, presence) => { line.field("presence").put(seat).put(presence); },
+            Self::Chat(sender, text) => { line.field("chat").put(sender).put(text); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        match line.field()?.as_str() {
+            "welcome" => Ok(Self::Welcome(line.get()?, line.get()?)),
+            "lobby" => Ok(Self::Lobby(line.get()?)),
+            "rejected" => Ok(Self::Rejected(line.get()?)),
+            "kicked" => Ok(Self::Kicked),
+            "watching" => Ok(Self::Watching(line.get()?)),
+            "observers" => Ok(Self::Observers(line.get()?)),
+            "snapshot" => Ok(Self::Snapshot(line.get()?)),
+            "update" => Ok(Self::Update(line.get()?, line.get()?, line.get()?)),
+            "presence" => Ok(Self::Presence(line.get()?, line.get()?)),
+            "chat" => Ok(Self::Chat(line.get()?, line.get()?)),
+            other => Err(ProtocolError(format!("unknown server message {:?}", other)))
+        }
+    }
+}
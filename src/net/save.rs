@@ -0,0 +1,558 @@
+/*
+ * save.rs
+ * saving a hosted game to a file and loading it back to play on later, and recording replays of it
+ *
+ * a save is a text file of lines in the same encoding the protocol uses (see protocol.rs), the first field of a line says what it holds
+ * the first line is "catarminal-save" followed by the version of the format, saves of any other version are refused
+ * the lines after it come in this order:
+ *
+ *   setup <GameSetup>                the players in turn order and the options, the occupants are not used when loading
+ *   board <Board>                    the tiles then the ports, checked against the board the map seed generates
+ *   game <fields>                    victory points to win, turn, phase, dice, robber, bank, longest road, largest army,
+ *                                    whether a development card was played this turn and the trade offer
+ *   deck <Vec<DevelopmentCard>>      the development deck in the order it is drawn from (last card first)
+ *   rng <seed> <stream> <word>       the game's ChaCha12 generator, the seed is 64 hex digits and word is how far into the stream it is
+ *   clock <Option<Vec<Duration>>>    what was left on every player's game clock in milliseconds, none without a game clock
+ *   player <PlayerState>             one line per player in seat order
+ *   road <a> <b> <player>            one line per road
+ *   building <plot> <Building> <player>    one line per building
+ *   event <GameEvent>                one line per event in the order they happened, nothing is redacted
+ *
+ * what the current player could still take back isn't saved, a loaded game starts with nothing to take back
+ * the turn that was going on when the game was saved starts over with a full turn clock, the game clocks carry on from what was left
+ *
+ * bump SAVE_VERSION whenever any of these lines change shape
+ *
+ * a replay starts with "catarminal-replay" followed by its version, then a whole save of the game as it was when recording started,
+ * then for every action that went through after that:
+ *
+ *   action <player> <Action>         who did what
+ *   event <GameEvent>                one line per event the action caused
+ *
+ * replays are only ever appended to while the game goes on
+ */
+
+use super::protocol::*;
+
+use crate::enums::{Resource, TileResource, PortResource, Building};
+use crate::logic::{lobby::GameSetup, game::*, board::{Board, BoardTile}, history::Record};
+use crate::render::drawing::map::MAP_GRAPH;
+
+use rand::{SeedableRng, rngs::StdRng};
+use rand_chacha::ChaCha12Rng;
+
+use std::{fs, io::{self, Write}, path::Path, time::Duration};
+
+pub const SAVE_VERSION: u32 = 2;
+pub const REPLAY_VERSION: u32 = 1;
+
+const SAVE_HEADER: &str = "catarminal-save";
+const REPLAY_HEADER: &str = "catarminal-replay";
+
+/* a game read back out of a save, ready to be continued */
+#[derive(Debug)]
+pub struct SavedGame {
+    pub setup: GameSetup,
+    pub game: GameState,
+    /* what was left on the game clocks, None when the game had none */
+    pub banks: Option<Vec<Duration>>,
+    /* everything that happened before the game was saved */
+    pub log: Vec<GameEvent>
+}
+
+/* write the game to the file, replacing whatever was there */
+pub fn save_game(path: &Path, setup: &GameSetup, game: &GameState, banks: Option<&[Duration]>, log: &[GameEvent]) -> io::Result<()> {
+    fs::write(path, encode_save(setup, game, banks, log))
+}
+
+pub fn load_game(path: &Path) -> io::Result<SavedGame> {
+    let text = fs::read_to_string(path)?;
+    decode_save(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a save we can load: {}", path.display(), err.0)))
+}
+
+pub fn encode_save(setup: &GameSetup, game: &GameState, banks: Option<&[Duration]>, log: &[GameEvent]) -> String {
+    let mut lines = vec![
+        LineWriter::default().field(SAVE_HEADER).put(&SAVE_VERSION).finish(),
+        LineWriter::default().field("setup").put(setup).finish(),
+        LineWriter::default().field("board").put(&game.board).finish(),
+        LineWriter::default()
+            .field("game")
+            .put(&game.victory_points_to_win)
+            .put(&game.turn)
+            .put(&game.phase)
+            .put(&game.dice)
+            .put(&game.robber)
+            .put(&game.bank)
+            .put(&game.longest_road)
+            .put(&game.largest_army)
+            .put(&game.has_played_development)
+            .put(&game.offer)
+            .finish(),
+        LineWriter::default().field("deck").put(&game.development_deck).finish(),
+        LineWriter::default().field("rng").put(&game.rng).finish(),
+        LineWriter::default().field("clock").put(&banks.map(|banks| banks.to_vec())).finish()
+    ];
+
+    lines.extend(game.players.iter().map(|player| LineWriter::default().field("player").put(player).finish()));
+    lines.extend(game.roads.iter().map(|(&(a, b), player)| LineWriter::default().field("road").put(&a).put(&b).put(player).finish()));
+    lines.extend(
+        game.buildings
+            .iter()
+            .enumerate()
+            .filter_map(|(plot, building)| building.map(|(building, player)| (plot, building, player)))
+            .map(|building| LineWriter::default().field("building").put(&building).finish())
+    );
+    lines.extend(log.iter().map(|event| LineWriter::default().field("event").put(event).finish()));
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+pub fn decode_save(text: &str) -> Result<SavedGame, ProtocolError> {
+    let mut reader = SaveReader::new(text);
+    let saved = reader.read_save()?;
+    reader.finish()?;
+    Ok(saved)
+}
+
+/* start writing a replay of the game from where it is now */
+pub fn create_replay(path: &Path, setup: &GameSetup, game: &GameState, banks: Option<&[Duration]>, log: &[GameEvent]) -> io::Result<Recording> {
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "{}", LineWriter::default().field(REPLAY_HEADER).put(&REPLAY_VERSION).finish())?;
+    file.write_all(encode_save(setup, game, banks, log).as_bytes())?;
+    Ok(Recording { file })
+}
+
+pub fn load_replay(path: &Path) -> io::Result<(SavedGame, Vec<Record>)> {
+    let text = fs::read_to_string(path)?;
+    decode_replay(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a replay we can load: {}", path.display(), err.0)))
+}
+
+/* the game as the replay starts and every record after it */
+pub fn decode_replay(text: &str) -> Result<(SavedGame, Vec<Record>), ProtocolError> {
+    let mut reader = SaveReader::new(text);
+    let version: u32 = reader.read(REPLAY_HEADER)?;
+    if version != REPLAY_VERSION {
+        return Err(ProtocolError(format!("the replay is version {} but only version {} can be loaded", version, REPLAY_VERSION)))
+    }
+
+    let start = reader.read_save()?;
+    let mut records = Vec::new();
+    while let Some((number, line)) = reader.next_line("action", false)? {
+        let (player, action) = SaveReader::read_line(number, line, |line| Ok((line.get()?, line.get()?)))?;
+        records.push(Record { player, action, events: reader.read_all("event")? });
+    }
+    reader.finish()?;
+
+    Ok((start, records))
+}
+
+/* a replay being written as the game goes, every record is written as soon as it is played so nothing is lost if the game is */
+#[derive(Debug)]
+pub struct Recording {
+    file: fs::File
+}
+
+impl Recording {
+    pub fn append(&mut self, record: &Record) -> io::Result<()> {
+        let mut lines = vec![LineWriter::default().field("action").put(&record.player).put(&record.action).finish()];
+        lines.extend(record.events.iter().map(|event| LineWriter::default().field("event").put(event).finish()));
+        lines.push(String::new());
+        self.file.write_all(lines.join("\n").as_bytes())
+    }
+}
+
+/* reads the lines of a save or a replay in order, errors say which line of the file was wrong */
+#[derive(Debug)]
+struct SaveReader<'a> {
+    /* the lines that aren't blank with their line numbers */
+    lines: Vec<(usize, &'a str)>,
+    next: usize
+}
+
+impl<'a> SaveReader<'a> {
+    fn new(text: &'a str) -> Self {
+        SaveReader {
+            lines: text.lines().enumerate().map(|(i, line)| (i + 1, line)).filter(|(_, line)| !line.trim().is_empty()).collect(),
+            next: 0
+        }
+    }
+
+    /* the next line if it holds what was asked for, read past the first field */
+    fn next_line(&mut self, tag: &str, is_required: bool) -> Result<Option<(usize, LineReader<'a>)>, ProtocolError> {
+        match self.lines.get(self.next) {
+            Some(&(number, line)) if line.split(' ').next() == Some(tag) => {
+                self.next += 1;
+                let mut reader = LineReader::new(line);
+                reader.field()?;
+                Ok(Some((number, reader)))
+            },
+            Some((number, line)) if is_required => {
+                Err(ProtocolError(format!("line {}: expected {} but got {:?}", number, tag, line.split(' ').next().unwrap_or_default())))
+            },
+            None if is_required => Err(ProtocolError(format!("the file ended before its {} line", tag))),
+            _ => Ok(None)
+        }
+    }
+
+    fn read_line<T>(number: usize, mut line: LineReader, read: impl FnOnce(&mut LineReader) -> Result<T, ProtocolError>) -> Result<T, ProtocolError> {
+        let value = read(&mut line).and_then(|value| line.finish().map(|_| value));
+        value.map_err(|err| ProtocolError(format!("line {}: {}", number, err.0)))
+    }
+
+    /* the value on the next line, which has to be there */
+    fn read<T: Wire>(&mut self, tag: &str) -> Result<T, ProtocolError> {
+        let (number, line) = self.next_line(tag, true)?.unwrap();
+        Self::read_line(number, line, |line| line.get())
+    }
+
+    /* the values on every line from here on that holds what was asked for */
+    fn read_all<T: Wire>(&mut self, tag: &str) -> Result<Vec<T>, ProtocolError> {
+        let mut values = Vec::new();
+        while let Some((number, line)) = self.next_line(tag, false)? {
+            values.push(Self::read_line(number, line, |line| line.get())?);
+        }
+        Ok(values)
+    }
+
+    fn read_save(&mut self) -> Result<SavedGame, ProtocolError> {
+        let version: u32 = self.read(SAVE_HEADER)?;
+        if version != SAVE_VERSION {
+            return Err(ProtocolError(format!("the save is version {} but only version {} can be loaded", version, SAVE_VERSION)))
+        }
+
+        let setup: GameSetup = self.read("setup")?;
+        let board: Board = self.read("board")?;
+        /* clients draw the board from the seed so it has to be the one the seed generates */
+        if board != Board::generate(&mut StdRng::seed_from_u64(setup.options.map_seed)) {
+            return Err(ProtocolError(format!("the board is not the one map seed {} generates", setup.options.map_seed)))
+        }
+
+        let (number, line) = self.next_line("game", true)?.unwrap();
+        let mut game = Self::read_line(number, line, |line| Ok(GameState {
+            victory_points_to_win: line.get()?,
+            turn: line.get()?,
+            phase: line.get()?,
+            dice: line.get()?,
+            robber: line.get()?,
+            bank: line.get()?,
+            longest_road: line.get()?,
+            largest_army: line.get()?,
+            has_played_development: line.get()?,
+            offer: line.get()?,
+            board,
+            players: Vec::new(),
+            roads: Default::default(),
+            buildings: vec![None; MAP_GRAPH.plot_points.len()],
+            development_deck: Vec::new(),
+            rng: ChaCha12Rng::seed_from_u64(0),
+            checkpoints: Vec::new()
+        }))?;
+
+        game.development_deck = self.read("deck")?;
+        game.rng = self.read("rng")?;
+        let banks: Option<Vec<Duration>> = self.read("clock")?;
+        game.players = self.read_all("player")?;
+        if game.players.len() != setup.players.len() {
+            return Err(ProtocolError(format!("the save has {} players but its setup seats {}", game.players.len(), setup.players.len())))
+        }
+        if banks.as_ref().is_some_and(|banks| banks.len() != game.players.len()) {
+            return Err(ProtocolError(format!("the save has {} players but a game clock for {}", game.players.len(), banks.map_or(0, |banks| banks.len()))))
+        }
+        check_game(&game)?;
+
+        for (a, b, player) in self.read_all::<(usize, usize, PlayerId)>("road")? {
+            if !MAP_GRAPH.plot_edges.get(a).is_some_and(|edges| edges.contains(&b)) || player >= game.players.len() {
+                return Err(ProtocolError(format!("there is no road from {} to {} for player {}", a, b, player)))
+            }
+            game.roads.insert((a.min(b), a.max(b)), player);
+        }
+
+        for (plot, building, player) in self.read_all::<(usize, Building, PlayerId)>("building")? {
+            if plot >= game.buildings.len() || player >= game.players.len() {
+                return Err(ProtocolError(format!("there is no plot {} for player {} to build on", plot, player)))
+            }
+            game.buildings[plot] = Some((building, player));
+        }
+
+        let log = self.read_all("event")?;
+        Ok(SavedGame { setup, game, banks, log })
+    }
+
+    /* makes sure nothing is left over */
+    fn finish(&self) -> Result<(), ProtocolError> {
+        match self.lines.get(self.next) {
+            None => Ok(()),
+            Some((number, line)) => Err(ProtocolError(format!("line {}: unexpected {:?}", number, line.split(' ').next().unwrap_or_default())))
+        }
+    }
+}
+
+/* 
+ * everything on the game line that points at a player, a plot or a tile has to point at one that is there, roads and buildings are checked as they are read
+ * the game indexes with them without checking so a bad save would panic the server later instead of failing to load
+ */
+fn check_game(game: &GameState) -> Result<(), ProtocolError> {
+    let player_count = game.players.len();
+    let is_player = |player: PlayerId| player < player_count;
+    let is_plot = |plot: usize| plot < MAP_GRAPH.plot_points.len();
+
+    if !is_player(game.turn) {
+        return Err(ProtocolError(format!("it is the turn of player {} but there are {} players", game.turn, player_count)))
+    }
+    if game.robber >= game.board.tiles.len() || game.robber >= MAP_GRAPH.tile_anchor_points.len() {
+        return Err(ProtocolError(format!("the robber is on tile {} which is off the board", game.robber)))
+    }
+    if !game.longest_road.into_iter().chain(game.largest_army).all(is_player) {
+        return Err(ProtocolError(String::from("the longest road or the largest army belongs to a player who is not there")))
+    }
+
+    match &game.phase {
+        Phase::Setup { settlement: Some(plot), .. } if !is_plot(*plot) => {
+            return Err(ProtocolError(format!("the setup settlement is on plot {} which is off the board", plot)))
+        },
+        Phase::Discard { amounts } if amounts.len() != player_count => {
+            return Err(ProtocolError(format!("{} players have to discard but there are {} players", amounts.len(), player_count)))
+        },
+        Phase::Finished { winner } if !is_player(*winner) => {
+            return Err(ProtocolError(format!("player {} won but there are {} players", winner, player_count)))
+        },
+        _ => ()
+    }
+
+    if let Some(offer) = &game.offer {
+        if !is_player(offer.from) || !offer.to.into_iter().chain(offer.declined.iter().copied()).all(is_player) {
+            return Err(ProtocolError(String::from("the trade offer is between players who are not there")))
+        }
+    }
+
+    Ok(())
+}
+
+/* Wire impls for what only saves hold */
+
+impl Wire for TileResource {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::OfDesert => { line.field("desert"); },
+            Self::Of(resource) => { line.put(resource); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        match field.as_str() {
+            "desert" => Ok(Self::OfDesert),
+            _ => Resource::from_name(&field).map(Self::Of).ok_or_else(|| ProtocolError(format!("unknown tile {:?}", field)))
+        }
+    }
+}
+
+impl Wire for PortResource {
+    fn write(&self, line: &mut LineWriter) {
+        match self {
+            Self::OfAnyKind => { line.field("any"); },
+            Self::Of(resource) => { line.put(resource); }
+        }
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        match field.as_str() {
+            "any" => Ok(Self::OfAnyKind),
+            _ => Resource::from_name(&field).map(Self::Of).ok_or_else(|| ProtocolError(format!("unknown port {:?}", field)))
+        }
+    }
+}
+
+impl Wire for BoardTile {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.roll).put(&self.resource);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(BoardTile {
+            roll: line.get()?,
+            resource: line.get()?
+        })
+    }
+}
+
+impl Wire for Board {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.tiles).put(&self.ports);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(Board {
+            tiles: line.get()?,
+            ports: line.get()?
+        })
+    }
+}
+
+impl Wire for PlayerState {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&self.hand)
+            .put(&self.development_cards)
+            .put(&self.new_development_cards)
+            .put(&self.knights_played)
+            .put(&self.road_length)
+            .put(&self.roads_left)
+            .put(&self.settlements_left)
+            .put(&self.cities_left);
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(PlayerState {
+            hand: line.get()?,
+            development_cards: line.get()?,
+            new_development_cards: line.get()?,
+            knights_played: line.get()?,
+            road_length: line.get()?,
+            roads_left: line.get()?,
+            settlements_left: line.get()?,
+            cities_left: line.get()?
+        })
+    }
+}
+
+/* in milliseconds */
+impl Wire for Duration {
+    fn write(&self, line: &mut LineWriter) {
+        line.put(&(self.as_millis() as u64));
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        Ok(Duration::from_millis(line.get()?))
+    }
+}
+
+/* the seed is written as hex so it stays one field */
+impl Wire for ChaCha12Rng {
+    fn write(&self, line: &mut LineWriter) {
+        let seed: String = self.get_seed().iter().map(|byte| format!("{:02x}", byte)).collect();
+        line.field(&seed).put(&self.get_stream()).put(&self.get_word_pos());
+    }
+    fn read(line: &mut LineReader) -> Result<Self, ProtocolError> {
+        let field = line.field()?;
+        let mut seed = [0u8; 32];
+        if field.len() != seed.len()*2 || !field.is_ascii() {
+            return Err(ProtocolError(format!("expected a seed of {} hex digits but got {:?}", seed.len()*2, field)))
+        }
+        for (i, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&field[i*2..i*2 + 2], 16).map_err(|_| ProtocolError(format!("bad hex in seed {:?}", field)))?;
+        }
+
+        let mut rng = ChaCha12Rng::from_seed(seed);
+        rng.set_stream(line.get()?);
+        rng.set_word_pos(line.get()?);
+        Ok(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::PlayerColor;
+    use crate::logic::{bot, hand::Hand, history::History, lobby::{Seat, Occupant, GameOptions}};
+
+    const SEED: u64 = 7;
+
+    fn get_setup() -> GameSetup {
+        let players = PlayerColor::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| Seat { name: format!("bot{}", i), color, is_ready: true, occupant: Occupant::Bot })
+            .collect();
+        GameSetup { players, options: GameOptions::new(SEED) }
+    }
+
+    /* bots play the given number of actions into a new game, the events they caused are the log */
+    fn play(setup: &GameSetup, actions: usize) -> (GameState, Vec<GameEvent>) {
+        let board = Board::generate(&mut StdRng::seed_from_u64(setup.options.map_seed));
+        let mut game = GameState::new(board, setup.players.len(), setup.options.victory_points, SEED);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut log = Vec::new();
+        for _ in 0..actions {
+            let player = game.get_actors()[0];
+            let action = bot::choose_action(&game, player, &mut rng).unwrap();
+            log.extend(game.apply(player, action).unwrap());
+        }
+        (game, log)
+    }
+
+    /* a game loads back exactly as it was saved, down to where the rng is, the order of the deck and the time on the clocks */
+    #[test]
+    fn save_round_trip() {
+        let setup = get_setup();
+        let (mut game, log) = play(&setup, 200);
+        game.checkpoints.clear();
+        let banks = vec![Duration::from_millis(61_500), Duration::ZERO, Duration::from_secs(600), Duration::from_millis(1)];
+        let saved = decode_save(&encode_save(&setup, &game, Some(&banks), &log)).unwrap();
+        assert_eq!(saved.setup, setup);
+        assert_eq!(saved.game, game);
+        assert_eq!(saved.banks, Some(banks));
+        assert_eq!(saved.game.rng.get_word_pos(), game.rng.get_word_pos());
+        assert_eq!(saved.game.development_deck, game.development_deck);
+        assert_eq!(saved.log, log);
+    }
+
+    /* a replay recorded from part way into a game plays out to the same events when loaded */
+    #[test]
+    fn replay_plays_out() {
+        let setup = get_setup();
+        let (mut game, log) = play(&setup, 40);
+        game.checkpoints.clear();
+        let path = std::env::temp_dir().join(format!("catarminal-test-{}.replay", std::process::id()));
+        let mut recording = create_replay(&path, &setup, &game, None, &log).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut records = Vec::new();
+        for _ in 0..200 {
+            let player = game.get_actors()[0];
+            let action = bot::choose_action(&game, player, &mut rng).unwrap();
+            let record = Record { player, action, events: game.apply(player, action).unwrap() };
+            recording.append(&record).unwrap();
+            records.push(record);
+        }
+        drop(recording);
+
+        let loaded = load_replay(&path);
+        fs::remove_file(&path).unwrap();
+        let (start, loaded_records) = loaded.unwrap();
+        assert_eq!(start.log, log);
+        assert_eq!(loaded_records, records);
+
+        let history = History::new(start.game, loaded_records).unwrap();
+        assert_eq!(history.get_record_count(), records.len());
+        let mut end = history.get_state(records.len()).clone();
+        end.checkpoints = game.checkpoints.clone();
+        assert_eq!(end, game);
+    }
+
+    fn assert_refused(setup: &GameSetup, game: &GameState) {
+        assert!(decode_save(&encode_save(setup, game, None, &[])).is_err());
+    }
+
+    #[test]
+    fn load_refuses_what_points_off_the_game() {
+        let setup = get_setup();
+        let (game, _) = play(&setup, 40);
+        assert!(decode_save(&encode_save(&setup, &game, None, &[])).is_ok());
+
+        let broken = [
+            GameState { phase: Phase::Discard { amounts: vec![0; 3] }, ..game.clone() },
+            GameState { phase: Phase::Finished { winner: 4 }, ..game.clone() },
+            GameState { turn: 4, ..game.clone() },
+            GameState { robber: 19, ..game.clone() },
+            GameState { largest_army: Some(4), ..game.clone() },
+            GameState { offer: Some(TradeOffer { from: 4, to: None, give: Hand::default(), get: Hand::default(), declined: Vec::new() }), ..game.clone() }
+        ];
+        for game in broken.iter() {
+            assert_refused(&setup, game);
+        }
+
+        let mut game = game;
+        game.roads.insert((0, 40), 0);
+        assert_refused(&setup, &game);
+        game.roads.remove(&(0, 40));
+        game.buildings.push(Some((Building::Settlement, 0)));
+        assert_refused(&setup, &game);
+    }
+}
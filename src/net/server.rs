@@ -0,0 +1,1030 @@
+/*
+ * server.rs
+ * the authoritative host of a networked game
+ *
+ * the server runs on its own thread and owns all the state, connections only ever talk to it through a channel
+ * an accept thread hands out client ids and spawns a reader thread per connection that decodes lines into events
+ * every connection also gets a writer thread fed through a queue, so a client that stops reading only ever stalls itself
+ * the host player is just another client that happens to connect first
+ *
+ * the server thread wakes up every tick even without events so it can notice when a disconnected player's time runs out,
+ * let bots take their turn, play out turns that ran out of time and hand god view spectators the updates that are old enough for them to see
+ */
+
+use super::{protocol::*, save::{self, SavedGame, Recording}};
+
+use crate::logic::{lobby::*, session::*, game::*, board::Board, history::Record, clock::{self, Clocks}, bot};
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError},
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    thread
+};
+
+const SERVER_TICK: Duration = Duration::from_millis(100);
+
+/* time between bot actions so people can follow along */
+const BOT_DELAY: Duration = Duration::from_millis(800);
+
+/* a client this many lines behind isn't reading anymore and is dropped */
+const SEND_QUEUE: usize = 1024;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub sessions: SessionConfig,
+    /* how far behind the game god view spectators are kept, god view is refused if this is None */
+    pub god_view_delay: Option<Duration>,
+    /* where to write a replay of the game as it is played, if anywhere */
+    pub record: Option<PathBuf>
+}
+
+/* someone watching the game without a seat */
+#[derive(Debug)]
+struct Spectator {
+    name: String,
+    god_view: bool,
+    /* god view messages waiting until they are old enough to send */
+    backlog: VecDeque<(Instant, ServerMessage)>
+}
+
+/* the server's end of a connection, lines are written out by its own thread */
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    lines: SyncSender<String>
+}
+
+/* what connection threads tell the server thread */
+#[derive(Debug)]
+enum ServerEvent {
+    Connected(ClientId, TcpStream),
+    Message(ClientId, ClientMessage),
+    Malformed(ClientId, ProtocolError),
+    Disconnected(ClientId)
+}
+
+#[derive(Debug)]
+enum ServerPhase {
+    Lobby(Lobby),
+    Started {
+        setup: GameSetup,
+        sessions: Sessions,
+        game: Box<GameState>,
+        /* every event so far unredacted, saves keep it */
+        log: Vec<GameEvent>,
+        /* the seat of whoever hosted the lobby, only they can save */
+        host: Option<usize>,
+        recording: Option<Recording>,
+        /* only there when the game has a time control */
+        clocks: Option<Box<Clocks>>,
+        /* bots and timed out turns both move at most this often */
+        next_bot_action: Instant
+    }
+}
+
+#[derive(Debug)]
+struct Server {
+    phase: ServerPhase,
+    config: ServerConfig,
+    connections: HashMap<ClientId, Connection>,
+    /* session tokens of clients seated in the lobby */
+    tokens: HashMap<ClientId, SessionToken>,
+    spectators: HashMap<ClientId, Spectator>,
+    /* the game to continue once the lobby is ready instead of starting a new one */
+    saved: Option<SavedGame>,
+    rng: StdRng
+}
+
+/*
+ * bind the server to the given address and start it on a new thread, returns the address it ended up bound to
+ * with a saved game the lobby plays on from the save instead, its options are the saved ones
+ */
+pub fn spawn<A: ToSocketAddrs>(addr: A, options: GameOptions, config: ServerConfig, saved: Option<SavedGame>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || accept(listener, sender));
+    thread::spawn(move || {
+        Server {
+            phase: ServerPhase::Lobby(Lobby::new(saved.as_ref().map_or(options, |saved| saved.setup.options))),
+            config,
+            connections: HashMap::new(),
+            tokens: HashMap::new(),
+            spectators: HashMap::new(),
+            saved,
+            rng: StdRng::from_entropy()
+        }.run(receiver)
+    });
+
+    Ok(local_addr)
+}
+
+fn accept(listener: TcpListener, sender: Sender<ServerEvent>) {
+    let mut next_id: ClientId = 1;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("failed to accept connection: {}", err);
+                continue
+            }
+        };
+
+        let id = next_id;
+        next_id += 1;
+
+        let reader = match stream.try_clone() {
+            Ok(reader) => reader,
+            Err(err) => {
+                log::warn!("failed to clone connection: {}", err);
+                continue
+            }
+        };
+
+        if sender.send(ServerEvent::Connected(id, stream)).is_err() {
+            return // server is gone
+        }
+
+        let sender = sender.clone();
+        thread::spawn(move || read_connection(id, reader, sender));
+    }
+}
+
+/* write every line the server queues for a connection until it is dropped or the client stops taking them */
+fn write_connection(id: ClientId, mut stream: TcpStream, lines: Receiver<String>) {
+    if let Err(err) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+        log::warn!("failed to set a write timeout for client {}: {}", id, err);
+    }
+
+    for mut line in lines {
+        line.push('\n');
+        if let Err(err) = stream.write_all(line.as_bytes()) {
+            log::warn!("failed to send to client {}: {}", id, err);
+            /* the reader thread notices and reports the disconnect */
+            stream.shutdown(std::net::Shutdown::Both).ok();
+            return
+        }
+    }
+
+    /* everything queued before the server let go of the connection is sent, only then is it closed */
+    stream.shutdown(std::net::Shutdown::Both).ok();
+}
+
+/* decode every line sent over a connection into server events until the connection closes */
+fn read_connection(id: ClientId, stream: TcpStream, sender: Sender<ServerEvent>) {
+    for line in BufReader::new(stream).lines() {
+        let event = match line {
+            Ok(line) => match decode::<ClientMessage>(&line) {
+                Ok(message) => ServerEvent::Message(id, message),
+                Err(err) => ServerEvent::Malformed(id, err)
+            },
+            Err(_) => break
+        };
+
+        if sender.send(event).is_err() {
+            return
+        }
+    }
+
+    sender.send(ServerEvent::Disconnected(id)).ok();
+}
+
+impl Server {
+    fn run(mut self, receiver: Receiver<ServerEvent>) {
+        loop {
+            match receiver.recv_timeout(SERVER_TICK) {
+                Ok(event) => self.handle_event(event),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return
+            }
+            self.tick();
+        }
+    }
+
+    fn handle_event(&mut self, event: ServerEvent) {
+        match event {
+            ServerEvent::Connected(id, stream) => {
+                log::info!("client {} connected from {:?}", id, stream.peer_addr());
+                let writer = match stream.try_clone() {
+                    Ok(writer) => writer,
+                    Err(err) => {
+                        log::warn!("failed to clone connection: {}", err);
+                        stream.shutdown(std::net::Shutdown::Both).ok();
+                        return
+                    }
+                };
+                let (lines, receiver) = mpsc::sync_channel(SEND_QUEUE);
+                thread::spawn(move || write_connection(id, writer, receiver));
+                self.connections.insert(id, Connection { stream, lines });
+            },
+            ServerEvent::Message(id, message) => self.handle_message(id, message),
+            ServerEvent::Malformed(id, err) => {
+                log::warn!("client {} sent a bad message: {}", id, err);
+                self.send(id, &ServerMessage::Rejected(err.to_string()));
+            },
+            ServerEvent::Disconnected(id) => {
+                log::info!("client {} disconnected", id);
+                self.connections.remove(&id);
+                if let Some(spectator) = self.spectators.remove(&id) {
+                    self.broadcast_observers();
+                    self.broadcast_system(format!("{} stopped watching", spectator.name));
+                    return
+                }
+                match &mut self.phase {
+                    ServerPhase::Lobby(lobby) => {
+                        lobby.leave(id);
+                        self.tokens.remove(&id);
+                        self.broadcast_lobby();
+                        /* whoever was holding the game up might have been the one who left */
+                        self.try_start();
+                    },
+                    ServerPhase::Started { setup, sessions, .. } => {
+                        if let Some(seat) = sessions.disconnect(id, Instant::now()) {
+                            let name = setup.players[seat].name.clone();
+                            log::info!("{} lost their connection, holding their seat", name);
+                            self.broadcast(&ServerMessage::Presence(seat, Presence::Away));
+                            self.broadcast_system(format!("{} lost their connection", name));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /* let the sessions know time has passed and tell everyone whose seat changed hands */
+    fn tick(&mut self) {
+        let now = Instant::now();
+        self.flush_backlogs(now);
+
+        let changes = match &mut self.phase {
+            ServerPhase::Started { sessions, .. } => sessions.tick(now),
+            ServerPhase::Lobby(_) => return
+        };
+
+        for (seat, presence) in changes {
+            log::info!("seat {} is now {:?}", seat, presence);
+            self.broadcast(&ServerMessage::Presence(seat, presence));
+            if let ServerPhase::Started { setup, .. } = &self.phase {
+                let name = setup.players[seat].name.clone();
+                match presence {
+                    Presence::Covered => self.broadcast_system(format!("a bot is playing for {} until they return", name)),
+                    Presence::Bot => self.broadcast_system(format!("a bot took over {}'s seat for good", name)),
+                    Presence::Connected | Presence::Away => ()
+                }
+            }
+        }
+
+        self.run_bots(now);
+        self.run_clocks(now);
+    }
+
+    /* let one bot controlled seat the game is waiting on make a move, bots answer trade offers made to them too */
+    fn run_bots(&mut self, now: Instant) {
+        let (sessions, game, next_bot_action) = match &mut self.phase {
+            ServerPhase::Started { sessions, game, next_bot_action, .. } if now >= *next_bot_action => (sessions, game, next_bot_action),
+            _ => return
+        };
+
+        let player = match game.get_actors().into_iter().chain(game.get_trade_responders()).find(|&player| sessions.get_presence(player).is_bot_controlled()) {
+            Some(player) => player,
+            None => return
+        };
+
+        *next_bot_action = now + BOT_DELAY;
+        let action = match bot::choose_action(game, player, &mut self.rng) {
+            Some(action) => action,
+            None => return log::warn!("bot for seat {} has nothing to do", player)
+        };
+        match game.apply(player, action) {
+            Ok(events) => self.record(player, action, events),
+            Err(err) => log::warn!("bot for seat {} made an illegal move: {}", player, err)
+        }
+    }
+
+    /* 
+     * once the turn player's time runs out play their turn out for them, one action at a time like a bot would
+     * seats that run out of time discarding have their discard picked for them right away
+     */
+    fn run_clocks(&mut self, now: Instant) {
+        let discards: Vec<(String, PlayerId, Action)> = match &mut self.phase {
+            ServerPhase::Started { setup, game, clocks: Some(clocks), .. } => clocks
+                .take_discard_timeouts(now)
+                .into_iter()
+                .filter_map(|player| clock::get_timeout_discard(game, player, &mut self.rng).map(|action| (setup.players[player].name.clone(), player, action)))
+                .collect(),
+            _ => return
+        };
+        for (name, player, action) in discards {
+            log::info!("{} ran out of time to discard", name);
+            self.broadcast_system(format!("{} ran out of time to discard", name));
+            let result = match &mut self.phase {
+                ServerPhase::Started { game, .. } => game.apply(player, action),
+                _ => return
+            };
+            match result {
+                Ok(events) => self.record(player, action, events),
+                Err(err) => log::warn!("timed out seat {} couldn't discard: {}", player, err)
+            }
+        }
+
+        let (setup, game, clocks, next_bot_action) = match &mut self.phase {
+            ServerPhase::Started { setup, game, clocks: Some(clocks), next_bot_action, .. } => (setup, game, clocks, next_bot_action),
+            _ => return
+        };
+
+        let timed_out = clocks.take_timeout(now);
+        if !clocks.is_timed_out() || now < *next_bot_action {
+            return
+        }
+
+        let name = timed_out.map(|player| setup.players[player].name.clone());
+        *next_bot_action = now + BOT_DELAY;
+        let result = clock::get_timeout_action(game, &mut self.rng).map(|(player, action)| (player, action, game.apply(player, action)));
+
+        if let Some(name) = name {
+            log::info!("{} ran out of time", name);
+            self.broadcast_system(format!("{} ran out of time", name));
+        }
+        match result {
+            Some((player, action, Ok(events))) => self.record(player, action, events),
+            Some((player, action, Err(err))) => log::warn!("timed out seat {} couldn't play {:?}: {}", player, action, err),
+            None => ()
+        }
+    }
+
+    /* keep what an action did for saves and the replay, then tell everyone about it */
+    fn record(&mut self, player: PlayerId, action: Action, events: Vec<GameEvent>) {
+        let mut failed = None;
+        if let ServerPhase::Started { game, log, recording, clocks, .. } = &mut self.phase {
+            let now = Instant::now();
+            for event in events.iter() {
+                match (clocks.as_mut(), event) {
+                    (Some(clocks), GameEvent::TurnStarted { player }) => clocks.start_turn(*player, now),
+                    (Some(clocks), GameEvent::Won { .. }) => clocks.stop(now),
+                    _ => ()
+                }
+            }
+            if let Some(clocks) = clocks.as_mut() {
+                clocks.sync_discards(&game.phase, now);
+            }
+
+            log.extend(events.iter().cloned());
+            if let Some(file) = recording {
+                if let Err(err) = file.append(&Record { player, action, events: events.clone() }) {
+                    log::warn!("failed to record the game: {}", err);
+                    *recording = None;
+                    failed = Some(err);
+                }
+            }
+        }
+
+        if let Some(err) = failed {
+            self.broadcast_system(format!("stopped recording the game: {}", err));
+        }
+        self.send_update(events);
+    }
+
+    /* send every god view message that has waited long enough */
+    fn flush_backlogs(&mut self, now: Instant) {
+        let mut ready = Vec::new();
+        for (&id, spectator) in self.spectators.iter_mut() {
+            while matches!(spectator.backlog.front(), Some((send_at, _)) if *send_at <= now) {
+                ready.push((id, spectator.backlog.pop_front().unwrap().1));
+            }
+        }
+
+        for (id, message) in ready {
+            self.send(id, &message);
+        }
+    }
+
+    /* tell every seat and spectator what just happened as far as they are allowed to know */
+    fn send_update(&mut self, events: Vec<GameEvent>) {
+        let (sessions, game, clocks) = match &self.phase {
+            ServerPhase::Started { sessions, game, clocks, .. } => (sessions, game, clocks.as_ref().map(|clocks| clocks.view(Instant::now()))),
+            ServerPhase::Lobby(_) => return
+        };
+
+        let update_for = |viewer: Viewer| ServerMessage::Update(
+            events.iter().map(|event| event.redact_for(viewer)).collect(),
+            game.view_for(viewer),
+            clocks.clone()
+        );
+
+        let mut messages: Vec<(ClientId, ServerMessage)> = (0..game.players.len())
+            .filter_map(|seat| sessions.get_client(seat).map(|client| (client, update_for(Viewer::Player(seat)))))
+            .collect();
+
+        let public = update_for(Viewer::Public);
+        let god = update_for(Viewer::God);
+        let send_at = Instant::now() + self.config.god_view_delay.unwrap_or_default();
+        for (&id, spectator) in self.spectators.iter_mut() {
+            if spectator.god_view {
+                spectator.backlog.push_back((send_at, god.clone()));
+            } else {
+                messages.push((id, public.clone()));
+            }
+        }
+
+        for (id, message) in messages {
+            self.send(id, &message);
+        }
+    }
+
+    /* the game as a spectator sees it right now */
+    fn spectator_snapshot(&self, viewer: Viewer) -> Option<GameSnapshot> {
+        match &self.phase {
+            ServerPhase::Started { setup, sessions, game, clocks, .. } => Some(GameSnapshot {
+                setup: setup.clone(),
+                seat: None,
+                presence: sessions.get_presences(),
+                view: game.view_for(viewer),
+                clocks: clocks.as_ref().map(|clocks| clocks.view(Instant::now()))
+            }),
+            ServerPhase::Lobby(_) => None
+        }
+    }
+
+    /* start watching the game, god view snapshots are held back like every other god view update */
+    fn watch(&mut self, id: ClientId, version: u32, name: String, god_view: bool) {
+        let is_seated = match &self.phase {
+            ServerPhase::Lobby(lobby) => lobby.seat_of(id).is_some(),
+            ServerPhase::Started { sessions, .. } => sessions.seat_of(id).is_some()
+        };
+
+        let reason = if version != PROTOCOL_VERSION {
+            Some(Self::version_mismatch(version))
+        } else if is_seated {
+            Some(String::from("players can't watch their own game"))
+        } else if self.spectators.contains_key(&id) {
+            Some(String::from("already watching"))
+        } else if god_view && self.config.god_view_delay.is_none() {
+            Some(String::from("the host does not allow god view"))
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            self.send(id, &ServerMessage::Rejected(reason));
+            if !is_seated && !self.spectators.contains_key(&id) {
+                self.disconnect(id);
+            }
+            return
+        }
+
+        log::info!("client {} is watching as {} (god view: {})", id, name, god_view);
+        let delay = if god_view { self.config.god_view_delay } else { None };
+        self.send(id, &ServerMessage::Watching(delay.map(|delay| delay.as_secs())));
+        if let ServerPhase::Lobby(lobby) = &self.phase {
+            let message = ServerMessage::Lobby(lobby.clone());
+            self.send(id, &message);
+        }
+
+        let mut spectator = Spectator { name, god_view, backlog: VecDeque::new() };
+        let viewer = if god_view { Viewer::God } else { Viewer::Public };
+        if let Some(snapshot) = self.spectator_snapshot(viewer) {
+            match delay {
+                Some(delay) => spectator.backlog.push_back((Instant::now() + delay, ServerMessage::Snapshot(snapshot))),
+                None => self.send(id, &ServerMessage::Snapshot(snapshot))
+            }
+        }
+
+        self.broadcast_system(format!("{} started watching", spectator.name));
+        self.spectators.insert(id, spectator);
+        self.broadcast_observers();
+    }
+
+    /*
+     * pass a chat message on to everyone who can read it
+     * spectators only talk among themselves since a god view spectator could otherwise tell players what is in someone's hand
+     */
+    fn chat(&mut self, id: ClientId, text: String) {
+        let text: String = text.trim().chars().take(MAX_CHAT_LENGTH).collect();
+        if text.is_empty() {
+            return
+        }
+
+        let sessions = match &self.phase {
+            ServerPhase::Started { sessions, .. } => sessions,
+            ServerPhase::Lobby(_) => return self.send(id, &ServerMessage::Rejected(String::from("chat opens once the game starts")))
+        };
+
+        if let Some(spectator) = self.spectators.get(&id) {
+            let message = ServerMessage::Chat(ChatSender::Observer(spectator.name.clone()), text);
+            let ids: Vec<ClientId> = self.spectators.keys().copied().collect();
+            for id in ids {
+                self.send(id, &message);
+            }
+        } else if let Some(seat) = sessions.seat_of(id) {
+            self.broadcast(&ServerMessage::Chat(ChatSender::Player(seat), text));
+        }
+    }
+
+    /* tell everyone in a started game about something that happened outside of the rules */
+    fn broadcast_system(&mut self, text: String) {
+        if let ServerPhase::Started { .. } = self.phase {
+            self.broadcast(&ServerMessage::Chat(ChatSender::System, text));
+        }
+    }
+
+    fn act(&mut self, id: ClientId, action: Action) {
+        let result = match &mut self.phase {
+            _ if self.spectators.contains_key(&id) => Err(String::from("spectators can't act")),
+            ServerPhase::Lobby(_) => Err(String::from("the game has not started yet")),
+            ServerPhase::Started { sessions, game, .. } => match sessions.seat_of(id) {
+                Some(seat) => game.apply(seat, action).map(|events| (seat, events)).map_err(|err| err.to_string()),
+                None => Err(String::from("you don't have a seat in this game"))
+            }
+        };
+
+        match result {
+            Ok((seat, events)) => self.record(seat, action, events),
+            Err(reason) => self.send(id, &ServerMessage::Rejected(reason))
+        }
+    }
+
+    /* put a client back into the seat their token belongs to and catch them up on the game */
+    fn resume(&mut self, id: ClientId, version: u32, token: SessionToken) {
+        let resumed = match &mut self.phase {
+            _ if version != PROTOCOL_VERSION => Err(Self::version_mismatch(version)),
+            ServerPhase::Lobby(_) => Err(String::from("there is no game to resume yet")),
+            ServerPhase::Started { setup, sessions, game, clocks, .. } => match sessions.resume(token, id) {
+                Some((seat, replaced)) => Ok((
+                    seat,
+                    replaced,
+                    GameSnapshot {
+                        setup: setup.clone(),
+                        seat: Some(seat),
+                        presence: sessions.get_presences(),
+                        view: game.view_for(Viewer::Player(seat)),
+                        clocks: clocks.as_ref().map(|clocks| clocks.view(Instant::now()))
+                    }
+                )),
+                None => Err(String::from("that seat is no longer yours"))
+            }
+        };
+
+        match resumed {
+            Ok((seat, replaced, snapshot)) => {
+                log::info!("client {} took back seat {}", id, seat);
+                if let Some(client) = replaced {
+                    self.disconnect(client);
+                }
+                self.send(id, &ServerMessage::Welcome(id, token));
+                self.send(id, &ServerMessage::Snapshot(snapshot));
+                self.send(id, &ServerMessage::Observers(self.get_observers()));
+                self.broadcast(&ServerMessage::Presence(seat, Presence::Connected));
+                if let ServerPhase::Started { setup, .. } = &self.phase {
+                    let name = setup.players[seat].name.clone();
+                    self.broadcast_system(format!("{} is back", name));
+                }
+            },
+            Err(reason) => {
+                self.send(id, &ServerMessage::Rejected(reason));
+                self.disconnect(id);
+            }
+        }
+    }
+
+    /* write the game to a file where the server was started from, only the host can */
+    fn save(&mut self, id: ClientId) {
+        let result = match &self.phase {
+            ServerPhase::Lobby(_) => Err(String::from("there is no game to save yet")),
+            ServerPhase::Started { sessions, host, .. } if host.is_none() || sessions.seat_of(id) != *host => {
+                Err(String::from("only the host can save the game"))
+            },
+            ServerPhase::Started { setup, game, log, clocks, .. } => {
+                let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+                let path = format!("game-{}.save", time);
+                let banks = clocks.as_ref().and_then(|clocks| clocks.get_banks(Instant::now()));
+                save::save_game(Path::new(&path), setup, game, banks.as_deref(), log)
+                    .map(|_| path.clone())
+                    .map_err(|err| format!("couldn't save the game to {}: {}", path, err))
+            }
+        };
+
+        match result {
+            Ok(path) => {
+                log::info!("saved the game to {}", path);
+                self.broadcast_system(format!("the host saved the game to {}, it can be continued with: catarminal host --load {}", path, path));
+            },
+            Err(reason) => self.send(id, &ServerMessage::Rejected(reason))
+        }
+    }
+
+    fn handle_message(&mut self, id: ClientId, message: ClientMessage) {
+        let message = match message {
+            ClientMessage::Resume { version, token } => return self.resume(id, version, token),
+            ClientMessage::Watch { version, name, god_view } => return self.watch(id, version, name, god_view),
+            ClientMessage::Act(action) => return self.act(id, action),
+            ClientMessage::Chat(text) => return self.chat(id, text),
+            ClientMessage::SaveGame => return self.save(id),
+            _ if self.spectators.contains_key(&id) => {
+                return self.send(id, &ServerMessage::Rejected(String::from("spectators can't change the lobby")))
+            },
+            message => message
+        };
+
+        let lobby = match &mut self.phase {
+            ServerPhase::Lobby(lobby) => lobby,
+            ServerPhase::Started { sessions, .. } => {
+                let is_seated = sessions.seat_of(id).is_some();
+                self.send(id, &ServerMessage::Rejected(String::from("the game has already started")));
+                if !is_seated {
+                    self.disconnect(id);
+                }
+                return
+            }
+        };
+
+        let mut kicked = None;
+        let mut joined = false;
+        let result = match message {
+            ClientMessage::Hello { version, name } => {
+                if version != PROTOCOL_VERSION {
+                    Err(Self::version_mismatch(version))
+                } else if lobby.seat_of(id).is_some() {
+                    Err(String::from("already joined"))
+                } else {
+                    lobby.join(id, &name)
+                        .map(|_| joined = true)
+                        .map_err(|err| err.to_string())
+                }
+            },
+            ClientMessage::SetName(name) => lobby.set_name(id, &name).map_err(|err| err.to_string()),
+            ClientMessage::SetColor(color) => lobby.set_color(id, color).map_err(|err| err.to_string()),
+            ClientMessage::SetReady(is_ready) => lobby.set_ready(id, is_ready).map_err(|err| err.to_string()),
+            ClientMessage::Kick(seat) => lobby.kick(id, seat)
+                .map(|client| kicked = client)
+                .map_err(|err| err.to_string()),
+            ClientMessage::AddBot => lobby.add_bot(id).map(|_| ()).map_err(|err| err.to_string()),
+            ClientMessage::SetOptions(_) if self.saved.is_some() => Err(String::from("the options of a saved game can't be changed")),
+            ClientMessage::SetOptions(options) => lobby.set_options(id, options).map_err(|err| err.to_string()),
+            ClientMessage::Resume { .. } | ClientMessage::Watch { .. } | ClientMessage::Act(_) | ClientMessage::Chat(_) | ClientMessage::SaveGame => unreachable!()
+        };
+        let is_seated = lobby.seat_of(id).is_some();
+
+        if let Err(reason) = result {
+            self.send(id, &ServerMessage::Rejected(reason));
+            if !is_seated {
+                /* clients that could not even take a seat are turned away */
+                self.disconnect(id);
+            }
+            return
+        }
+
+        if joined {
+            let token = rand::random();
+            self.tokens.insert(id, token);
+            self.send(id, &ServerMessage::Welcome(id, token));
+            self.send(id, &ServerMessage::Observers(self.get_observers()));
+        }
+
+        if let Some(client) = kicked {
+            self.tokens.remove(&client);
+            self.send(client, &ServerMessage::Kicked);
+            self.disconnect(client);
+        }
+
+        self.broadcast_lobby();
+        self.try_start();
+    }
+
+    /*
+     * start the game as soon as everyone in the lobby is ready
+     * a saved game only starts with as many players as it was saved with, they take over the saved seats in seat order
+     */
+    fn try_start(&mut self) {
+        let lobby = match &self.phase {
+            ServerPhase::Lobby(lobby) if lobby.is_ready() => lobby,
+            _ => return
+        };
+
+        let mut setup = lobby.to_setup();
+        let host = lobby.host.and_then(|host| setup.players.iter().position(|seat| seat.occupant == Occupant::Client(host)));
+        let (game, banks, log) = match self.saved.take() {
+            Some(saved) if saved.setup.players.len() != setup.players.len() => {
+                let reason = format!("the saved game is for {} players", saved.setup.players.len());
+                self.saved = Some(saved);
+                return self.broadcast(&ServerMessage::Rejected(reason))
+            },
+            Some(saved) => {
+                log::info!("continuing a saved game with {} players", setup.players.len());
+                for (seat, saved_seat) in setup.players.iter_mut().zip(saved.setup.players) {
+                    seat.name = saved_seat.name;
+                    seat.color = saved_seat.color;
+                }
+                (Box::new(saved.game), saved.banks, saved.log)
+            },
+            None => {
+                log::info!("starting game with {} players", setup.players.len());
+                /* the board comes from the same seed clients draw their map from */
+                let board = Board::generate(&mut StdRng::seed_from_u64(setup.options.map_seed));
+                (Box::new(GameState::new(board, setup.players.len(), setup.options.victory_points, rand::random())), None, Vec::new())
+            }
+        };
+
+        let time_control = setup.options.time_control;
+        let clocks = time_control.is_on().then(|| {
+            let mut clocks = Clocks::new(time_control, setup.players.len(), game.turn, banks, Instant::now());
+            clocks.sync_discards(&game.phase, Instant::now());
+            Box::new(clocks)
+        });
+
+        /* the replay starts from the game as it is now, saved games included */
+        let (recording, note) = match &self.config.record {
+            Some(path) => match save::create_replay(path, &setup, &game, clocks.as_ref().and_then(|clocks| clocks.get_banks(Instant::now())).as_deref(), &log) {
+                Ok(file) => (Some(file), Some(format!("recording the game to {}", path.display()))),
+                Err(err) => {
+                    log::warn!("failed to start recording to {}: {}", path.display(), err);
+                    (None, Some(format!("couldn't record the game to {}: {}", path.display(), err)))
+                }
+            },
+            None => (None, None)
+        };
+        let sessions = Sessions::new(&setup, self.config.sessions, |client| self.tokens.get(&client).copied());
+        self.phase = ServerPhase::Started { setup, sessions, game, log, host, recording, clocks, next_bot_action: Instant::now() + BOT_DELAY };
+        self.send_start();
+        if let Some(note) = note {
+            self.broadcast_system(note);
+        }
+    }
+
+    /* hand everyone their first look at the game */
+    fn send_start(&mut self) {
+        let (setup, sessions, game, clocks) = match &self.phase {
+            ServerPhase::Started { setup, sessions, game, clocks, .. } => (setup, sessions, game, clocks.as_ref().map(|clocks| clocks.view(Instant::now()))),
+            ServerPhase::Lobby(_) => return
+        };
+
+        let snapshot_for = |seat: Option<usize>, viewer: Viewer| ServerMessage::Snapshot(GameSnapshot {
+            setup: setup.clone(),
+            seat,
+            presence: sessions.get_presences(),
+            view: game.view_for(viewer),
+            clocks: clocks.clone()
+        });
+
+        let mut messages: Vec<(ClientId, ServerMessage)> = (0..setup.players.len())
+            .filter_map(|seat| sessions.get_client(seat).map(|client| (client, snapshot_for(Some(seat), Viewer::Player(seat)))))
+            .collect();
+
+        let send_at = Instant::now() + self.config.god_view_delay.unwrap_or_default();
+        for (&id, spectator) in self.spectators.iter_mut() {
+            if spectator.god_view {
+                spectator.backlog.push_back((send_at, snapshot_for(None, Viewer::God)));
+            } else {
+                messages.push((id, snapshot_for(None, Viewer::Public)));
+            }
+        }
+
+        for (id, message) in messages {
+            self.send(id, &message);
+        }
+    }
+
+    /* names of everyone watching, sorted so every client lists them the same way */
+    fn get_observers(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.spectators.values().map(|spectator| spectator.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    fn broadcast_observers(&mut self) {
+        let message = ServerMessage::Observers(self.get_observers());
+        self.broadcast(&message);
+    }
+
+    fn version_mismatch(version: u32) -> String {
+        format!("server speaks protocol version {} but client speaks {}", PROTOCOL_VERSION, version)
+    }
+
+    fn broadcast_lobby(&mut self) {
+        if let ServerPhase::Lobby(lobby) = &self.phase {
+            let message = ServerMessage::Lobby(lobby.clone());
+            self.broadcast(&message);
+        }
+    }
+
+    fn broadcast(&mut self, message: &ServerMessage) {
+        let line = encode(message);
+        let ids: Vec<ClientId> = self.connections.keys().copied().collect();
+        for id in ids {
+            self.send_line(id, &line);
+        }
+    }
+
+    fn send(&mut self, id: ClientId, message: &ServerMessage) {
+        let line = encode(message);
+        self.send_line(id, &line);
+    }
+
+    /* never waits on the client, one that has fallen too far behind is dropped instead */
+    fn send_line(&mut self, id: ClientId, line: &str) {
+        let result = match self.connections.get(&id) {
+            Some(connection) => connection.lines.try_send(line.to_owned()),
+            None => return
+        };
+
+        match result {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                log::warn!("client {} stopped reading, dropping them", id);
+                if let Some(connection) = self.connections.remove(&id) {
+                    connection.stream.shutdown(std::net::Shutdown::Both).ok();
+                }
+            },
+            Err(TrySendError::Disconnected(_)) => self.disconnect(id)
+        }
+    }
+
+    /*
+     * closing the reading half ends its reader thread which will report the disconnect
+     * dropping the queue lets the writer thread send what is left, like why they were turned away, and close the rest
+     */
+    fn disconnect(&mut self, id: ClientId) {
+        if let Some(connection) = self.connections.remove(&id) {
+            connection.stream.shutdown(std::net::Shutdown::Read).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /* a bare connection that talks the protocol by hand */
+    struct TestClient {
+        stream: TcpStream,
+        lines: io::Lines<BufReader<TcpStream>>
+    }
+
+    impl TestClient {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream.set_read_timeout(Some(WAIT)).unwrap();
+            TestClient { lines: BufReader::new(stream.try_clone().unwrap()).lines(), stream }
+        }
+
+        fn join(addr: SocketAddr, name: &str) -> Self {
+            let mut client = Self::connect(addr);
+            client.send(ClientMessage::Hello { version: PROTOCOL_VERSION, name: name.to_owned() });
+            client.wait_for(|message| matches!(message, ServerMessage::Welcome(..)));
+            client
+        }
+
+        fn send(&mut self, message: ClientMessage) {
+            writeln!(self.stream, "{}", encode(&message)).unwrap();
+        }
+
+        /* skip messages until one matches, panics once the server goes quiet for too long */
+        fn wait_for(&mut self, matches: impl Fn(&ServerMessage) -> bool) -> ServerMessage {
+            loop {
+                let line = self.lines.next().expect("the server closed the connection").expect("the server went quiet");
+                let message = decode(&line).unwrap();
+                if matches(&message) {
+                    return message
+                }
+            }
+        }
+    }
+
+    fn host() -> SocketAddr {
+        host_with(ServerConfig::default())
+    }
+
+    fn host_with(config: ServerConfig) -> SocketAddr {
+        spawn(("127.0.0.1", 0), GameOptions::new(0), config, None).unwrap()
+    }
+
+    /* a game between a player and a bot that has already started */
+    fn start(config: ServerConfig) -> (SocketAddr, TestClient) {
+        let addr = host_with(config);
+        let mut ann = TestClient::join(addr, "Ann");
+        ann.send(ClientMessage::AddBot);
+        ann.send(ClientMessage::SetReady(true));
+        ann.wait_for(|message| matches!(message, ServerMessage::Snapshot(_)));
+        (addr, ann)
+    }
+
+    /* nobody else does anything once the only player who wasn't ready leaves, the game still has to start */
+    #[test]
+    fn leaving_the_lobby_can_start_the_game() {
+        let addr = host();
+        let mut ann = TestClient::join(addr, "Ann");
+        ann.send(ClientMessage::AddBot);
+        let bob = TestClient::join(addr, "Bob");
+
+        ann.send(ClientMessage::SetReady(true));
+        ann.wait_for(|message| matches!(message, ServerMessage::Lobby(lobby) if lobby.seats[0].as_ref().is_some_and(|seat| seat.is_ready)));
+        drop(bob);
+
+        match ann.wait_for(|message| matches!(message, ServerMessage::Snapshot(_))) {
+            ServerMessage::Snapshot(snapshot) => assert_eq!(snapshot.setup.players.len(), 2),
+            _ => unreachable!()
+        }
+    }
+
+    /* both ends of a connection over loopback */
+    fn stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (listener.accept().unwrap().0, client)
+    }
+
+    /* someone who stops reading gets dropped instead of holding up everyone else */
+    #[test]
+    fn stalled_clients_are_dropped() {
+        let mut server = Server {
+            phase: ServerPhase::Lobby(Lobby::new(GameOptions::new(0))),
+            config: ServerConfig::default(),
+            connections: HashMap::new(),
+            tokens: HashMap::new(),
+            spectators: HashMap::new(),
+            saved: None,
+            rng: StdRng::seed_from_u64(0)
+        };
+
+        /* nothing ever takes lines out of the queues like a writer thread stuck on a full connection would */
+        let (reading, _reading_client) = stream_pair();
+        let (stalled, mut stalled_client) = stream_pair();
+        let (lines, reading_queue) = mpsc::sync_channel(SEND_QUEUE);
+        server.connections.insert(1, Connection { stream: reading, lines });
+        let (lines, _stalled_queue) = mpsc::sync_channel(SEND_QUEUE);
+        server.connections.insert(2, Connection { stream: stalled, lines });
+
+        for _ in 0..=SEND_QUEUE {
+            server.broadcast(&ServerMessage::Observers(Vec::new()));
+            reading_queue.try_iter().for_each(drop);
+        }
+
+        assert!(server.connections.contains_key(&1));
+        assert!(!server.connections.contains_key(&2));
+        assert_eq!(stalled_client.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    /* what is queued for a client that gets turned away still reaches them */
+    #[test]
+    fn kicked_clients_hear_why() {
+        let addr = host();
+        let mut ann = TestClient::join(addr, "Ann");
+        let mut bob = TestClient::join(addr, "Bob");
+
+        ann.send(ClientMessage::Kick(1));
+        bob.wait_for(|message| *message == ServerMessage::Kicked);
+        assert!(bob.lines.next().is_none());
+    }
+
+    /* public spectators see the game right away without anyone's hand, god view sees every hand but only after the delay */
+    #[test]
+    fn spectators_watch() {
+        const DELAY: Duration = Duration::from_millis(500);
+        let (addr, _ann) = start(ServerConfig { god_view_delay: Some(DELAY), ..ServerConfig::default() });
+
+        let mut public = TestClient::connect(addr);
+        public.send(ClientMessage::Watch { version: PROTOCOL_VERSION, name: String::from("Bob"), god_view: false });
+        assert_eq!(public.wait_for(|message| matches!(message, ServerMessage::Watching(_))), ServerMessage::Watching(None));
+        match public.wait_for(|message| matches!(message, ServerMessage::Snapshot(_))) {
+            ServerMessage::Snapshot(snapshot) => {
+                assert_eq!(snapshot.seat, None);
+                assert!(snapshot.view.players.iter().all(|player| player.hand.is_none()));
+            },
+            _ => unreachable!()
+        }
+
+        let watched_at = Instant::now();
+        let mut god = TestClient::connect(addr);
+        god.send(ClientMessage::Watch { version: PROTOCOL_VERSION, name: String::from("Cy"), god_view: true });
+        god.wait_for(|message| matches!(message, ServerMessage::Watching(Some(_))));
+        match god.wait_for(|message| matches!(message, ServerMessage::Snapshot(_))) {
+            ServerMessage::Snapshot(snapshot) => assert!(snapshot.view.players.iter().all(|player| player.hand.is_some())),
+            _ => unreachable!()
+        }
+        assert!(watched_at.elapsed() >= DELAY);
+
+        /* watching doesn't come with a say in the game */
+        public.send(ClientMessage::Act(Action::Roll));
+        assert_eq!(public.wait_for(|message| matches!(message, ServerMessage::Rejected(_))), ServerMessage::Rejected(String::from("spectators can't act")));
+    }
+
+    #[test]
+    fn god_view_has_to_be_allowed() {
+        let (addr, _ann) = start(ServerConfig::default());
+        let mut god = TestClient::connect(addr);
+        god.send(ClientMessage::Watch { version: PROTOCOL_VERSION, name: String::from("Cy"), god_view: true });
+        god.wait_for(|message| matches!(message, ServerMessage::Rejected(_)));
+        assert!(god.lines.next().is_none());
+    }
+}
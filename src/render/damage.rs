@@ -0,0 +1,274 @@
+/*
+ * damage.rs
+ * module of constructs used in redrawing only the parts of the screen that changed
+ *
+ * every relayout the space each mount is drawn in is compared with where it was drawn the relayout before
+ * mounts that moved, resized, showed, hid or are animating their layout damage where they were and where they are now
+ * mounts that change how they look without their layout changing (like an animation they play themselves) damage themselves with ctx.invalidate
+ * anything that changes drawings outside of a relayout (input, the update hook of the run loop) marks the mount so it damages itself on its next relayout
+ * only a resize damages the whole screen
+ */
+
+use super::{space::*, mount::*};
+
+use std::collections::HashMap;
+
+/* where a mount was laid out and drawn, a hidden mount isn't drawn anywhere */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Footprint {
+    layout_space: AbsoluteSpace,
+    draw_space: Option<AbsoluteSpace>
+}
+
+#[derive(Debug, Default)]
+pub struct DamageService {
+    /* footprints of the last relayout and the one in progress */
+    footprints: HashMap<MountId, Footprint>,
+    next_footprints: HashMap<MountId, Footprint>,
+    /* damaged regions that don't overlap each other */
+    regions: Vec<AbsoluteSpace>,
+    is_all_damaged: bool,
+    invalidation_count: u64,
+    last_regions: Vec<AbsoluteSpace>
+}
+
+impl DamageService {
+    /* the whole screen gets redrawn next frame */
+    pub fn invalidate_all(&mut self) {
+        self.is_all_damaged = true;
+    }
+
+    /* the space gets redrawn next frame */
+    pub fn invalidate(&mut self, space: AbsoluteSpace) {
+        self.invalidate_drawn(Some(space));
+    }
+
+    /* 
+     * something changed so where it is drawn gets redrawn next frame, if it is drawn at all
+     * it is counted either way (see get_invalidation_count) and regions that overlap are merged so no cell gets drawn twice
+     */
+    pub fn invalidate_drawn(&mut self, draw_space: Option<AbsoluteSpace>) {
+        self.invalidation_count += 1;
+        let mut space = match draw_space {
+            Some(space) if !self.is_all_damaged && space.size.x > 0 && space.size.y > 0 => space,
+            _ => return
+        };
+
+        while let Some(i) = self.regions.iter().position(|&region| region.intersects(space)) {
+            space = space.union(self.regions.swap_remove(i));
+        }
+        self.regions.push(space);
+    }
+
+    /* record where a mount is after its relayout, damaging both spaces if it changed and where it is if its layout is animating */
+    pub fn track(&mut self, id: MountId, layout_space: AbsoluteSpace, draw_space: Option<AbsoluteSpace>, is_animating: bool) {
+        let footprint = Footprint { layout_space, draw_space };
+        match self.footprints.get(&id).copied() {
+            Some(last_footprint) if last_footprint == footprint => (),
+            Some(last_footprint) => {
+                self.invalidate_drawn(last_footprint.draw_space);
+                self.invalidate_drawn(draw_space);
+            },
+            None => self.invalidate_drawn(draw_space)
+        }
+
+        if is_animating {
+            self.invalidate_drawn(draw_space);
+        }
+
+        self.next_footprints.insert(id, footprint);
+    }
+
+    /* mounts that weren't laid out this time are gone so wherever they were drawn is damaged */
+    pub fn finish_relayout(&mut self) {
+        let footprints = std::mem::take(&mut self.next_footprints);
+        let gone: Vec<AbsoluteSpace> = self.footprints
+            .iter()
+            .filter(|(id, _)| !footprints.contains_key(id))
+            .filter_map(|(_, footprint)| footprint.draw_space)
+            .collect();
+        for space in gone {
+            self.invalidate(space);
+        }
+        self.footprints = footprints;
+    }
+
+    /* take the damaged regions inside the screen space, leaving nothing damaged */
+    pub fn take_regions(&mut self, screen_space: AbsoluteSpace) -> Vec<AbsoluteSpace> {
+        let regions = if std::mem::take(&mut self.is_all_damaged) {
+            self.regions.clear();
+            vec![screen_space]
+        } else {
+            self.regions
+                .drain(..)
+                .filter_map(|region| region.try_intersection(screen_space))
+                .collect()
+        };
+        self.last_regions = regions.clone();
+        regions
+    }
+
+    /* 
+     * how many times something was damaged so far, drawn or not, comparing it before and after relaying out children tells if any of them changed
+     * drawings that draw their children somewhere other than where they are laid out use this to damage themselves instead
+     */
+    pub fn get_invalidation_count(&self) -> u64 {
+        self.invalidation_count
+    }
+
+    /* how many cells were redrawn the last time regions were taken */
+    pub fn get_last_redraw_area(&self) -> u32 {
+        self.last_regions.iter().map(|region| region.size.x as u32*region.size.y as u32).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{prelude::*, screen::Screen, snapshot, drawing::events::*};
+
+    use tui::{buffer::Buffer, layout::Rect, widgets::StatefulWidget};
+
+    const SCREEN: AbsoluteSpace = AbsoluteSpace::new(0, 0, 40, 10);
+
+    fn get_damage(spaces: &[AbsoluteSpace]) -> DamageService {
+        let mut damage = DamageService::default();
+        for &space in spaces {
+            damage.invalidate(space);
+        }
+        damage
+    }
+
+    fn sorted(mut regions: Vec<AbsoluteSpace>) -> Vec<AbsoluteSpace> {
+        regions.sort_by_key(|region| (region.position.y, region.position.x));
+        regions
+    }
+
+    #[test]
+    fn overlapping_regions_merge() {
+        let (a, b) = (AbsoluteSpace::new(0, 0, 4, 2), AbsoluteSpace::new(10, 0, 4, 2));
+        let mut damage = get_damage(&[a, b]);
+        assert_eq!(sorted(damage.take_regions(SCREEN)), vec![a, b]);
+        assert!(damage.take_regions(SCREEN).is_empty());
+
+        /* a region touching both pulls them into one */
+        let mut damage = get_damage(&[a, b, AbsoluteSpace::new(2, 1, 10, 1)]);
+        assert_eq!(damage.take_regions(SCREEN), vec![AbsoluteSpace::new(0, 0, 14, 2)]);
+
+        /* nothing drawn is nothing damaged, it still counts as an invalidation */
+        let mut damage = get_damage(&[AbsoluteSpace::new(3, 3, 0, 5)]);
+        damage.invalidate_drawn(None);
+        assert_eq!(damage.get_invalidation_count(), 2);
+        assert!(damage.take_regions(SCREEN).is_empty());
+    }
+
+    #[test]
+    fn regions_stay_on_the_screen() {
+        let mut damage = get_damage(&[AbsoluteSpace::new(36, 8, 8, 4), AbsoluteSpace::new(50, 0, 2, 2)]);
+        assert_eq!(damage.take_regions(SCREEN), vec![AbsoluteSpace::new(36, 8, 4, 2)]);
+        assert_eq!(damage.get_last_redraw_area(), 8);
+
+        let mut damage = get_damage(&[AbsoluteSpace::new(0, 0, 4, 2)]);
+        damage.invalidate_all();
+        damage.invalidate(AbsoluteSpace::new(10, 0, 4, 2));
+        assert_eq!(damage.take_regions(SCREEN), vec![SCREEN]);
+        assert!(damage.take_regions(SCREEN).is_empty());
+    }
+
+    /* mounts damage where they were and where they are when they move, and where they were when they are gone */
+    #[test]
+    fn tracked_mounts_damage_where_they_move() {
+        let (a, b) = (AbsoluteSpace::new(0, 0, 4, 2), AbsoluteSpace::new(10, 0, 4, 2));
+        let mut damage = DamageService::default();
+        damage.track(1, a, Some(a), false);
+        damage.finish_relayout();
+        assert_eq!(damage.take_regions(SCREEN), vec![a]);
+
+        damage.track(1, a, Some(a), false);
+        damage.finish_relayout();
+        assert!(damage.take_regions(SCREEN).is_empty());
+
+        damage.track(1, b, Some(b), false);
+        damage.finish_relayout();
+        assert_eq!(sorted(damage.take_regions(SCREEN)), vec![a, b]);
+
+        damage.track(1, b, Some(b), true);
+        damage.finish_relayout();
+        assert_eq!(damage.take_regions(SCREEN), vec![b]);
+
+        damage.finish_relayout();
+        assert_eq!(damage.take_regions(SCREEN), vec![b]);
+    }
+
+    /* two feeds side by side */
+    #[derive(Debug)]
+    struct Panes {
+        left: EventFeed,
+        right: EventFeed,
+        layout: DrawLayout,
+        mount: Mount
+    }
+
+    impl Layoutable for Panes {
+        fn layout_ref(&self) -> &DrawLayout { &self.layout }
+        fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+    }
+
+    impl StatefulDrawable for Panes {
+        type State = ();
+        fn stateful_draw(&self, ctx: &mut DrawContext, state: &Self::State) {
+            ctx.draw_stateful_child(&self.left, state);
+            ctx.draw_stateful_child(&self.right, state);
+        }
+    }
+
+    impl MountableLayout for Panes {
+        fn mount_ref(&self) -> &Mount { &self.mount }
+        fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+        fn child_ref(&self, i: usize) -> Option<&dyn MountableLayout> {
+            match i {
+                0 => Some(self.left.as_trait_ref()),
+                1 => Some(self.right.as_trait_ref()),
+                _ => None
+            }
+        }
+        fn child_mut(&mut self, i: usize) -> Option<&mut dyn MountableLayout> {
+            match i {
+                0 => Some(self.left.as_trait_mut()),
+                1 => Some(self.right.as_trait_mut()),
+                _ => None
+            }
+        }
+    }
+
+    fn push(feed: &mut EventFeed, text: &str) {
+        feed.push(FeedEntry { time: std::time::Duration::default(), spans: vec![(text.to_owned(), *EVENT_TEXT_STYLE)], target: None });
+    }
+
+    /* a change to one mount redraws only where that mount is, the rest of the screen is kept from the frame before */
+    #[test]
+    fn changes_only_redraw_their_mount() {
+        let half = UDim2::new(0.5, 0, 1.0, 0);
+        let mut screen = Screen::new(Panes {
+            left: EventFeed::new(DrawLayout::default().set_size(half).clone()),
+            right: EventFeed::new(DrawLayout::default().set_size(half).set_position(UDim2::new(0.5, 0, 0.0, 0)).clone()),
+            layout: DrawLayout::FULL,
+            mount: Mount::default()
+        });
+        push(&mut screen.root.left, "left");
+        let mut buf = screen.render_to_buffer(SCREEN.size, &mut ());
+        assert_eq!(screen.service.damage.last_regions, [SCREEN]);
+
+        let render = |screen: &mut Screen<Panes>, buf: &mut Buffer| {
+            *buf = Buffer::empty(Rect::new(0, 0, SCREEN.size.x, SCREEN.size.y));
+            screen.as_stateful_widget().render(buf.area, buf, &mut ());
+        };
+        render(&mut screen, &mut buf);
+        assert!(screen.service.damage.last_regions.is_empty());
+
+        push(&mut screen.root.right, "right");
+        render(&mut screen, &mut buf);
+        assert_eq!(screen.service.damage.last_regions, [AbsoluteSpace::new(20, 0, 20, 10)]);
+        assert!(snapshot::to_text(&buf).starts_with("00:00 left          00:00 right\n"));
+    }
+}
@@ -6,6 +6,7 @@ use super::{space::*, anim::*, mount::{MountableLayout, Mount}, shape::BitShape,
 
 use tui::{
     buffer::{Buffer, Cell},
+    layout::Rect,
     widgets::{Widget, StatefulWidget}, 
     style::Style
 };
@@ -114,9 +115,14 @@ impl Animator for SpaceAnimator {
     }
 }
 
-/* this struct defines fields and methods necessary for a drawing to draw itself out onto the given space in the given buffer */
+/* 
+ * this struct defines fields and methods necessary for a drawing to draw itself out onto the given space in the given buffer
+ * the draw space is what can be drawn to right now, the visible space is what could be drawn to if the whole screen was being drawn
+ * they only differ when just a damaged region of the screen is being redrawn
+ */
 pub struct DrawContext<'a> {
     pub absolute_draw_space: AbsoluteSpace,
+    pub absolute_visible_space: AbsoluteSpace,
     pub absolute_layout_space: AbsoluteSpace,
     pub buf: &'a mut Buffer
 }
@@ -126,6 +132,7 @@ impl<'a> DrawContext<'a> {
         let rect_space = AbsoluteSpace::from_rect(buf.area);
         DrawContext { 
             absolute_draw_space: rect_space, 
+            absolute_visible_space: rect_space, 
             absolute_layout_space: rect_space, 
             buf 
         }
@@ -234,6 +241,7 @@ impl<'a> DrawContext<'a> {
             if let Some(subarea_absolute_draw_space) = self.absolute_draw_space.try_intersection(subarea_absolute_layout_space) {
                 child.draw(&mut DrawContext {
                     absolute_draw_space: subarea_absolute_draw_space,
+                    absolute_visible_space: self.absolute_visible_space.intersection(subarea_absolute_layout_space),
                     absolute_layout_space: subarea_absolute_layout_space,
                     buf: self.buf
                 });
@@ -255,6 +263,7 @@ impl<'a> DrawContext<'a> {
                 child.stateful_draw(
                     &mut DrawContext {
                         absolute_draw_space: subarea_absolute_draw_space,
+                        absolute_visible_space: self.absolute_visible_space.intersection(subarea_absolute_layout_space),
                         absolute_layout_space: subarea_absolute_layout_space,
                         buf: self.buf
                     },
@@ -264,6 +273,7 @@ impl<'a> DrawContext<'a> {
         }
     }
 
+    #[allow(dead_code)]
     pub fn draw_stateful_children<T: StatefulDrawable>(&mut self, children: &[T], states: &[T::State])
         where T::State: Sized 
     {
@@ -273,17 +283,45 @@ impl<'a> DrawContext<'a> {
     }
 
     pub fn draw_widget<T: Widget>(&mut self, widget: T, layout_space: AbsoluteSpace) {
-        let absolute_layout_space = self.absolute_layout_space.absolute_space_of(layout_space);
-        if let Some(absolute_draw_space) = absolute_layout_space.try_intersection(self.absolute_draw_space) {
-            widget.render(absolute_draw_space.to_rect(), self.buf);
-        }
+        self.render_widget_in(layout_space, |rect, buf| widget.render(rect, buf));
     }
 
     #[allow(dead_code)]
     pub fn draw_stateful_widget<T: StatefulWidget>(&mut self, stateful_widget: T, mut state: T::State, layout_space: AbsoluteSpace) {
+        self.render_widget_in(layout_space, |rect, buf| stateful_widget.render(rect, buf, &mut state));
+    }
+
+    /* 
+     * widgets lay themselves out in the rect they render to (a block puts its borders on the edges of it)
+     * so they always render to their visible space and when only part of that is being redrawn they render off to the side
+     * in a workspace holding what is under them and only the part being redrawn is copied back
+     */
+    fn render_widget_in<F>(&mut self, layout_space: AbsoluteSpace, render: F)
+        where F: FnOnce(Rect, &mut Buffer)
+    {
         let absolute_layout_space = self.absolute_layout_space.absolute_space_of(layout_space);
-        if let Some(absolute_draw_space) = absolute_layout_space.try_intersection(self.absolute_draw_space) {
-            stateful_widget.render(absolute_draw_space.to_rect(), self.buf, &mut state);
+        let absolute_widget_space = match absolute_layout_space.try_intersection(self.absolute_visible_space) {
+            Some(absolute_widget_space) => absolute_widget_space,
+            None => return
+        };
+
+        match absolute_widget_space.try_intersection(self.absolute_draw_space) {
+            Some(absolute_draw_space) if absolute_draw_space == absolute_widget_space => render(absolute_widget_space.to_rect(), self.buf),
+            Some(absolute_draw_space) => {
+                let mut workspace = Buffer::empty(absolute_widget_space.to_rect());
+                for point in absolute_draw_space {
+                    let (x, y) = (point.x as u16, point.y as u16);
+                    *workspace.get_mut(x, y) = self.buf.get(x, y).clone();
+                }
+
+                render(workspace.area, &mut workspace);
+
+                for point in absolute_draw_space {
+                    let (x, y) = (point.x as u16, point.y as u16);
+                    *self.buf.get_mut(x, y) = workspace.get(x, y).clone();
+                }
+            },
+            None => ()
         }
     }
 
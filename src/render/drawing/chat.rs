@@ -0,0 +1,301 @@
+/*
+ * chat.rs
+ * the chat pane, a scrollable history of messages above a line to type into
+ *
+ * messages are wrapped to the width of the pane when drawn so resizing never loses anything
+ * typed messages pile up in an outbox that whoever owns the connection drains
+ * the chat only reads the keyboard while it is focused
+ */
+
+use super::text::{self, StyledRow};
+
+use crate::render::prelude::*;
+use crate::net::protocol::MAX_CHAT_LENGTH;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::style::{Color, Modifier, Style};
+use unicode_width::UnicodeWidthChar;
+
+/* rows scrolled per page key press */
+const PAGE_ROWS: usize = 5;
+
+lazy_static! {
+    static ref TEXT_STYLE: Style = Style::default().fg(Color::White);
+    static ref SYSTEM_STYLE: Style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC);
+    static ref HINT_STYLE: Style = Style::default().fg(Color::DarkGray);
+    static ref CURSOR_STYLE: Style = Style::default().fg(Color::Black).bg(Color::White);
+}
+
+/* a message in the history, system messages have no author */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatLine {
+    pub author: Option<(String, Color)>,
+    pub text: String
+}
+
+impl ChatLine {
+    pub fn from(name: String, color: Color, text: String) -> Self {
+        ChatLine { author: Some((name, color)), text }
+    }
+
+    pub fn system(text: String) -> Self {
+        ChatLine { author: None, text }
+    }
+
+    /* the line as styled pieces before it gets wrapped */
+    fn get_spans(&self) -> Vec<(String, Style)> {
+        match self.author.as_ref() {
+            Some((name, color)) => vec![
+                (format!("{}: ", name), Style::default().fg(*color).add_modifier(Modifier::BOLD)),
+                (self.text.clone(), *TEXT_STYLE)
+            ],
+            None => vec![(format!("* {}", self.text), *SYSTEM_STYLE)]
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Chat {
+    lines: Vec<ChatLine>,
+    /* how many rows the history is scrolled up from the newest message */
+    scroll: usize,
+    /* the message being typed, None while not typing */
+    input: Option<String>,
+    /* char index of the cursor in the input */
+    cursor: usize,
+    outbox: Vec<String>,
+    is_focused: bool,
+    /* set when the input is opened from outside so the chat grabs focus on the next relayout */
+    wants_focus: bool,
+    /* set when anything drawn changes outside of a relayout */
+    is_damaged: bool,
+    size: Size2D,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl Chat {
+    pub fn new(layout: DrawLayout) -> Self {
+        Chat {
+            lines: Vec::new(),
+            scroll: 0,
+            input: None,
+            cursor: 0,
+            outbox: Vec::new(),
+            is_focused: false,
+            wants_focus: false,
+            is_damaged: false,
+            size: Size2D::default(),
+            layout,
+            mount: Mount::default()
+        }
+    }
+
+    /* add a message to the history, the view stays put if it is scrolled up */
+    pub fn push(&mut self, line: ChatLine) {
+        if self.scroll > 0 {
+            self.scroll += Self::wrap(&line, self.size.x).len();
+        }
+        self.lines.push(line);
+        self.is_damaged = true;
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /* start typing a message and take focus */
+    pub fn open(&mut self) {
+        if self.input.is_none() {
+            self.input = Some(String::new());
+            self.cursor = 0;
+            self.is_damaged = true;
+        }
+        self.wants_focus = true;
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    fn byte_index(input: &str, cursor: usize) -> usize {
+        input.char_indices().nth(cursor).map(|(i, _)| i).unwrap_or(input.len())
+    }
+
+    /* positive scrolls toward older messages */
+    fn scroll_by(&mut self, rows: i16) -> bool {
+        let scroll = (self.scroll as i16 + rows).max(0).min(self.get_max_scroll() as i16) as usize;
+        if scroll == self.scroll {
+            return false
+        }
+        self.scroll = scroll;
+        true
+    }
+
+    fn get_history_height(size: Size2D) -> usize {
+        size.y.saturating_sub(1) as usize
+    }
+
+    fn get_max_scroll(&self) -> usize {
+        let rows: usize = self.lines.iter().map(|line| Self::wrap(line, self.size.x).len()).sum();
+        rows.saturating_sub(Self::get_history_height(self.size))
+    }
+
+    fn wrap(line: &ChatLine, width: u16) -> Vec<StyledRow> {
+        text::wrap(line.get_spans(), width)
+    }
+
+    fn draw_input(&self, ctx: &mut DrawContext, y: i16) {
+        let input = match self.input.as_ref() {
+            Some(input) => input,
+            None => {
+                ctx.draw_string_line("enter to chat, pgup/pgdn to scroll, tab to move around", Point2D::new(0, y), *HINT_STYLE);
+                return
+            }
+        };
+
+        /* keep the cursor in view by only drawing the end of long messages */
+        let chars: Vec<char> = input.chars().collect();
+        let visible = (ctx.absolute_layout_space.size.x as usize).saturating_sub(3).max(1);
+        let start = (self.cursor + 1).saturating_sub(visible);
+
+        ctx.draw_string_line(">", Point2D::new(0, y), *TEXT_STYLE);
+        let mut x = 2;
+        for (i, &c) in chars.iter().enumerate().skip(start) {
+            let style = if i == self.cursor && self.is_focused { *CURSOR_STYLE } else { *TEXT_STYLE };
+            ctx.draw_unicode_line(&c.to_string(), Point2D::new(x, y), style);
+            x += c.width().unwrap_or(0) as i16;
+        }
+        if self.cursor == chars.len() && self.is_focused {
+            ctx.draw_string_line(" ", Point2D::new(x, y), *CURSOR_STYLE);
+        }
+    }
+}
+
+impl Layoutable for Chat {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for Chat {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let size = ctx.absolute_layout_space.size;
+        let height = Self::get_history_height(size);
+        let rows: Vec<StyledRow> = self.lines.iter().flat_map(|line| Self::wrap(line, size.x)).collect();
+
+        let end = rows.len().saturating_sub(self.scroll.min(rows.len().saturating_sub(height)));
+        let start = end.saturating_sub(height);
+        for (y, row) in rows[start..end].iter().enumerate() {
+            text::draw_row(ctx, row, Point2D::new(0, y as i16));
+        }
+
+        if end < rows.len() && height > 0 {
+            ctx.draw_unicode_line("▼", Point2D::new(size.x as i16 - 1, height as i16 - 1), *HINT_STYLE);
+        }
+
+        self.draw_input(ctx, size.y as i16 - 1);
+    }
+}
+
+impl MountableLayout for Chat {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        self.size = ctx.get_absolute_size_of(self);
+        if std::mem::take(&mut self.wants_focus) {
+            ctx.service.focus.focus(ctx.id);
+        }
+        /* the cursor is only drawn while focused */
+        let is_focused = ctx.relayout_focus_of(self);
+        self.is_damaged |= is_focused != self.is_focused;
+        self.is_focused = is_focused;
+        ctx.relayout_input_space_of(self, Space::FULL);
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let is_handled = self.on_key(key);
+        self.is_damaged |= is_handled;
+        is_handled
+    }
+
+    fn on_mouse_input(&mut self, event: InputEvent) -> bool {
+        let is_handled = match event.kind {
+            InputEventKind::Scroll(_, rows) => self.scroll_by(-rows),
+            InputEventKind::Click(_) if self.input.is_none() => {
+                self.open();
+                true
+            },
+            _ => false
+        };
+        self.is_damaged |= is_handled;
+        is_handled
+    }
+}
+
+impl Chat {
+    /* enter starts and sends a message, escape stops typing, page keys scroll, everything else edits the input */
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::PageUp => return self.scroll_by(PAGE_ROWS as i16),
+            KeyCode::PageDown => return self.scroll_by(-(PAGE_ROWS as i16)),
+            _ => ()
+        }
+
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => {
+                if key.code == KeyCode::Enter {
+                    self.input = Some(String::new());
+                    self.cursor = 0;
+                    return true
+                }
+                return false
+            }
+        };
+
+        let length = input.chars().count();
+        match key.code {
+            KeyCode::Char(c) => {
+                if length < MAX_CHAT_LENGTH {
+                    input.insert(Self::byte_index(input, self.cursor), c);
+                    self.cursor += 1;
+                }
+            },
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    input.remove(Self::byte_index(input, self.cursor));
+                }
+            },
+            KeyCode::Delete => {
+                if self.cursor < length {
+                    input.remove(Self::byte_index(input, self.cursor));
+                }
+            },
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(length),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = length,
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => {
+                let text = input.trim().to_owned();
+                if !text.is_empty() {
+                    self.outbox.push(text);
+                    self.scroll = 0;
+                }
+                self.input = None;
+            },
+            _ => return false
+        }
+
+        true
+    }
+}
@@ -0,0 +1,298 @@
+/*
+ * command.rs
+ * the command line along the bottom of the screen, for playing by typing things like "build settlement 23"
+ *
+ * it is only there while open and takes focus when it opens, handing focus back to whatever had it when it closes
+ * tab cycles through the legal actions that start with what was typed, up and down go through what was sent before
+ * text that doesn't parse stays on the line with the reason next to it, actions that do go to an outbox
+ */
+
+use crate::render::prelude::*;
+use crate::logic::{game::Action, notation};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::style::{Color, Style};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const MAX_COMMAND_LENGTH: usize = 100;
+const PROMPT: &str = ":";
+
+lazy_static! {
+    static ref TEXT_STYLE: Style = Style::default().fg(Color::White).bg(Color::Black);
+    static ref HINT_STYLE: Style = Style::default().fg(Color::DarkGray).bg(Color::Black);
+    static ref ERROR_STYLE: Style = Style::default().fg(Color::Red).bg(Color::Black);
+    static ref CURSOR_STYLE: Style = Style::default().fg(Color::Black).bg(Color::White);
+}
+
+#[derive(Debug)]
+pub struct CommandLine {
+    /* the command being typed, None while closed */
+    input: Option<String>,
+    /* char index of the cursor in the input */
+    cursor: usize,
+    /* why the last command couldn't be sent, cleared by typing */
+    error: Option<String>,
+    /* what the line can complete to, set by the owner every relayout */
+    pub completions: Vec<String>,
+    /* players' names by seat for reading @name */
+    pub names: Vec<String>,
+    /* completions matching what was typed before tab was first pressed and which one is on the line */
+    matches: Vec<String>,
+    selected: Option<usize>,
+    /* commands sent so far and how far back up is looking, None is the line being typed */
+    history: Vec<String>,
+    history_index: Option<usize>,
+    outbox: Vec<Action>,
+    is_focused: bool,
+    wants_focus: bool,
+    /* set when anything drawn changes outside of a relayout */
+    is_damaged: bool,
+    focus_before: Option<MountId>,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl CommandLine {
+    pub fn new(layout: DrawLayout) -> Self {
+        CommandLine {
+            input: None,
+            cursor: 0,
+            error: None,
+            completions: Vec::new(),
+            names: Vec::new(),
+            matches: Vec::new(),
+            selected: None,
+            history: Vec::new(),
+            history_index: None,
+            outbox: Vec::new(),
+            is_focused: false,
+            wants_focus: false,
+            is_damaged: false,
+            focus_before: None,
+            layout,
+            mount: Mount::default()
+        }
+    }
+
+    /* open the line with some text already typed and take focus */
+    pub fn open(&mut self, text: &str) {
+        self.set_input(text.to_owned());
+        self.history_index = None;
+        self.wants_focus = true;
+        self.is_damaged = true;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /* the action the line reads as right now, for previewing it before it is sent */
+    pub fn get_action(&self) -> Option<Action> {
+        self.input.as_ref().and_then(|input| notation::parse(input, &self.names).ok())
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    fn close(&mut self) {
+        self.input = None;
+        self.error = None;
+        self.matches.clear();
+        self.selected = None;
+    }
+
+    fn set_input(&mut self, input: String) {
+        self.cursor = input.chars().count();
+        self.input = Some(input);
+        self.error = None;
+    }
+
+    /* anything typed starts the completions over */
+    fn edit(&mut self) {
+        self.error = None;
+        self.matches.clear();
+        self.selected = None;
+    }
+
+    fn byte_index(input: &str, cursor: usize) -> usize {
+        input.char_indices().nth(cursor).map(|(i, _)| i).unwrap_or(input.len())
+    }
+
+    /* put the next (or previous) completion of what was typed on the line */
+    fn complete(&mut self, forward: bool) {
+        if self.selected.is_none() {
+            let typed = self.input.as_deref().unwrap_or("").trim_start().to_lowercase();
+            self.matches = self.completions.iter().filter(|completion| completion.to_lowercase().starts_with(&typed)).cloned().collect();
+        }
+
+        let count = self.matches.len();
+        if count == 0 {
+            self.error = Some(String::from("nothing you can do starts like that"));
+            return
+        }
+
+        let selected = match self.selected {
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+            None if forward => 0,
+            None => count - 1
+        };
+        self.selected = Some(selected);
+        self.set_input(self.matches[selected].clone());
+    }
+
+    /* walk through the history, older is up */
+    fn recall(&mut self, older: bool) {
+        let index = match (self.history_index, older) {
+            (None, true) => self.history.len().checked_sub(1),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (_, false) => None
+        };
+
+        if index.is_none() && self.history_index.is_none() {
+            return
+        }
+
+        self.history_index = index;
+        self.edit();
+        self.set_input(index.map(|i| self.history[i].clone()).unwrap_or_default());
+    }
+
+    fn submit(&mut self) {
+        let input = self.input.as_deref().unwrap_or("").trim().to_owned();
+        match notation::parse(&input, &self.names) {
+            Ok(action) => {
+                self.outbox.push(action);
+                if self.history.last() != Some(&input) {
+                    self.history.push(input);
+                }
+                self.history_index = None;
+                self.close();
+            },
+            Err(err) => self.error = Some(err)
+        }
+    }
+}
+
+impl Layoutable for CommandLine {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for CommandLine {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let input = match self.input.as_ref() {
+            Some(input) => input,
+            None => return
+        };
+
+        let width = ctx.absolute_layout_space.size.x as usize;
+        ctx.draw_string_line(&" ".repeat(width), Point2D::new(0, 0), *TEXT_STYLE);
+
+        /* the reason the command was turned down or where tab is in the completions goes on the right */
+        let (status, status_style) = match (self.error.as_ref(), self.selected) {
+            (Some(error), _) => (error.clone(), *ERROR_STYLE),
+            (None, Some(selected)) => (format!("{}/{}", selected + 1, self.matches.len()), *HINT_STYLE),
+            (None, None) if input.is_empty() => (String::from("tab to see what you can do"), *HINT_STYLE),
+            (None, None) => (String::new(), *HINT_STYLE)
+        };
+        let status: String = status.chars().take(width/2).collect();
+        let status_x = width.saturating_sub(status.width() + 1);
+        ctx.draw_unicode_line(&status, Point2D::new(status_x as i16, 0), status_style);
+
+        /* keep the cursor in view by only drawing the end of long commands */
+        let chars: Vec<char> = input.chars().collect();
+        let visible = status_x.saturating_sub(PROMPT.len() + 2).max(1);
+        let start = (self.cursor + 1).saturating_sub(visible);
+
+        ctx.draw_string_line(PROMPT, Point2D::new(0, 0), *TEXT_STYLE);
+        let mut x = PROMPT.len() as i16;
+        for (i, &c) in chars.iter().enumerate().skip(start) {
+            let style = if i == self.cursor && self.is_focused { *CURSOR_STYLE } else { *TEXT_STYLE };
+            ctx.draw_unicode_line(&c.to_string(), Point2D::new(x, 0), style);
+            x += c.width().unwrap_or(0) as i16;
+        }
+        if self.cursor == chars.len() && self.is_focused {
+            ctx.draw_string_line(" ", Point2D::new(x, 0), *CURSOR_STYLE);
+        }
+    }
+}
+
+impl MountableLayout for CommandLine {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        if std::mem::take(&mut self.wants_focus) {
+            self.focus_before = ctx.service.focus.get_focused().filter(|&id| id != ctx.id);
+            ctx.service.focus.focus(ctx.id);
+        }
+
+        if self.input.is_none() {
+            if ctx.service.focus.is_focused(ctx.id) {
+                match self.focus_before.take() {
+                    Some(id) => ctx.service.focus.focus(id),
+                    None => ctx.service.focus.blur()
+                }
+            }
+            self.is_focused = false;
+            return
+        }
+
+        /* the cursor is only drawn while focused */
+        let is_focused = ctx.relayout_focus_of(self);
+        self.is_damaged |= is_focused != self.is_focused;
+        self.is_focused = is_focused;
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+
+    /* while open every key belongs to the line so nothing typed runs a game command */
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => return false
+        };
+
+        let length = input.chars().count();
+        match key.code {
+            KeyCode::Char(c) if length < MAX_COMMAND_LENGTH => {
+                input.insert(Self::byte_index(input, self.cursor), c);
+                self.cursor += 1;
+                self.edit();
+            },
+            /* backspacing past the start closes the line */
+            KeyCode::Backspace if length == 0 => self.close(),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                input.remove(Self::byte_index(input, self.cursor));
+                self.edit();
+            },
+            KeyCode::Delete if self.cursor < length => {
+                input.remove(Self::byte_index(input, self.cursor));
+                self.edit();
+            },
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(length),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = length,
+            KeyCode::Up => self.recall(true),
+            KeyCode::Down => self.recall(false),
+            KeyCode::Tab => self.complete(true),
+            KeyCode::BackTab => self.complete(false),
+            KeyCode::Esc => self.close(),
+            KeyCode::Enter => self.submit(),
+            _ => ()
+        }
+        self.is_damaged = true;
+
+        true
+    }
+}
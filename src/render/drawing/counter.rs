@@ -1,6 +1,6 @@
 use crate::render::{draw::*, space::*};
 
-use tui::style::Style;
+use tui::style::{Color, Modifier, Style};
 
 pub const COUNTER_SIZE: Size2D = Size2D::new(4, 3);
 
@@ -8,6 +8,10 @@ const CARD_COUNTER_TEXT_OFFSET: Point2D = Point2D::new(2, 0);
 const DEFAULT_COUNTER_TEXT_OFFSET: Point2D = Point2D::new(1, 2);
 const SYMBOL_OFFSET: Point2D = Point2D::new(1, 1);
 
+lazy_static! {
+    pub static ref HIGHLIGHTED_TEXT_STYLE: Style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum SymbolCounterKind {
     Default,
@@ -21,6 +25,13 @@ pub struct SymbolCounterStyle {
     pub bkg: Style,
 }
 
+/* what the counter shows, highlighted counts stand out (like right after they changed) */
+#[derive(Debug, Copy, Clone)]
+pub struct SymbolCount {
+    pub count: u8,
+    pub is_highlighted: bool
+}
+
 #[derive(Debug)]
 pub struct SymbolCounter {
     symbol: String,
@@ -48,8 +59,8 @@ impl Layoutable for SymbolCounter {
 }
 
 impl StatefulDrawable for SymbolCounter {
-    type State = u8;
-    fn stateful_draw(&self, ctx: &mut DrawContext, count: &u8) {
+    type State = SymbolCount;
+    fn stateful_draw(&self, ctx: &mut DrawContext, state: &SymbolCount) {
         if self.kind == SymbolCounterKind::Card {
             ctx.transform_cells(|cell| {
                 cell.set_style(self.style.bkg); 
@@ -58,11 +69,11 @@ impl StatefulDrawable for SymbolCounter {
         
         ctx.draw_unicode_line(&self.symbol, SYMBOL_OFFSET, self.style.symbol);
         ctx.draw_string_line(
-            &format!("{:0width$}", (*count).min(99), width = 2 as usize), 
+            &format!("{:0width$}", state.count.min(99), width = 2 as usize), 
             if self.kind == SymbolCounterKind::Default
                 { DEFAULT_COUNTER_TEXT_OFFSET } else 
                 { CARD_COUNTER_TEXT_OFFSET }, 
-            self.style.text
+            if state.is_highlighted { *HIGHLIGHTED_TEXT_STYLE } else { self.style.text }
         );
     }
 }
\ No newline at end of file
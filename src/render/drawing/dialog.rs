@@ -0,0 +1,651 @@
+/*
+ * dialog.rs
+ * modal dialogs stacked in the middle of the screen, each with a title, some lines of text, counters and buttons
+ *
+ * while any dialog is up the stack takes the whole screen for input and the keyboard so nothing underneath can be clicked or typed to
+ * only the top dialog answers, the keymap's cancel dismisses it and a pressed button closes it, either way the owner gets a result with the key it opened it with
+ * required dialogs can't be dismissed, they stay up until a button is pressed or their owner closes them
+ * dialogs grow out of their middle when they open and shrink back into it when they close
+ */
+
+use crate::render::prelude::*;
+use crate::keymap::{Command, Keymap};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{style::{Color, Modifier, Style}, text::Span, widgets::{Block, Borders, Clear}};
+use unicode_width::UnicodeWidthStr;
+
+const OPEN_DURATION: f32 = 0.15;
+/* every dialog stacked on another sits down and to the right of it so the ones under it show */
+const STACK_OFFSET: Point2D = Point2D::new(2, 1);
+const MIN_INNER_WIDTH: u16 = 24;
+const COUNTER_CONTROLS: &str = "[-]    [+]";
+
+lazy_static! {
+    static ref DIALOG_STYLE: Style = Style::default().fg(Color::White).bg(Color::Black);
+    static ref SELECTED_STYLE: Style = Style::default().fg(Color::Black).bg(Color::Yellow);
+    static ref DISABLED_STYLE: Style = Style::default().fg(Color::DarkGray).bg(Color::Black);
+    static ref TITLE_STYLE: Style = Style::default().fg(Color::Yellow).bg(Color::Black).add_modifier(Modifier::BOLD);
+}
+
+/* how a dialog went away, button is None when it was dismissed and counts are where the counters were left */
+#[derive(Debug)]
+pub struct DialogResult<K> {
+    pub key: K,
+    pub button: Option<usize>,
+    pub counts: Vec<u8>
+}
+
+#[derive(Debug)]
+struct DialogCounter {
+    label: String,
+    count: u8,
+    max: u8
+}
+
+#[derive(Debug)]
+struct DialogButton {
+    label: String,
+    is_enabled: bool,
+    /* if the counters have to add up to the dialog's total before it can be pressed */
+    needs_total: bool
+}
+
+#[derive(Debug)]
+pub struct Dialog<K> {
+    pub key: K,
+    title: String,
+    body: Vec<String>,
+    counters: Vec<DialogCounter>,
+    total: Option<u16>,
+    buttons: Vec<DialogButton>,
+    is_required: bool,
+    /* the row the keyboard is on, counters first and the buttons last, and the button it is on */
+    row: usize,
+    button: usize,
+    /* set when the dialog is done, it is taken off the stack once it has shrunk away */
+    result: Option<Option<usize>>,
+    is_closing: bool,
+    /* how many dialogs were up under it when it opened */
+    depth: usize,
+    /* set when anything drawn changes outside of a relayout, like the keyboard moving between buttons */
+    is_damaged: bool,
+    layout: DrawLayout,
+    mount: Mount
+}
+
+impl<K> Dialog<K> {
+    pub fn new(key: K, title: &str) -> Self {
+        Dialog {
+            key,
+            title: format!(" {} ", title),
+            body: Vec::new(),
+            counters: Vec::new(),
+            total: None,
+            buttons: Vec::new(),
+            is_required: false,
+            row: 0,
+            button: 0,
+            result: None,
+            is_closing: false,
+            depth: 0,
+            is_damaged: false,
+            layout: DrawLayout::default(),
+            mount: Mount::default()
+        }
+    }
+
+    pub fn line(mut self, text: String) -> Self {
+        self.body.push(text);
+        self
+    }
+
+    /* for when the game is waiting on an answer, the dialog can't be dismissed */
+    pub fn required(mut self) -> Self {
+        self.is_required = true;
+        self
+    }
+
+    /* a count picked with [-] and [+] from 0 to max */
+    pub fn counter(mut self, label: &str, max: u8) -> Self {
+        self.counters.push(DialogCounter { label: label.to_owned(), count: 0, max });
+        self
+    }
+
+    /* what the counters have to add up to for buttons that need it */
+    pub fn total(mut self, total: u16) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    pub fn button(mut self, label: &str, is_enabled: bool) -> Self {
+        self.buttons.push(DialogButton { label: label.to_owned(), is_enabled, needs_total: false });
+        self
+    }
+
+    /* a button that can only be pressed once the counters add up to the total */
+    pub fn total_button(mut self, label: &str) -> Self {
+        self.buttons.push(DialogButton { label: label.to_owned(), is_enabled: true, needs_total: true });
+        self
+    }
+
+    fn get_count_total(&self) -> u16 {
+        self.counters.iter().map(|counter| counter.count as u16).sum()
+    }
+
+    fn is_pressable(&self, button: usize) -> bool {
+        self.buttons.get(button).is_some_and(|button| button.is_enabled && (!button.needs_total || self.total.is_none_or(|total| total == self.get_count_total())))
+    }
+
+    fn get_label_width(&self) -> usize {
+        self.counters.iter().map(|counter| counter.label.width()).max().unwrap_or(0)
+    }
+
+    /* the lines of the inside of the dialog where the counters, the total and the buttons go */
+    fn get_counter_y(&self, counter: usize) -> i16 {
+        1 + self.body.len() as i16 + !self.body.is_empty() as i16 + counter as i16
+    }
+
+    fn get_buttons_y(&self) -> i16 {
+        let counter_lines = self.counters.len() + self.total.is_some() as usize;
+        self.get_counter_y(0) + counter_lines as i16 + (counter_lines > 0) as i16
+    }
+
+    /* where each button starts on the button line and how wide it is, the buttons are centered */
+    fn get_button_spans(&self, width: u16) -> Vec<(i16, u16)> {
+        let total_width: usize = self.buttons.iter().map(|button| button.label.width() + 4).sum::<usize>() + 2*self.buttons.len().saturating_sub(1);
+        let mut x = (width as i16 - total_width as i16)/2;
+        self.buttons
+            .iter()
+            .map(|button| {
+                let span = (x, button.label.width() as u16 + 4);
+                x += span.1 as i16 + 2;
+                span
+            })
+            .collect()
+    }
+
+    fn get_size(&self) -> Size2D {
+        let counter_width = self.get_label_width() + 2 + COUNTER_CONTROLS.len();
+        let button_width: usize = self.buttons.iter().map(|button| button.label.width() + 4).sum::<usize>() + 2*self.buttons.len().saturating_sub(1);
+        let inner_width = self.body
+            .iter()
+            .map(|line| line.width())
+            .chain([self.title.width() + 2, counter_width, button_width, MIN_INNER_WIDTH as usize])
+            .max()
+            .unwrap_or(0);
+        Size2D::new(inner_width as u16 + 4, self.get_buttons_y() as u16 + 2)
+    }
+
+    /* the space the dialog takes in the stack once it is open, or shrunk into its middle */
+    fn get_space(&self, is_shown: bool) -> Space {
+        let size = if is_shown { self.get_size() } else { Size2D::new(0, 0) };
+        DrawLayout::default()
+            .set_position(UDim2::new(0.5, STACK_OFFSET.x*self.depth as i16, 0.5, STACK_OFFSET.y*self.depth as i16))
+            .set_anchor(Float2D::new(0.5, 0.5))
+            .set_size(UDim2::from_size2d(size))
+            .space
+    }
+
+    fn finish(&mut self, button: Option<usize>) {
+        if self.result.is_none() {
+            self.result = Some(button);
+        }
+    }
+
+    fn press(&mut self, button: usize) {
+        if self.is_pressable(button) {
+            self.finish(Some(button));
+        }
+    }
+
+    fn change_count(&mut self, counter: usize, up: bool) {
+        if let Some(counter) = self.counters.get_mut(counter) {
+            counter.count = if up { counter.count.saturating_add(1).min(counter.max) } else { counter.count.saturating_sub(1) };
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent, is_cancel: bool) {
+        self.is_damaged = true;
+        if is_cancel {
+            if !self.is_required {
+                self.finish(None);
+            }
+            return
+        }
+
+        let button_row = self.counters.len();
+        let button_count = self.buttons.len().max(1);
+        match key.code {
+            KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Down => self.row = (self.row + 1).min(button_row),
+            KeyCode::Left | KeyCode::Char('-') if self.row < button_row => self.change_count(self.row, false),
+            KeyCode::Right | KeyCode::Char('+') if self.row < button_row => self.change_count(self.row, true),
+            KeyCode::Left => self.button = (self.button + button_count - 1) % button_count,
+            KeyCode::Right => self.button = (self.button + 1) % button_count,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Enter if self.row < button_row => self.row = button_row,
+            KeyCode::Tab => self.button = (self.button + 1) % button_count,
+            KeyCode::BackTab => self.button = (self.button + button_count - 1) % button_count,
+            KeyCode::Enter => self.press(self.button),
+            _ => ()
+        }
+    }
+
+    /* clicks are relative to the top left of the dialog */
+    fn on_click(&mut self, point: Point2D, width: u16) {
+        self.is_damaged = true;
+        if point.y == self.get_buttons_y() {
+            if let Some(button) = self.get_button_spans(width).iter().position(|&(x, w)| point.x >= x && point.x < x + w as i16) {
+                self.row = self.counters.len();
+                self.button = button;
+                self.press(button);
+            }
+            return
+        }
+
+        let controls_x = 2 + self.get_label_width() as i16 + 2;
+        if let Some(counter) = (0..self.counters.len()).find(|&counter| self.get_counter_y(counter) == point.y) {
+            self.row = counter;
+            match point.x - controls_x {
+                0..=2 => self.change_count(counter, false),
+                7..=9 => self.change_count(counter, true),
+                _ => ()
+            }
+        }
+    }
+}
+
+impl<K> Layoutable for Dialog<K> {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl<K: std::fmt::Debug> StatefulDrawable for Dialog<K> {
+    /* if it is the top dialog, the one the keyboard is in */
+    type State = bool;
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, is_top: &Self::State) {
+        let size = ctx.absolute_layout_space.size;
+        let whole = AbsoluteSpace::new(0, 0, size.x, size.y);
+        ctx.draw_widget(Clear, whole);
+        ctx.draw_widget(
+            Block::default()
+                .title(Span::styled(self.title.as_str(), *TITLE_STYLE))
+                .borders(Borders::ALL)
+                .border_style(*DIALOG_STYLE)
+                .style(*DIALOG_STYLE),
+            whole
+        );
+
+        for (i, line) in self.body.iter().enumerate() {
+            ctx.draw_unicode_line(line, Point2D::new(2, 1 + i as i16), *DIALOG_STYLE);
+        }
+
+        let label_width = self.get_label_width();
+        let button_row = self.counters.len();
+        for (i, counter) in self.counters.iter().enumerate() {
+            let y = self.get_counter_y(i);
+            let is_selected = *is_top && self.row == i;
+            let style = if counter.max == 0 { *DISABLED_STYLE } else if is_selected { *SELECTED_STYLE } else { *DIALOG_STYLE };
+            ctx.draw_unicode_line(&counter.label, Point2D::new(2, y), style);
+            ctx.draw_string_line(
+                &format!("[-] {:>2} [+]", counter.count),
+                Point2D::new(2 + label_width as i16 + 2, y),
+                if counter.max == 0 { *DISABLED_STYLE } else { *DIALOG_STYLE }
+            );
+        }
+
+        if let Some(total) = self.total {
+            let picked = self.get_count_total();
+            let style = if picked == total { *DIALOG_STYLE } else { *DISABLED_STYLE };
+            ctx.draw_string_line(&format!("picked {} of {}", picked, total), Point2D::new(2, self.get_counter_y(self.counters.len())), style);
+        }
+
+        let y = self.get_buttons_y();
+        for (i, (&(x, _), button)) in self.get_button_spans(size.x).iter().zip(self.buttons.iter()).enumerate() {
+            let style = if !self.is_pressable(i) {
+                *DISABLED_STYLE
+            } else if *is_top && self.row == button_row && self.button == i {
+                *SELECTED_STYLE
+            } else {
+                *DIALOG_STYLE
+            };
+            ctx.draw_unicode_line(&format!("[ {} ]", button.label), Point2D::new(x, y), style);
+        }
+    }
+}
+
+impl<K: std::fmt::Debug + 'static> MountableLayout for Dialog<K> {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DialogStack<K> {
+    dialogs: Vec<Dialog<K>>,
+    results: Vec<DialogResult<K>>,
+    absolute_space: AbsoluteSpace,
+    focus_before: Option<MountId>,
+    /* for which keys dismiss the top dialog */
+    pub keymap: Keymap,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl<K: Clone + PartialEq + std::fmt::Debug + 'static> DialogStack<K> {
+    pub fn new(layout: DrawLayout) -> Self {
+        DialogStack {
+            dialogs: Vec::new(),
+            results: Vec::new(),
+            absolute_space: AbsoluteSpace::default(),
+            focus_before: None,
+            keymap: Keymap::default(),
+            layout,
+            mount: Mount::default()
+        }
+    }
+
+    /* put a dialog on top of the stack */
+    pub fn open(&mut self, mut dialog: Dialog<K>, anim_service: &mut AnimationService) {
+        dialog.button = (0..dialog.buttons.len()).find(|&button| dialog.is_pressable(button)).unwrap_or(0);
+
+        dialog.depth = self.get_keys().len();
+        let (from, to) = (dialog.get_space(false), dialog.get_space(true));
+        dialog.layout.set_space(from);
+        dialog.animate_space_from(anim_service, from, to, OPEN_DURATION, EasingStyle::Cubic, EasingDirection::Out);
+        self.dialogs.push(dialog);
+        self.remount();
+    }
+
+    /* close the dialog opened with the key without a result, like when what it asks about is over */
+    pub fn close(&mut self, key: &K, anim_service: &mut AnimationService) {
+        if let Some(index) = self.dialogs.iter().position(|dialog| dialog.key == *key && !dialog.is_closing) {
+            self.start_closing(index, anim_service);
+        }
+    }
+
+    pub fn is_open(&self, key: &K) -> bool {
+        self.dialogs.iter().any(|dialog| dialog.key == *key && !dialog.is_closing)
+    }
+
+    /* if there are no dialogs up, ones on their way out don't count */
+    pub fn is_empty(&self) -> bool {
+        self.dialogs.iter().all(|dialog| dialog.is_closing)
+    }
+
+    /* keys of the dialogs that are open and not on their way out */
+    pub fn get_keys(&self) -> Vec<K> {
+        self.dialogs.iter().filter(|dialog| !dialog.is_closing).map(|dialog| dialog.key.clone()).collect()
+    }
+
+    pub fn take_results(&mut self) -> Vec<DialogResult<K>> {
+        std::mem::take(&mut self.results)
+    }
+
+    fn get_top_mut(&mut self) -> Option<&mut Dialog<K>> {
+        self.dialogs.iter_mut().rev().find(|dialog| !dialog.is_closing)
+    }
+
+    fn start_closing(&mut self, index: usize, anim_service: &mut AnimationService) {
+        let dialog = &mut self.dialogs[index];
+        dialog.is_closing = true;
+        let to = dialog.get_space(false);
+        dialog.animate_space(anim_service, to, OPEN_DURATION, EasingStyle::Cubic, EasingDirection::In);
+        self.damage_all();
+    }
+
+    /* dialogs come and go so their mounts are handed out again by where they are in the stack */
+    fn remount(&mut self) {
+        let id = self.mount.id;
+        self.mount(Mount { id, children: 0 });
+        self.damage_all();
+    }
+
+    /* only the top dialog shows where the keyboard is, so every dialog is redrawn when a dialog comes or goes */
+    fn damage_all(&mut self) {
+        for dialog in self.dialogs.iter_mut() {
+            dialog.is_damaged = true;
+        }
+    }
+}
+
+impl<K> Layoutable for DialogStack<K> {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl<K: std::fmt::Debug> StatefulDrawable for DialogStack<K> {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let top = self.dialogs.iter().rposition(|dialog| !dialog.is_closing);
+        for (i, dialog) in self.dialogs.iter().enumerate() {
+            ctx.draw_stateful_child(dialog, &(Some(i) == top));
+        }
+    }
+}
+
+impl<K: Clone + PartialEq + std::fmt::Debug + 'static> MountableLayout for DialogStack<K> {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+
+    fn child_ref(&self, i: usize) -> Option<&dyn MountableLayout> {
+        self.dialogs.get(i).map(|dialog| dialog.as_trait_ref())
+    }
+
+    fn child_mut(&mut self, i: usize) -> Option<&mut dyn MountableLayout> {
+        self.dialogs.get_mut(i).map(|dialog| dialog.as_trait_mut())
+    }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        /* answered dialogs hand their result over and start closing, closed ones come off the stack */
+        for index in 0..self.dialogs.len() {
+            if let Some(button) = self.dialogs[index].result.take() {
+                let dialog = &self.dialogs[index];
+                self.results.push(DialogResult {
+                    key: dialog.key.clone(),
+                    button,
+                    counts: dialog.counters.iter().map(|counter| counter.count).collect()
+                });
+                self.start_closing(index, &mut ctx.service.animation);
+            }
+        }
+        let count = self.dialogs.len();
+        self.dialogs.retain(|dialog| !dialog.is_closing || dialog.layout.anim.as_ref().is_some_and(|anim| anim.state.get_alpha() < 1.0));
+        if self.dialogs.len() != count {
+            self.remount();
+        }
+
+        /* the stack has the keyboard while anything is up and gives it back to whatever had it before */
+        let is_focused = ctx.service.focus.is_focused(ctx.id);
+        if self.is_empty() {
+            if is_focused {
+                match self.focus_before.take() {
+                    Some(id) => ctx.service.focus.focus(id),
+                    None => ctx.service.focus.blur()
+                }
+            }
+        } else {
+            if !is_focused {
+                self.focus_before = ctx.service.focus.get_focused();
+                ctx.service.focus.focus(ctx.id);
+            }
+            ctx.relayout_focus_of(self);
+            ctx.relayout_input_space_of(self, Space::FULL);
+        }
+
+        self.absolute_space = ctx.get_absolute_layout_space_of(self);
+        ctx.relayout_children_of(self);
+    }
+
+    /* every key belongs to the top dialog while there is one */
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let is_cancel = self.keymap.is_bound_to(key, Command::Cancel);
+        match self.get_top_mut() {
+            Some(dialog) => {
+                dialog.on_key(key, is_cancel);
+                true
+            },
+            None => false
+        }
+    }
+
+    /* clicks anywhere else are swallowed so nothing under the dialogs gets them */
+    fn on_mouse_input(&mut self, event: InputEvent) -> bool {
+        let point = match event.kind {
+            InputEventKind::Click(point) => point,
+            _ => return false
+        };
+
+        let stack_space = self.absolute_space;
+        if let Some(dialog) = self.get_top_mut() {
+            let dialog_space = dialog.to_absolute_layout_space(stack_space);
+            if dialog_space.is_interior_point(point) {
+                dialog.on_click(dialog_space.relative_position_of(point), dialog_space.size.x);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{screen::Screen, snapshot};
+
+    use crossterm::event::{KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
+
+    const SIZE: Size2D = Size2D::new(60, 20);
+
+    /* a stack with the dialog opened on it and grown to full size */
+    fn get_screen(dialog: Dialog<u8>) -> Screen<DialogStack<u8>> {
+        let mut screen = Screen::new(DialogStack::new(DrawLayout::FULL));
+        screen.root.open(dialog, &mut screen.service.animation);
+        draw(&mut screen);
+        wait_out();
+        draw(&mut screen);
+        screen
+    }
+
+    fn draw(screen: &mut Screen<DialogStack<u8>>) -> String {
+        snapshot::to_text(&screen.render_to_buffer(SIZE, &mut ()))
+    }
+
+    fn wait_out() {
+        std::thread::sleep(std::time::Duration::from_secs_f32(OPEN_DURATION + 0.1));
+    }
+
+    fn press(screen: &mut Screen<DialogStack<u8>>, code: KeyCode) -> bool {
+        screen.service.focus.handle_key_input(KeyEvent::new(code, KeyModifiers::NONE), &mut screen.root)
+    }
+
+    /* the buttons and counts the dialogs were answered with since the last call, answering takes a relayout */
+    fn get_results(screen: &mut Screen<DialogStack<u8>>) -> Vec<(u8, Option<usize>, Vec<u8>)> {
+        draw(screen);
+        screen.root.take_results().into_iter().map(|result| (result.key, result.button, result.counts)).collect()
+    }
+
+    #[test]
+    fn buttons_answer_dialogs() {
+        let mut screen = get_screen(Dialog::new(1, "Trade").line(String::from("take the offer?")).button("yes", true).button("no", true));
+        let text = draw(&mut screen);
+        assert!(text.contains("Trade") && text.contains("take the offer?") && text.contains("[ yes ]  [ no ]"), "{}", text);
+        assert!(screen.root.is_open(&1));
+
+        assert!(press(&mut screen, KeyCode::Right));
+        assert!(press(&mut screen, KeyCode::Enter));
+        assert_eq!(get_results(&mut screen), vec![(1, Some(1), Vec::new())]);
+        assert!(!screen.root.is_open(&1) && screen.root.is_empty());
+
+        wait_out();
+        assert!(!draw(&mut screen).contains("Trade"));
+        assert!(!press(&mut screen, KeyCode::Enter));
+    }
+
+    /* cancel dismisses a dialog without a button unless it is required */
+    #[test]
+    fn required_dialogs_stay_up() {
+        let mut screen = get_screen(Dialog::new(1, "Discard").button("ok", true).required());
+        assert!(press(&mut screen, KeyCode::Esc));
+        assert!(get_results(&mut screen).is_empty());
+        assert!(screen.root.is_open(&1));
+
+        screen.root.open(Dialog::new(2, "Steal").button("ok", true), &mut screen.service.animation);
+        assert_eq!(screen.root.get_keys(), vec![1, 2]);
+        assert!(press(&mut screen, KeyCode::Esc));
+        assert_eq!(get_results(&mut screen), vec![(2, None, Vec::new())]);
+        assert_eq!(screen.root.get_keys(), vec![1]);
+
+        /* their owner can still take them down */
+        screen.root.close(&1, &mut screen.service.animation);
+        assert!(screen.root.is_empty());
+        assert!(get_results(&mut screen).is_empty());
+    }
+
+    /* counters stay between 0 and their max and total buttons only press once the counts add up */
+    #[test]
+    fn counters_add_up_to_the_total() {
+        let dialog = Dialog::new(1, "Discard").counter("wood", 2).counter("ore", 1).total(2).total_button("discard");
+        let mut screen = get_screen(dialog);
+        assert!(draw(&mut screen).contains("picked 0 of 2"));
+
+        for code in [KeyCode::Right, KeyCode::Right, KeyCode::Right, KeyCode::Down, KeyCode::Char('+'), KeyCode::Enter, KeyCode::Enter] {
+            press(&mut screen, code);
+        }
+        assert!(get_results(&mut screen).is_empty());
+        assert!(draw(&mut screen).contains("picked 3 of 2"));
+
+        for code in [KeyCode::Up, KeyCode::Char('-'), KeyCode::Left, KeyCode::Left, KeyCode::Tab, KeyCode::Enter] {
+            press(&mut screen, code);
+        }
+        assert_eq!(get_results(&mut screen), vec![(1, Some(0), vec![2, 0])]);
+    }
+
+    /* clicking a button presses it and clicks outside the dialog go nowhere */
+    #[test]
+    fn clicking_buttons() {
+        let mut screen = get_screen(Dialog::new(1, "Trade").button("yes", true).button("no", false).button("maybe", true));
+        let click = |screen: &mut Screen<DialogStack<u8>>, point: Point2D| {
+            for kind in [MouseEventKind::Down(MouseButton::Left), MouseEventKind::Up(MouseButton::Left)] {
+                let event = MouseEvent { kind, column: point.x as u16, row: point.y as u16, modifiers: KeyModifiers::NONE };
+                screen.service.input.handle_mouse_input(event, &mut screen.root);
+            }
+        };
+
+        let dialog = &screen.root.dialogs[0];
+        let space = dialog.to_absolute_layout_space(screen.root.absolute_space);
+        let y = space.top() + dialog.get_buttons_y();
+        let spans: Vec<Point2D> = dialog.get_button_spans(space.size.x).iter().map(|&(x, _)| Point2D::new(space.left() + x + 1, y)).collect();
+
+        click(&mut screen, Point2D::new(0, 0));
+        click(&mut screen, spans[1]);
+        assert!(get_results(&mut screen).is_empty());
+        click(&mut screen, spans[2]);
+        assert_eq!(get_results(&mut screen), vec![(1, Some(2), Vec::new())]);
+    }
+
+    /* the stack takes the keyboard while it has dialogs up and hands it back after */
+    #[test]
+    fn keyboard_is_given_back() {
+        let mut screen = Screen::new(DialogStack::new(DrawLayout::FULL));
+        screen.service.focus.focus(99);
+        draw(&mut screen);
+        assert_eq!(screen.service.focus.get_focused(), Some(99));
+
+        screen.root.open(Dialog::new(1, "Trade").button("ok", true), &mut screen.service.animation);
+        draw(&mut screen);
+        assert_eq!(screen.service.focus.get_focused(), Some(screen.root.mount_ref().id));
+
+        assert!(press(&mut screen, KeyCode::Enter));
+        draw(&mut screen);
+        assert_eq!(screen.service.focus.get_focused(), Some(99));
+    }
+}
@@ -1,6 +1,37 @@
 use crate::render::{prelude::*, iter::CustomIterator};
 
-use tui::style::Style;
+use std::time::Instant;
+use tui::{buffer::{Buffer, Cell}, layout::Rect, style::{Color, Style}};
+use unicode_width::UnicodeWidthStr;
+
+/* how far a pan key pans, rows are about twice as tall as columns are wide */
+const KEY_PAN_STEP: Point2D = Point2D::new(6, 3);
+
+/* how long an animated pan takes in seconds */
+const PAN_DURATION: f32 = 0.6;
+
+/* 
+ * letting go of a fast drag keeps the drawing coasting, its speed decays by e every time constant until it is under the min speed
+ * speeds are in cells a second, drags that stopped before letting go don't coast
+ */
+const COAST_TIME_CONSTANT: f32 = 0.325;
+const COAST_MIN_SPEED: f32 = 4.0;
+const COAST_IDLE_TIME: f32 = 0.1;
+/* how much of the newest drag goes into the speed, the rest is the speed before it */
+const DRAG_VELOCITY_SMOOTHING: f32 = 0.8;
+
+/* the drawing stretches half as far as it is pulled past its edges, up to this far, and springs back when let go */
+const MAX_OVERSCROLL: Point2D = Point2D::new(6, 3);
+const SPRING_DURATION: f32 = 0.3;
+
+/* zoom levels as how many cells of the drawing go across (and down) one cell of the window */
+const ZOOM_SCALES: [i16; 3] = [1, 2, 4];
+
+/* 
+ * zoomed out blocks of the drawing are drawn with quadrant characters in two colors
+ * indexed by which quadrants take the second color, top left is 8, top right 4, bottom left 2 and bottom right 1
+ */
+const QUADRANT_SYMBOLS: [&str; 16] = [" ", "▗", "▖", "▄", "▝", "▐", "▞", "▟", "▘", "▚", "▌", "▙", "▀", "▜", "▛", "█"];
 
 #[derive(Debug)]
 pub struct Dragger<T: MountableLayout + StatefulDrawable> {
@@ -9,9 +40,76 @@ pub struct Dragger<T: MountableLayout + StatefulDrawable> {
     mount: Mount,
     canvas_offset: Point2D,
     mouse_location: Point2D,
+    window_space: AbsoluteSpace,
+    /* if the mouse moved since it went down, so letting go after panning is not a click */
+    did_drag: bool,
+    /* where the drawing was last clicked, relative to the drawing */
+    click: Option<Point2D>,
+    /* where the mouse was last seen over the window */
+    hover: Option<Point2D>,
+    /* index into ZOOM_SCALES, None until the window is known and the closest level the whole drawing fits in is picked */
+    zoom: Option<usize>,
+    /* a pan to somewhere on the drawing or a spring back from past the edge, anything the user does to move the drawing stops it */
+    pan_anim: Option<Animation<PanAnimator>>,
+    /* the drawing sliding on after a fast drag was let go */
+    coast_anim: Option<Animation<CoastAnimator>>,
+    /* if the mouse is holding the drawing, the drawing isn't kept in bounds while it is held */
+    is_grabbed: bool,
+    /* if the drawing was let go since the last relayout, which decides whether it coasts or springs back */
+    did_release: bool,
+    /* where the canvas offset would be if the drawing didn't stretch past its edges */
+    drag_offset: Point2D,
+    /* how fast the canvas offset was moving in cells a second and when it last moved */
+    drag_velocity: Float2D,
+    last_drag: Instant,
+    /* the scale and canvas offset the window was last laid out with */
+    last_view: (i16, Point2D),
     style: Style
 }
 
+/* eases the canvas offset from where it was to where it is going */
+#[derive(Debug)]
+struct PanAnimator {
+    offset0: Point2D,
+    offset1: Point2D,
+    direction: EasingDirection
+}
+
+impl Animator for PanAnimator {
+    type Target = Point2D;
+
+    fn update(&mut self, state: &AnimationState, target: &mut Self::Target) {
+        *target = self.offset0.lerp(self.offset1, ease(state.get_alpha(), EasingStyle::Cubic, self.direction));
+    }
+}
+
+/* moves the canvas offset on at a speed that decays over time, the distance covered approaches velocity*time constant */
+#[derive(Debug)]
+struct CoastAnimator {
+    offset0: Point2D,
+    velocity: Float2D
+}
+
+impl CoastAnimator {
+    /* how long until the speed drops under the min speed */
+    fn get_duration(velocity: Float2D) -> f32 {
+        let speed = velocity.x.abs().max(velocity.y.abs());
+        COAST_TIME_CONSTANT*(speed/COAST_MIN_SPEED).ln().max(0.0)
+    }
+}
+
+impl Animator for CoastAnimator {
+    type Target = Point2D;
+
+    fn update(&mut self, state: &AnimationState, target: &mut Self::Target) {
+        let distance = COAST_TIME_CONSTANT*(1.0 - (-state.get_elapsed()/COAST_TIME_CONSTANT).exp());
+        *target = self.offset0 + Point2D::new(
+            (self.velocity.x*distance).round() as i16,
+            (self.velocity.y*distance).round() as i16
+        );
+    }
+}
+
 impl<T: MountableLayout + StatefulDrawable> Dragger<T> {
     pub fn new(drawing: T, style: Style) -> Self {
         Dragger {
@@ -20,32 +118,348 @@ impl<T: MountableLayout + StatefulDrawable> Dragger<T> {
             layout: DrawLayout::FULL,
             mount: Mount::default(),
             canvas_offset: Point2D::default(),
-            mouse_location: Point2D::default()
+            mouse_location: Point2D::default(),
+            window_space: AbsoluteSpace::default(),
+            did_drag: false,
+            click: None,
+            hover: None,
+            zoom: None,
+            pan_anim: None,
+            coast_anim: None,
+            is_grabbed: false,
+            did_release: false,
+            drag_offset: Point2D::default(),
+            drag_velocity: Float2D::default(),
+            last_drag: Instant::now(),
+            last_view: (1, Point2D::default())
         }
     }
 
+    /* how many cells of the drawing go across one cell of the window */
+    pub fn get_scale(&self) -> i16 {
+        ZOOM_SCALES[self.zoom.unwrap_or(0)]
+    }
+
+    /* zoom out by some levels (in when negative) around the middle of the window */
+    pub fn zoom(&mut self, steps: i16) -> bool {
+        let window = self.window_space;
+        self.zoom_at(steps, window.position + Point2D::new(window.size.x as i16/2, window.size.y as i16/2))
+    }
+
+    /* zoom out by some levels (in when negative) keeping the part of the drawing under the point where it is */
+    pub fn zoom_at(&mut self, steps: i16, point: Point2D) -> bool {
+        let zoom = self.zoom.unwrap_or(0);
+        let next = (zoom as i16 + steps).clamp(0, ZOOM_SCALES.len() as i16 - 1) as usize;
+        if next == zoom {
+            return false
+        }
+
+        self.stop();
+        let (scale, next_scale) = (ZOOM_SCALES[zoom], ZOOM_SCALES[next]);
+        let window_point = point - self.window_space.position;
+        self.canvas_offset = Point2D::new(
+            (window_point.x + self.canvas_offset.x)*scale/next_scale - window_point.x,
+            (window_point.y + self.canvas_offset.y)*scale/next_scale - window_point.y
+        );
+        self.zoom = Some(next);
+        true
+    }
+
+    /* scroll so the given point of the drawing is in the middle of the window, as far as the drawing allows */
+    pub fn focus(&mut self, point: Point2D) {
+        self.stop();
+        self.canvas_offset = self.get_centered_canvas_offset(point);
+    }
+
+    /* like focus but the drawing slides there */
+    pub fn pan_to(&mut self, point: Point2D, anim_service: &mut AnimationService) {
+        let offset1 = self.get_constrained_canvas_offset(self.get_centered_canvas_offset(point), self.window_space);
+        let mut offset0 = self.get_constrained_canvas_offset(self.canvas_offset, self.window_space);
+        if let Some(mut anim) = self.pan_anim.take() {
+            anim.cancel(anim_service);
+            offset0 = self.canvas_offset;
+        }
+        if let Some(mut anim) = self.coast_anim.take() {
+            anim.cancel(anim_service);
+            offset0 = self.canvas_offset;
+        }
+        self.start_pan(offset0, offset1, PAN_DURATION, EasingDirection::InOut, anim_service);
+    }
+
+    /* slide the middle of the space into the middle of the window if any of it is out of view, returns if it had to */
+    pub fn pan_into_view(&mut self, space: AbsoluteSpace, anim_service: &mut AnimationService) -> bool {
+        let view = self.get_view();
+        if view.try_intersection(space) == Some(space) {
+            return false
+        }
+
+        self.pan_to(space.position + Point2D::new(space.size.x as i16/2, space.size.y as i16/2), anim_service);
+        true
+    }
+
+    /* scroll a step in the direction, returns false if the drawing is already as far as it goes */
+    pub fn pan(&mut self, direction: Point2D) -> bool {
+        self.stop();
+        let canvas_offset = self.canvas_offset;
+        self.canvas_offset = self.get_constrained_canvas_offset(
+            canvas_offset + Point2D::new(direction.x*KEY_PAN_STEP.x, direction.y*KEY_PAN_STEP.y), 
+            self.window_space
+        );
+        self.canvas_offset != canvas_offset
+    }
+
+    /* scroll as little as it takes to get the space of the drawing in the window */
+    pub fn reveal(&mut self, space: AbsoluteSpace) {
+        self.stop();
+        let scale = self.get_scale();
+        let size = self.window_space.size;
+        let mut offset = self.canvas_offset;
+        offset.x = offset.x.min(space.left()/scale).max((space.right() + scale - 1)/scale - size.x as i16);
+        offset.y = offset.y.min(space.top()/scale).max((space.bottom() + scale - 1)/scale - size.y as i16);
+        self.canvas_offset = offset;
+    }
+
+    /* if the drawing at the current zoom doesn't fit in the window */
+    pub fn is_clipped(&self) -> bool {
+        let size = self.get_absolute_canvas_size(self.window_space);
+        size.x > self.window_space.size.x || size.y > self.window_space.size.y
+    }
+
+    /* the size of the drawing when it isn't zoomed out */
+    pub fn get_drawing_size(&self) -> Size2D {
+        self.drawing.to_absolute_layout_space(self.window_space).size
+    }
+
+    /* the part of the drawing that is in the window, relative to the drawing */
+    pub fn get_view(&self) -> AbsoluteSpace {
+        let scale = self.get_scale();
+        let window = self.window_space;
+        let offset = self.get_constrained_canvas_offset(self.canvas_offset, window);
+        let drawing_position = self.drawing
+            .to_absolute_layout_space(AbsoluteSpace { position: Point2D::default(), size: window.size })
+            .position;
+        AbsoluteSpace::new(
+            offset.x*scale - drawing_position.x,
+            offset.y*scale - drawing_position.y,
+            window.size.x.saturating_mul(scale as u16),
+            window.size.y.saturating_mul(scale as u16)
+        )
+    }
+
+    fn start_pan(&mut self, offset0: Point2D, offset1: Point2D, duration: f32, direction: EasingDirection, anim_service: &mut AnimationService) {
+        if offset0 == offset1 {
+            return
+        }
+
+        let mut anim = Animation::with_duration(duration, PanAnimator { offset0, offset1, direction });
+        anim.play(anim_service);
+        self.pan_anim = Some(anim);
+    }
+
+    /* stop any pan or coast where it is, their animations are left for the screen to expire */
+    fn stop(&mut self) {
+        self.pan_anim = None;
+        self.coast_anim = None;
+    }
+
+    /* 
+     * a let go drawing that was moving fast enough coasts, one that was stretched past its edges springs back
+     * a coast that runs past an edge stretches until it can't and then springs back too
+     */
+    fn relayout_motion(&mut self, anim_service: &mut AnimationService) {
+        let window = self.window_space;
+        if std::mem::take(&mut self.did_release) {
+            let velocity = if self.last_drag.elapsed().as_secs_f32() > COAST_IDLE_TIME { Float2D::default() } else { self.drag_velocity };
+            let duration = CoastAnimator::get_duration(velocity);
+            if duration > 0.0 && self.get_constrained_canvas_offset(self.canvas_offset, window) == self.canvas_offset {
+                self.drag_offset = self.canvas_offset;
+                let mut anim = Animation::with_duration(duration, CoastAnimator { offset0: self.canvas_offset, velocity });
+                anim.play(anim_service);
+                self.coast_anim = Some(anim);
+            } else {
+                self.spring_back(anim_service);
+            }
+        }
+
+        if let Some(anim) = self.coast_anim.as_mut() {
+            anim.update(&mut self.drag_offset);
+            let is_done = anim.state.playback != PlaybackState::Playing;
+            self.canvas_offset = self.get_elastic_canvas_offset(self.drag_offset, window);
+            if is_done || self.is_overscroll_maxed(self.drag_offset, window) {
+                if let Some(mut anim) = self.coast_anim.take() {
+                    anim.cancel(anim_service);
+                }
+                self.spring_back(anim_service);
+            }
+        }
+
+        if let Some(anim) = self.pan_anim.as_mut() {
+            anim.update(&mut self.canvas_offset);
+            if anim.state.playback != PlaybackState::Playing {
+                self.pan_anim = None;
+            }
+        }
+    }
+
+    fn spring_back(&mut self, anim_service: &mut AnimationService) {
+        let offset1 = self.get_constrained_canvas_offset(self.canvas_offset, self.window_space);
+        self.start_pan(self.canvas_offset, offset1, SPRING_DURATION, EasingDirection::Out, anim_service);
+    }
+
+    /* the point of the drawing clicked since this was last called */
+    pub fn take_click(&mut self) -> Option<Point2D> {
+        self.click.take()
+    }
+
+    /* the point of the drawing under the mouse */
+    pub fn get_hover(&self) -> Option<Point2D> {
+        self.hover.map(|point| self.to_drawing_point(point))
+    }
+
+    /* zoomed out points land on the top left of the block of the drawing under them */
+    fn to_drawing_point(&self, point: Point2D) -> Point2D {
+        let scale = self.get_scale();
+        let window = self.window_space;
+        let point = window.position + Point2D::new((point.x - window.position.x)*scale, (point.y - window.position.y)*scale);
+        let canvas_space = self.get_absolute_canvas_space(window);
+        self.drawing.to_absolute_layout_space(canvas_space).relative_position_of(point)
+    }
+
+    /* the size of the drawing at the current zoom */
     fn get_absolute_canvas_size(&self, absolute_window_space: AbsoluteSpace) -> Size2D {
-        self.drawing.to_absolute_layout_space(absolute_window_space).size
+        let scale = self.get_scale() as u16;
+        let size = self.drawing.to_absolute_layout_space(absolute_window_space).size;
+        Size2D::new(size.x.div_ceil(scale), size.y.div_ceil(scale))
+    }
+
+    /* the closest zoom the whole drawing fits in the window at, or the farthest out if it never does */
+    fn get_fitting_zoom(&self, absolute_window_space: AbsoluteSpace) -> usize {
+        let size = self.drawing.to_absolute_layout_space(absolute_window_space).size;
+        let window_size = absolute_window_space.size;
+        ZOOM_SCALES
+            .iter()
+            .position(|&scale| size.x.div_ceil(scale as u16) <= window_size.x && size.y.div_ceil(scale as u16) <= window_size.y)
+            .unwrap_or(ZOOM_SCALES.len() - 1)
+    }
+
+    /* the canvas offset that puts the point of the drawing in the middle of the window */
+    fn get_centered_canvas_offset(&self, point: Point2D) -> Point2D {
+        let scale = self.get_scale();
+        Point2D::new(
+            point.x/scale - self.window_space.size.x as i16/2,
+            point.y/scale - self.window_space.size.y as i16/2
+        )
     }
 
-    fn get_constrained_canvas_offset(&self, absolute_window_space: AbsoluteSpace) -> Point2D {
+    /* past the edges the drawing only follows half as far as it is pulled, up to the max overscroll */
+    fn get_elastic_canvas_offset(&self, canvas_offset: Point2D, absolute_window_space: AbsoluteSpace) -> Point2D {
+        let bound = self.get_constrained_canvas_offset(canvas_offset, absolute_window_space);
+        let stretch = |excess: i16, max: i16| (excess/2).clamp(-max, max);
+        Point2D::new(
+            bound.x + stretch(canvas_offset.x - bound.x, MAX_OVERSCROLL.x),
+            bound.y + stretch(canvas_offset.y - bound.y, MAX_OVERSCROLL.y)
+        )
+    }
+
+    fn is_overscroll_maxed(&self, canvas_offset: Point2D, absolute_window_space: AbsoluteSpace) -> bool {
+        let bound = self.get_constrained_canvas_offset(canvas_offset, absolute_window_space);
+        (canvas_offset.x - bound.x).abs()/2 >= MAX_OVERSCROLL.x || (canvas_offset.y - bound.y).abs()/2 >= MAX_OVERSCROLL.y
+    }
+
+    fn get_constrained_canvas_offset(&self, canvas_offset: Point2D, absolute_window_space: AbsoluteSpace) -> Point2D {
         let canvas_size = self.get_absolute_canvas_size(absolute_window_space);
         let max_offset_x = i16::try_from(canvas_size.x.saturating_sub(absolute_window_space.size.x)).unwrap_or(i16::MAX);
         let max_offset_y = i16::try_from(canvas_size.y.saturating_sub(absolute_window_space.size.y)).unwrap_or(i16::MAX);
         Point2D::new(
-            self.canvas_offset.x.min(max_offset_x).max(0), 
-            self.canvas_offset.y.min(max_offset_y).max(0)
+            canvas_offset.x.min(max_offset_x).max(0), 
+            canvas_offset.y.min(max_offset_y).max(0)
         )
     }
 
+    /* where the drawing's parent would be at full size, zoomed out this is off the screen and only used to find points of the drawing */
     fn get_absolute_canvas_space(&self, window_space: AbsoluteSpace) -> AbsoluteSpace {
+        let scale = self.get_scale();
         AbsoluteSpace::new(
-            window_space.position.x.saturating_sub(self.canvas_offset.x), 
-            window_space.position.y.saturating_sub(self.canvas_offset.y), 
+            window_space.position.x.saturating_sub(self.canvas_offset.x*scale), 
+            window_space.position.y.saturating_sub(self.canvas_offset.y*scale), 
             window_space.size.x,
             window_space.size.y
         )
     }
+
+    /* 
+     * draw the drawing at full size into a buffer of its own and squeeze every block of it into one cell of the window
+     * blocks keep their first narrow symbol if they have one so markers and labels survive, otherwise they become quadrants
+     */
+    fn draw_zoomed(&self, ctx: &mut DrawContext, state: &T::State) {
+        let scale = self.get_scale();
+        let window = ctx.absolute_layout_space;
+        let canvas_space = self.get_absolute_canvas_space(window);
+        let drawing_space = self.drawing.to_absolute_layout_space(canvas_space);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, drawing_space.size.x, drawing_space.size.y));
+        for cell in buf.content.iter_mut() {
+            cell.set_style(self.style);
+        }
+        let buf_space = AbsoluteSpace::from_rect(buf.area);
+        DrawContext::from_buffer(&mut buf)
+            .transform(AbsoluteSpace {
+                position: canvas_space.position - drawing_space.position,
+                size: canvas_space.size
+            })
+            .draw_stateful_child(&self.drawing, state);
+
+        let outside = Cell::default().set_style(self.style).clone();
+        for y in 0..window.size.y as i16 {
+            for x in 0..window.size.x as i16 {
+                let corner = drawing_space.relative_position_of(window.position + Point2D::new(x*scale, y*scale));
+                let block: Vec<&Cell> = (0..scale*scale)
+                    .map(|i| corner + Point2D::new(i % scale, i/scale))
+                    .map(|point| if buf_space.is_interior_point(point) { buf.get(point.x as u16, point.y as u16) } else { &outside })
+                    .collect();
+                if let Some(cell) = ctx.cell_at_mut(Point2D::new(x, y)) {
+                    *cell = Self::squeeze(&block, scale);
+                }
+            }
+        }
+    }
+
+    fn squeeze(block: &[&Cell], scale: i16) -> Cell {
+        let quadrant_of = |i: usize| (2*(i as i16/scale)/scale*2 + 2*(i as i16 % scale)/scale) as usize;
+        let quadrants: Vec<Color> = (0..4)
+            .map(|quadrant| get_most_common((0..block.len()).filter(|&i| quadrant_of(i) == quadrant).map(|i| block[i].bg)))
+            .collect();
+        let bg = get_most_common(quadrants.iter().copied());
+
+        let mut cell = Cell::default();
+        if let Some(symbol_cell) = block.iter().find(|cell| cell.symbol.width() == 1 && !cell.symbol.trim().is_empty()) {
+            cell.set_symbol(&symbol_cell.symbol).set_style(symbol_cell.style().bg(bg));
+            return cell
+        }
+
+        match quadrants.iter().copied().filter(|&color| color != bg).max_by_key(|&color| quadrants.iter().filter(|&&other| other == color).count()) {
+            Some(fg) => {
+                let bits = (0..4).filter(|&quadrant| quadrants[quadrant] == fg).fold(0, |bits, quadrant| bits | 8 >> quadrant);
+                cell.set_symbol(QUADRANT_SYMBOLS[bits]).set_fg(fg).set_bg(bg);
+            },
+            None => {
+                cell.set_bg(bg);
+            }
+        }
+        cell
+    }
+}
+
+/* the color that shows up the most, ties go to whichever came first */
+fn get_most_common(colors: impl Iterator<Item = Color>) -> Color {
+    let mut counts: Vec<(Color, usize)> = Vec::new();
+    for color in colors {
+        match counts.iter_mut().find(|(other, _)| *other == color) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((color, 1))
+        }
+    }
+    counts.iter().rev().max_by_key(|(_, count)| *count).map(|(color, _)| *color).unwrap_or(Color::Reset)
 }
 
 impl<T: MountableLayout + StatefulDrawable> Layoutable for Dragger<T> {
@@ -61,6 +475,10 @@ impl<T: MountableLayout + StatefulDrawable> StatefulDrawable for Dragger<T> {
             cell.set_style(self.style);
         }
 
+        if self.get_scale() > 1 {
+            return self.draw_zoomed(ctx, state)
+        }
+
         let canvas_space = self.get_absolute_canvas_space(ctx.absolute_layout_space);
         ctx.transform(canvas_space).draw_stateful_child(&self.drawing, state);
     }
@@ -91,27 +509,81 @@ impl<T: MountableLayout + StatefulDrawable> MountableLayout for Dragger<T> {
 
     fn relayout(&mut self, ctx: &mut LayoutContext) {
         let absolute_window_space = ctx.get_absolute_layout_space_of(self);
-        self.canvas_offset = self.get_constrained_canvas_offset(absolute_window_space);
+        self.window_space = absolute_window_space;
+        if self.zoom.is_none() && absolute_window_space.size.x > 0 && absolute_window_space.size.y > 0 {
+            self.zoom = Some(self.get_fitting_zoom(absolute_window_space));
+        }
+        let is_moving = self.pan_anim.is_some() || self.coast_anim.is_some();
+        self.relayout_motion(&mut ctx.service.animation);
+        if !self.is_grabbed && self.pan_anim.is_none() && self.coast_anim.is_none() {
+            self.canvas_offset = self.get_constrained_canvas_offset(self.canvas_offset, absolute_window_space);
+        }
         let absolute_canvas_space = self.get_absolute_canvas_space(absolute_window_space);
 
         ctx.relayout_input_space_of(self, Space::FULL);
+        let invalidation_count = ctx.service.damage.get_invalidation_count();
         ctx.relayout_children_in_space_of(self, absolute_canvas_space);
+
+        /* a zoomed drawing isn't drawn where it is laid out so anything damaged in it damages the whole window, so does zooming or panning */
+        let view = (self.get_scale(), self.canvas_offset);
+        let did_move = std::mem::replace(&mut self.last_view, view) != view;
+        if is_moving || did_move || (self.get_scale() > 1 && ctx.service.damage.get_invalidation_count() != invalidation_count) {
+            ctx.invalidate(self);
+        }
     }
 
     fn on_mouse_input(&mut self, event: InputEvent) -> bool {
         match event.kind {
+            /* grabbing the drawing stops it from sliding anywhere */
             InputEventKind::Down(point) => {
+                self.stop();
+                self.is_grabbed = true;
+                self.drag_offset = self.canvas_offset;
+                self.drag_velocity = Float2D::default();
+                self.last_drag = Instant::now();
                 self.mouse_location = point;
+                self.did_drag = false;
                 false
             },
+            /* the pointer is left wherever it was let go, so that is where the hover is */
+            InputEventKind::Up(point) => {
+                self.is_grabbed = false;
+                self.did_release = true;
+                self.hover = Some(point);
+                true
+            },
+            InputEventKind::Click(point) if !self.did_drag => {
+                self.click = Some(self.to_drawing_point(point));
+                true
+            },
+            /* scrolling up zooms in */
+            InputEventKind::Scroll(point, rows) => self.zoom_at(rows, point),
+            InputEventKind::Move(point) => {
+                let did_move = self.hover != Some(point);
+                self.hover = Some(point);
+                did_move
+            },
             InputEventKind::Drag(point) => {
-                let canvas_offset = Point2D::new(
-                    self.canvas_offset.x.saturating_sub(point.x.saturating_sub(self.mouse_location.x)),
-                    self.canvas_offset.y.saturating_sub(point.y.saturating_sub(self.mouse_location.y))
+                let delta = Point2D::new(
+                    point.x.saturating_sub(self.mouse_location.x),
+                    point.y.saturating_sub(self.mouse_location.y)
                 );
                 self.mouse_location = point;
+                self.hover = Some(point);
+
+                /* the speed the offset is moving at, smoothed since terminals send drags in bursts */
+                let elapsed = self.last_drag.elapsed().as_secs_f32().max(0.001);
+                self.last_drag = Instant::now();
+                self.drag_velocity = self.drag_velocity.lerp(
+                    Float2D::new(-delta.x as f32/elapsed, -delta.y as f32/elapsed), 
+                    DRAG_VELOCITY_SMOOTHING
+                );
+
+                self.drag_offset = Point2D::new(self.drag_offset.x.saturating_sub(delta.x), self.drag_offset.y.saturating_sub(delta.y));
+                let canvas_offset = self.get_elastic_canvas_offset(self.drag_offset, self.window_space);
                 
                 if canvas_offset != self.canvas_offset {
+                    self.did_drag = true;
                     self.canvas_offset = canvas_offset;
                     true
                 } else {
@@ -121,4 +593,275 @@ impl<T: MountableLayout + StatefulDrawable> MountableLayout for Dragger<T> {
             _ => false
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::screen::Screen;
+
+    use crossterm::event::{KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
+
+    const DRAWING_SIZE: Size2D = Size2D::new(40, 20);
+    const WINDOW_SIZE: Size2D = Size2D::new(20, 10);
+
+    /* a drawing with an x at some of its points */
+    #[derive(Debug)]
+    struct Marks {
+        points: Vec<Point2D>,
+        layout: DrawLayout,
+        mount: Mount
+    }
+
+    impl Layoutable for Marks {
+        fn layout_ref(&self) -> &DrawLayout { &self.layout }
+        fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+    }
+
+    impl StatefulDrawable for Marks {
+        type State = ();
+        fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+            for &point in self.points.iter() {
+                ctx.draw_unicode_line("x", point, Style::default());
+            }
+        }
+    }
+
+    impl MountableLayout for Marks {
+        fn mount_ref(&self) -> &Mount { &self.mount }
+        fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+        fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+        fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+    }
+
+    /* the drawing is twice the size of the window so it starts zoomed out, tests that want it full size zoom in */
+    fn get_screen(points: Vec<Point2D>) -> Screen<Dragger<Marks>> {
+        let marks = Marks {
+            points,
+            layout: DrawLayout::default().set_size(UDim2::from_size2d(DRAWING_SIZE)).clone(),
+            mount: Mount::default()
+        };
+        let mut screen = Screen::new(Dragger::new(marks, Style::default()));
+        draw(&mut screen);
+        screen
+    }
+
+    fn draw(screen: &mut Screen<Dragger<Marks>>) -> Buffer {
+        screen.render_to_buffer(WINDOW_SIZE, &mut ())
+    }
+
+    fn get_marked(buf: &Buffer) -> Vec<Point2D> {
+        AbsoluteSpace::from_rect(buf.area)
+            .into_iter()
+            .filter(|point| buf.get(point.x as u16, point.y as u16).symbol == "x")
+            .collect()
+    }
+
+    fn mouse(screen: &mut Screen<Dragger<Marks>>, kind: MouseEventKind, point: Point2D) -> bool {
+        let event = MouseEvent { kind, column: point.x as u16, row: point.y as u16, modifiers: KeyModifiers::NONE };
+        screen.service.input.handle_mouse_input(event, &mut screen.root)
+    }
+
+    /* the drawing starts at the closest zoom it fits in whole, every block of it squeezed into a cell */
+    #[test]
+    fn starts_zoomed_to_fit() {
+        let mut screen = get_screen(vec![Point2D::new(10, 4), Point2D::new(39, 19)]);
+        assert_eq!(screen.root.get_scale(), 2);
+        assert!(!screen.root.is_clipped());
+        assert_eq!(get_marked(&draw(&mut screen)), vec![Point2D::new(5, 2), Point2D::new(19, 9)]);
+
+        let marks = Marks { points: Vec::new(), layout: DrawLayout::default().set_size(UDim2::from_size2d(WINDOW_SIZE)).clone(), mount: Mount::default() };
+        let mut screen = Screen::new(Dragger::new(marks, Style::default()));
+        screen.render_to_buffer(WINDOW_SIZE, &mut ());
+        assert_eq!(screen.root.get_scale(), 1);
+    }
+
+    /* the wheel zooms in and out a level at a time keeping what is under the mouse where it is */
+    #[test]
+    fn wheel_zooms_around_the_mouse() {
+        let mark = Point2D::new(10, 4);
+        let mut screen = get_screen(vec![mark]);
+        let under = Point2D::new(5, 2);
+
+        assert!(mouse(&mut screen, MouseEventKind::ScrollUp, under));
+        assert_eq!(screen.root.get_scale(), 1);
+        assert!(screen.root.is_clipped());
+        assert_eq!(get_marked(&draw(&mut screen)), vec![under]);
+        assert!(!mouse(&mut screen, MouseEventKind::ScrollUp, under));
+
+        assert!(mouse(&mut screen, MouseEventKind::ScrollDown, under));
+        assert!(mouse(&mut screen, MouseEventKind::ScrollDown, under));
+        assert_eq!(screen.root.get_scale(), 4);
+        assert!(!mouse(&mut screen, MouseEventKind::ScrollDown, under));
+        assert_eq!(get_marked(&draw(&mut screen)), vec![Point2D::new(mark.x/4, mark.y/4)]);
+    }
+
+    /* blocks without a symbol become quadrants of their most common colors */
+    #[test]
+    fn zoomed_out_blocks_keep_their_colors() {
+        let cell = |bg: Color| Cell::default().set_bg(bg).clone();
+        let (red, blue) = (cell(Color::Red), cell(Color::Blue));
+
+        let squeezed = Dragger::<Marks>::squeeze(&[&red, &blue, &red, &blue], 2);
+        assert_eq!((squeezed.symbol.as_str(), squeezed.fg, squeezed.bg), ("▐", Color::Blue, Color::Red));
+        let squeezed = Dragger::<Marks>::squeeze(&[&red, &red, &red, &red], 2);
+        assert_eq!((squeezed.symbol.as_str(), squeezed.bg), (" ", Color::Red));
+
+        let mut marked = red.clone();
+        marked.set_symbol("x");
+        let squeezed = Dragger::<Marks>::squeeze(&[&blue, &blue, &blue, &marked], 2);
+        assert_eq!((squeezed.symbol.as_str(), squeezed.bg), ("x", Color::Blue));
+    }
+
+    /* pan keys move a step at a time and stop at the edges of the drawing */
+    #[test]
+    fn keys_pan_a_step() {
+        /* zoomed out the whole drawing is in view so there is nowhere to go */
+        let mut screen = get_screen(Vec::new());
+        assert!(!screen.root.pan(Point2D::new(1, 0)));
+        assert!(screen.root.zoom(-1));
+        screen.root.focus(Point2D::new(0, 0));
+        draw(&mut screen);
+        assert_eq!(screen.root.get_view(), AbsoluteSpace::new(0, 0, WINDOW_SIZE.x, WINDOW_SIZE.y));
+
+        assert!(screen.root.pan(Point2D::new(1, 1)));
+        assert_eq!(screen.root.get_view().position, KEY_PAN_STEP);
+        assert!(screen.root.pan(Point2D::new(-1, 0)));
+        assert_eq!(screen.root.get_view().position, Point2D::new(0, KEY_PAN_STEP.y));
+        assert!(!screen.root.pan(Point2D::new(-1, 0)));
+
+        let mut steps = 0;
+        while screen.root.pan(Point2D::new(1, 1)) {
+            steps += 1;
+            assert!(steps < 10);
+        }
+        let far = Point2D::new((DRAWING_SIZE.x - WINDOW_SIZE.x) as i16, (DRAWING_SIZE.y - WINDOW_SIZE.y) as i16);
+        assert_eq!(screen.root.get_view().position, far);
+    }
+
+    fn get_zoomed_in_screen() -> Screen<Dragger<Marks>> {
+        get_zoomed_in_screen_with(Vec::new())
+    }
+
+    fn get_zoomed_in_screen_with(points: Vec<Point2D>) -> Screen<Dragger<Marks>> {
+        let mut screen = get_screen(points);
+        screen.root.zoom(-1);
+        screen.root.focus(Point2D::new(0, 0));
+        draw(&mut screen);
+        screen
+    }
+
+    fn wait_out(duration: f32) {
+        std::thread::sleep(std::time::Duration::from_secs_f32(duration + 0.1));
+    }
+
+    /* spaces out of view slide to the middle of the window, as far as the drawing goes */
+    #[test]
+    fn panning_slides_into_view() {
+        let mut screen = get_zoomed_in_screen();
+        assert!(!screen.root.pan_into_view(AbsoluteSpace::new(2, 2, 4, 2), &mut screen.service.animation));
+
+        let far = AbsoluteSpace::new(34, 16, 4, 2);
+        assert!(screen.root.pan_into_view(far, &mut screen.service.animation));
+        draw(&mut screen);
+        assert_ne!(screen.root.get_view().try_intersection(far), Some(far));
+
+        wait_out(PAN_DURATION);
+        draw(&mut screen);
+        let end = Point2D::new((DRAWING_SIZE.x - WINDOW_SIZE.x) as i16, (DRAWING_SIZE.y - WINDOW_SIZE.y) as i16);
+        assert_eq!(screen.root.get_view().position, end);
+        assert!(!screen.root.pan_into_view(far, &mut screen.service.animation));
+    }
+
+    /* jumping somewhere stops a pan that is still going */
+    #[test]
+    fn jumps_stop_pans() {
+        let mut screen = get_zoomed_in_screen();
+        screen.root.pan_to(Point2D::new(39, 19), &mut screen.service.animation);
+        draw(&mut screen);
+        screen.root.focus(Point2D::new(20, 10));
+        let middle = Point2D::new(20 - WINDOW_SIZE.x as i16/2, 10 - WINDOW_SIZE.y as i16/2);
+        draw(&mut screen);
+        assert_eq!(screen.root.get_view().position, middle);
+
+        wait_out(PAN_DURATION);
+        draw(&mut screen);
+        assert_eq!(screen.root.get_view().position, middle);
+    }
+
+    /* revealing scrolls only as far as it takes to get the space in the window */
+    #[test]
+    fn reveal_scrolls_as_little_as_it_can() {
+        let mut screen = get_zoomed_in_screen();
+        screen.root.reveal(AbsoluteSpace::new(25, 3, 4, 2));
+        draw(&mut screen);
+        assert_eq!(screen.root.get_view().position, Point2D::new(29 - WINDOW_SIZE.x as i16, 0));
+
+        screen.root.reveal(AbsoluteSpace::new(1, 1, 2, 2));
+        draw(&mut screen);
+        assert_eq!(screen.root.get_view().position, Point2D::new(1, 0));
+    }
+
+    /* grab the drawing at a point, drag it along the points and let go at the last one */
+    fn drag(screen: &mut Screen<Dragger<Marks>>, points: &[Point2D]) {
+        mouse(screen, MouseEventKind::Down(MouseButton::Left), points[0]);
+        for &point in points[1..].iter() {
+            mouse(screen, MouseEventKind::Drag(MouseButton::Left), point);
+        }
+    }
+
+    fn let_go(screen: &mut Screen<Dragger<Marks>>, point: Point2D) {
+        mouse(screen, MouseEventKind::Up(MouseButton::Left), point);
+        draw(screen);
+    }
+
+    /* 
+     * letting go of a fast drag keeps the drawing going for a moment, how far it goes follows from the speed
+     * the speed is set by hand since the drags here come faster than any mouse
+     */
+    #[test]
+    fn fast_drags_coast() {
+        let mut screen = get_zoomed_in_screen();
+        drag(&mut screen, &[Point2D::new(10, 5), Point2D::new(6, 5)]);
+        assert_eq!(screen.root.get_view().position, Point2D::new(4, 0));
+
+        let velocity = Float2D::new(5.0*COAST_MIN_SPEED, 0.0);
+        screen.root.drag_velocity = velocity;
+        screen.root.last_drag = Instant::now();
+        let_go(&mut screen, Point2D::new(6, 5));
+        assert!(screen.root.coast_anim.is_some());
+
+        let duration = CoastAnimator::get_duration(velocity);
+        wait_out(duration);
+        draw(&mut screen);
+        let distance = velocity.x*COAST_TIME_CONSTANT*(1.0 - (-duration/COAST_TIME_CONSTANT).exp());
+        assert_eq!(screen.root.get_view().position, Point2D::new(4 + distance.round() as i16, 0));
+        assert!(screen.root.coast_anim.is_none());
+    }
+
+    /* drags that stopped before letting go stay where they are */
+    #[test]
+    fn stopped_drags_stay_put() {
+        let mut screen = get_zoomed_in_screen();
+        drag(&mut screen, &[Point2D::new(10, 5), Point2D::new(6, 5)]);
+        wait_out(COAST_IDLE_TIME);
+        let_go(&mut screen, Point2D::new(6, 5));
+        assert!(screen.root.coast_anim.is_none() && screen.root.pan_anim.is_none());
+        assert_eq!(screen.root.get_view().position, Point2D::new(4, 0));
+    }
+
+    /* past its edge the drawing follows half as far as it is pulled, up to a limit, and springs back when let go */
+    #[test]
+    fn edges_stretch_and_spring_back() {
+        let mut screen = get_zoomed_in_screen_with(vec![Point2D::new(0, 0)]);
+        drag(&mut screen, &[Point2D::new(2, 5), Point2D::new(8, 5)]);
+        assert_eq!(get_marked(&draw(&mut screen)), vec![Point2D::new(3, 0)]);
+
+        drag(&mut screen, &[Point2D::new(8, 5), Point2D::new(19, 5)]);
+        assert_eq!(get_marked(&draw(&mut screen)), vec![Point2D::new(MAX_OVERSCROLL.x, 0)]);
+
+        let_go(&mut screen, Point2D::new(19, 5));
+        assert!(screen.root.pan_anim.is_some());
+        wait_out(SPRING_DURATION);
+        assert_eq!(get_marked(&draw(&mut screen)), vec![Point2D::new(0, 0)]);
+    }
+}
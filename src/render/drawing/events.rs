@@ -0,0 +1,346 @@
+/*
+ * events.rs
+ * the events pane, a scrollable feed of everything that happened in the game
+ *
+ * entries that happened somewhere on the map can be clicked, the click is kept until the game takes it to point the map there
+ * while focused the arrow keys pick an entry and enter does the same as clicking it
+ */
+
+use super::{text::{self, StyledRow}, map::MapTarget};
+
+use crate::render::prelude::*;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::style::{Color, Modifier, Style};
+
+use std::time::Duration;
+
+lazy_static! {
+    static ref TIME_STYLE: Style = Style::default().fg(Color::DarkGray);
+    static ref TARGET_STYLE: Style = Style::default().fg(Color::Yellow);
+    pub static ref EVENT_TEXT_STYLE: Style = Style::default().fg(Color::White);
+    pub static ref EVENT_NOTE_STYLE: Style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC);
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    /* how far into the game it happened */
+    pub time: Duration,
+    pub spans: Vec<(String, Style)>,
+    pub target: Option<MapTarget>
+}
+
+impl FeedEntry {
+    /* the entry as styled pieces starting with its timestamp, entries with a target end in a marker to show they can be clicked */
+    fn get_spans(&self) -> Vec<(String, Style)> {
+        let seconds = self.time.as_secs();
+        let mut spans = vec![(format!("{:02}:{:02} ", seconds/60, seconds%60), *TIME_STYLE)];
+        spans.extend(self.spans.iter().cloned());
+        if self.target.is_some() {
+            spans.push((String::from(" ◎"), *TARGET_STYLE));
+        }
+        spans
+    }
+}
+
+#[derive(Debug)]
+pub struct EventFeed {
+    entries: Vec<FeedEntry>,
+    /* how many rows the feed is scrolled up from the newest entry */
+    scroll: usize,
+    clicked: Option<MapTarget>,
+    /* the entry picked with the keyboard */
+    selected: Option<usize>,
+    is_focused: bool,
+    /* set when anything drawn changes outside of a relayout */
+    is_damaged: bool,
+    absolute_space: AbsoluteSpace,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl EventFeed {
+    pub fn new(layout: DrawLayout) -> Self {
+        EventFeed {
+            entries: Vec::new(),
+            scroll: 0,
+            clicked: None,
+            selected: None,
+            is_focused: false,
+            is_damaged: false,
+            absolute_space: AbsoluteSpace::default(),
+            layout,
+            mount: Mount::default()
+        }
+    }
+
+    /* add an entry to the feed, the view stays put if it is scrolled up */
+    pub fn push(&mut self, entry: FeedEntry) {
+        if self.scroll > 0 {
+            self.scroll += text::wrap(entry.get_spans(), self.absolute_space.size.x).len();
+        }
+        self.entries.push(entry);
+        self.is_damaged = true;
+    }
+
+    /* take every entry out of the feed, like when a replay goes back to before they happened */
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.scroll = 0;
+        self.selected = None;
+        self.clicked = None;
+        self.is_damaged = true;
+    }
+
+    /* the target of the last entry clicked since this was last called */
+    pub fn take_clicked(&mut self) -> Option<MapTarget> {
+        self.clicked.take()
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    /* move the selection by some entries, the newest entry is picked first and the view scrolls to keep the selection in it */
+    fn select_by(&mut self, entries: isize) -> bool {
+        if self.entries.is_empty() {
+            return false
+        }
+
+        let last = self.entries.len() - 1;
+        let selected = match self.selected {
+            Some(selected) => (selected as isize + entries).clamp(0, last as isize) as usize,
+            None => last
+        };
+        if self.selected == Some(selected) {
+            return false
+        }
+        self.selected = Some(selected);
+
+        let size = self.absolute_space.size;
+        let rows = self.get_rows(size.x);
+        let (start, end) = self.get_visible_range(rows.len(), size.y as usize);
+        let first = rows.iter().position(|&(i, _)| i == selected).unwrap_or(0);
+        let last = rows.iter().rposition(|&(i, _)| i == selected).unwrap_or(0);
+        if first < start {
+            self.scroll += start - first;
+        } else if last >= end {
+            self.scroll = self.scroll.saturating_sub(last + 1 - end);
+        }
+        true
+    }
+
+    /* every row of the feed with the index of the entry it belongs to */
+    fn get_rows(&self, width: u16) -> Vec<(usize, StyledRow)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .flat_map(|(i, entry)| text::wrap(entry.get_spans(), width).into_iter().map(move |row| (i, row)))
+            .collect()
+    }
+
+    /* the range of rows that fit in the pane */
+    fn get_visible_range(&self, row_count: usize, height: usize) -> (usize, usize) {
+        let end = row_count.saturating_sub(self.scroll.min(row_count.saturating_sub(height)));
+        (end.saturating_sub(height), end)
+    }
+}
+
+impl Layoutable for EventFeed {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for EventFeed {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let size = ctx.absolute_layout_space.size;
+        let rows = self.get_rows(size.x);
+        let (start, end) = self.get_visible_range(rows.len(), size.y as usize);
+        for (y, (i, row)) in rows[start..end].iter().enumerate() {
+            if self.is_focused && self.selected == Some(*i) {
+                let row: StyledRow = row.iter().map(|(text, style)| (text.clone(), style.add_modifier(Modifier::REVERSED))).collect();
+                text::draw_row(ctx, &row, Point2D::new(0, y as i16));
+            } else {
+                text::draw_row(ctx, row, Point2D::new(0, y as i16));
+            }
+        }
+
+        if end < rows.len() && size.y > 0 {
+            ctx.draw_unicode_line("▼", Point2D::new(size.x as i16 - 1, size.y as i16 - 1), *TIME_STYLE);
+        }
+    }
+}
+
+impl MountableLayout for EventFeed {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        self.absolute_space = ctx.get_absolute_layout_space_of(self);
+        /* the selection is only drawn while focused */
+        let is_focused = ctx.relayout_focus_of(self);
+        self.is_damaged |= is_focused != self.is_focused;
+        self.is_focused = is_focused;
+        ctx.relayout_input_space_of(self, Space::FULL);
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let is_handled = match key.code {
+            KeyCode::Up => self.select_by(-1),
+            KeyCode::Down => self.select_by(1),
+            KeyCode::PageUp => self.select_by(-5),
+            KeyCode::PageDown => self.select_by(5),
+            KeyCode::Enter => {
+                self.clicked = self.selected.and_then(|selected| self.entries[selected].target);
+                self.clicked.is_some()
+            },
+            _ => false
+        };
+        self.is_damaged |= is_handled;
+        is_handled
+    }
+
+    fn on_mouse_input(&mut self, event: InputEvent) -> bool {
+        let size = self.absolute_space.size;
+        let is_handled = match event.kind {
+            InputEventKind::Scroll(_, rows) => {
+                let row_count = self.get_rows(size.x).len();
+                let scroll = (self.scroll as i16 - rows).max(0).min(row_count.saturating_sub(size.y as usize) as i16) as usize;
+                let did_scroll = scroll != self.scroll;
+                self.scroll = scroll;
+                did_scroll
+            },
+            InputEventKind::Click(point) => {
+                let rows = self.get_rows(size.x);
+                let (start, end) = self.get_visible_range(rows.len(), size.y as usize);
+                let row = start + self.absolute_space.relative_position_of(point).y.max(0) as usize;
+                if row >= end {
+                    return false
+                }
+
+                self.clicked = self.entries[rows[row].0].target;
+                self.clicked.is_some()
+            },
+            _ => false
+        };
+        self.is_damaged |= is_handled;
+        is_handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{screen::Screen, snapshot};
+
+    use crossterm::event::{KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
+
+    const SIZE: Size2D = Size2D::new(30, 4);
+
+    /* a feed of entries a second apart, every other one happened on a tile */
+    fn get_screen(count: usize) -> Screen<EventFeed> {
+        let mut screen = Screen::new(EventFeed::new(DrawLayout::FULL));
+        for i in 0..count {
+            push(&mut screen, i);
+        }
+        screen
+    }
+
+    fn push(screen: &mut Screen<EventFeed>, i: usize) {
+        screen.root.push(FeedEntry {
+            time: Duration::from_secs(i as u64),
+            spans: vec![(format!("event {}", i), *EVENT_TEXT_STYLE)],
+            target: if i.is_multiple_of(2) { Some(MapTarget::Tile(i)) } else { None }
+        });
+    }
+
+    fn draw(screen: &mut Screen<EventFeed>) -> String {
+        snapshot::to_text(&screen.render_to_buffer(SIZE, &mut ()))
+    }
+
+    fn mouse(screen: &mut Screen<EventFeed>, kind: MouseEventKind, row: u16) -> bool {
+        let event = MouseEvent { kind, column: 1, row, modifiers: KeyModifiers::NONE };
+        screen.service.input.handle_mouse_input(event, &mut screen.root)
+    }
+
+    fn press(screen: &mut Screen<EventFeed>, code: KeyCode) -> bool {
+        screen.service.focus.handle_key_input(KeyEvent::new(code, KeyModifiers::NONE), &mut screen.root)
+    }
+
+    #[test]
+    fn entries_are_timestamped() {
+        let mut screen = get_screen(0);
+        screen.root.push(FeedEntry { time: Duration::from_secs(65), spans: vec![(String::from("robbed"), *EVENT_TEXT_STYLE)], target: Some(MapTarget::Tile(3)) });
+        screen.root.push(FeedEntry { time: Duration::from_secs(3600), spans: vec![(String::from("traded"), *EVENT_TEXT_STYLE)], target: None });
+        assert_eq!(draw(&mut screen), "01:05 robbed ◎\n60:00 traded\n\n\n");
+    }
+
+    /* the newest entries are shown at the bottom, scrolling up keeps the same entries in view as more come in */
+    #[test]
+    fn scrolled_view_stays_put() {
+        let mut screen = get_screen(6);
+        assert_eq!(draw(&mut screen), "00:02 event 2 ◎\n00:03 event 3\n00:04 event 4 ◎\n00:05 event 5\n");
+
+        assert!(mouse(&mut screen, MouseEventKind::ScrollUp, 0));
+        push(&mut screen, 6);
+        push(&mut screen, 7);
+        let text = draw(&mut screen);
+        assert!(text.starts_with("00:01 event 1\n"), "{}", text);
+        let last = text.lines().last().unwrap();
+        assert!(last.starts_with("00:04 event 4 ◎") && last.ends_with('▼'), "{}", text);
+
+        /* the wheel stops at either end */
+        for _ in 0..3 {
+            mouse(&mut screen, MouseEventKind::ScrollDown, 0);
+        }
+        assert!(!mouse(&mut screen, MouseEventKind::ScrollDown, 0));
+        assert!(draw(&mut screen).ends_with("00:07 event 7\n"));
+        for _ in 0..4 {
+            mouse(&mut screen, MouseEventKind::ScrollUp, 0);
+        }
+        assert!(!mouse(&mut screen, MouseEventKind::ScrollUp, 0));
+        assert!(draw(&mut screen).starts_with("00:00 event 0 ◎\n"));
+    }
+
+    #[test]
+    fn clicking_an_entry_points_at_it() {
+        let mut screen = get_screen(6);
+        draw(&mut screen);
+
+        for (row, target) in [(0, Some(MapTarget::Tile(2))), (1, None)] {
+            mouse(&mut screen, MouseEventKind::Down(MouseButton::Left), row);
+            mouse(&mut screen, MouseEventKind::Up(MouseButton::Left), row);
+            assert_eq!(screen.root.take_clicked(), target);
+        }
+        assert_eq!(screen.root.take_clicked(), None);
+    }
+
+    /* the arrows pick the newest entry first and scroll to keep the pick in view, enter clicks it */
+    #[test]
+    fn picking_an_entry_with_the_keyboard() {
+        let mut screen = get_screen(6);
+        screen.service.focus.focus(screen.root.mount_ref().id);
+        draw(&mut screen);
+
+        /* entries that didn't happen anywhere on the map can't be clicked */
+        assert!(press(&mut screen, KeyCode::Up));
+        assert!(!press(&mut screen, KeyCode::Enter));
+        assert_eq!(screen.root.take_clicked(), None);
+        assert!(press(&mut screen, KeyCode::Up));
+        assert!(press(&mut screen, KeyCode::Enter));
+        assert_eq!(screen.root.take_clicked(), Some(MapTarget::Tile(4)));
+
+        assert!(press(&mut screen, KeyCode::PageUp));
+        assert!(!press(&mut screen, KeyCode::PageUp));
+        assert!(draw(&mut screen).starts_with("00:00 event 0 ◎\n"));
+        assert!(press(&mut screen, KeyCode::Enter));
+        assert_eq!(screen.root.take_clicked(), Some(MapTarget::Tile(0)));
+    }
+}
@@ -1,19 +1,53 @@
-use super::{players::{self, *}, map::{self, Map}, drag::Dragger};
+use super::{players::{self, *}, map::{self, Map, MapTarget, BuildMode, BuildPreview}, drag::Dragger, minimap::Minimap, chat::{Chat, ChatLine}, command::CommandLine, dialog::{Dialog, DialogStack}, events::*, stats::StatsView};
 
 use crate::render::prelude::*;
+use crate::net::protocol::ClientMessage;
+use crate::logic::{game::{Action, GameEvent, Phase, Piece, PlayerId, TradeOffer}, hand::Hand, model::GameModel, notation};
+use crate::enums::{DevelopmentCard, Resource};
+use crate::keymap::{Command, Keymap};
 
+use crossterm::event::KeyEvent;
+use std::{path::Path, time::{Instant, SystemTime, UNIX_EPOCH}};
 use tui::{
     layout::*, 
-    style::{Color, Style}, 
+    style::{Color, Modifier, Style}, 
     widgets::{Block, Borders}
 };
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 struct GameRegions {
     map: AbsoluteSpace,
     events: AbsoluteSpace,
     chat: AbsoluteSpace,
-    players: AbsoluteSpace
+    players: AbsoluteSpace,
+    command_line: AbsoluteSpace
+}
+
+/* what the game draws itself around and over the panes, as of the last relayout so only what changed about it gets redrawn */
+#[derive(Debug, Default, Clone, PartialEq)]
+struct GameChrome {
+    regions: GameRegions,
+    /* the title, focus and build hint on the map's border */
+    map_border: (String, bool, Option<String>),
+    events_border: bool,
+    chat_border: bool,
+    banner: Option<String>,
+    observers: Option<String>
+}
+
+/* what a dialog over the game asks about, it stays up for as long as the game keeps asking */
+#[derive(Debug, Clone, PartialEq)]
+enum GameDialog {
+    /* how many cards have to go */
+    Discard(u8),
+    /* who the robber can take from */
+    Steal(Vec<PlayerId>),
+    Offer(TradeOffer),
+    /* the cards that can be played right now */
+    Development(Vec<DevelopmentCard>),
+    YearOfPlenty,
+    Monopoly
 }
 
 #[derive(Debug)]
@@ -21,40 +55,667 @@ pub struct Game {
     mount: Mount,
     layout: DrawLayout,
     players: PlayerList,
-    regions: GameRegions,    
+    regions: GameRegions,
+    chrome: GameChrome,
 
     pub map_dragger: Dragger<Map>,
+    pub chat: Chat,
+    pub events: EventFeed,
+    pub command_line: CommandLine,
+    minimap: Minimap,
+    stats: StatsView,
+    dialogs: DialogStack<GameDialog>,
+    /* dialogs that were answered or dismissed, they don't open on their own again until the game stops asking */
+    answered_dialogs: Vec<GameDialog>,
+    /* the play a card dialog was asked for with the keyboard */
+    wants_development_dialog: bool,
+    /* event feed timestamps count from here */
+    started: Instant,
+    /* the state of the game, the map and player frames show whatever it has in it */
+    pub model: GameModel,
+    /* the first sync fills the map in without moving it around */
+    is_synced: bool,
+    /* the stats open on their own when the game goes from being played to being won */
+    is_finished: bool,
+    /* message shown over the top of the map, like when the connection to the server is lost */
+    pub banner: Option<String>,
+    /* names of the spectators watching the game */
+    pub observers: Vec<String>,
+    /* the piece picked to build with the keyboard, see get_build_mode */
+    build_mode: Option<BuildMode>,
+    keymap: Keymap,
+    outbox: Vec<ClientMessage>
 }
 
 impl Game {
-    pub fn new(map: Map) -> Self {
+    /* the model needs a seat for every player */
+    pub fn new(model: GameModel, players: Vec<(String, Color)>) -> Self {
         Game {
             mount: Mount::default(),
             layout: DrawLayout::FULL,
-            map_dragger: Dragger::new(map, Style::default().bg(map::MAP_OCEAN_COLOR)),
+            map_dragger: Dragger::new(Map::from_board(&model.board), Style::default().bg(map::MAP_OCEAN_COLOR)),
+            chat: Chat::new(DrawLayout::default()),
+            events: EventFeed::new(DrawLayout::default()),
+            command_line: CommandLine::new(DrawLayout::default()),
+            minimap: Minimap::new(),
+            stats: StatsView::new(model.board.clone(), players.clone()),
+            dialogs: DialogStack::new(DrawLayout::FULL),
+            answered_dialogs: Vec::new(),
+            wants_development_dialog: false,
+            started: Instant::now(),
             regions: GameRegions::default(),
+            chrome: GameChrome::default(),
+            model,
+            is_synced: false,
+            is_finished: false,
+            banner: None,
+            observers: Vec::new(),
+            build_mode: None,
+            keymap: Keymap::default(),
+            outbox: Vec::new(),
             players: PlayerList::new(
-                vec![
-                    PlayerFrame::new(
-                        String::from("Andre"), 
-                        Color::Red, 
-                        DrawLayout::default()
-                    ),
-                    PlayerFrame::new(
-                        String::from("Unbreakable Patches"), 
-                        Color::Green, 
-                        DrawLayout::default()
-                    ),
-                    PlayerFrame::new(
-                        String::from("Seigward"), 
-                        Color::Blue, 
-                        DrawLayout::default()
-                    )
-                ], 
+                players
+                    .into_iter()
+                    .map(|(name, color)| PlayerFrame::new(name, color, DrawLayout::default()))
+                    .collect(),
                 DrawLayout::FULL
             )
         }
     }
+
+    pub fn get_player_color(&self, player: usize) -> Color {
+        self.players.frames[player].get_color()
+    }
+
+    pub fn get_player_name(&self, player: usize) -> &str {
+        self.players.frames[player].get_name()
+    }
+
+    /* 
+     * bring the map and player frames in line with the model, animating what changed and sliding the map over to it
+     * relayout does this every frame, whoever changes the model can do it right away so only what animates gets redrawn
+     */
+    pub fn sync(&mut self, anim_service: &mut AnimationService) {
+        let colors: Vec<Color> = self.players.frames.iter().map(|frame| frame.get_color()).collect();
+        let changed = self.map_dragger.drawing.sync(&self.model.pieces, &colors, anim_service);
+        if std::mem::replace(&mut self.is_synced, true) {
+            self.show_on_map(&changed, anim_service);
+        }
+        self.players.sync(&self.model.players, anim_service);
+
+        self.stats.sync(&self.model.stats);
+        let is_finished = matches!(self.model.phase, Phase::Finished { .. });
+        if is_finished && !self.is_finished {
+            self.stats.set_open(true);
+        }
+        self.is_finished = is_finished;
+    }
+
+    /* slide the map over to where something happened if any of it is out of view */
+    pub fn show_on_map(&mut self, targets: &[MapTarget], anim_service: &mut AnimationService) {
+        let corners: Vec<Point2D> = targets
+            .iter()
+            .map(|target| target.get_space())
+            .flat_map(|space| [space.position, Point2D::new(space.right() - 1, space.bottom() - 1)])
+            .collect();
+        if !corners.is_empty() {
+            self.map_dragger.pan_into_view(AbsoluteSpace::from_point_cloud(&corners), anim_service);
+        }
+    }
+
+    /* add what happened to the events feed */
+    pub fn push_event(&mut self, event: &GameEvent) {
+        let name = |player: PlayerId| self.get_name_span(player);
+        let text = |text: String| (text, *EVENT_TEXT_STYLE);
+        let (spans, target) = match *event {
+            GameEvent::TurnStarted { player } => (vec![text(String::from("── ")), name(player), text(String::from("'s turn ──"))], None),
+            GameEvent::Rolled { player, dice: (a, b) } => (vec![name(player), text(format!(" rolled {} ({}+{})", a + b, a, b))], None),
+            GameEvent::Produced { player, resources } => (vec![name(player), text(format!(" got {}", resources))], None),
+            GameEvent::Received { player, resources } => (vec![name(player), text(format!(" received {}", resources))], None),
+            GameEvent::Discarded { player, resources } => (vec![name(player), text(format!(" discarded {}", resources))], None),
+            GameEvent::RobberMoved { player, tile } => (vec![name(player), text(String::from(" moved the robber"))], Some(MapTarget::Tile(tile))),
+            GameEvent::Stole { thief, victim, resource } => (
+                vec![
+                    name(thief),
+                    text(match resource {
+                        Some(resource) => format!(" stole 1 {} from ", resource.get_name()),
+                        None => String::from(" stole a card from ")
+                    }),
+                    name(victim)
+                ],
+                None
+            ),
+            GameEvent::Built { player, piece } => {
+                let what = match piece {
+                    Piece::Road(..) => "a road",
+                    Piece::Settlement(_) => "a settlement",
+                    Piece::City(_) => "a city"
+                };
+                (vec![name(player), text(format!(" built {}", what))], Some(MapTarget::from_piece(piece)))
+            },
+            GameEvent::BoughtDevelopment { player, card } => (
+                vec![
+                    name(player),
+                    text(match card {
+                        Some(card) => format!(" bought a {} card", card.get_name()),
+                        None => String::from(" bought a development card")
+                    })
+                ],
+                None
+            ),
+            GameEvent::PlayedDevelopment { player, card } => (vec![name(player), text(format!(" played {}", card.get_name()))], None),
+            GameEvent::Monopolized { player, resource, count } => (
+                vec![name(player), text(format!(" took {} {} with a monopoly", count, resource.get_name()))],
+                None
+            ),
+            GameEvent::TradedWithBank { player, gave, got } => (vec![name(player), text(format!(" traded {} with the bank for {}", gave, got))], None),
+            GameEvent::TradeOffered { player, to, give, get } => {
+                let mut spans = vec![name(player), text(format!(" offers {} for {}", give, get))];
+                if let Some(to) = to {
+                    spans.extend([text(String::from(" to ")), name(to)]);
+                }
+                (spans, None)
+            },
+            GameEvent::TradeDeclined { player } => (vec![name(player), text(String::from(" declined the trade"))], None),
+            GameEvent::TradeCanceled { player } => (vec![name(player), text(String::from("'s offer is off the table"))], None),
+            GameEvent::Traded { from, to, gave, got } => (
+                vec![name(from), text(format!(" traded {} to ", gave)), name(to), text(format!(" for {}", got))],
+                None
+            ),
+            GameEvent::LongestRoad { player: Some(player) } => (vec![name(player), text(String::from(" has the longest road"))], None),
+            GameEvent::LongestRoad { player: None } => (vec![text(String::from("nobody has the longest road"))], None),
+            GameEvent::LargestArmy { player: Some(player) } => (vec![name(player), text(String::from(" has the largest army"))], None),
+            GameEvent::LargestArmy { player: None } => (vec![text(String::from("nobody has the largest army"))], None),
+            GameEvent::Won { player } => (vec![name(player), text(String::from(" won the game!"))], None),
+            GameEvent::Undone { player, action } => {
+                let names: Vec<String> = (0..self.players.frames.len()).map(|player| self.get_player_name(player).to_owned()).collect();
+                (vec![name(player), text(format!(" took back \"{}\"", notation::format(&action, &names)))], None)
+            }
+        };
+
+        self.push_feed(spans, target);
+    }
+
+    /* tell the feed who started or stopped watching */
+    pub fn set_observers(&mut self, observers: Vec<String>) {
+        let joined: Vec<String> = observers.iter().filter(|name| !self.observers.contains(name)).cloned().collect();
+        let left: Vec<String> = self.observers.iter().filter(|name| !observers.contains(name)).cloned().collect();
+        for (names, what) in [(joined, "started watching"), (left, "stopped watching")] {
+            for name in names {
+                self.push_feed(vec![(format!("{} (observer) {}", name, what), *EVENT_NOTE_STYLE)], None);
+            }
+        }
+        self.observers = observers;
+    }
+
+    fn push_feed(&mut self, spans: Vec<(String, Style)>, target: Option<MapTarget>) {
+        self.events.push(FeedEntry { time: self.started.elapsed(), spans, target });
+    }
+
+    fn get_name_span(&self, player: PlayerId) -> (String, Style) {
+        match self.players.frames.get(player) {
+            Some(frame) => (frame.get_name().to_owned(), Style::default().fg(frame.get_color()).add_modifier(Modifier::BOLD)),
+            None => (format!("player {}", player + 1), *EVENT_TEXT_STYLE)
+        }
+    }
+
+    /* 
+     * the piece being placed, either picked with a key or the only thing that can be built right now
+     * like the free settlements and roads at the start of the game
+     */
+    fn get_build_mode(&self) -> Option<BuildMode> {
+        self.model.seat?;
+        if self.build_mode.is_some() {
+            return self.build_mode
+        }
+
+        let mut modes = self.model.legal_actions.iter().map(|action| match action {
+            Action::BuildRoad(_, _) => Some(BuildMode::Road),
+            Action::BuildSettlement(_) => Some(BuildMode::Settlement),
+            Action::BuildCity(_) => Some(BuildMode::City),
+            Action::MoveRobber(_) => Some(BuildMode::Robber),
+            _ => None
+        });
+        let first = modes.next()??;
+        modes.all(|mode| mode == Some(first)).then_some(first)
+    }
+
+    /* the spots the legal actions let the player build the piece on or move the robber to */
+    fn get_legal_targets(&self, mode: BuildMode) -> Vec<MapTarget> {
+        self.model.legal_actions
+            .iter()
+            .filter_map(|action| match (mode, action) {
+                (BuildMode::Road, &Action::BuildRoad(a, b)) => Some(MapTarget::Road(a, b)),
+                (BuildMode::Settlement, &Action::BuildSettlement(plot)) | (BuildMode::City, &Action::BuildCity(plot)) => Some(MapTarget::Plot(plot)),
+                (BuildMode::Robber, &Action::MoveRobber(tile)) => Some(MapTarget::Tile(tile)),
+                _ => None
+            })
+            .collect()
+    }
+
+    /* the spot of the map at the point for the piece, outside of a build mode plots win over roads */
+    fn hit_test(&self, mode: Option<BuildMode>, point: Point2D) -> Option<MapTarget> {
+        let map = &self.map_dragger.drawing;
+        match mode {
+            Some(BuildMode::Road) => map.hit_test_road(point).map(|(a, b)| MapTarget::Road(a, b)),
+            Some(BuildMode::Robber) => map.hit_test_tile(point).map(MapTarget::Tile),
+            Some(_) => map.hit_test_plot(point).map(MapTarget::Plot),
+            None => map.hit_test(point)
+        }
+    }
+
+    /* 
+     * the build action for clicking part of the map, clicking your own settlement upgrades it
+     * and clicking a tile moves the robber there when it is being moved
+     * anything else is left for the server to reject so the player hears why
+     */
+    fn get_build_action(&self, mode: Option<BuildMode>, target: MapTarget) -> Option<Action> {
+        match (mode, target) {
+            (Some(BuildMode::City), MapTarget::Plot(plot)) => Some(Action::BuildCity(plot)),
+            (None, MapTarget::Plot(plot)) if self.model.legal_actions.contains(&Action::BuildCity(plot)) => Some(Action::BuildCity(plot)),
+            (_, MapTarget::Plot(plot)) => Some(Action::BuildSettlement(plot)),
+            (_, MapTarget::Road(a, b)) => Some(Action::BuildRoad(a, b)),
+            (_, MapTarget::Tile(tile)) => Some(Action::MoveRobber(tile)).filter(|action| self.model.legal_actions.contains(action))
+        }
+    }
+
+    /* picking the piece being built again puts it away */
+    fn toggle_build_mode(&mut self, mode: BuildMode) -> bool {
+        if self.model.seat.is_none() {
+            return false
+        }
+
+        self.build_mode = if self.build_mode == Some(mode) { None } else { Some(mode) };
+        true
+    }
+
+    /* actions from the keyboard go to the server as is, it says so if they can't be done right now */
+    fn act(&mut self, action: Action) -> bool {
+        if self.model.seat.is_none() {
+            return false
+        }
+
+        self.outbox.push(ClientMessage::Act(action));
+        true
+    }
+
+    /* the server turns these down for anyone but the host */
+    fn act_as_host(&mut self, message: ClientMessage) -> bool {
+        if self.model.seat.is_none() {
+            return false
+        }
+
+        self.outbox.push(message);
+        true
+    }
+
+    /* open the command line with the text already typed, spectators have nothing to type */
+    fn open_command_line(&mut self, text: &str) -> bool {
+        if self.model.seat.is_none() {
+            return false
+        }
+
+        self.command_line.open(text);
+        true
+    }
+
+    /* write the board as it is now next to where the game was started from, for sharing it */
+    fn export_board(&mut self) -> bool {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        let path = format!("board-{}.svg", time);
+        self.chat.push(ChatLine::system(match map::export_board(&self.map_dragger.drawing, Path::new(&path)) {
+            Ok(()) => format!("saved the board to {}", path),
+            Err(err) => format!("couldn't save the board to {}: {}", path, err)
+        }));
+        true
+    }
+
+    /* the play a card dialog only opens if there is a card to play */
+    fn open_development_dialog(&mut self) -> bool {
+        if self.model.seat.is_none() || self.get_playable_developments().is_empty() {
+            return false
+        }
+
+        self.wants_development_dialog = true;
+        true
+    }
+
+    /* the cards the legal actions let the seat play, victory points are never played */
+    fn get_playable_developments(&self) -> Vec<DevelopmentCard> {
+        DevelopmentCard::ALL
+            .into_iter()
+            .filter(|&card| self.model.legal_actions.iter().any(|action| matches!(
+                (card, action),
+                (DevelopmentCard::Knight, Action::PlayKnight)
+                | (DevelopmentCard::RoadBuilding, Action::PlayRoadBuilding)
+                | (DevelopmentCard::YearOfPlenty, Action::PlayYearOfPlenty(..))
+                | (DevelopmentCard::Monopoly, Action::PlayMonopoly(_))
+            )))
+            .collect()
+    }
+
+    fn get_steal_victims(&self) -> Vec<PlayerId> {
+        self.model.legal_actions
+            .iter()
+            .filter_map(|action| match *action {
+                Action::Steal(victim) => Some(victim),
+                _ => None
+            })
+            .collect()
+    }
+
+    /* the trade on the table if the seat gets a say in it */
+    fn get_open_offer(&self) -> Option<&TradeOffer> {
+        self.model.offer.as_ref().filter(|_| self.model.legal_actions.contains(&Action::DeclineTrade))
+    }
+
+    /* if the game is still asking what the dialog asks */
+    fn is_dialog_current(&self, dialog: &GameDialog) -> bool {
+        match dialog {
+            GameDialog::Discard(count) => self.model.discard_count == *count,
+            GameDialog::Steal(victims) => self.get_steal_victims() == *victims,
+            GameDialog::Offer(offer) => self.get_open_offer() == Some(offer),
+            GameDialog::Development(cards) => self.get_playable_developments() == *cards,
+            GameDialog::YearOfPlenty => self.get_playable_developments().contains(&DevelopmentCard::YearOfPlenty),
+            GameDialog::Monopoly => self.get_playable_developments().contains(&DevelopmentCard::Monopoly)
+        }
+    }
+
+    /* dialogs the game can't go on without an answer to */
+    fn is_dialog_required(dialog: &GameDialog) -> bool {
+        matches!(dialog, GameDialog::Discard(_) | GameDialog::Steal(_))
+    }
+
+    /* the dialogs that open on their own because the game is waiting on the seat */
+    fn get_prompted_dialogs(&self) -> Vec<GameDialog> {
+        let mut dialogs = Vec::new();
+        if self.model.discard_count > 0 {
+            dialogs.push(GameDialog::Discard(self.model.discard_count));
+        }
+        let victims = self.get_steal_victims();
+        if !victims.is_empty() {
+            dialogs.push(GameDialog::Steal(victims));
+        }
+        if let Some(offer) = self.get_open_offer() {
+            dialogs.push(GameDialog::Offer(offer.clone()));
+        }
+        dialogs
+    }
+
+    fn create_dialog(&self, key: GameDialog) -> Dialog<GameDialog> {
+        let hand = self.model.seat.and_then(|seat| self.model.players[seat].hand).unwrap_or_default();
+        let is_required = Self::is_dialog_required(&key);
+        let dialog = match key.clone() {
+            GameDialog::Discard(count) => Resource::ALL
+                .iter()
+                .fold(
+                    Dialog::new(key, "Discard").line(format!("pick {} cards to give up to the robber", count)),
+                    |dialog, &resource| dialog.counter(resource.get_name(), hand.get(resource))
+                )
+                .total(count as u16)
+                .total_button("discard"),
+            GameDialog::Steal(victims) => victims
+                .iter()
+                .fold(
+                    Dialog::new(key, "Steal").line(String::from("pick who to take a card from")),
+                    |dialog, &victim| dialog.button(self.get_player_name(victim), true)
+                ),
+            GameDialog::Offer(offer) => Dialog::new(key, "Trade")
+                .line(format!("{} offers {}", self.get_player_name(offer.from), offer.give))
+                .line(format!("for {}", offer.get))
+                .button("accept", self.model.legal_actions.contains(&Action::AcceptTrade))
+                .button("decline", true),
+            GameDialog::Development(cards) => cards
+                .iter()
+                .fold(
+                    Dialog::new(key, "Play a card").line(String::from("pick a development card to play")),
+                    |dialog, card| dialog.button(card.get_name(), true)
+                ),
+            GameDialog::YearOfPlenty => Resource::ALL
+                .iter()
+                .fold(
+                    Dialog::new(key, "Year of plenty").line(String::from("pick 2 cards to take from the bank")),
+                    |dialog, &resource| dialog.counter(resource.get_name(), 2)
+                )
+                .total(2)
+                .total_button("take"),
+            GameDialog::Monopoly => Resource::ALL
+                .iter()
+                .fold(
+                    Dialog::new(key, "Monopoly").line(String::from("pick a resource to take from everyone")),
+                    |dialog, &resource| dialog.button(resource.get_name(), true)
+                )
+        };
+        if is_required { dialog.required() } else { dialog }
+    }
+
+    /* turn a pressed button into an action, the cards that need more picking open another dialog */
+    fn answer_dialog(&mut self, key: GameDialog, button: usize, counts: &[u8], anim_service: &mut AnimationService) {
+        let picked: Vec<Resource> = Resource::ALL
+            .iter()
+            .zip(counts)
+            .flat_map(|(&resource, &count)| std::iter::repeat_n(resource, count as usize))
+            .collect();
+        match key {
+            GameDialog::Discard(_) => {
+                let mut hand = Hand::default();
+                for &resource in picked.iter() {
+                    hand.add(resource, 1);
+                }
+                self.act(Action::Discard(hand));
+            },
+            GameDialog::Steal(victims) => {
+                if let Some(&victim) = victims.get(button) {
+                    self.act(Action::Steal(victim));
+                }
+            },
+            GameDialog::Offer(_) => {
+                self.act(if button == 0 { Action::AcceptTrade } else { Action::DeclineTrade });
+            },
+            GameDialog::Development(cards) => match cards.get(button) {
+                Some(DevelopmentCard::Knight) => { self.act(Action::PlayKnight); },
+                Some(DevelopmentCard::RoadBuilding) => { self.act(Action::PlayRoadBuilding); },
+                Some(DevelopmentCard::YearOfPlenty) => self.dialogs.open(self.create_dialog(GameDialog::YearOfPlenty), anim_service),
+                Some(DevelopmentCard::Monopoly) => self.dialogs.open(self.create_dialog(GameDialog::Monopoly), anim_service),
+                _ => ()
+            },
+            GameDialog::YearOfPlenty => {
+                if let [a, b] = picked[..] {
+                    self.act(Action::PlayYearOfPlenty(a, b));
+                }
+            },
+            GameDialog::Monopoly => {
+                if let Some(&resource) = Resource::ALL.get(button) {
+                    self.act(Action::PlayMonopoly(resource));
+                }
+            }
+        }
+    }
+
+    /* 
+     * act on answered dialogs, close the ones the game stopped asking about
+     * and open the ones it started asking about that weren't answered already
+     */
+    fn relayout_dialogs(&mut self, anim_service: &mut AnimationService) {
+        for result in self.dialogs.take_results() {
+            self.answered_dialogs.push(result.key.clone());
+            if let Some(button) = result.button {
+                self.answer_dialog(result.key, button, &result.counts, anim_service);
+            }
+        }
+
+        for key in self.dialogs.get_keys() {
+            if !self.is_dialog_current(&key) {
+                self.dialogs.close(&key, anim_service);
+            }
+        }
+        let answered = std::mem::take(&mut self.answered_dialogs);
+        self.answered_dialogs = answered.into_iter().filter(|key| self.is_dialog_current(key)).collect();
+
+        if std::mem::take(&mut self.wants_development_dialog) {
+            let cards = self.get_playable_developments();
+            let key = GameDialog::Development(cards.clone());
+            if !cards.is_empty() && !self.dialogs.is_open(&key) {
+                self.dialogs.open(self.create_dialog(key), anim_service);
+            }
+        }
+        for key in self.get_prompted_dialogs() {
+            if !self.dialogs.is_open(&key) && !self.answered_dialogs.contains(&key) {
+                self.dialogs.open(self.create_dialog(key), anim_service);
+            }
+        }
+    }
+
+    /* run a command from the keymap, returns false if it doesn't apply right now so the next command on the chord gets a try */
+    fn run_command(&mut self, command: Command) -> bool {
+        match command {
+            Command::Cancel if self.stats.is_open() => {
+                self.stats.set_open(false);
+                true
+            },
+            Command::Cancel => self.build_mode.take().is_some(),
+            Command::Roll => self.act(Action::Roll),
+            Command::EndTurn => self.act(Action::EndTurn),
+            Command::BuyDevelopment => self.act(Action::BuyDevelopment),
+            Command::Undo => self.act(Action::Undo),
+            Command::BuildRoad => self.toggle_build_mode(BuildMode::Road),
+            Command::BuildSettlement => self.toggle_build_mode(BuildMode::Settlement),
+            Command::BuildCity => self.toggle_build_mode(BuildMode::City),
+            Command::OpenTrade => self.open_command_line("trade "),
+            Command::OpenCommandLine => self.open_command_line(""),
+            Command::PlayDevelopment => self.open_development_dialog(),
+            Command::ExportBoard => self.export_board(),
+            Command::SaveGame => self.act_as_host(ClientMessage::SaveGame),
+            Command::ToggleStats => {
+                self.stats.set_open(!self.stats.is_open());
+                true
+            },
+            Command::OpenChat => {
+                self.chat.open();
+                true
+            },
+            Command::PanUp => self.map_dragger.pan(Point2D::new(0, -1)),
+            Command::PanDown => self.map_dragger.pan(Point2D::new(0, 1)),
+            Command::PanLeft => self.map_dragger.pan(Point2D::new(-1, 0)),
+            Command::PanRight => self.map_dragger.pan(Point2D::new(1, 0)),
+            Command::ZoomIn => self.map_dragger.zoom(-1),
+            Command::ZoomOut => self.map_dragger.zoom(1),
+            /* quitting and going through a replay are up to whoever runs the screen */
+            Command::Quit
+                | Command::StepForward
+                | Command::StepBack
+                | Command::NextTurn
+                | Command::PreviousTurn
+                | Command::GoToTurn
+                | Command::TogglePlayback
+                | Command::PlayFaster
+                | Command::PlaySlower => false
+        }
+    }
+
+    fn get_map_title(&self) -> String {
+        match self.map_dragger.get_scale() {
+            1 => String::from(" Map "),
+            scale => format!(" Map 1:{} ", scale)
+        }
+    }
+
+    /* what is being placed and how to stop, along the bottom of the map */
+    fn get_build_hint(&self) -> Option<String> {
+        let mode = self.get_build_mode().filter(|_| !self.stats.is_open())?;
+        let doing = match mode {
+            BuildMode::Robber => String::from("moving the robber"),
+            _ => format!("placing a {}", mode.get_name())
+        };
+        let hint = match self.build_mode.and(self.keymap.get_chord(Command::Cancel)) {
+            Some(chord) => format!(" {}, click a bright spot or press {} to stop ", doing, chord),
+            None => format!(" {}, click a bright spot ", doing)
+        };
+        let width = self.regions.map.size.x.saturating_sub(4) as usize;
+        Some(hint.chars().take(width).collect())
+    }
+
+    /* who is watching, above the players */
+    fn get_observers_line(&self) -> Option<String> {
+        if self.observers.is_empty() || self.stats.is_open() {
+            return None
+        }
+        let observers = format!("watching: {}", self.observers.join(", "));
+        let width = self.regions.players.size.x.saturating_sub(4) as usize;
+        Some(observers.chars().take(width).collect())
+    }
+
+    /* 
+     * redraw the parts of the chrome that changed, the panes moving around redraws everything
+     * borders are redrawn along their edges and the banner and observers along their line
+     */
+    fn relayout_chrome(&mut self, ctx: &mut LayoutContext) {
+        let chrome = GameChrome {
+            regions: self.regions.clone(),
+            map_border: (self.get_map_title(), self.map_dragger.drawing.is_focused(), self.get_build_hint()),
+            events_border: self.events.is_focused(),
+            chat_border: self.chat.is_focused(),
+            banner: self.banner.clone(),
+            observers: self.get_observers_line()
+        };
+        let drawn = std::mem::replace(&mut self.chrome, chrome.clone());
+        if chrome.regions != drawn.regions {
+            ctx.invalidate(self);
+            return
+        }
+
+        let regions = &chrome.regions;
+        let get_edges = |space: AbsoluteSpace| [
+            AbsoluteSpace::new(space.left(), space.top(), space.size.x, 1),
+            AbsoluteSpace::new(space.left(), space.bottom() - 1, space.size.x, 1),
+            AbsoluteSpace::new(space.left(), space.top(), 1, space.size.y),
+            AbsoluteSpace::new(space.right() - 1, space.top(), 1, space.size.y)
+        ];
+        let mut spaces = Vec::new();
+        if chrome.map_border != drawn.map_border {
+            spaces.extend(get_edges(regions.map));
+        }
+        if chrome.events_border != drawn.events_border {
+            spaces.extend(get_edges(regions.events));
+        }
+        if chrome.chat_border != drawn.chat_border {
+            spaces.extend(get_edges(regions.chat));
+        }
+        /* a long banner can run past the map so its whole line goes */
+        if chrome.banner != drawn.banner {
+            spaces.push(AbsoluteSpace::new(0, regions.map.top() + 1, ctx.get_absolute_size_of(self).x, 1));
+        }
+        if chrome.observers != drawn.observers {
+            spaces.push(AbsoluteSpace::new(regions.players.left(), regions.players.top() + 1, regions.players.size.x, 1));
+        }
+
+        let absolute_game_space = ctx.get_absolute_layout_space_of(self);
+        for space in spaces {
+            ctx.invalidate_space_in(self, absolute_game_space.absolute_space_of(space));
+        }
+    }
+
+    /* the dialogs answer to the same keys as the game */
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.dialogs.keymap = keymap.clone();
+        self.keymap = keymap;
+    }
+
+    /* 
+     * the server turned down something the seat sent, so what the game is still waiting on gets asked again
+     * like a discard that didn't add up, the dialogs would otherwise stay answered while the game waits
+     */
+    pub fn reprompt(&mut self) {
+        self.answered_dialogs.retain(|key| !Self::is_dialog_required(key));
+    }
+
+    /* messages for the server, drained by whoever owns the connection */
+    pub fn take_outbox(&mut self) -> Vec<ClientMessage> {
+        let mut outbox = std::mem::take(&mut self.outbox);
+        outbox.extend(self.command_line.take_outbox().into_iter().map(ClientMessage::Act));
+        outbox.extend(self.chat.take_outbox().into_iter().map(ClientMessage::Chat));
+        outbox
+    }
 }
 
 impl Layoutable for Game {
@@ -65,24 +726,26 @@ impl Layoutable for Game {
 impl StatefulDrawable for Game {
     type State = ();
     fn stateful_draw(&self, ctx: &mut DrawContext, state: &Self::State) {
+        /* the pane with keyboard focus gets a yellow border */
+        let border_style = |is_focused: bool| Style::default().fg(if is_focused { Color::Yellow } else { Color::White }).bg(Color::Black);
         ctx.draw_widget(
             Block::default()
-                .title(" Map ")
-                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .title(self.get_map_title())
+                .border_style(border_style(self.map_dragger.drawing.is_focused()))
                 .borders(Borders::ALL), 
             self.regions.map
         );
         ctx.draw_widget(
             Block::default()
                 .title(" Events ")
-                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .border_style(border_style(self.events.is_focused()))
                 .borders(Borders::ALL), 
             self.regions.events
         );
         ctx.draw_widget(
             Block::default()
                 .title(" Chat ")
-                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .border_style(border_style(self.chat.is_focused()))
                 .borders(Borders::ALL), 
             self.regions.chat
         );
@@ -95,30 +758,36 @@ impl StatefulDrawable for Game {
         );
         
         ctx.draw_stateful_child(&self.map_dragger, state);
-        ctx.draw_stateful_child(&self.players, &[
-            // TEST DATA
-            PlayerFrameState {
-                victory_point_count: 9,
-                largest_army_count: 2,
-                longest_road_count: 3,
-                resource_card_count: 7,
-                development_card_count: 0
-            },
-            PlayerFrameState {
-                victory_point_count: 10,
-                largest_army_count: 5,
-                longest_road_count: 8,
-                resource_card_count: 22,
-                development_card_count: 6
-            },
-            PlayerFrameState {
-                victory_point_count: 4,
-                largest_army_count: 0,
-                longest_road_count: 0,
-                resource_card_count: 0,
-                development_card_count: 0
-            }
-        ]);
+        ctx.draw_stateful_child(&self.minimap, state);
+        ctx.draw_stateful_child(&self.chat, state);
+        ctx.draw_stateful_child(&self.events, state);
+        ctx.draw_stateful_child(&self.players, state);
+        ctx.draw_stateful_child(&self.command_line, state);
+        ctx.draw_stateful_child(&self.stats, state);
+
+        if let Some(observers) = self.get_observers_line() {
+            ctx.draw_unicode_line(&observers, self.regions.players.position + Point2D::new(2, 1), Style::default().fg(Color::DarkGray));
+        }
+
+        if let Some(hint) = self.get_build_hint() {
+            ctx.draw_unicode_line(
+                &hint,
+                self.regions.map.position + Point2D::new(2, self.regions.map.size.y as i16 - 1),
+                Style::default().fg(Color::White).bg(Color::Black)
+            );
+        }
+
+        if let Some(banner) = self.banner.as_ref() {
+            let banner = format!(" {} ", banner);
+            ctx.draw_unicode_line(
+                &banner,
+                self.regions.map.position + Point2D::new((self.regions.map.size.x as i16 - banner.width() as i16)/2, 1),
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            );
+        }
+
+        /* dialogs go over everything */
+        ctx.draw_stateful_child(&self.dialogs, state);
     }
 }
 
@@ -128,24 +797,54 @@ impl MountableLayout for Game {
     fn child_ref(&self, i: usize) -> Option<&dyn MountableLayout> { 
         match i {
             0 => Some(self.map_dragger.as_trait_ref()),
+            1 => Some(self.chat.as_trait_ref()),
+            2 => Some(self.events.as_trait_ref()),
+            3 => Some(self.command_line.as_trait_ref()),
+            4 => Some(self.minimap.as_trait_ref()),
+            5 => Some(self.players.as_trait_ref()),
+            6 => Some(self.stats.as_trait_ref()),
+            7 => Some(self.dialogs.as_trait_ref()),
             _ => None
         } 
     }
     fn child_mut(&mut self, i: usize) -> Option<&mut dyn MountableLayout> { 
         match i {
             0 => Some(self.map_dragger.as_trait_mut()),
+            1 => Some(self.chat.as_trait_mut()),
+            2 => Some(self.events.as_trait_mut()),
+            3 => Some(self.command_line.as_trait_mut()),
+            4 => Some(self.minimap.as_trait_mut()),
+            5 => Some(self.players.as_trait_mut()),
+            6 => Some(self.stats.as_trait_mut()),
+            7 => Some(self.dialogs.as_trait_mut()),
             _ => None
         } 
     }
 
+    /* whatever the focused pane didn't want goes through the keymap */
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let commands: Vec<Command> = self.keymap.get_commands(key).collect();
+        commands.into_iter().any(|command| self.run_command(command))
+    }
+
     fn relayout(&mut self, ctx: &mut LayoutContext) {
-        let space = ctx.get_absolute_size_of(self).to_rect();
+        self.sync(&mut ctx.service.animation);
+
+        /* the command line takes the bottom row while it is open */
+        let mut space = ctx.get_absolute_size_of(self).to_rect();
+        let command_line_height = self.command_line.is_open() as u16;
+        space.height = space.height.saturating_sub(command_line_height);
+        self.regions.command_line = AbsoluteSpace::new(0, space.height as i16, space.width, command_line_height);
+        self.command_line.layout.set_size(UDim2::from_size2d(self.regions.command_line.size));
+        self.command_line.layout.set_position(UDim2::from_point2d(self.regions.command_line.position));
+
         let rects = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Length(space.width.saturating_sub(39)), Constraint::Min(39)].as_ref())
             .split(space);
 
-        let player_list_height = (self.players.frames.len() as u16)*(players::PLAYER_FRAME_SIZE.y.offset as u16) + 2;
+        /* spectators get a line above the players */
+        let player_list_height = (self.players.frames.len() as u16)*(players::PLAYER_FRAME_SIZE.y.offset as u16) + 2 + !self.observers.is_empty() as u16;
         let right_pane_rects = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Length(rects[1].height.saturating_sub(player_list_height)), Constraint::Min(player_list_height)].as_ref())
@@ -168,6 +867,77 @@ impl MountableLayout for Game {
         self.map_dragger.layout.set_size(UDim2::from_size2d(map_space.size));
         self.map_dragger.layout.set_position(UDim2::from_point2d(map_space.position));
 
+        let events_space = AbsoluteSpace::from_rect(
+            Block::default()
+                .borders(Borders::ALL)
+                .inner(self.regions.events.to_rect())
+        );
+        self.events.layout.set_size(UDim2::from_size2d(events_space.size));
+        self.events.layout.set_position(UDim2::from_point2d(events_space.position));
+
+        /* clicking an entry in the feed points the map at where it happened */
+        if let Some(target) = self.events.take_clicked() {
+            self.map_dragger.focus(target.get_center());
+            self.map_dragger.drawing.highlight(target, &mut ctx.service.animation);
+        }
+
+        /* what the command line can complete to, and a build typed into it shows up on the map like it is being placed */
+        let names: Vec<String> = (0..self.players.frames.len()).map(|player| self.get_player_name(player).to_owned()).collect();
+        self.command_line.completions = self.model.legal_actions.iter().map(|action| notation::format(action, &names)).collect();
+        self.command_line.names = names;
+        let typed = match self.command_line.get_action() {
+            Some(Action::BuildRoad(a, b)) => Some((BuildMode::Road, MapTarget::Road(a.min(b), a.max(b)))),
+            Some(Action::BuildSettlement(plot)) => Some((BuildMode::Settlement, MapTarget::Plot(plot))),
+            Some(Action::BuildCity(plot)) => Some((BuildMode::City, MapTarget::Plot(plot))),
+            Some(Action::MoveRobber(tile)) => Some((BuildMode::Robber, MapTarget::Tile(tile))),
+            _ => None
+        }.filter(|(_, target)| target.is_on_map());
+
+        /* clicking the map or pressing enter on the cursor builds there, spectators have nothing to build */
+        let mode = typed.map(|(mode, _)| mode).or_else(|| self.get_build_mode());
+        let clicked = self.map_dragger
+            .take_click()
+            .and_then(|point| self.hit_test(mode, point))
+            .or_else(|| self.map_dragger.drawing.take_selected());
+        let action = clicked
+            .filter(|_| self.model.seat.is_some())
+            .and_then(|target| self.get_build_action(mode, target));
+        if let Some(action) = action {
+            self.outbox.push(ClientMessage::Act(action));
+        }
+
+        /* the keyboard cursor sticks to what is being built and the map follows it around */
+        if let Some(mode) = mode {
+            self.map_dragger.drawing.set_cursor_kind(mode);
+        }
+        if let Some(cursor) = self.map_dragger.drawing.take_moved_cursor() {
+            self.map_dragger.reveal(cursor.get_space());
+        }
+
+        let cursor = self.map_dragger.drawing.get_cursor();
+        let preview = mode.map(|mode| BuildPreview {
+            mode,
+            legal: self.get_legal_targets(mode),
+            hover: typed.map(|(_, target)| target).or(cursor).or_else(|| self.map_dragger.get_hover().and_then(|point| self.hit_test(Some(mode), point))),
+            style: Style::default().fg(self.model.seat.map(|seat| self.get_player_color(seat)).unwrap_or(Color::White))
+        });
+        self.map_dragger.drawing.preview = preview;
+
+        /* the minimap sits in the top right of the map while the map doesn't fit, clicking it moves the map there */
+        if let Some(point) = self.minimap.take_jump() {
+            self.map_dragger.focus(point);
+        }
+        self.minimap.update(&self.map_dragger, map_space.size);
+        self.minimap.layout.set_position(UDim2::from_point2d(map_space.position + Point2D::new(map_space.size.x as i16, 0)));
+
+        let chat_space = AbsoluteSpace::from_rect(
+            Block::default()
+                .borders(Borders::ALL)
+                .inner(self.regions.chat.to_rect())
+        );
+        self.chat.layout.set_size(UDim2::from_size2d(chat_space.size));
+        self.chat.layout.set_position(UDim2::from_point2d(chat_space.position));
+
         let players_space = AbsoluteSpace::from_rect(
             Block::default()
                 .borders(Borders::ALL)
@@ -175,7 +945,99 @@ impl MountableLayout for Game {
         );
         self.players.layout.set_position(UDim2::from_point2d(players_space.position));
         self.players.layout.set_size(UDim2::from_size2d(players_space.size));
+
+        /* the stats go over everything but the command line */
+        self.stats.layout.set_size(UDim2::from_size2d(Size2D::new(space.width, space.height)));
+        self.stats.hint = match (self.keymap.get_chord(Command::ToggleStats), self.keymap.get_chord(Command::Cancel)) {
+            (Some(toggle), Some(cancel)) => format!(" {} or {} to close ", toggle, cancel),
+            (Some(chord), None) | (None, Some(chord)) => format!(" {} to close ", chord),
+            (None, None) => String::new()
+        };
+
+        self.relayout_dialogs(&mut ctx.service.animation);
         
         ctx.relayout_children_of(self.as_trait_mut());
+        self.relayout_chrome(ctx);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{screen::Screen, snapshot};
+    use crate::logic::{game::{GameState, Viewer}, board::Board, bot};
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const SEED: u64 = 7;
+    const PLAYERS: [(&str, Color); 3] = [("Ann", Color::Red), ("Bob", Color::Blue), ("Cy", Color::Green)];
+    const SIZE: Size2D = Size2D::new(120, 40);
+
+    /* bots play the setup and a few turns of a game seen from the first seat, the map settled in */
+    fn get_screen(actions: usize) -> Screen<Game> {
+        let board = Board::generate(&mut StdRng::seed_from_u64(SEED));
+        let mut state = GameState::new(board.clone(), PLAYERS.len(), 10, SEED);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for _ in 0..actions {
+            let player = state.get_actors()[0];
+            let action = bot::choose_action(&state, player, &mut rng).unwrap();
+            state.apply(player, action).unwrap();
+        }
+
+        let mut model = GameModel::new(board, PLAYERS.len());
+        model.seat = Some(0);
+        model.apply_view(&state.view_for(Viewer::Player(0)));
+
+        let mut screen = Screen::new(Game::new(model, PLAYERS.iter().map(|&(name, color)| (String::from(name), color)).collect()));
+        screen.render_to_buffer(SIZE, &mut ());
+        screen.root.map_dragger.drawing.settle(&mut screen.service.animation);
+        screen
+    }
+
+    /* 
+     * the first settlement is the only thing that can be built so it is picked without asking
+     * the map marks where it can go and picking something else marks where that can go instead
+     */
+    #[test]
+    fn setup_marks_where_settlements_go() {
+        let mut screen = get_screen(0);
+        let plots: Vec<MapTarget> = screen.root.model.legal_actions
+            .iter()
+            .filter_map(|action| match action {
+                &Action::BuildSettlement(plot) => Some(MapTarget::Plot(plot)),
+                _ => None
+            })
+            .collect();
+        assert!(!plots.is_empty());
+        assert_eq!(screen.root.get_build_mode(), Some(BuildMode::Settlement));
+
+        let get_preview = |screen: &mut Screen<Game>| {
+            screen.render_to_buffer(SIZE, &mut ());
+            screen.root.map_dragger.drawing.preview.clone().map(|preview| (preview.mode, preview.legal, preview.style.fg))
+        };
+        assert_eq!(get_preview(&mut screen), Some((BuildMode::Settlement, plots.clone(), Some(Color::Red))));
+
+        assert!(screen.root.toggle_build_mode(BuildMode::Road));
+        assert_eq!(get_preview(&mut screen), Some((BuildMode::Road, Vec::new(), Some(Color::Red))));
+        assert!(screen.root.toggle_build_mode(BuildMode::Road));
+        assert_eq!(get_preview(&mut screen).map(|(mode, _, _)| mode), Some(BuildMode::Settlement));
+
+        /* picking a city builds one on the plot, tiles are only clicked to move the robber */
+        assert_eq!(screen.root.get_build_action(Some(BuildMode::City), MapTarget::Plot(3)), Some(Action::BuildCity(3)));
+        assert_eq!(screen.root.get_build_action(None, MapTarget::Plot(3)), Some(Action::BuildSettlement(3)));
+        assert_eq!(screen.root.get_build_action(None, MapTarget::Tile(0)), None);
+
+        /* spectators have nothing to build */
+        screen.root.model.seat = None;
+        assert!(!screen.root.toggle_build_mode(BuildMode::City));
+        assert_eq!(screen.root.get_build_mode(), None);
+        assert_eq!(get_preview(&mut screen), None);
     }
-}
\ No newline at end of file
+
+    /* the whole game screen, panes, borders and all */
+    #[test]
+    fn game() {
+        let mut screen = get_screen(20);
+        let buf = screen.render_to_buffer(SIZE, &mut ());
+        snapshot::assert_snapshot("render/drawing/snapshots", "game", &buf);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::render::prelude::*;
+use crate::logic::{lobby::*, clock::{TimeSetting, format_time}};
+use crate::net::protocol::ClientMessage;
+use crate::enums::PlayerColor;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders}
+};
+use unicode_width::UnicodeWidthStr;
+
+use std::time::Duration;
+
+const LOBBY_PANEL_SIZE: Size2D = Size2D::new(64, 21);
+
+const SEAT_ROW_Y: i16 = 2;
+const SEAT_NAME_X: i16 = 7;
+const SEAT_COLOR_X: i16 = 29;
+const SEAT_STATUS_X: i16 = 39;
+const SEAT_BUTTON_X: i16 = 50;
+const OBSERVERS_ROW_Y: i16 = 6;
+const OPTIONS_ROW_Y: i16 = 7;
+const NAME_ROW_Y: i16 = 13;
+const COLOR_ROW_Y: i16 = 14;
+const READY_ROW_Y: i16 = 16;
+const STATUS_ROW_Y: i16 = 18;
+
+/* the rows of the time control options under the victory points */
+const TIME_SETTINGS: [TimeSetting; 3] = [TimeSetting::Turn, TimeSetting::Game, TimeSetting::Increment];
+
+lazy_static! {
+    static ref BUTTON_STYLE: Style = Style::default().fg(Color::Black).bg(Color::White);
+    static ref TEXT_STYLE: Style = Style::default().fg(Color::White);
+    static ref DIM_STYLE: Style = Style::default().fg(Color::DarkGray);
+    static ref HEADER_STYLE: Style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+}
+
+/* everything in the lobby that can be clicked */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LobbyButton {
+    Kick(usize),
+    AddBot,
+    ShuffleMap,
+    FewerPoints,
+    MorePoints,
+    LessTime(TimeSetting),
+    MoreTime(TimeSetting),
+    EditName,
+    Color(PlayerColor),
+    Ready
+}
+
+/*
+ * LobbyView:
+ * draws the lobby the server last told us about and turns clicks and key presses into messages for the server
+ * messages pile up in an outbox that whoever owns the connection drains
+ * the owner sets what the server said straight on the view, so the view compares it with what it last drew to know when to redraw
+ */
+#[derive(Debug)]
+pub struct LobbyView {
+    pub lobby: Lobby,
+    pub client: Option<ClientId>,
+    pub status: String,
+    pub observers: Vec<String>,
+    name_input: Option<String>,
+    outbox: Vec<ClientMessage>,
+    drawn: Option<(Lobby, Option<ClientId>, String, Vec<String>)>,
+    is_damaged: bool,
+    absolute_space: AbsoluteSpace,
+    layout: DrawLayout,
+    mount: Mount
+}
+
+impl LobbyView {
+    pub fn new() -> Self {
+        LobbyView {
+            lobby: Lobby::new(GameOptions::new(0)),
+            client: None,
+            status: String::from("connecting..."),
+            observers: Vec::new(),
+            name_input: None,
+            outbox: Vec::new(),
+            drawn: None,
+            is_damaged: false,
+            absolute_space: AbsoluteSpace::default(),
+            layout: DrawLayout::FULL,
+            mount: Mount::default()
+        }
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<ClientMessage> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    fn my_seat(&self) -> Option<&Seat> {
+        self.client
+            .and_then(|client| self.lobby.seat_of(client))
+            .and_then(|index| self.lobby.seats[index].as_ref())
+    }
+
+    fn is_host(&self) -> bool {
+        self.client.is_some() && self.lobby.host == self.client
+    }
+
+    fn get_panel_origin(size: Size2D) -> Point2D {
+        Point2D::new(
+            (size.x.saturating_sub(LOBBY_PANEL_SIZE.x)/2) as i16,
+            (size.y.saturating_sub(LOBBY_PANEL_SIZE.y)/2) as i16
+        )
+    }
+
+    /* buttons with their label and position relative to the panel */
+    fn get_buttons(&self) -> Vec<(Point2D, String, LobbyButton)> {
+        let mut buttons = Vec::new();
+        let is_host = self.is_host();
+
+        if is_host {
+            for (i, seat) in self.lobby.seats.iter().enumerate() {
+                let position = Point2D::new(SEAT_BUTTON_X, SEAT_ROW_Y + i as i16);
+                match seat {
+                    Some(seat) if seat.occupant != Occupant::Client(self.client.unwrap()) =>
+                        buttons.push((position, String::from(" kick "), LobbyButton::Kick(i))),
+                    None => buttons.push((position, String::from(" + bot "), LobbyButton::AddBot)),
+                    _ => ()
+                }
+            }
+
+            buttons.push((Point2D::new(30, OPTIONS_ROW_Y), String::from(" shuffle "), LobbyButton::ShuffleMap));
+            buttons.push((Point2D::new(53, OPTIONS_ROW_Y + 1), String::from(" - "), LobbyButton::FewerPoints));
+            buttons.push((Point2D::new(57, OPTIONS_ROW_Y + 1), String::from(" + "), LobbyButton::MorePoints));
+            for (i, &setting) in TIME_SETTINGS.iter().enumerate() {
+                let y = OPTIONS_ROW_Y + 2 + i as i16;
+                buttons.push((Point2D::new(53, y), String::from(" - "), LobbyButton::LessTime(setting)));
+                buttons.push((Point2D::new(57, y), String::from(" + "), LobbyButton::MoreTime(setting)));
+            }
+        }
+
+        if let Some(seat) = self.my_seat() {
+            if self.name_input.is_none() {
+                buttons.push((Point2D::new(14, NAME_ROW_Y), format!(" {} ", seat.name), LobbyButton::EditName));
+            }
+
+            for (i, &color) in PlayerColor::ALL.iter().enumerate() {
+                buttons.push((Point2D::new(14 + 10*i as i16, COLOR_ROW_Y), format!(" {:<6} ", color.get_name()), LobbyButton::Color(color)));
+            }
+
+            buttons.push((
+                Point2D::new(2, READY_ROW_Y),
+                String::from(if seat.is_ready { " ready! (click to unready) " } else { " click when ready " }),
+                LobbyButton::Ready
+            ));
+        }
+
+        buttons
+    }
+
+    fn press(&mut self, button: LobbyButton) {
+        let message = match button {
+            LobbyButton::Kick(seat) => ClientMessage::Kick(seat),
+            LobbyButton::AddBot => ClientMessage::AddBot,
+            LobbyButton::ShuffleMap => ClientMessage::SetOptions(GameOptions { map_seed: rand::random(), ..self.lobby.options }),
+            LobbyButton::FewerPoints => ClientMessage::SetOptions(GameOptions {
+                victory_points: self.lobby.options.victory_points.saturating_sub(1).max(MIN_VICTORY_POINTS),
+                ..self.lobby.options
+            }),
+            LobbyButton::MorePoints => ClientMessage::SetOptions(GameOptions {
+                victory_points: (self.lobby.options.victory_points + 1).min(MAX_VICTORY_POINTS),
+                ..self.lobby.options
+            }),
+            LobbyButton::LessTime(setting) => ClientMessage::SetOptions(GameOptions {
+                time_control: self.lobby.options.time_control.step(setting, false),
+                ..self.lobby.options
+            }),
+            LobbyButton::MoreTime(setting) => ClientMessage::SetOptions(GameOptions {
+                time_control: self.lobby.options.time_control.step(setting, true),
+                ..self.lobby.options
+            }),
+            LobbyButton::EditName => {
+                self.name_input = self.my_seat().map(|seat| seat.name.clone());
+                return
+            },
+            LobbyButton::Color(color) => ClientMessage::SetColor(color),
+            LobbyButton::Ready => ClientMessage::SetReady(!self.my_seat().map(|seat| seat.is_ready).unwrap_or(false))
+        };
+
+        self.outbox.push(message);
+    }
+}
+
+impl Layoutable for LobbyView {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for LobbyView {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let origin = Self::get_panel_origin(ctx.absolute_layout_space.size);
+        ctx.draw_widget(
+            Block::default()
+                .title(" Lobby ")
+                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .borders(Borders::ALL),
+            AbsoluteSpace { position: origin, size: LOBBY_PANEL_SIZE }
+        );
+
+        ctx.draw_string_line("Seats", origin + Point2D::new(2, SEAT_ROW_Y - 1), *HEADER_STYLE);
+        for (i, seat) in self.lobby.seats.iter().enumerate() {
+            let y = SEAT_ROW_Y + i as i16;
+            ctx.draw_string_line(&format!("{}.", i + 1), origin + Point2D::new(2, y), *TEXT_STYLE);
+            match seat {
+                Some(seat) => {
+                    if matches!(seat.occupant, Occupant::Client(id) if self.lobby.host == Some(id)) {
+                        ctx.draw_unicode_line("★", origin + Point2D::new(5, y), Style::default().fg(Color::Yellow));
+                    }
+                    ctx.draw_unicode_line(
+                        &seat.name,
+                        origin + Point2D::new(SEAT_NAME_X, y),
+                        Style::default().fg(seat.color.get_color()).add_modifier(Modifier::BOLD)
+                    );
+                    ctx.draw_string_line("  ", origin + Point2D::new(SEAT_COLOR_X, y), Style::default().bg(seat.color.get_color()));
+                    ctx.draw_string_line(seat.color.get_name(), origin + Point2D::new(SEAT_COLOR_X + 3, y), *TEXT_STYLE);
+                    let (status, style) = match (seat.occupant, seat.is_ready) {
+                        (Occupant::Bot, _) => ("bot", *DIM_STYLE),
+                        (_, true) => ("ready", Style::default().fg(Color::Green)),
+                        (_, false) => ("waiting", Style::default().fg(Color::Yellow))
+                    };
+                    ctx.draw_string_line(status, origin + Point2D::new(SEAT_STATUS_X, y), style);
+                },
+                None => ctx.draw_string_line("(empty)", origin + Point2D::new(SEAT_NAME_X, y), *DIM_STYLE)
+            }
+        }
+
+        if !self.observers.is_empty() {
+            let observers = format!("Observers: {}", self.observers.join(", "));
+            let observers: String = observers.chars().take(LOBBY_PANEL_SIZE.x as usize - 4).collect();
+            ctx.draw_unicode_line(&observers, origin + Point2D::new(2, OBSERVERS_ROW_Y), *DIM_STYLE);
+        }
+
+        ctx.draw_string_line(
+            &format!("Map seed: {}", self.lobby.options.map_seed),
+            origin + Point2D::new(2, OPTIONS_ROW_Y),
+            *TEXT_STYLE
+        );
+        ctx.draw_string_line(
+            &format!("Victory points to win: {}", self.lobby.options.victory_points),
+            origin + Point2D::new(2, OPTIONS_ROW_Y + 1),
+            *TEXT_STYLE
+        );
+        let time = |seconds: Option<u16>| seconds.map_or_else(|| String::from("off"), |seconds| format_time(Duration::from_secs(seconds as u64)));
+        let time_control = &self.lobby.options.time_control;
+        for (i, setting) in TIME_SETTINGS.iter().enumerate() {
+            let (text, is_used) = match setting {
+                TimeSetting::Turn => (format!("Time for each turn: {}", time(time_control.turn_time)), true),
+                TimeSetting::Game => (format!("Time for the whole game: {}", time(time_control.game_time)), true),
+                /* the increment only goes on the game clock */
+                TimeSetting::Increment => (
+                    format!("Added after each turn: {}s", time_control.increment),
+                    time_control.game_time.is_some()
+                )
+            };
+            ctx.draw_string_line(&text, origin + Point2D::new(2, OPTIONS_ROW_Y + 2 + i as i16), if is_used { *TEXT_STYLE } else { *DIM_STYLE });
+        }
+
+        if self.my_seat().is_some() {
+            ctx.draw_string_line("Your name:", origin + Point2D::new(2, NAME_ROW_Y), *TEXT_STYLE);
+            ctx.draw_string_line("Your color:", origin + Point2D::new(2, COLOR_ROW_Y), *TEXT_STYLE);
+            if let Some(input) = self.name_input.as_ref() {
+                let field = format!(" {}_ ", input);
+                ctx.draw_unicode_line(&field, origin + Point2D::new(14, NAME_ROW_Y), *BUTTON_STYLE);
+                ctx.draw_string_line("(enter to save)", origin + Point2D::new(16 + field.width() as i16, NAME_ROW_Y), *DIM_STYLE);
+            }
+        }
+
+        for (position, label, button) in self.get_buttons() {
+            let style = match button {
+                LobbyButton::Color(color) => Style::default().fg(Color::Black).bg(color.get_color()),
+                _ => *BUTTON_STYLE
+            };
+            ctx.draw_unicode_line(&label, origin + position, style);
+        }
+
+        ctx.draw_unicode_line(&self.status, origin + Point2D::new(2, STATUS_ROW_Y), *DIM_STYLE);
+        ctx.draw_string_line(
+            "the game starts when everyone is ready, esc to quit",
+            origin + Point2D::new(2, STATUS_ROW_Y + 1),
+            *DIM_STYLE
+        );
+    }
+}
+
+impl MountableLayout for LobbyView {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        self.absolute_space = ctx.get_absolute_layout_space_of(self);
+        ctx.relayout_input_space_of(self, Space::FULL);
+
+        let shown = (self.lobby.clone(), self.client, self.status.clone(), self.observers.clone());
+        if self.drawn.as_ref() != Some(&shown) {
+            self.drawn = Some(shown);
+            self.is_damaged = true;
+        }
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+
+    /* key presses go to the name field while it is being edited, returns true if the lobby needs to be drawn again */
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        let input = match self.name_input.as_mut() {
+            Some(input) => input,
+            None => return false
+        };
+
+        match key.code {
+            KeyCode::Char(c) => {
+                if input.chars().count() < MAX_NAME_LENGTH {
+                    input.push(c);
+                }
+            },
+            KeyCode::Backspace => {
+                input.pop();
+            },
+            KeyCode::Enter => {
+                self.outbox.push(ClientMessage::SetName(input.clone()));
+                self.name_input = None;
+            },
+            _ => return false
+        }
+
+        self.is_damaged = true;
+        true
+    }
+
+    fn on_mouse_input(&mut self, event: InputEvent) -> bool {
+        if let InputEventKind::Click(point) = event.kind {
+            let point = self.absolute_space.relative_position_of(point) - Self::get_panel_origin(self.absolute_space.size);
+            let pressed = self.get_buttons()
+                .into_iter()
+                .find(|(position, label, _)| point.y == position.y && point.x >= position.x && point.x < position.x + label.width() as i16)
+                .map(|(_, _, button)| button);
+
+            if let Some(button) = pressed {
+                self.press(button);
+                self.is_damaged = true;
+                return true
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * export.rs
+ * the board as a standalone svg (or an html page around one) for sharing it outside of the terminal
+ *
+ * everything is placed from MAP_GRAPH in map coordinates, a cell of the map becomes a box twice as tall as it is wide
+ * so the board keeps the shape it has in the terminal, tiles are the hexagons their plots make
+ */
+
+use super::{Map, parse::MAP_GRAPH, parse::MAP_BKG_DRAW_STRING, TILE_SIZE, MAP_OCEAN_COLOR, MAP_SAND_COLOR, PORT_COLOR};
+
+use crate::render::space::Point2D;
+use crate::enums::{self, TileResource};
+
+use tui::style::Color;
+
+use std::{fmt::Write, fs, io, path::Path};
+
+const CELL_WIDTH: f32 = 8.0;
+const CELL_HEIGHT: f32 = 16.0;
+
+const TOKEN_RADIUS: f32 = 22.0;
+const TOKEN_COLOR: &str = "#f4e7c5";
+const TOKEN_BEST_COLOR: &str = "#c0392b";
+const TOKEN_TEXT_COLOR: &str = "#222222";
+const PORT_RADIUS: f32 = 20.0;
+const ROAD_WIDTH: f32 = 9.0;
+const ROBBER_COLOR: &str = "#3b3b3b";
+const OUTLINE_COLOR: &str = "#111111";
+
+/* the board as an svg document */
+pub fn to_svg(map: &Map) -> String {
+    let size = MAP_BKG_DRAW_STRING.size;
+    let (width, height) = (size.x as f32*CELL_WIDTH, size.y as f32*CELL_HEIGHT);
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central">"#, width, height);
+    let _ = writeln!(svg, r#"<rect width="{}" height="{}" fill="{}"/>"#, width, height, get_css_color(MAP_OCEAN_COLOR));
+
+    draw_ports(&mut svg, map);
+    draw_tiles(&mut svg, map);
+    draw_roads(&mut svg, map);
+    draw_buildings(&mut svg, map);
+    draw_robber(&mut svg, map);
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/* a page with nothing on it but the board */
+pub fn to_html(map: &Map) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>catarminal board</title>\n<style>body {{ margin: 0; background: {}; display: flex; justify-content: center; }}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        get_css_color(MAP_OCEAN_COLOR),
+        to_svg(map)
+    )
+}
+
+/* the middle of a cell of the map in the svg */
+fn to_svg_point(point: Point2D) -> (f32, f32) {
+    ((point.x as f32 + 0.5)*CELL_WIDTH, (point.y as f32 + 0.5)*CELL_HEIGHT)
+}
+
+fn get_tile_center(tile: usize) -> (f32, f32) {
+    let anchor = MAP_GRAPH.tile_anchor_points[tile];
+    to_svg_point(Point2D::new(anchor.x + TILE_SIZE.x as i16/2, anchor.y))
+}
+
+fn draw_tiles(svg: &mut String, map: &Map) {
+    for (i, tile) in map.get_tiles().iter().enumerate() {
+        let (x, y) = get_tile_center(i);
+
+        /* the plots of a tile aren't stored in order around it so they are sorted by angle */
+        let mut corners: Vec<(f32, f32)> = MAP_GRAPH.tile_plots[i].iter().map(|&plot| to_svg_point(MAP_GRAPH.plot_points[plot])).collect();
+        corners.sort_by(|a, b| (a.1 - y).atan2(a.0 - x).total_cmp(&(b.1 - y).atan2(b.0 - x)));
+        let points: Vec<String> = corners.iter().map(|(cx, cy)| format!("{},{}", cx, cy)).collect();
+        let _ = writeln!(
+            svg,
+            r#"<polygon points="{}" fill="{}" stroke="{}" stroke-width="{}" stroke-linejoin="round"/>"#,
+            points.join(" "),
+            get_css_color(tile.resource.get_color()),
+            get_css_color(MAP_SAND_COLOR),
+            CELL_WIDTH
+        );
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="24">{}</text>"#, x, y - TILE_SIZE.y as f32/4.0*CELL_HEIGHT, tile.resource.get_symbol());
+
+        if tile.resource == TileResource::OfDesert {
+            continue
+        }
+
+        /* the dots under the number are how many ways two dice roll it */
+        let rarity = 6 - tile.roll.abs_diff(7);
+        let color = if rarity >= 5 { TOKEN_BEST_COLOR } else { TOKEN_TEXT_COLOR };
+        let _ = writeln!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}"/>"#, x, y, TOKEN_RADIUS, TOKEN_COLOR, OUTLINE_COLOR);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="18" font-weight="bold" fill="{}">{}</text>"#, x, y - 4.0, color, tile.roll);
+        for dot in 0..rarity {
+            let dx = (dot as f32 - (rarity - 1) as f32/2.0)*5.0;
+            let _ = writeln!(svg, r#"<circle cx="{}" cy="{}" r="1.8" fill="{}"/>"#, x + dx, y + 11.0, color);
+        }
+    }
+}
+
+/* ports are a dock out to each of their plots and a sign with what they trade and at what ratio */
+fn draw_ports(svg: &mut String, map: &Map) {
+    for (i, port) in map.get_ports().iter().enumerate() {
+        let (x, y) = to_svg_point(MAP_GRAPH.port_points[i]);
+        for &plot in MAP_GRAPH.port_plots[i].iter() {
+            let (px, py) = to_svg_point(MAP_GRAPH.plot_points[plot]);
+            let _ = writeln!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="6"/>"#, x, y, px, py, get_css_color(PORT_COLOR));
+        }
+
+        let (give, take) = port.resource.get_ratio();
+        let _ = writeln!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}"/>"#, x, y, PORT_RADIUS, TOKEN_COLOR, OUTLINE_COLOR);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="14">{}</text>"#, x, y - 6.0, port.resource.get_symbol());
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="11" fill="{}">{}:{}</text>"#, x, y + 9.0, TOKEN_TEXT_COLOR, give, take);
+    }
+}
+
+fn draw_roads(svg: &mut String, map: &Map) {
+    for (a, b, color) in map.get_built_roads() {
+        let (x1, y1) = to_svg_point(MAP_GRAPH.plot_points[a]);
+        let (x2, y2) = to_svg_point(MAP_GRAPH.plot_points[b]);
+        for (stroke, width) in [(OUTLINE_COLOR.to_owned(), ROAD_WIDTH + 3.0), (get_css_color(color), ROAD_WIDTH)] {
+            let _ = writeln!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" stroke-linecap="round"/>"#, x1, y1, x2, y2, stroke, width);
+        }
+    }
+}
+
+/* settlements are a little house and cities a house with a tower, both standing on their plot */
+fn draw_buildings(svg: &mut String, map: &Map) {
+    for (plot, kind, color) in map.get_built_buildings() {
+        let (x, y) = to_svg_point(MAP_GRAPH.plot_points[plot]);
+        let outline: &[(f32, f32)] = match kind {
+            enums::Building::Settlement => &[(-10.0, 8.0), (10.0, 8.0), (10.0, -4.0), (0.0, -13.0), (-10.0, -4.0)],
+            enums::Building::City => &[(-16.0, 10.0), (16.0, 10.0), (16.0, -4.0), (2.0, -4.0), (2.0, -10.0), (-7.0, -18.0), (-16.0, -10.0)]
+        };
+        let points: Vec<String> = outline.iter().map(|(dx, dy)| format!("{},{}", x + dx, y + dy)).collect();
+        let _ = writeln!(svg, r#"<polygon points="{}" fill="{}" stroke="{}" stroke-width="2" stroke-linejoin="round"/>"#, points.join(" "), get_css_color(color), OUTLINE_COLOR);
+    }
+}
+
+/* a pawn standing to the left of the number of the tile it is on */
+fn draw_robber(svg: &mut String, map: &Map) {
+    let (x, y) = get_tile_center(map.get_robber_tile());
+    let x = x - 2.5*TOKEN_RADIUS;
+    let _ = writeln!(svg, r#"<ellipse cx="{}" cy="{}" rx="10" ry="16" fill="{}" stroke="{}"/>"#, x, y + 4.0, ROBBER_COLOR, OUTLINE_COLOR);
+    let _ = writeln!(svg, r#"<circle cx="{}" cy="{}" r="7" fill="{}" stroke="{}"/>"#, x, y - 15.0, ROBBER_COLOR, OUTLINE_COLOR);
+}
+
+/* terminal colors as css, the named ones are the usual xterm colors */
+fn get_css_color(color: Color) -> String {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+    ];
+
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) if i < 16 => ANSI[i as usize],
+        /* the 6x6x6 color cube and then the grays */
+        Color::Indexed(i) if i < 232 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + 40*n };
+            let i = i - 16;
+            (level(i/36), level(i/6 % 6), level(i % 6))
+        },
+        Color::Indexed(i) => {
+            let gray = 8 + 10*(i - 232);
+            (gray, gray, gray)
+        },
+        Color::Black | Color::Reset => ANSI[0],
+        Color::Red => ANSI[1],
+        Color::Green => ANSI[2],
+        Color::Yellow => ANSI[3],
+        Color::Blue => ANSI[4],
+        Color::Magenta => ANSI[5],
+        Color::Cyan => ANSI[6],
+        Color::Gray => ANSI[7],
+        Color::DarkGray => ANSI[8],
+        Color::LightRed => ANSI[9],
+        Color::LightGreen => ANSI[10],
+        Color::LightYellow => ANSI[11],
+        Color::LightBlue => ANSI[12],
+        Color::LightMagenta => ANSI[13],
+        Color::LightCyan => ANSI[14],
+        Color::White => ANSI[15]
+    };
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/* write the board to the path, an .html path gets the page and anything else gets the svg */
+pub fn export_board(map: &Map, path: &Path) -> io::Result<()> {
+    let is_html = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm"));
+    fs::write(path, if is_html { to_html(map) } else { to_svg(map) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::anim::AnimationService;
+    use crate::logic::{game::Piece, model::Pieces};
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const COLORS: [Color; 2] = [Color::Red, Color::Rgb(1, 2, 3)];
+
+    /* the board of a seed with a road and a settlement for the first player, a city for the second and the robber off the desert */
+    fn get_map() -> Map {
+        let mut map = Map::generate(&mut StdRng::seed_from_u64(7));
+        let mut anim_service = AnimationService::default();
+        let mut pieces = Pieces::new(map.get_tiles().iter().position(|tile| tile.resource != TileResource::OfDesert).unwrap());
+        pieces.place(0, Piece::Road(0, MAP_GRAPH.plot_edges[0][0]));
+        pieces.place(0, Piece::Settlement(0));
+        pieces.place(1, Piece::City(10));
+        map.sync(&pieces, &COLORS, &mut anim_service);
+        map.settle(&mut anim_service);
+        map
+    }
+
+    #[test]
+    fn svg_has_every_part() {
+        let map = get_map();
+        let svg = to_svg(&map);
+        let count = |pattern: &str| svg.matches(pattern).count();
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+
+        /* every tile is a hexagon and every building an outline in the color of whoever built it */
+        assert_eq!(count("<polygon"), map.get_tiles().len() + 2);
+        let tokens = map.get_tiles().iter().filter(|tile| tile.resource != TileResource::OfDesert).count();
+        assert_eq!(count(&format!(r#"r="{}""#, TOKEN_RADIUS)), tokens);
+        assert_eq!(count(&format!(r#"fill="{}" stroke="{}" stroke-width="2""#, get_css_color(COLORS[0]), OUTLINE_COLOR)), 1);
+        assert_eq!(count(&format!(r#"fill="{}" stroke="{}" stroke-width="2""#, get_css_color(COLORS[1]), OUTLINE_COLOR)), 1);
+
+        /* roads are drawn over an outline */
+        assert_eq!(count(r#"stroke-linecap="round""#), 2);
+        assert_eq!(count(&format!(r#"stroke="{}" stroke-width="{}""#, get_css_color(COLORS[0]), ROAD_WIDTH)), 1);
+
+        for port in map.get_ports() {
+            let (give, take) = port.resource.get_ratio();
+            assert!(svg.contains(&format!(">{}:{}</text>", give, take)));
+        }
+        assert_eq!(count(&format!(r#"r="{}""#, PORT_RADIUS)), map.get_ports().len());
+        assert_eq!(count("<ellipse"), 1);
+    }
+
+    /* the robber stands next to the number of the tile it is on */
+    #[test]
+    fn robber_is_on_its_tile() {
+        let map = get_map();
+        let (x, y) = get_tile_center(map.get_robber_tile());
+        assert!(to_svg(&map).contains(&format!(r#"<ellipse cx="{}" cy="{}""#, x - 2.5*TOKEN_RADIUS, y + 4.0)));
+    }
+
+    #[test]
+    fn html_wraps_the_svg() {
+        let map = get_map();
+        let html = to_html(&map);
+        assert!(html.starts_with("<!DOCTYPE html>\n") && html.contains(&to_svg(&map)) && html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn css_colors() {
+        assert_eq!(get_css_color(Color::Red), "#cd0000");
+        assert_eq!(get_css_color(Color::Reset), "#000000");
+        assert_eq!(get_css_color(Color::Rgb(1, 2, 255)), "#0102ff");
+        assert_eq!(get_css_color(Color::Indexed(9)), "#ff0000");
+        assert_eq!(get_css_color(Color::Indexed(16)), "#000000");
+        assert_eq!(get_css_color(Color::Indexed(17)), "#00005f");
+        assert_eq!(get_css_color(Color::Indexed(231)), "#ffffff");
+        assert_eq!(get_css_color(Color::Indexed(232)), "#080808");
+        assert_eq!(get_css_color(Color::Indexed(255)), "#eeeeee");
+    }
+
+    /* the extension picks between the page and the bare svg */
+    #[test]
+    fn export_picks_by_extension() {
+        let map = get_map();
+        let dir = std::env::temp_dir().join(format!("catarminal-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, is_html) in [("board.svg", false), ("board.HTML", true), ("board", false)] {
+            let path = dir.join(name);
+            export_board(&map, &path).unwrap();
+            let written = fs::read_to_string(&path).unwrap();
+            assert_eq!(written.starts_with("<!DOCTYPE html>"), is_html, "{}", name);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,17 +2,137 @@ use super::{Tile, TileDigitsAnimation, Port, parse, placement::*, TILE_SIZE};
 
 use crate::render::{prelude::*, iter::CustomIterator};
 use crate::enums;
+use crate::logic::{board::Board, game::Piece, model::{Pieces, PieceChange}};
 
-use tui::style::{Color, Style};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::style::{Color, Modifier, Style};
+
+use rand::Rng;
 
 pub const MAP_SAND_COLOR: Color = Color::Rgb(221, 178, 100);
 pub const MAP_OCEAN_COLOR: Color = Color::Rgb(9, 103, 166);
 
 const ROBBER_OFFSET: Point2D = Point2D::new(8, -4); // robber offset from tile offset
 
+const REVEAL_TILE_DELAY: f32 = 0.25;
+const REVEAL_PORT_DELAY: f32 = 0.25;
+
+/* how far from a plot point a click still counts as the plot, buildings sit above their point */
+const PLOT_HIT_RADIUS: Point2D = Point2D::new(2, 2);
+
+const HIGHLIGHT_DURATION: f32 = 2.0;
+const HIGHLIGHT_BLINK: f32 = 0.25;
+
 lazy_static! {
     static ref ROBBER_BITSHAPE: BitShape128 = BitShape128::new(0b011101111101110111111111111111, Size2D::new(5, 6));
     static ref ROBBER_STYLE: Style = Style::default().bg(Color::Magenta);
+    static ref HIGHLIGHT_STYLE: Style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    static ref ILLEGAL_STYLE: Style = Style::default().fg(Color::DarkGray);
+    static ref CURSOR_STYLE: Style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+}
+
+/* a part of the map that can be pointed out to the player */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapTarget {
+    Tile(usize),
+    Plot(usize),
+    Road(usize, usize)
+}
+
+impl MapTarget {
+    /* the space the highlight surrounds in map coordinates */
+    pub fn get_space(&self) -> AbsoluteSpace {
+        match *self {
+            Self::Tile(tile) => {
+                let anchor = parse::MAP_GRAPH.tile_anchor_points[tile];
+                AbsoluteSpace::new(anchor.x, anchor.y - TILE_SIZE.y as i16/2, TILE_SIZE.x, TILE_SIZE.y)
+            },
+            /* buildings sit on top of their plot */
+            Self::Plot(plot) => {
+                let point = parse::MAP_GRAPH.plot_points[plot];
+                AbsoluteSpace::new(point.x - 4, point.y - 3, 9, 4)
+            },
+            Self::Road(a, b) => AbsoluteSpace::from_point_cloud(&[parse::MAP_GRAPH.plot_points[a], parse::MAP_GRAPH.plot_points[b]])
+        }
+    }
+
+    pub fn from_piece(piece: Piece) -> Self {
+        match piece {
+            Piece::Road(a, b) => Self::Road(a, b),
+            Piece::Settlement(plot) | Piece::City(plot) => Self::Plot(plot)
+        }
+    }
+
+    /* if the target is really on the map, targets typed by hand can be anything */
+    pub fn is_on_map(&self) -> bool {
+        match *self {
+            Self::Tile(tile) => tile < parse::MAP_GRAPH.tile_anchor_points.len(),
+            Self::Plot(plot) => plot < parse::MAP_GRAPH.plot_points.len(),
+            Self::Road(a, b) => parse::MAP_GRAPH.plot_edges.get(a).is_some_and(|edges| edges.contains(&b))
+        }
+    }
+
+    /* every part of the map of the same kind as this one */
+    fn get_all_of_kind(&self) -> Vec<MapTarget> {
+        match self {
+            Self::Tile(_) => (0..parse::MAP_GRAPH.tile_anchor_points.len()).map(Self::Tile).collect(),
+            Self::Plot(_) => (0..parse::MAP_GRAPH.plot_points.len()).map(Self::Plot).collect(),
+            Self::Road(_, _) => parse::MAP_GRAPH.plot_edges
+                .iter()
+                .enumerate()
+                .flat_map(|(a, edges)| edges.iter().filter(move |&&b| a < b).map(move |&b| Self::Road(a, b)))
+                .collect()
+        }
+    }
+
+    /* the part of the map of the same kind closest to the point */
+    fn get_nearest_of_kind(&self, point: Point2D) -> MapTarget {
+        self.get_all_of_kind()
+            .into_iter()
+            .min_by_key(|target| {
+                let offset = target.get_center() - point;
+                (offset.x as i32).pow(2) + (2*offset.y as i32).pow(2)
+            })
+            .unwrap_or(*self)
+    }
+
+    pub fn get_center(&self) -> Point2D {
+        let space = self.get_space();
+        space.position + Point2D::new(space.size.x as i16/2, space.size.y as i16/2)
+    }
+}
+
+/* what kind of piece the player is picking a spot for, the robber gets moved to a tile the same way */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildMode {
+    Road,
+    Settlement,
+    City,
+    Robber
+}
+
+impl BuildMode {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Road => "road",
+            Self::Settlement => "settlement",
+            Self::City => "city",
+            Self::Robber => "robber"
+        }
+    }
+}
+
+/* 
+ * drawn over the map while the player picks a spot to build
+ * every spot gets a marker, the ones that can't be built on are dimmed and the hovered spot gets a ghost of the piece
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildPreview {
+    pub mode: BuildMode,
+    pub legal: Vec<MapTarget>,
+    pub hover: Option<MapTarget>,
+    /* only the foreground is used so the map shows through */
+    pub style: Style
 }
 
 #[derive(Debug)]
@@ -24,20 +144,56 @@ pub struct Map {
     roads: Vec<Vec<Option<Road>>>,
     road_index: Vec<(usize, usize)>,
     buildings: Vec<Building>,
+    /* pieces taken back that are still lifting off the map */
+    removing: Vec<Piece>,
     robber: DrawLeaf<Shape128>,
+    /* the pieces as of the last sync, the robber is drawn where it is headed */
+    pieces: Pieces,
+    reveal: Option<MapReveal>,
+    highlight: Option<(Vec<MapTarget>, Animation<()>)>,
+    pub preview: Option<BuildPreview>,
+    /* the part of the map walked to with the keyboard, only shown while the map is focused */
+    cursor: Option<MapTarget>,
+    did_move_cursor: bool,
+    /* where the cursor was when enter was last pressed */
+    selected: Option<MapTarget>,
+    /* the preview and cursor as of the last relayout, so only what changed about them gets redrawn */
+    drawn_preview: Option<BuildPreview>,
+    drawn_cursor: Option<MapTarget>,
+    is_focused: bool,
     layout: DrawLayout,
     mount: Mount
 }
 
+/* the board reveal drops tiles in one after the other and then shows the ports one after the other */
+#[derive(Debug)]
+struct MapReveal {
+    anim: Animation<()>,
+    tiles_placed: usize,
+    ports_shown: usize
+}
+
 impl Map {
+    /* generate a random map, the same rng state always generates the same map */
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        Map::from_board(&Board::generate(rng))
+    }
+
+    /* the map drawing of a board */
+    pub fn from_board(board: &Board) -> Self {
+        Map::new(
+            board.tiles.iter().map(|tile| Tile::new(tile.roll, tile.resource)).collect(),
+            board.ports.iter().enumerate().map(|(i, &resource)| Port::new(i, resource)).collect()
+        )
+    }
+
     pub fn new(tiles: Vec<Tile>, ports: Vec<Port>) -> Self {
+        let robber_tile = tiles
+            .iter()
+            .position(|tile| tile.resource == enums::TileResource::OfDesert)
+            .unwrap_or(0);
         let robber_init_tile_position = parse::MAP_GRAPH.tile_anchor_points
-            .get(
-                tiles
-                    .iter()
-                    .position(|tile| tile.resource == enums::TileResource::OfDesert)
-                    .unwrap_or(0)
-            )
+            .get(robber_tile)
             .cloned()
             .unwrap_or_default();
 
@@ -101,11 +257,22 @@ impl Map {
         let mut map = Map { 
             tiles,
             tile_digit_anims: Vec::new(),
+            removing: Vec::new(),
             ports,
             roads,
             road_index,
             buildings,
             robber,
+            pieces: Pieces::new(robber_tile),
+            reveal: None,
+            highlight: None,
+            preview: None,
+            cursor: None,
+            did_move_cursor: false,
+            selected: None,
+            drawn_preview: None,
+            drawn_cursor: None,
+            is_focused: false,
             bkg: &parse::MAP_BKG_SHAPE,
             layout: DrawLayout::default(), 
             mount: Mount::default() 
@@ -121,8 +288,136 @@ impl Map {
         map
     }
 
-    #[allow(dead_code)]
-    pub fn move_robber(&mut self, tile_index: usize, anim_service: &mut AnimationService) {
+    /* the color of the placed tile over a point of the map, for drawing the map small */
+    pub fn get_tile_color_at(&self, point: Point2D) -> Option<Color> {
+        let placed = self.reveal.as_ref().map(|reveal| reveal.tiles_placed).unwrap_or(self.tiles.len());
+        self.tiles[..placed]
+            .iter()
+            .zip(parse::MAP_GRAPH.tile_anchor_points.iter())
+            .find(|(_, anchor)| {
+                /* tiles are hexagons that narrow by a column on each side every row away from the middle */
+                let (dx, dy) = (point.x - anchor.x, (point.y - anchor.y).abs());
+                dy <= TILE_SIZE.y as i16/2 && dx >= dy && dx < TILE_SIZE.x as i16 - dy
+            })
+            .map(|(tile, _)| tile.resource.get_color())
+    }
+
+    pub fn get_tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn get_ports(&self) -> &[Port] {
+        &self.ports
+    }
+
+    pub fn get_robber_tile(&self) -> usize {
+        self.pieces.robber
+    }
+
+    /* the roads that have been built with the color of whoever built them */
+    pub fn get_built_roads(&self) -> Vec<(usize, usize, Color)> {
+        self.road_index
+            .iter()
+            .filter_map(|&(idx0, idx1)| {
+                let road = self.roads[idx0][idx1].as_ref()?;
+                road.layout.is_visible.then(|| (idx0, parse::MAP_GRAPH.plot_edges[idx0][idx1], road.style.bg.unwrap_or(Color::White)))
+            })
+            .collect()
+    }
+
+    /* same as get_built_roads for settlements and cities */
+    pub fn get_built_buildings(&self) -> Vec<(usize, enums::Building, Color)> {
+        self.buildings
+            .iter()
+            .enumerate()
+            .filter(|(_, building)| building.layout.is_visible)
+            .map(|(plot, building)| (plot, building.kind, building.style.bg.unwrap_or(Color::White)))
+            .collect()
+    }
+
+    /* how long the board reveal takes from start to finish */
+    pub fn get_reveal_duration(&self) -> f32 {
+        REVEAL_TILE_DELAY*self.tiles.len() as f32 + REVEAL_PORT_DELAY*self.ports.len() as f32
+    }
+
+    /* play the board reveal, tiles and ports get placed during relayout as the reveal progresses */
+    pub fn reveal(&mut self, anim_service: &mut AnimationService) {
+        let mut anim = Animation::with_duration(self.get_reveal_duration(), ());
+        anim.play(anim_service);
+        self.reveal = Some(MapReveal { anim, tiles_placed: 0, ports_shown: 0 });
+    }
+
+    /* put every tile, port, piece and the robber where their animations leave them right away, for drawing the board without watching it */
+    pub fn settle(&mut self, anim_service: &mut AnimationService) {
+        if let Some(mut reveal) = self.reveal.take() {
+            reveal.anim.cancel(anim_service);
+        }
+        for (_, mut anim) in self.tile_digit_anims.drain(..) {
+            anim.cancel(anim_service);
+        }
+        for tile in self.tiles.iter_mut() {
+            tile.settle(anim_service);
+        }
+        for port in self.ports.iter_mut() {
+            port.settle(anim_service);
+        }
+        for road in self.roads.iter_mut().flatten().flatten() {
+            road.settle(anim_service);
+        }
+        for building in self.buildings.iter_mut() {
+            building.settle(anim_service);
+        }
+        for piece in std::mem::take(&mut self.removing) {
+            self.get_placement_mut(piece).unbuild(anim_service);
+        }
+
+        if let Some(mut anim) = self.robber.layout.anim.take() {
+            anim.cancel(anim_service);
+        }
+        self.robber.layout
+            .set_visible(true)
+            .set_position(UDim2::from_point2d(parse::MAP_GRAPH.tile_anchor_points[self.pieces.robber] + ROBBER_OFFSET));
+    }
+
+    /* 
+     * bring the pieces on the map in line with the model, animating whatever changed since the last sync
+     * returns the parts of the map that changed so whoever shows the map can point them out
+     */
+    pub fn sync(&mut self, pieces: &Pieces, colors: &[Color], anim_service: &mut AnimationService) -> Vec<MapTarget> {
+        if self.pieces == *pieces {
+            return Vec::new()
+        }
+
+        let changes = self.pieces.diff(pieces);
+        let mut targets = Vec::with_capacity(changes.len());
+        for change in changes {
+            targets.push(match change {
+                PieceChange::Placed(piece, player) => {
+                    let style = Style::default().bg(colors.get(player).copied().unwrap_or(Color::White));
+                    match piece {
+                        Piece::Road(a, b) => self.place_road(a, b, style, anim_service),
+                        Piece::Settlement(plot) => self.place_building(plot, enums::Building::Settlement, style, anim_service),
+                        Piece::City(plot) => self.place_building(plot, enums::Building::City, style, anim_service)
+                    }
+                    MapTarget::from_piece(piece)
+                },
+                PieceChange::Removed(piece) => {
+                    self.remove_piece(piece, anim_service);
+                    MapTarget::from_piece(piece)
+                },
+                PieceChange::RobberMoved(tile) => {
+                    self.move_robber(tile, anim_service);
+                    MapTarget::Tile(tile)
+                }
+            });
+        }
+        self.pieces = pieces.clone();
+        targets
+    }
+
+    /* the robber can be moved before the board is revealed, it just lands where it was moved to when the desert drops in */
+    fn move_robber(&mut self, tile_index: usize, anim_service: &mut AnimationService) {
+        self.pieces.robber = tile_index;
         if !self.robber.layout.is_visible {
             return
         }
@@ -135,14 +430,270 @@ impl Map {
         self.robber.animate_space(anim_service, to, 1.0, EasingStyle::Cubic, EasingDirection::InOut);
     }
 
+    /* what is under the point in map coordinates, plots win over the roads that run into them */
+    pub fn hit_test(&self, point: Point2D) -> Option<MapTarget> {
+        self.hit_test_plot(point)
+            .map(MapTarget::Plot)
+            .or_else(|| self.hit_test_road(point).map(|(a, b)| MapTarget::Road(a, b)))
+    }
+
+    /* the tile with its middle closest to the point in map coordinates, if the point is on one at all */
+    pub fn hit_test_tile(&self, point: Point2D) -> Option<usize> {
+        (0..parse::MAP_GRAPH.tile_anchor_points.len())
+            .filter(|&tile| MapTarget::Tile(tile).get_space().is_interior_point(point))
+            .min_by_key(|&tile| {
+                let offset = MapTarget::Tile(tile).get_center() - point;
+                (offset.x as i32).pow(2) + (2*offset.y as i32).pow(2)
+            })
+    }
+
+    /* 
+     * the plot closest to the point in map coordinates, if the point is within the hit radius of one
+     * the radius goes both above the plot where its building stands and below it so a click just under a building still counts
+     * cells are about twice as tall as they are wide so rows count double when telling which plot is closer
+     */
+    pub fn hit_test_plot(&self, point: Point2D) -> Option<usize> {
+        parse::MAP_GRAPH.plot_points
+            .iter()
+            .enumerate()
+            .map(|(plot, &plot_point)| (plot, point - plot_point))
+            .filter(|(_, offset)| offset.x.abs() <= PLOT_HIT_RADIUS.x && offset.y.abs() <= PLOT_HIT_RADIUS.y)
+            .min_by_key(|(_, offset)| (offset.x as i32).pow(2) + (2*offset.y as i32).pow(2))
+            .map(|(plot, _)| plot)
+    }
+
+    /* the road space covering the point in map coordinates with the smaller plot first */
+    pub fn hit_test_road(&self, point: Point2D) -> Option<(usize, usize)> {
+        self.road_index
+            .iter()
+            .find(|&&(idx0, idx1)| self.roads[idx0][idx1].as_ref().unwrap().covers(point))
+            .map(|&(idx0, idx1)| (idx0, parse::MAP_GRAPH.plot_edges[idx0][idx1]))
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    /* the part of the map the keyboard cursor is on while the map is focused */
+    pub fn get_cursor(&self) -> Option<MapTarget> {
+        self.cursor.filter(|_| self.is_focused)
+    }
+
+    /* the cursor if it moved since this was last called, so whoever shows the map can keep it in view */
+    pub fn take_moved_cursor(&mut self) -> Option<MapTarget> {
+        if std::mem::take(&mut self.did_move_cursor) { self.get_cursor() } else { None }
+    }
+
+    /* the part of the map enter was pressed on since this was last called */
+    pub fn take_selected(&mut self) -> Option<MapTarget> {
+        self.selected.take()
+    }
+
+    /* snap the cursor to the closest part of the map of the kind being built */
+    pub fn set_cursor_kind(&mut self, mode: BuildMode) {
+        let cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => return
+        };
+
+        let kind = match mode {
+            BuildMode::Road => MapTarget::Road(0, 0),
+            BuildMode::Settlement | BuildMode::City => MapTarget::Plot(0),
+            BuildMode::Robber => MapTarget::Tile(0)
+        };
+        if std::mem::discriminant(&kind) != std::mem::discriminant(&cursor) {
+            self.move_cursor(kind.get_nearest_of_kind(cursor.get_center()));
+        }
+    }
+
+    fn move_cursor(&mut self, cursor: MapTarget) {
+        self.did_move_cursor = self.did_move_cursor || self.cursor != Some(cursor);
+        self.cursor = Some(cursor);
+    }
+
+    /* move the cursor to the closest part of the map of the same kind in the direction */
+    fn walk_cursor(&mut self, direction: Point2D) -> bool {
+        let cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => return false
+        };
+
+        let from = cursor.get_center();
+        let next = cursor.get_all_of_kind()
+            .into_iter()
+            .filter_map(|target| get_walk_score(from, target.get_center(), direction).map(|score| (score, target)))
+            .min_by_key(|&(score, _)| score)
+            .map(|(_, target)| target);
+
+        match next {
+            Some(next) => {
+                self.move_cursor(next);
+                true
+            },
+            None => false
+        }
+    }
+
+    /*
+     * the preview markers change all over the map so new ones redraw all of it,
+     * a ghost or cursor that moved only redraws where it was and where it is now, brackets and road ghosts stick out a little around their target
+     */
+    fn relayout_overlay(&mut self, ctx: &mut LayoutContext, absolute_map_space: AbsoluteSpace) {
+        let cursor = self.get_cursor();
+        let preview = self.preview.clone();
+        let drawn_cursor = std::mem::replace(&mut self.drawn_cursor, cursor);
+        let drawn_preview = std::mem::replace(&mut self.drawn_preview, preview.clone());
+
+        let get_markers = |preview: &Option<BuildPreview>| preview.as_ref().map(|preview| (preview.mode, preview.legal.clone(), preview.style));
+        if get_markers(&preview) != get_markers(&drawn_preview) {
+            ctx.invalidate(self);
+            return
+        }
+
+        let hover = preview.and_then(|preview| preview.hover);
+        let drawn_hover = drawn_preview.and_then(|preview| preview.hover);
+        if hover == drawn_hover && cursor == drawn_cursor {
+            return
+        }
+        for target in [hover, drawn_hover, cursor, drawn_cursor].into_iter().flatten() {
+            let space = target.get_space();
+            let space = AbsoluteSpace::new(space.left() - 2, space.top() - 1, space.size.x + 4, space.size.y + 2);
+            ctx.invalidate_space_in(self, absolute_map_space.absolute_space_of(space));
+        }
+    }
+
+    /* blink brackets around part of the map for a moment */
+    pub fn highlight(&mut self, highlight: MapTarget, anim_service: &mut AnimationService) {
+        self.highlight_all(vec![highlight], anim_service);
+    }
+
+    /* blink brackets around several parts of the map at once, like the tiles that produced on a roll */
+    pub fn highlight_all(&mut self, highlights: Vec<MapTarget>, anim_service: &mut AnimationService) {
+        if let Some((_, mut anim)) = self.highlight.take() {
+            anim.cancel(anim_service);
+        }
+
+        let mut anim = Animation::with_duration(HIGHLIGHT_DURATION, ());
+        anim.play(anim_service);
+        self.highlight = Some((highlights, anim));
+    }
+
+    fn draw_highlight(&self, ctx: &mut DrawContext) {
+        let (highlights, anim) = match self.highlight.as_ref() {
+            Some(highlight) => highlight,
+            None => return
+        };
+
+        if (anim.state.get_elapsed()/HIGHLIGHT_BLINK) as u32 % 2 == 1 {
+            return
+        }
+
+        for highlight in highlights.iter() {
+            Self::draw_brackets(ctx, highlight.get_space(), *HIGHLIGHT_STYLE);
+        }
+    }
+
+    fn draw_cursor(&self, ctx: &mut DrawContext) {
+        if let Some(cursor) = self.get_cursor() {
+            Self::draw_brackets(ctx, cursor.get_space(), *CURSOR_STYLE);
+        }
+    }
+
+    fn draw_brackets(ctx: &mut DrawContext, space: AbsoluteSpace, style: Style) {
+        let (left, top, right, bottom) = (space.left() - 1, space.top() - 1, space.right(), space.bottom());
+        ctx.draw_unicode_line("┏", Point2D::new(left, top), style);
+        ctx.draw_unicode_line("┓", Point2D::new(right, top), style);
+        ctx.draw_unicode_line("┗", Point2D::new(left, bottom), style);
+        ctx.draw_unicode_line("┛", Point2D::new(right, bottom), style);
+    }
+
+    fn draw_preview(&self, ctx: &mut DrawContext) {
+        let preview = match self.preview.as_ref() {
+            Some(preview) => preview,
+            None => return
+        };
+
+        let marker_style = preview.style.add_modifier(Modifier::BOLD);
+        match preview.mode {
+            BuildMode::Road => for &(idx0, idx1) in self.road_index.iter() {
+                let target = MapTarget::Road(idx0, parse::MAP_GRAPH.plot_edges[idx0][idx1]);
+                let style = if preview.legal.contains(&target) { marker_style } else { *ILLEGAL_STYLE };
+                ctx.draw_unicode_line("·", self.roads[idx0][idx1].as_ref().unwrap().get_middle(), style);
+            },
+            BuildMode::Settlement | BuildMode::City => for (plot, &point) in parse::MAP_GRAPH.plot_points.iter().enumerate() {
+                let style = if preview.legal.contains(&MapTarget::Plot(plot)) { marker_style } else { *ILLEGAL_STYLE };
+                ctx.draw_unicode_line("●", point, style);
+            },
+            /* tiles get their marker where the robber would stand on them */
+            BuildMode::Robber => for (tile, &point) in parse::MAP_GRAPH.tile_anchor_points.iter().enumerate() {
+                let style = if preview.legal.contains(&MapTarget::Tile(tile)) { marker_style } else { *ILLEGAL_STYLE };
+                ctx.draw_unicode_line("◆", point + ROBBER_OFFSET + Point2D::new(ROBBER_BITSHAPE.size.x as i16/2, ROBBER_BITSHAPE.size.y as i16/2), style);
+            }
+        }
+
+        /* the ghost is grayed out over spots that can't be built on */
+        let hover = match preview.hover {
+            Some(hover) => hover,
+            None => return
+        };
+        let style = if preview.legal.contains(&hover) { preview.style } else { *ILLEGAL_STYLE };
+        match (preview.mode, hover) {
+            (BuildMode::Road, MapTarget::Road(a, b)) => ctx.draw_child(
+                &Road::new(parse::MAP_GRAPH.plot_points[a], parse::MAP_GRAPH.plot_points[b], style, DrawLayout::default()).into_ghost()
+            ),
+            (BuildMode::Settlement | BuildMode::City, MapTarget::Plot(plot)) => ctx.draw_child(
+                &Building::new(
+                    if preview.mode == BuildMode::City { enums::Building::City } else { enums::Building::Settlement },
+                    style,
+                    DrawLayout::default()
+                        .set_position(UDim2::from_point2d(parse::MAP_GRAPH.plot_points[plot]))
+                        .set_anchor(Float2D::new(0.5, 1.0))
+                        .clone()
+                ).into_ghost()
+            ),
+            (BuildMode::Robber, MapTarget::Tile(tile)) => ctx.draw_child(
+                &Shape128::new(
+                    &ROBBER_BITSHAPE,
+                    GHOST_SYMBOL,
+                    style,
+                    DrawLayout::default()
+                        .set_position(UDim2::from_point2d(parse::MAP_GRAPH.tile_anchor_points[tile] + ROBBER_OFFSET))
+                        .clone()
+                )
+            ),
+            _ => ()
+        }
+    }
+
     pub fn show_port(&mut self, port: usize, anim_service: &mut AnimationService) {
         self.ports[port].animate(anim_service);
     }
 
-    pub fn place_road(&mut self, plot_a: usize, plot_b: usize, style: Style, anim_service: &mut AnimationService) {
+    fn get_road_mut(&mut self, plot_a: usize, plot_b: usize) -> &mut Road {
         let idx0 = plot_a.min(plot_b);
         let idx1 = parse::MAP_GRAPH.plot_edges[idx0].iter().position(|&plot| plot == plot_a.max(plot_b)).unwrap();
-        self.roads[idx0][idx1].as_mut().unwrap().build(style, anim_service);
+        self.roads[idx0][idx1].as_mut().unwrap()
+    }
+
+    /* the drawing a piece is built with, settlements and cities share the building on their plot */
+    fn get_placement_mut(&mut self, piece: Piece) -> &mut dyn Placement {
+        match piece {
+            Piece::Road(a, b) => self.get_road_mut(a, b),
+            Piece::Settlement(plot) | Piece::City(plot) => &mut self.buildings[plot]
+        }
+    }
+
+    fn place_road(&mut self, plot_a: usize, plot_b: usize, style: Style, anim_service: &mut AnimationService) {
+        self.removing.retain(|&piece| piece != Piece::Road(plot_a, plot_b) && piece != Piece::Road(plot_b, plot_a));
+        self.get_road_mut(plot_a, plot_b).build(style, anim_service);
+    }
+
+    /* pieces taken back off the board lift off it the way they dropped in and are hidden during relayout once they are gone */
+    fn remove_piece(&mut self, piece: Piece, anim_service: &mut AnimationService) {
+        self.get_placement_mut(piece).unplace(anim_service);
+        if !self.removing.contains(&piece) {
+            self.removing.push(piece);
+        }
     }
 
     pub fn place_tile(&mut self, tile_index: usize, anim_service: &mut AnimationService) {
@@ -154,7 +705,7 @@ impl Map {
         if tile.resource == enums::TileResource::OfDesert {
             let (start, duration) = tile.get_map_fall_parameters(Point2D::new(0, TILE_SIZE.y as i16/2) + ROBBER_OFFSET, 0);
             let mut to = self.robber.layout.space;
-            to.position = UDim2::from_point2d(parse::MAP_GRAPH.tile_anchor_points[tile_index] + ROBBER_OFFSET);
+            to.position = UDim2::from_point2d(parse::MAP_GRAPH.tile_anchor_points[self.pieces.robber] + ROBBER_OFFSET);
             self.robber.layout
                 .set_visible(true)
                 .set_position(UDim2::from_point2d(start));
@@ -164,7 +715,8 @@ impl Map {
         self.tile_digit_anims.push((tile_index, anim));
     }
 
-    pub fn place_building(&mut self, plot: usize, kind: enums::Building, style: Style, anim_service: &mut AnimationService) {
+    fn place_building(&mut self, plot: usize, kind: enums::Building, style: Style, anim_service: &mut AnimationService) {
+        self.removing.retain(|&piece| !matches!(piece, Piece::Settlement(other) | Piece::City(other) if other == plot));
         if self.buildings[plot].kind != kind {
             let mut mount = *self.buildings[plot].mount_ref(); // manual remounting
             mount.children = 0;
@@ -217,6 +769,10 @@ impl Drawable for Map {
 
         ctx.draw_child(&self.robber);
 
+        self.draw_preview(ctx);
+        self.draw_cursor(ctx);
+        self.draw_highlight(ctx);
+
         for (_, anim) in self.tile_digit_anims.iter() {
             if let Some(animator) = anim.animator.digit0.as_ref() {
                 ctx.draw_child(&animator.digit);
@@ -238,11 +794,106 @@ impl StatefulDrawable for Map {
 // TODO: this is ridiculous... please find a better approach LMFAO
 impl MountableLayout for Map {
     fn relayout(&mut self, ctx: &mut LayoutContext) {
+        if let Some(mut reveal) = self.reveal.take() {
+            reveal.anim.update(&mut ());
+            let elapsed = if reveal.anim.state.playback == PlaybackState::Playing { reveal.anim.state.get_elapsed() } else { f32::INFINITY };
+            while reveal.tiles_placed < self.tiles.len() && elapsed >= REVEAL_TILE_DELAY*(reveal.tiles_placed + 1) as f32 {
+                self.place_tile(reveal.tiles_placed, &mut ctx.service.animation);
+                reveal.tiles_placed += 1;
+            }
+
+            let ports_start = REVEAL_TILE_DELAY*self.tiles.len() as f32;
+            while reveal.ports_shown < self.ports.len() && elapsed >= ports_start + REVEAL_PORT_DELAY*(reveal.ports_shown + 1) as f32 {
+                self.show_port(reveal.ports_shown, &mut ctx.service.animation);
+                reveal.ports_shown += 1;
+            }
+
+            if reveal.anim.state.playback == PlaybackState::Playing {
+                self.reveal = Some(reveal);
+            }
+        }
+
+        if let Some((_, anim)) = self.highlight.as_mut() {
+            anim.update(&mut ());
+            if anim.state.playback != PlaybackState::Playing {
+                self.highlight = None;
+            }
+            ctx.invalidate(self);
+        }
+
+        /* the cursor starts on the tile in the middle of the map */
+        self.is_focused = ctx.relayout_focus_of(self);
+        if self.is_focused && self.cursor.is_none() {
+            let size = parse::MAP_BKG_DRAW_STRING.size;
+            self.move_cursor(MapTarget::Tile(0).get_nearest_of_kind(Point2D::new(size.x as i16/2, size.y as i16/2)));
+        }
+
+        let absolute_map_space = ctx.get_absolute_layout_space_of(self);
+        self.relayout_overlay(ctx, absolute_map_space);
+
+        /* falling digits are damaged where they were and where they are now along with the tile they land on */
+        let mut digit_spaces = Vec::new();
         for (tile, anim) in self.tile_digit_anims.iter_mut() {
+            digit_spaces.push(self.tiles[*tile].to_absolute_layout_space(absolute_map_space));
+            for animator in [anim.animator.digit0.as_ref(), anim.animator.digit1.as_ref()].into_iter().flatten() {
+                digit_spaces.push(animator.digit.to_absolute_layout_space(absolute_map_space));
+            }
             anim.update(&mut self.tiles[*tile]);
+            for animator in [anim.animator.digit0.as_ref(), anim.animator.digit1.as_ref()].into_iter().flatten() {
+                digit_spaces.push(animator.digit.to_absolute_layout_space(absolute_map_space));
+            }
+        }
+        for space in digit_spaces {
+            ctx.invalidate_space_in(self, space);
         }
         self.tile_digit_anims.retain(|(_, anim)| anim.state.playback == PlaybackState::Playing);
         ctx.relayout_children_of(self);
+
+        /* pieces done lifting off are hidden after their last step has been laid out, so where they ended up is redrawn */
+        let mut lifted_spaces = Vec::new();
+        for piece in std::mem::take(&mut self.removing) {
+            let placement = self.get_placement_mut(piece);
+            if placement.layout_ref().anim.is_some() {
+                self.removing.push(piece);
+                continue
+            }
+            lifted_spaces.push(placement.to_absolute_layout_space(absolute_map_space));
+            placement.unbuild(&mut ctx.service.animation);
+        }
+        for space in lifted_spaces {
+            ctx.invalidate_space_in(self, space);
+        }
+    }
+
+    /* arrows walk the cursor, space switches between tiles, plots and roads and enter picks what the cursor is on */
+    fn on_key_input(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::SHIFT) {
+            return false
+        }
+
+        match key.code {
+            KeyCode::Up => self.walk_cursor(Point2D::new(0, -1)),
+            KeyCode::Down => self.walk_cursor(Point2D::new(0, 1)),
+            KeyCode::Left => self.walk_cursor(Point2D::new(-1, 0)),
+            KeyCode::Right => self.walk_cursor(Point2D::new(1, 0)),
+            KeyCode::Char(' ') => match self.cursor {
+                Some(cursor) => {
+                    let kind = match cursor {
+                        MapTarget::Tile(_) => MapTarget::Plot(0),
+                        MapTarget::Plot(_) => MapTarget::Road(0, 0),
+                        MapTarget::Road(_, _) => MapTarget::Tile(0)
+                    };
+                    self.move_cursor(kind.get_nearest_of_kind(cursor.get_center()));
+                    true
+                },
+                None => false
+            },
+            KeyCode::Enter => {
+                self.selected = self.cursor;
+                self.selected.is_some()
+            },
+            _ => false
+        }
     }
 
     fn mount_ref(&self) -> &Mount { &self.mount }
@@ -305,4 +956,212 @@ impl MountableLayout for Map {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/*
+ * golden snapshots of the board with everything in place, checked as plain text and with colors
+ * after changing how something on the board is drawn run the tests with UPDATE_SNAPSHOTS=1 to write them again
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{screen::Screen, snapshot};
+
+    use rand::{SeedableRng, rngs::StdRng};
+    use tui::{buffer::Buffer, layout::Rect};
+
+    const SEED: u64 = 7;
+
+    /* the board of the seed with the setup done to it, settled and drawn at its own size */
+    fn draw_board(setup: impl FnOnce(&mut Map, &mut AnimationService)) -> (Map, Buffer) {
+        let mut screen = Screen::new(Map::generate(&mut StdRng::seed_from_u64(SEED)));
+        setup(&mut screen.root, &mut screen.service.animation);
+        screen.root.settle(&mut screen.service.animation);
+
+        let buf = screen.render_to_buffer(parse::MAP_BKG_DRAW_STRING.size, &mut ());
+        (screen.root, buf)
+    }
+
+    fn get_corners(space: AbsoluteSpace) -> [Point2D; 2] {
+        [space.position, Point2D::new(space.right() - 1, space.bottom() - 1)]
+    }
+
+    fn crop(buf: &Buffer, space: AbsoluteSpace) -> Buffer {
+        let space = space.intersection(AbsoluteSpace::from_rect(buf.area));
+        let mut cropped = Buffer::empty(Rect::new(0, 0, space.size.x, space.size.y));
+        for y in 0..space.size.y {
+            for x in 0..space.size.x {
+                *cropped.get_mut(x, y) = buf.get(space.position.x as u16 + x, space.position.y as u16 + y).clone();
+            }
+        }
+        cropped
+    }
+
+    fn assert_snapshot(name: &str, buf: &Buffer) {
+        snapshot::assert_snapshot("render/drawing/map/snapshots", name, buf);
+    }
+
+    #[test]
+    fn board() {
+        let (_, buf) = draw_board(|_, _| ());
+        assert_snapshot("board", &buf);
+    }
+
+    #[test]
+    fn port() {
+        let (map, buf) = draw_board(|_, _| ());
+        let space = map.ports[0].to_absolute_layout_space(AbsoluteSpace::from_rect(buf.area));
+        assert_snapshot("port", &crop(&buf, space));
+    }
+
+    /* 
+     * the robber moved off the desert onto the first tile that isn't one, it stands over the top of the tile
+     * it is only a color so its cells get a symbol to show up in the text snapshot too
+     */
+    #[test]
+    fn robber() {
+        let (map, mut buf) = draw_board(|map, anim_service| {
+            let tile = map.tiles.iter().position(|tile| tile.resource != enums::TileResource::OfDesert).unwrap();
+            map.move_robber(tile, anim_service);
+        });
+
+        let robber = map.robber.to_absolute_layout_space(AbsoluteSpace::from_rect(buf.area));
+        let mut marked = 0;
+        for cell in buf.content.iter_mut().filter(|cell| cell.bg == ROBBER_STYLE.bg.unwrap()) {
+            cell.set_symbol("R");
+            marked += 1;
+        }
+        assert_eq!(marked, ROBBER_BITSHAPE.bits.count_ones());
+
+        let tile = MapTarget::Tile(map.pieces.robber).get_space();
+        let space = AbsoluteSpace::from_point_cloud(&[get_corners(robber), get_corners(tile)].concat());
+        assert_snapshot("robber", &crop(&buf, space));
+    }
+
+    fn get_preview(mode: BuildMode, legal: Vec<MapTarget>, hover: Option<MapTarget>) -> BuildPreview {
+        BuildPreview { mode, legal, hover, style: Style::default().fg(Color::Red) }
+    }
+
+    /* every spot gets a marker in the player's color if it can be built on and dimmed if it can't */
+    #[test]
+    fn legal_spots_are_marked() {
+        let legal = vec![MapTarget::Plot(0), MapTarget::Plot(5)];
+        let (_, buf) = draw_board(|map, _| map.preview = Some(get_preview(BuildMode::Settlement, legal.clone(), None)));
+        for (plot, point) in parse::MAP_GRAPH.plot_points.iter().enumerate() {
+            let cell = buf.get(point.x as u16, point.y as u16);
+            assert_eq!(cell.symbol, "●");
+            if legal.contains(&MapTarget::Plot(plot)) {
+                assert_eq!((cell.fg, cell.modifier.contains(Modifier::BOLD)), (Color::Red, true));
+            } else {
+                assert_eq!(cell.fg, ILLEGAL_STYLE.fg.unwrap());
+            }
+        }
+
+        let (map, buf) = draw_board(|map, _| map.preview = Some(get_preview(BuildMode::Road, Vec::new(), None)));
+        for &(idx0, idx1) in map.road_index.iter() {
+            let point = map.roads[idx0][idx1].as_ref().unwrap().get_middle();
+            let cell = buf.get(point.x as u16, point.y as u16);
+            assert_eq!((cell.symbol.as_str(), cell.fg), ("·", ILLEGAL_STYLE.fg.unwrap()));
+        }
+    }
+
+    /* the ghost of the piece shows up over the hovered spot, grayed out if it can't be built there */
+    #[test]
+    fn ghost_follows_hover() {
+        let get_ghost = |buf: &Buffer| -> Vec<(Point2D, Color)> {
+            AbsoluteSpace::from_rect(buf.area)
+                .into_iter()
+                .map(|point| (point, buf.get(point.x as u16, point.y as u16)))
+                .filter(|(_, cell)| cell.symbol == GHOST_SYMBOL)
+                .map(|(point, cell)| (point, cell.fg))
+                .collect()
+        };
+
+        let (_, buf) = draw_board(|map, _| map.preview = Some(get_preview(BuildMode::Settlement, vec![MapTarget::Plot(0)], None)));
+        assert!(get_ghost(&buf).is_empty());
+
+        for (plot, color) in [(0, Color::Red), (1, ILLEGAL_STYLE.fg.unwrap())] {
+            let preview = get_preview(BuildMode::Settlement, vec![MapTarget::Plot(0)], Some(MapTarget::Plot(plot)));
+            let (_, buf) = draw_board(|map, _| map.preview = Some(preview));
+            let ghost = get_ghost(&buf);
+            let point = parse::MAP_GRAPH.plot_points[plot];
+            assert!(!ghost.is_empty());
+            assert!(ghost.iter().all(|&(cell, fg)| fg == color && (cell.x - point.x).abs() <= PLOT_HIT_RADIUS.x && cell.y <= point.y), "{:?}", ghost);
+        }
+
+        let (map, _) = draw_board(|_, _| ());
+        let (a, idx1) = map.road_index[0];
+        let b = parse::MAP_GRAPH.plot_edges[a][idx1];
+        let preview = get_preview(BuildMode::Road, vec![MapTarget::Road(a, b)], Some(MapTarget::Road(a, b)));
+        let (map, buf) = draw_board(|map, _| map.preview = Some(preview));
+        let ghost = get_ghost(&buf);
+        assert!(!ghost.is_empty());
+        assert!(ghost.iter().all(|&(point, fg)| fg == Color::Red && map.hit_test_road(point) == Some((a, b))), "{:?}", ghost);
+    }
+
+    /* 
+     * the cursor only shows while the map is focused and starts on the middle tile
+     * arrows walk to the next spot of the same kind, space switches between tiles, plots and roads and enter picks the spot
+     */
+    #[test]
+    fn keyboard_walks_the_map() {
+        let size = parse::MAP_BKG_DRAW_STRING.size;
+        let mut screen = Screen::new(Map::generate(&mut StdRng::seed_from_u64(SEED)));
+        screen.render_to_buffer(size, &mut ());
+        assert_eq!(screen.root.get_cursor(), None);
+
+        screen.service.focus.focus(screen.root.mount_ref().id);
+        screen.render_to_buffer(size, &mut ());
+        let middle = MapTarget::Tile(0).get_nearest_of_kind(Point2D::new(size.x as i16/2, size.y as i16/2));
+        assert_eq!(screen.root.take_moved_cursor(), Some(middle));
+        assert_eq!(screen.root.take_moved_cursor(), None);
+
+        let press = |screen: &mut Screen<Map>, code: KeyCode| screen.service.focus.handle_key_input(KeyEvent::new(code, KeyModifiers::NONE), &mut screen.root);
+        let mut walked = 0;
+        while press(&mut screen, KeyCode::Right) {
+            let cursor = screen.root.get_cursor().unwrap();
+            assert!(matches!(cursor, MapTarget::Tile(_)) && cursor.get_center().x > middle.get_center().x);
+            walked += 1;
+            assert!(walked < 10);
+        }
+        assert!(walked > 0);
+        assert!(screen.root.take_moved_cursor().is_some());
+
+        for is_kind in [|target| matches!(target, MapTarget::Plot(_)), |target| matches!(target, MapTarget::Road(_, _)), |target| matches!(target, MapTarget::Tile(_))] {
+            assert!(press(&mut screen, KeyCode::Char(' ')));
+            assert!(is_kind(screen.root.get_cursor().unwrap()));
+        }
+
+        assert_eq!(screen.root.take_selected(), None);
+        assert!(press(&mut screen, KeyCode::Enter));
+        assert_eq!(screen.root.take_selected(), screen.root.get_cursor());
+        assert!(!screen.service.focus.handle_key_input(KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT), &mut screen.root));
+    }
+
+    /* clicks between the two closest plots go to whichever one is closer, or to neither out of reach of both */
+    #[test]
+    fn closest_plot_is_hit() {
+        let (map, _) = draw_board(|_, _| ());
+        let points = &parse::MAP_GRAPH.plot_points;
+        let distance = |offset: Point2D| (offset.x as i32).pow(2) + (2*offset.y as i32).pow(2);
+        let (a, b) = (0..points.len())
+            .flat_map(|a| (a + 1..points.len()).map(move |b| (a, b)))
+            .min_by_key(|&(a, b)| distance(points[b] - points[a]))
+            .unwrap();
+
+        let offset = points[b] - points[a];
+        let steps = offset.x.abs().max(offset.y.abs());
+        for step in 0..=steps {
+            let point = points[a] + Point2D::new(offset.x*step/steps, offset.y*step/steps);
+            let closer = if distance(point - points[a]) <= distance(point - points[b]) { a } else { b };
+            let hit = map.hit_test_plot(point);
+            assert!(hit.is_none() || hit == Some(closer), "{:?} between {} and {} hit {:?}", point, a, b, hit);
+        }
+        assert_eq!(map.hit_test_plot(points[a] + Point2D::new(offset.x.signum(), 0)), Some(a));
+        assert_eq!(map.hit_test_plot(points[b] - Point2D::new(offset.x.signum(), 0)), Some(b));
+
+        /* just under a plot counts as much as just over it */
+        assert_eq!(map.hit_test_plot(points[a] + Point2D::new(0, 1)), Some(a));
+        assert_eq!(map.hit_test_plot(points[a] - Point2D::new(0, 1)), Some(a));
+    }
+}
@@ -3,9 +3,11 @@ mod tile;
 mod port;
 mod parse;
 mod placement;
+mod export;
 
 pub use map::*;
 pub use tile::*;
 pub use port::*;
 pub use placement::*;
+pub use export::*;
 pub use parse::{MapGraph, MAP_GRAPH, MAP_TILE_CAPACITY, MAP_PORT_CAPACITY};
\ No newline at end of file
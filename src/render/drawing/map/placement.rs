@@ -7,6 +7,9 @@ use tui::{style::{Style, Color}, buffer::Cell};
 
 const PLACEMENT_Y_OFFSET: i16 = -10;
 
+/* ghosts are drawn with this over whatever is under them so the map shows through */
+pub const GHOST_SYMBOL: &str = "░";
+
 pub trait Placement: MountableLayout {
     fn set_placement_style(&mut self, style: Style);
     fn get_placement_space(&self) -> Space;
@@ -23,17 +26,47 @@ pub trait Placement: MountableLayout {
     
         self.animate_space_from(service, start_space, end_space, 0.5, EasingStyle::Cubic, EasingDirection::Out);
     } 
+
+    /* skip to the end of building the piece */
+    fn settle(&mut self, service: &mut AnimationService) {
+        let end_space = self.get_placement_space();
+        let layout = self.layout_mut();
+        if let Some(mut anim) = layout.anim.take() {
+            anim.cancel(service);
+        }
+        layout.set_space(end_space);
+    }
+
+    /* building in reverse, the piece lifts back up off the map and has to be unbuilt once it is done */
+    fn unplace(&mut self, service: &mut AnimationService) {
+        let mut end_space = self.get_placement_space();
+        end_space.position.y.offset += PLACEMENT_Y_OFFSET;
+        self.animate_space(service, end_space, 0.5, EasingStyle::Cubic, EasingDirection::In);
+    }
+
+    /* take the piece off the map right away, leaving it where it would be built again */
+    fn unbuild(&mut self, service: &mut AnimationService) {
+        let end_space = self.get_placement_space();
+        let layout = self.layout_mut();
+        if let Some(mut anim) = layout.anim.take() {
+            anim.cancel(service);
+        }
+        layout.set_visible(false).set_space(end_space);
+    }
 }
 
 // ROAD
 
 #[derive(Debug)]
 pub struct Road {
+    /* top left of the road in map coordinates */
+    origin: Point2D,
     start: Point2D,
     change: Point2D,
     steps: u8,
     mount: Mount,
-    style: Style,
+    pub style: Style,
+    is_ghost: bool,
     placement_space: Space,
     pub layout: DrawLayout,
 }
@@ -51,17 +84,46 @@ impl Road {
             .set_position(UDim2::from_point2d(top_left_point));
         
         Road {
+            origin: top_left_point,
             start: from,
             change: Point2D::new(dx.signum(), dy.signum()),
             steps: dx.abs().max(dy.abs()) as u8,
             mount: Mount::default(),
             style,
+            is_ghost: false,
             placement_space: layout.space,
             layout
         }
     }
 }
 
+impl Road {
+    /* a see-through preview of the road, style only needs a foreground */
+    pub fn into_ghost(mut self) -> Self {
+        self.is_ghost = true;
+        self.layout.set_visible(true);
+        self
+    }
+
+    /* if the road covers the point in map coordinates whether it is built or not */
+    pub fn covers(&self, map_point: Point2D) -> bool {
+        let point = map_point - self.origin;
+        (0..=self.steps as i16).any(|step| {
+            let cell = self.get_step(step);
+            cell.y == point.y && (cell.x - point.x).abs() <= 1
+        })
+    }
+
+    /* the middle of the road in map coordinates */
+    pub fn get_middle(&self) -> Point2D {
+        self.origin + self.get_step(self.steps as i16/2)
+    }
+
+    fn get_step(&self, step: i16) -> Point2D {
+        self.start + Point2D::new(self.change.x*step, self.change.y*step)
+    }
+}
+
 impl Placement for Road {
     fn get_placement_space(&self) -> Space { self.placement_space }
     fn set_placement_style(&mut self, style: Style) { self.style = style; }
@@ -69,11 +131,12 @@ impl Placement for Road {
 
 impl Drawable for Road {
     fn draw(&self, ctx: &mut DrawContext) {
+        let symbol = if self.is_ghost { GHOST_SYMBOL } else { " " };
         for offset in -1..=1 {
             let mut point = self.start + Point2D::new(offset, 0);
             for _ in 0..=self.steps {
                 if let Some(cell) = ctx.cell_at_mut(point) {
-                    cell.set_symbol(" ").set_style(self.style);
+                    cell.set_symbol(symbol).set_style(self.style);
                 }
                 point = point + self.change;
             }
@@ -114,6 +177,7 @@ pub struct Building {
 
     pub kind: enums::Building,
 
+    is_ghost: bool,
     pub style: Style,
     pub layout: DrawLayout,
 }
@@ -128,10 +192,18 @@ impl Building {
             placement_space: layout.space,
             kind,
             mount: Mount::default(),
+            is_ghost: false,
             style,
             layout,
         }
     }
+
+    /* a see-through preview of the building, style only needs a foreground */
+    pub fn into_ghost(mut self) -> Self {
+        self.is_ghost = true;
+        self.layout.set_visible(true);
+        self
+    }
 }
 
 impl Placement for Building {
@@ -141,6 +213,11 @@ impl Placement for Building {
 
 impl Drawable for Building {
     fn draw(&self, ctx: &mut DrawContext) {
+        if self.is_ghost {
+            ctx.draw_child(&Shape128::new(self.shape, GHOST_SYMBOL, self.style, DrawLayout::default()));
+            return
+        }
+
         ctx.draw_child(&Shape128::new(self.shape, " ", self.style, DrawLayout::default()));
         for point in self.hole {
             if let Some(cell) = ctx.cell_at_mut(point) {
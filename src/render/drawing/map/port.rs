@@ -9,7 +9,7 @@ use std::cell::RefCell;
 
 const PORT_RESOURCE_SYMBOL_OFFSET: Point2D = Point2D::new(-1, 0);
 const PORT_RATIO_OFFSET: Point2D = Point2D::new(-1, 1);
-const PORT_COLOR: Color = Color::Rgb(221, 149, 47);
+pub const PORT_COLOR: Color = Color::Rgb(221, 149, 47);
 
 lazy_static! {
     static ref PORT_BITSHAPES: Vec<(AbsoluteSpace, BitShape)> = {
@@ -58,7 +58,7 @@ lazy_static! {
 #[derive(Debug)]
 pub struct Port {
     layout: DrawLayout,
-    resource: enums::PortResource,
+    pub resource: enums::PortResource,
     ratio: String,
     shape: Shape<'static>,
     mark: Point2D,
@@ -123,6 +123,14 @@ impl Port {
         anim.1.play(anim_service);
         self.anim = Some(anim);
     }
+
+    /* show the port without growing it in */
+    pub fn settle(&mut self, anim_service: &mut AnimationService) {
+        if let Some(mut anim) = self.anim.take() {
+            anim.1.cancel(anim_service);
+        }
+        self.layout.set_visible(true);
+    }
 }
 
 impl Layoutable for Port {
@@ -164,6 +172,7 @@ impl MountableLayout for Port {
             if anim.1.state.playback != PlaybackState::Playing {
                 self.anim = None;
             }
+            ctx.invalidate(self);
         }
 
         ctx.relayout_children_of(self)
@@ -61,6 +61,7 @@ lazy_static! {
 pub struct Tile {
     pub layout: DrawLayout,
     pub resource: enums::TileResource,
+    pub roll: u8,
     is_best: bool,
     bkg: Shape<'static>,
     digit0: Option<Shape128>,
@@ -122,6 +123,7 @@ impl Tile {
         Tile {
             layout,
             resource,
+            roll,
             is_best,
             bkg,
             anim: TileAnimation::new(rarity),
@@ -135,6 +137,16 @@ impl Tile {
         self.anim.play(anim_service);
     }
 
+    /* skip to how the tile looks once it has dropped in */
+    pub fn settle(&mut self, anim_service: &mut AnimationService) {
+        self.anim.cancel(anim_service);
+        self.anim.animator.bkg_alpha = 1.0;
+        self.anim.animator.rarity_alphas = [1.0; 6];
+        for digit in [self.digit0.as_mut(), self.digit1.as_mut()].into_iter().flatten() {
+            digit.layout.set_visible(true);
+        }
+    }
+
     fn draw_common(&self, area: &mut DrawContext) {
         area.draw_child(&self.bkg);
         area.draw_unicode_line(
@@ -221,6 +233,7 @@ impl MountableLayout for Tile {
     fn relayout(&mut self, ctx: &mut LayoutContext) {
         if self.anim.state.playback == PlaybackState::Playing {
             self.anim.update(&mut ());
+            ctx.invalidate(self);
         }
         ctx.relayout_children_of(self);
     }
@@ -0,0 +1,289 @@
+/*
+ * minimap.rs
+ * the whole map drawn small in the corner of the map pane while the map doesn't fit in it
+ *
+ * every cell is two pixels stacked with a half block, each pixel colored by the tile under its middle
+ * the part of the map in view is outlined and clicking (or dragging) somewhere on the minimap moves the view there
+ */
+
+use super::{drag::Dragger, map::{Map, MAP_OCEAN_COLOR}};
+
+use crate::render::prelude::*;
+
+use tui::{style::{Color, Style}, widgets::{Block, Borders}};
+
+/* how many cells across the inside of the minimap is, the height follows from the map */
+const MINIMAP_WIDTH: u16 = 16;
+const VIEW_COLOR: Color = Color::White;
+
+lazy_static! {
+    static ref BORDER_STYLE: Style = Style::default().fg(Color::White).bg(Color::Black);
+}
+
+#[derive(Debug)]
+pub struct Minimap {
+    /* pixel colors row by row, two rows of pixels to a row of cells */
+    pixels: Vec<Color>,
+    /* cells inside the border */
+    size: Size2D,
+    /* how many cells of the map go across and down one pixel */
+    pixel_size: Float2D,
+    /* the point of the map the minimap was clicked on since this was last taken */
+    jump: Option<Point2D>,
+    /* set when the pixels changed since the last relayout */
+    is_damaged: bool,
+    absolute_space: AbsoluteSpace,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Minimap {
+            pixels: Vec::new(),
+            size: Size2D::default(),
+            pixel_size: Float2D::new(1.0, 1.0),
+            jump: None,
+            is_damaged: false,
+            absolute_space: AbsoluteSpace::default(),
+            layout: DrawLayout::default()
+                .set_anchor(Float2D::new(1.0, 0.0))
+                .set_visible(false)
+                .clone(),
+            mount: Mount::default()
+        }
+    }
+
+    /*
+     * redraw the pixels from the map and its view, the minimap only shows when the map is clipped
+     * and there is room for it in the given space without covering most of the map
+     */
+    pub fn update(&mut self, dragger: &Dragger<Map>, room: Size2D) {
+        let drawing_size = dragger.get_drawing_size();
+        if drawing_size.x == 0 || drawing_size.y == 0 {
+            self.layout.set_visible(false);
+            return
+        }
+
+        /* pixels are about square because cells are about twice as tall as they are wide */
+        let rows = ((MINIMAP_WIDTH as u32*drawing_size.y as u32 + drawing_size.x as u32/2)/drawing_size.x as u32).max(1) as u16;
+        self.size = Size2D::new(MINIMAP_WIDTH, rows);
+        self.layout.set_size(UDim2::from_size2d(Size2D::new(self.size.x + 2, self.size.y + 2)));
+        self.layout.set_visible(
+            dragger.is_clipped()
+            && 2*(self.size.x + 2) <= room.x
+            && 2*(self.size.y + 2) <= room.y
+        );
+        if !self.layout.is_visible {
+            return
+        }
+
+        let (width, height) = (self.size.x as usize, 2*self.size.y as usize);
+        self.pixel_size = Float2D::new(drawing_size.x as f32/width as f32, drawing_size.y as f32/height as f32);
+        let mut pixels: Vec<Color> = (0..width*height)
+            .map(|i| {
+                let pixel = Point2D::new((i % width) as i16, (i/width) as i16);
+                dragger.drawing.get_tile_color_at(self.to_map_point(pixel)).unwrap_or(MAP_OCEAN_COLOR)
+            })
+            .collect();
+
+        /* outline the view, clamped so a view past the edge of the map still shows */
+        let view = dragger.get_view();
+        let left = ((view.left() as f32/self.pixel_size.x) as usize).min(width - 1);
+        let right = (((view.right() - 1) as f32/self.pixel_size.x) as usize).clamp(left, width - 1);
+        let top = ((view.top() as f32/self.pixel_size.y) as usize).min(height - 1);
+        let bottom = (((view.bottom() - 1) as f32/self.pixel_size.y) as usize).clamp(top, height - 1);
+        for x in left..=right {
+            pixels[top*width + x] = VIEW_COLOR;
+            pixels[bottom*width + x] = VIEW_COLOR;
+        }
+        for y in top..=bottom {
+            pixels[y*width + left] = VIEW_COLOR;
+            pixels[y*width + right] = VIEW_COLOR;
+        }
+
+        if pixels != self.pixels {
+            self.pixels = pixels;
+            self.is_damaged = true;
+        }
+    }
+
+    /* the point of the map the minimap was clicked on since this was last called */
+    pub fn take_jump(&mut self) -> Option<Point2D> {
+        self.jump.take()
+    }
+
+    /* the middle of a pixel in map coordinates */
+    fn to_map_point(&self, pixel: Point2D) -> Point2D {
+        Point2D::new(
+            ((pixel.x as f32 + 0.5)*self.pixel_size.x) as i16,
+            ((pixel.y as f32 + 0.5)*self.pixel_size.y) as i16
+        )
+    }
+}
+
+impl Layoutable for Minimap {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for Minimap {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        ctx.draw_widget(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(*BORDER_STYLE),
+            AbsoluteSpace::new(0, 0, self.size.x + 2, self.size.y + 2)
+        );
+
+        let width = self.size.x as usize;
+        for y in 0..self.size.y as usize {
+            for x in 0..width {
+                let (top, bottom) = (self.pixels[2*y*width + x], self.pixels[(2*y + 1)*width + x]);
+                if let Some(cell) = ctx.cell_at_mut(Point2D::new(x as i16 + 1, y as i16 + 1)) {
+                    cell.set_symbol("▀").set_fg(top).set_bg(bottom);
+                }
+            }
+        }
+    }
+}
+
+impl MountableLayout for Minimap {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        self.absolute_space = ctx.get_absolute_layout_space_of(self);
+        ctx.relayout_input_space_of(self, Space::FULL);
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+    }
+
+    /* pressing or dragging over the minimap jumps the view to that spot */
+    fn on_mouse_input(&mut self, event: InputEvent) -> bool {
+        match event.kind {
+            InputEventKind::Down(point) | InputEventKind::Drag(point) => {
+                let cell = self.absolute_space.relative_position_of(point) - Point2D::new(1, 1);
+                if cell.x < 0 || cell.y < 0 || cell.x >= self.size.x as i16 || cell.y >= self.size.y as i16 {
+                    return false
+                }
+
+                let point = self.to_map_point(Point2D::new(cell.x, 2*cell.y));
+                self.jump = Some(Point2D::new(point.x, point.y + (self.pixel_size.y/2.0) as i16));
+                true
+            },
+            InputEventKind::Click(_) => true,
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::screen::Screen;
+
+    use crossterm::event::{KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const WINDOW_SIZE: Size2D = Size2D::new(40, 20);
+    const ROOM: Size2D = Size2D::new(80, 40);
+
+    /* the map in a window too small for it at full size, zoomed in and scrolled to its top left */
+    fn get_dragger() -> Screen<Dragger<Map>> {
+        let mut screen = Screen::new(Dragger::new(Map::generate(&mut StdRng::seed_from_u64(7)), Style::default()));
+        screen.render_to_buffer(WINDOW_SIZE, &mut ());
+        while screen.root.zoom(-1) {}
+        screen.root.focus(Point2D::new(0, 0));
+        screen.render_to_buffer(WINDOW_SIZE, &mut ());
+        screen
+    }
+
+    fn get_pixel(minimap: &Minimap, pixel: Point2D) -> Color {
+        minimap.pixels[pixel.y as usize*minimap.size.x as usize + pixel.x as usize]
+    }
+
+    #[test]
+    fn only_shows_while_the_map_is_clipped() {
+        let mut dragger = get_dragger();
+        let mut minimap = Minimap::new();
+        minimap.update(&dragger.root, ROOM);
+        assert!(minimap.layout.is_visible);
+
+        /* not when it would cover most of the map */
+        minimap.update(&dragger.root, Size2D::new(ROOM.x, minimap.size.y));
+        assert!(!minimap.layout.is_visible);
+
+        while dragger.root.zoom(1) {}
+        dragger.render_to_buffer(WINDOW_SIZE, &mut ());
+        assert!(!dragger.root.is_clipped());
+        minimap.update(&dragger.root, ROOM);
+        assert!(!minimap.layout.is_visible);
+    }
+
+    /* pixels take the color of the tile under them and the view is outlined on top */
+    #[test]
+    fn pixels_show_tiles_and_the_view() {
+        let dragger = get_dragger();
+        let mut minimap = Minimap::new();
+        minimap.update(&dragger.root, ROOM);
+
+        let view = dragger.root.get_view();
+        let outline = Point2D::new(
+            ((view.right() - 1) as f32/minimap.pixel_size.x) as i16,
+            ((view.bottom() - 1) as f32/minimap.pixel_size.y) as i16
+        );
+        let mut colors = Vec::new();
+        for y in 0..2*minimap.size.y as i16 {
+            for x in 0..minimap.size.x as i16 {
+                let pixel = Point2D::new(x, y);
+                let color = get_pixel(&minimap, pixel);
+                let is_outline = (x == 0 || x == outline.x) && y <= outline.y || (y == 0 || y == outline.y) && x <= outline.x;
+                if is_outline {
+                    assert_eq!(color, VIEW_COLOR, "{:?}", pixel);
+                } else {
+                    assert_eq!(color, dragger.root.drawing.get_tile_color_at(minimap.to_map_point(pixel)).unwrap_or(MAP_OCEAN_COLOR));
+                    if !colors.contains(&color) {
+                        colors.push(color);
+                    }
+                }
+            }
+        }
+        assert!(colors.contains(&MAP_OCEAN_COLOR));
+        assert!(colors.len() > 3, "{:?}", colors);
+    }
+
+    /* pressing inside the border jumps the map to the middle of the cell pressed */
+    #[test]
+    fn pressing_jumps_the_view() {
+        let dragger = get_dragger();
+        let mut minimap = Minimap::new();
+        minimap.update(&dragger.root, ROOM);
+        minimap.layout.set_anchor(Float2D::new(0.0, 0.0));
+
+        let size = Size2D::new(minimap.size.x + 2, minimap.size.y + 2);
+        let mut screen = Screen::new(minimap);
+        let buf = screen.render_to_buffer(size, &mut ());
+        assert_eq!((buf.get(1, 1).symbol.as_str(), buf.get(1, 1).fg), ("▀", VIEW_COLOR));
+
+        let mut press = |column, row| {
+            let event = MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, modifiers: KeyModifiers::NONE };
+            screen.service.input.handle_mouse_input(event, &mut screen.root);
+            let event = MouseEvent { kind: MouseEventKind::Up(MouseButton::Left), column, row, modifiers: KeyModifiers::NONE };
+            screen.service.input.handle_mouse_input(event, &mut screen.root);
+            screen.root.take_jump()
+        };
+        assert_eq!(press(0, 0), None);
+
+        let jump = press(3, 2).unwrap();
+        let pixel_size = screen.root.pixel_size;
+        assert_eq!((jump.x as f32/pixel_size.x) as i16, 2);
+        assert_eq!((jump.y as f32/(2.0*pixel_size.y)) as i16, 1);
+        assert_eq!(screen.root.take_jump(), None);
+    }
+}
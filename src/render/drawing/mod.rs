@@ -2,4 +2,12 @@ pub mod map;
 pub mod game;
 pub mod players;
 pub mod counter;
-pub mod drag;
\ No newline at end of file
+pub mod drag;
+pub mod minimap;
+pub mod lobby;
+pub mod chat;
+pub mod command;
+pub mod dialog;
+pub mod events;
+pub mod stats;
+pub mod text;
\ No newline at end of file
@@ -1,14 +1,28 @@
-use crate::render::{draw::*, space::*, drawing::counter::*};
+use crate::render::{prelude::*, drawing::counter::*};
+use crate::logic::{session::Presence, model::PlayerModel, clock::format_time};
 
 use tui::style::{Style, Color, Modifier};
 use unicode_width::UnicodeWidthStr;
 
+use std::time::Instant;
+
+/* how long the numbers that changed stay lit up */
+const CHANGE_FLASH_DURATION: f32 = 1.5;
+
+/* how much of the turn has to be left for the time bar to be green and then yellow, it is red after that */
+const TIME_BAR_GREEN: f32 = 0.5;
+const TIME_BAR_YELLOW: f32 = 0.2;
+
 /* PlayerList */
 
 #[derive(Debug)]
 pub struct PlayerList {
     pub frames: Vec<PlayerFrame>,
-    pub layout: DrawLayout
+    /* the first sync fills the frames in without anything lighting up */
+    is_synced: bool,
+    is_damaged: bool,
+    pub layout: DrawLayout,
+    mount: Mount
 }
 
 impl PlayerList {
@@ -18,7 +32,23 @@ impl PlayerList {
                 .set_position(UDim2::new(0.5, 0, 1.0, -(i as i16)*(PLAYER_FRAME_SIZE.y.offset as i16)))
                 .set_anchor(Float2D::new(0.5, 1.0));
         }
-        PlayerList { frames, layout }
+        PlayerList { frames, is_synced: false, is_damaged: false, layout, mount: Mount::default() }
+    }
+
+    /* show the players as the model has them, lighting up the numbers that changed since the last sync */
+    pub fn sync(&mut self, players: &[PlayerModel], anim_service: &mut AnimationService) {
+        for (frame, player) in self.frames.iter_mut().zip(players.iter()) {
+            if frame.player == *player {
+                continue
+            }
+
+            if self.is_synced {
+                frame.flash_changes(player, anim_service);
+            }
+            frame.player = player.clone();
+            self.is_damaged = true;
+        }
+        self.is_synced = true;
     }
 }
 
@@ -28,21 +58,71 @@ impl Layoutable for PlayerList {
 }
 
 impl StatefulDrawable for PlayerList {
-    type State = [PlayerFrameState];
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        ctx.draw_children(&self.frames);
+    }
+}
+
+impl MountableLayout for PlayerList {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
 
-    fn stateful_draw(&self, ctx: &mut DrawContext, state: &Self::State) {
-        ctx.draw_stateful_children(self.frames.as_slice(), state);
+    /* the frames aren't mounted so the list redraws itself while anything in it changes, or just the frame with a clock running down */
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        let absolute_list_space = ctx.get_absolute_layout_space_of(self);
+        let now = Instant::now();
+        for frame in self.frames.iter() {
+            if frame.player.clock.is_some_and(|clock| clock.get_turn_left(now).is_some()) {
+                ctx.invalidate_space_in(self, frame.to_absolute_layout_space(absolute_list_space));
+            }
+        }
+
+        let mut is_damaged = std::mem::take(&mut self.is_damaged);
+        for frame in self.frames.iter_mut() {
+            if let Some(anim) = frame.flash.as_mut() {
+                anim.update(&mut ());
+                if anim.state.playback != PlaybackState::Playing {
+                    frame.flash = None;
+                    frame.changed.clear();
+                }
+                is_damaged = true;
+            }
+        }
+
+        if is_damaged {
+            ctx.invalidate(self);
+        }
     }
 }
 
-pub const PLAYER_FRAME_SIZE: UDim2 = UDim2::new(1.0, 0, 0.0, 5);
+pub const PLAYER_FRAME_SIZE: UDim2 = UDim2::new(1.0, 0, 0.0, 6);
+
+/* the numbers on a player frame */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PlayerStat {
+    VictoryPoints,
+    Resources,
+    Developments,
+    Army,
+    Road
+}
+
+impl PlayerStat {
+    const ALL: [PlayerStat; 5] = [Self::VictoryPoints, Self::Resources, Self::Developments, Self::Army, Self::Road];
 
-pub struct PlayerFrameState {
-    pub victory_point_count: u8,
-    pub resource_card_count: u8,
-    pub development_card_count: u8,
-    pub largest_army_count: u8,
-    pub longest_road_count: u8
+    fn get(&self, player: &PlayerModel) -> u8 {
+        match self {
+            Self::VictoryPoints => player.victory_points,
+            Self::Resources => player.resource_count,
+            Self::Developments => player.development_count,
+            Self::Army => player.knights_played,
+            Self::Road => player.road_length
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -53,12 +133,49 @@ pub struct PlayerFrame {
     road_counter: SymbolCounter,
     resource_counter: SymbolCounter,
     development_counter: SymbolCounter,
+    /* the player as of the last sync and the numbers lit up since they changed */
+    player: PlayerModel,
+    changed: Vec<PlayerStat>,
+    flash: Option<Animation<()>>,
     
     pub layout: DrawLayout
 }
 
 /* PlayerFrame */
 
+impl PlayerFrame {
+    pub fn get_color(&self) -> Color {
+        self.player_color
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.player_name
+    }
+
+    /* light up the numbers that differ in the player, the ones still lit from before stay lit */
+    fn flash_changes(&mut self, player: &PlayerModel, anim_service: &mut AnimationService) {
+        let changed: Vec<PlayerStat> = PlayerStat::ALL
+            .into_iter()
+            .filter(|stat| stat.get(&self.player) != stat.get(player) && !self.changed.contains(stat))
+            .collect();
+        if changed.is_empty() {
+            return
+        }
+        self.changed.extend(changed);
+
+        if let Some(mut anim) = self.flash.take() {
+            anim.cancel(anim_service);
+        }
+        let mut anim = Animation::with_duration(CHANGE_FLASH_DURATION, ());
+        anim.play(anim_service);
+        self.flash = Some(anim);
+    }
+
+    fn get_text_style(&self, stat: PlayerStat, style: Style) -> Style {
+        if self.changed.contains(&stat) { *HIGHLIGHTED_TEXT_STYLE } else { style }
+    }
+}
+
 lazy_static! {
     static ref CARD_SYMBOL_STYLE: Style = Style::default().fg(Color::White);
     static ref CARD_TEXT_STYLE: Style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
@@ -71,6 +188,9 @@ impl PlayerFrame {
             layout,
             player_name,
             player_color,
+            player: PlayerModel::default(),
+            changed: Vec::new(),
+            flash: None,
             resource_counter: SymbolCounter::new(
                 String::from("??"), 
                 SymbolCounterKind::Card, 
@@ -133,29 +253,60 @@ impl Layoutable for PlayerFrame {
     fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
 }
 
-impl StatefulDrawable for PlayerFrame {
-    type State = PlayerFrameState;
-
-    fn stateful_draw(&self, ctx: &mut DrawContext, state: &Self::State) {
-        ctx.draw_stateful_child(&self.army_counter, &state.largest_army_count);
-        ctx.draw_stateful_child(&self.road_counter, &state.longest_road_count);
-        ctx.draw_stateful_child(&self.resource_counter, &state.resource_card_count);
-        ctx.draw_stateful_child(&self.development_counter, &state.development_card_count);
+impl Drawable for PlayerFrame {
+    fn draw(&self, ctx: &mut DrawContext) {
+        let state = &self.player;
+        for (counter, stat) in [
+            (&self.army_counter, PlayerStat::Army),
+            (&self.road_counter, PlayerStat::Road),
+            (&self.resource_counter, PlayerStat::Resources),
+            (&self.development_counter, PlayerStat::Developments)
+        ] {
+            ctx.draw_stateful_child(counter, &SymbolCount { count: stat.get(state), is_highlighted: self.changed.contains(&stat) });
+        }
 
-        let vp_string = &format!("🏆: {:0width$}", state.victory_point_count.min(99), width = 2 as usize);
+        let vp_string = &format!("🏆: {:0width$}", state.victory_points.min(99), width = 2 as usize);
 
         ctx.draw_unicode_line(
             &vp_string,
             Point2D::new((ctx.absolute_layout_space.size.x as i16 - vp_string.width() as i16)/2, 1),
-            Style::default()
-                .fg(Color::White)
-                .add_modifier(Modifier::BOLD)
+            self.get_text_style(
+                PlayerStat::VictoryPoints,
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD)
+            )
         );
         ctx.draw_unicode_line(
             &tui::symbols::line::NORMAL.horizontal.repeat(ctx.absolute_layout_space.size.x as usize),
             Point2D::new(0, 0),
             Style::default().fg(Color::White)
         );
+
+        /* the running clock shrinks along the top line with its countdown on the right, everyone else shows their game clock there */
+        if let Some(clock) = state.clock {
+            let width = ctx.absolute_layout_space.size.x as i16;
+            let (text, style) = match clock.get_turn_left(Instant::now()) {
+                Some((left, fraction)) => {
+                    let color = if fraction > TIME_BAR_GREEN {
+                        Color::Green
+                    } else if fraction > TIME_BAR_YELLOW {
+                        Color::Yellow
+                    } else {
+                        Color::Red
+                    };
+                    ctx.draw_unicode_line(
+                        &tui::symbols::line::THICK.horizontal.repeat((fraction*width as f32).ceil() as usize),
+                        Point2D::new(0, 0),
+                        Style::default().fg(color)
+                    );
+                    (format_time(left), Style::default().fg(color).add_modifier(Modifier::BOLD))
+                },
+                None => (clock.bank.map(format_time).unwrap_or_default(), Style::default().fg(Color::DarkGray))
+            };
+            ctx.draw_string_line(&text, Point2D::new(width - text.len() as i16 - 1, 1), style);
+        }
+
         ctx.draw_string_line(
             &format!(" {} ", self.player_name),
             Point2D::new((ctx.absolute_layout_space.size.x as i16 - self.player_name.len() as i16)/2 - 1, 0),
@@ -163,6 +314,20 @@ impl StatefulDrawable for PlayerFrame {
                 .fg(self.player_color)
                 .add_modifier(Modifier::BOLD)
         );
+
+        /* let everyone know when a player dropped and who is playing their seat */
+        if state.presence != Presence::Connected {
+            ctx.draw_string_line(state.presence.get_label(), Point2D::new(1, 1), Style::default().fg(Color::DarkGray));
+        }
+
+        if let Some(hand) = state.hand.as_ref() {
+            let hand_string = hand.to_string();
+            ctx.draw_string_line(
+                &hand_string,
+                Point2D::new((ctx.absolute_layout_space.size.x as i16 - hand_string.len() as i16)/2, 5),
+                Style::default().fg(Color::Gray)
+            );
+        }
     }
 }
 
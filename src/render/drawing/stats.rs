@@ -0,0 +1,376 @@
+/*
+ * stats.rs
+ * the statistics dashboard, drawn over the game while it is open and opened on its own once the game is won
+ *
+ * dice rolls are bars with a line across each one at the height the odds say the bar should have reached by now
+ * tiles are bars colored by their resource going from the tile that handed out the most on down, the desert never hands out anything so it is left out
+ * everything shown is counted by the model (see logic/stats.rs), the dashboard only redraws when the counts change
+ */
+
+use crate::render::prelude::*;
+use crate::logic::{board::Board, stats::*};
+use crate::enums::{Resource, TileResource};
+
+use tui::{
+    layout::*,
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{Axis, BarChart, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table}
+};
+
+const ROLL_BAR_COLOR: Color = Color::Cyan;
+const EXPECTED_COLOR: Color = Color::Yellow;
+/* the widest bars get, past that the charts just get more room around them */
+const MAX_BAR_WIDTH: u16 = 5;
+/* tile bars are labeled with numbers up to 12, tiles that don't fit at this width are left off the end */
+const MIN_TILE_BAR_WIDTH: u16 = 2;
+
+lazy_static! {
+    static ref BORDER_STYLE: Style = Style::default().fg(Color::White).bg(Color::Black);
+    static ref NOTE_STYLE: Style = Style::default().fg(Color::DarkGray);
+    static ref HEADER_STYLE: Style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
+}
+
+#[derive(Debug)]
+pub struct StatsView {
+    stats: GameStats,
+    board: Board,
+    players: Vec<(String, Color)>,
+    /* how to close the dashboard, shown on its bottom border */
+    pub hint: String,
+    is_damaged: bool,
+    pub layout: DrawLayout,
+    mount: Mount
+}
+
+impl StatsView {
+    /* closed until it is opened */
+    pub fn new(board: Board, players: Vec<(String, Color)>) -> Self {
+        StatsView {
+            stats: GameStats::new(players.len(), board.tiles.len()),
+            board,
+            players,
+            hint: String::new(),
+            is_damaged: false,
+            layout: DrawLayout::default().set_visible(false).clone(),
+            mount: Mount::default()
+        }
+    }
+
+    /* show the stats as the model has them */
+    pub fn sync(&mut self, stats: &GameStats) {
+        if self.stats != *stats {
+            self.stats = stats.clone();
+            self.is_damaged = true;
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.layout.is_visible
+    }
+
+    pub fn set_open(&mut self, is_open: bool) {
+        self.layout.set_visible(is_open);
+    }
+
+    /* bars as wide as fit with a gap between them, the space left over goes on both sides */
+    fn fit_bars(width: u16, count: u16, min_width: u16) -> (u16, u16) {
+        let bar_width = (width/count.max(1)).saturating_sub(1).clamp(min_width, MAX_BAR_WIDTH);
+        let used = (count*(bar_width + 1)).saturating_sub(1);
+        (bar_width, width.saturating_sub(used)/2)
+    }
+
+    fn draw_dice(&self, ctx: &mut DrawContext, space: AbsoluteSpace) {
+        let total = self.stats.get_total_rolls();
+        let inner = Self::draw_section(ctx, " Dice ", space);
+        ctx.draw_widget(
+            Paragraph::new(Spans::from(vec![
+                Span::styled(format!("{} rolls, ", total), *NOTE_STYLE),
+                Span::styled(symbols::line::NORMAL.horizontal, Style::default().fg(EXPECTED_COLOR)),
+                Span::styled(" expected", *NOTE_STYLE)
+            ])),
+            AbsoluteSpace::new(inner.position.x, inner.position.y, inner.size.x, 1)
+        );
+
+        let rolls: Vec<u8> = (MIN_ROLL..=MAX_ROLL).collect();
+        let labels: Vec<String> = rolls.iter().map(|roll| roll.to_string()).collect();
+        let counts: Vec<u64> = rolls.iter().map(|&roll| self.stats.get_roll_count(roll) as u64).collect();
+        let expected: Vec<f64> = rolls.iter().map(|&roll| self.stats.get_expected_rolls(roll)).collect();
+        let max = counts
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(expected.iter().copied().fold(0.0, f64::max).ceil() as u64)
+            .max(1);
+
+        let (bar_width, offset) = Self::fit_bars(inner.size.x, rolls.len() as u16, 1);
+        let chart = AbsoluteSpace::new(
+            inner.position.x + offset as i16,
+            inner.position.y + 1,
+            inner.size.x.saturating_sub(offset),
+            inner.size.y.saturating_sub(1)
+        );
+        let data: Vec<(&str, u64)> = labels.iter().map(String::as_str).zip(counts.iter().copied()).collect();
+        ctx.draw_widget(
+            BarChart::default()
+                .data(&data)
+                .max(max)
+                .bar_width(bar_width)
+                .bar_gap(1)
+                .bar_style(Style::default().fg(ROLL_BAR_COLOR))
+                .value_style(Style::default().fg(Color::Black).bg(ROLL_BAR_COLOR))
+                .label_style(Style::default().fg(Color::White)),
+            chart
+        );
+
+        /* the bar chart leaves its bottom row for labels, the expected line goes in the row the expected height ends in */
+        let height = chart.size.y.saturating_sub(1);
+        if height == 0 || total == 0 {
+            return
+        }
+        for (i, &expected) in expected.iter().enumerate() {
+            let rows = ((expected*height as f64/max as f64).ceil() as u16).clamp(1, height);
+            let y = chart.position.y + (height - rows) as i16;
+            for x in 0..bar_width {
+                let point = Point2D::new(chart.position.x + (i as u16*(bar_width + 1) + x) as i16, y);
+                if let Some(cell) = ctx.cell_at_mut(point) {
+                    let bg = if cell.symbol == " " { Color::Black } else { ROLL_BAR_COLOR };
+                    cell.set_symbol(symbols::line::NORMAL.horizontal).set_fg(EXPECTED_COLOR).set_bg(bg);
+                }
+            }
+        }
+    }
+
+    fn draw_production(&self, ctx: &mut DrawContext, space: AbsoluteSpace) {
+        let inner = Self::draw_section(ctx, " Production per tile ", space);
+        let legend: Vec<Span> = Resource::ALL
+            .iter()
+            .map(|resource| Span::styled(format!("{} ", resource.get_name()), Style::default().fg(resource.get_color())))
+            .collect();
+        ctx.draw_widget(Paragraph::new(Spans::from(legend)), AbsoluteSpace::new(inner.position.x, inner.position.y, inner.size.x, 1));
+
+        /* the tiles that handed out the most first, ties go in board order */
+        let mut tiles: Vec<(usize, Resource)> = self.board.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(tile, board_tile)| match board_tile.resource {
+                TileResource::Of(resource) => Some((tile, resource)),
+                TileResource::OfDesert => None
+            })
+            .collect();
+        tiles.sort_by_key(|&(tile, _)| std::cmp::Reverse(self.stats.get_production(tile)));
+
+        let labels: Vec<String> = tiles.iter().map(|&(tile, _)| self.board.tiles[tile].roll.to_string()).collect();
+        let data: Vec<(&str, u64)> = labels
+            .iter()
+            .map(String::as_str)
+            .zip(tiles.iter().map(|&(tile, _)| self.stats.get_production(tile) as u64))
+            .collect();
+
+        let (bar_width, offset) = Self::fit_bars(inner.size.x, tiles.len() as u16, MIN_TILE_BAR_WIDTH);
+        let chart = AbsoluteSpace::new(
+            inner.position.x + offset as i16,
+            inner.position.y + 1,
+            inner.size.x.saturating_sub(offset),
+            inner.size.y.saturating_sub(1)
+        );
+        ctx.draw_widget(
+            BarChart::default()
+                .data(&data)
+                .bar_width(bar_width)
+                .bar_gap(1)
+                .value_style(Style::default().fg(Color::Black).add_modifier(Modifier::BOLD))
+                .label_style(Style::default().fg(Color::White)),
+            chart
+        );
+
+        /* the bar chart draws every bar in one color, so each bar is painted over in the color of its tile's resource */
+        let height = chart.size.y.saturating_sub(1);
+        for (i, &(_, resource)) in tiles.iter().enumerate() {
+            for x in 0..bar_width {
+                for y in 0..height {
+                    let point = Point2D::new(chart.position.x + (i as u16*(bar_width + 1) + x) as i16, chart.position.y + y as i16);
+                    if let Some(cell) = ctx.cell_at_mut(point) {
+                        if cell.symbol.chars().all(|symbol| symbol.is_ascii_digit()) {
+                            cell.set_bg(resource.get_color());
+                        } else {
+                            cell.set_fg(resource.get_color());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_victory_points(&self, ctx: &mut DrawContext, space: AbsoluteSpace) {
+        let samples = self.stats.get_victory_points();
+        let lines: Vec<Vec<(f64, f64)>> = (0..self.players.len())
+            .map(|player| {
+                samples
+                    .iter()
+                    .enumerate()
+                    .map(|(turn, points)| (turn as f64, points.get(player).copied().unwrap_or(0) as f64))
+                    .collect()
+            })
+            .collect();
+        let most = samples.iter().flatten().copied().max().unwrap_or(0).max(1);
+        let turns = samples.len().saturating_sub(1).max(1);
+
+        let datasets = self.players
+            .iter()
+            .zip(lines.iter())
+            .map(|((name, color), line)| {
+                Dataset::default()
+                    .name(name.as_str())
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(*color))
+                    .data(line)
+            })
+            .collect();
+        let label = |text: String| Span::styled(text, *NOTE_STYLE);
+        ctx.draw_widget(
+            Chart::new(datasets)
+                .block(Self::get_section_block(" Victory points "))
+                .x_axis(
+                    Axis::default()
+                        .title(label(String::from("turn")))
+                        .style(*NOTE_STYLE)
+                        .bounds([0.0, turns as f64])
+                        .labels(vec![label(String::from("0")), label(turns.to_string())])
+                )
+                .y_axis(
+                    Axis::default()
+                        .style(*NOTE_STYLE)
+                        .bounds([0.0, most as f64])
+                        .labels(vec![label(String::from("0")), label(most.to_string())])
+                )
+                .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2))),
+            space
+        );
+    }
+
+    fn draw_resources(&self, ctx: &mut DrawContext, space: AbsoluteSpace) {
+        let header = Row::new(vec!["", "gained", "robbed", "gave", "got", "spent"]).style(*HEADER_STYLE);
+        let rows: Vec<Row> = self.players
+            .iter()
+            .enumerate()
+            .map(|(player, (name, color))| {
+                let flow = self.stats.get_flow(player);
+                Row::new(vec![
+                    Span::styled(name.clone(), Style::default().fg(*color).add_modifier(Modifier::BOLD)),
+                    Span::raw(flow.gained.to_string()),
+                    Span::raw(flow.lost_to_robber.to_string()),
+                    Span::raw(flow.traded_away.to_string()),
+                    Span::raw(flow.traded_for.to_string()),
+                    Span::raw(flow.spent.to_string())
+                ])
+            })
+            .collect();
+
+        let inner = Self::draw_section(ctx, " Resources ", space);
+        let table_height = (rows.len() as u16 + 1).min(inner.size.y);
+        ctx.draw_widget(
+            Table::new(rows)
+                .header(header)
+                .style(Style::default().fg(Color::White))
+                .column_spacing(1)
+                .widths(&[Constraint::Min(8), Constraint::Length(6), Constraint::Length(6), Constraint::Length(6), Constraint::Length(6), Constraint::Length(6)]),
+            AbsoluteSpace::new(inner.position.x, inner.position.y, inner.size.x, table_height)
+        );
+
+        let notes = [
+            "gained from rolls, the bank, thefts, monopolies",
+            "robbed by discards on a 7 and thefts",
+            "gave and got in trades with anyone",
+            "spent on pieces and development cards"
+        ];
+        for (i, note) in notes.iter().enumerate() {
+            let y = table_height + 1 + i as u16;
+            if y >= inner.size.y {
+                break
+            }
+            ctx.draw_widget(Paragraph::new(Span::styled(*note, *NOTE_STYLE)), AbsoluteSpace::new(inner.position.x, inner.position.y + y as i16, inner.size.x, 1));
+        }
+    }
+
+    fn get_section_block(title: &str) -> Block<'_> {
+        Block::default()
+            .title(Span::styled(title, *HEADER_STYLE))
+            .borders(Borders::ALL)
+            .border_style(*BORDER_STYLE)
+    }
+
+    /* a bordered box with a title, returns the space inside it */
+    fn draw_section(ctx: &mut DrawContext, title: &str, space: AbsoluteSpace) -> AbsoluteSpace {
+        ctx.draw_widget(Self::get_section_block(title), space);
+        AbsoluteSpace::from_rect(Block::default().borders(Borders::ALL).inner(space.to_rect()))
+    }
+}
+
+impl Layoutable for StatsView {
+    fn layout_ref(&self) -> &DrawLayout { &self.layout }
+    fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+}
+
+impl StatefulDrawable for StatsView {
+    type State = ();
+
+    fn stateful_draw(&self, ctx: &mut DrawContext, _: &Self::State) {
+        let size = ctx.absolute_layout_space.size;
+        let space = AbsoluteSpace::new(0, 0, size.x, size.y);
+        ctx.draw_widget(Clear, space);
+        ctx.draw_widget(
+            Block::default()
+                .title(" Statistics ")
+                .borders(Borders::ALL)
+                .border_style(*BORDER_STYLE)
+                .style(Style::default().bg(Color::Black)),
+            space
+        );
+        if !self.hint.is_empty() && size.y > 0 {
+            let hint: String = self.hint.chars().take(size.x.saturating_sub(4) as usize).collect();
+            ctx.draw_unicode_line(&hint, Point2D::new(2, size.y as i16 - 1), *BORDER_STYLE);
+        }
+
+        let inner = Block::default().borders(Borders::ALL).inner(space.to_rect());
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(inner);
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(rows[0]);
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(rows[1]);
+
+        self.draw_dice(ctx, AbsoluteSpace::from_rect(top[0]));
+        self.draw_production(ctx, AbsoluteSpace::from_rect(top[1]));
+        self.draw_victory_points(ctx, AbsoluteSpace::from_rect(bottom[0]));
+        self.draw_resources(ctx, AbsoluteSpace::from_rect(bottom[1]));
+    }
+}
+
+impl MountableLayout for StatsView {
+    fn mount_ref(&self) -> &Mount { &self.mount }
+    fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+    fn child_ref(&self, _: usize) -> Option<&dyn MountableLayout> { None }
+    fn child_mut(&mut self, _: usize) -> Option<&mut dyn MountableLayout> { None }
+
+    fn relayout(&mut self, ctx: &mut LayoutContext) {
+        if std::mem::take(&mut self.is_damaged) {
+            ctx.invalidate(self);
+        }
+        ctx.relayout_input_space_of(self, Space::FULL);
+    }
+
+    /* nothing under the dashboard gets the mouse while it is open */
+    fn on_mouse_input(&mut self, _: InputEvent) -> bool {
+        true
+    }
+}
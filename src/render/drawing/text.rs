@@ -0,0 +1,50 @@
+/*
+ * text.rs
+ * helpers for panes that show wrapped lines of styled text
+ */
+
+use crate::render::prelude::*;
+
+use tui::style::Style;
+use unicode_width::UnicodeWidthChar;
+
+/* one row of text made of differently styled pieces */
+pub type StyledRow = Vec<(String, Style)>;
+
+/* break styled pieces into rows no wider than the given width */
+pub fn wrap(spans: Vec<(String, Style)>, width: u16) -> Vec<StyledRow> {
+    let width = width.max(1) as usize;
+    let mut rows = Vec::new();
+    let mut row: StyledRow = Vec::new();
+    let mut row_width = 0;
+    for (text, style) in spans {
+        let mut piece = String::new();
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if row_width + char_width > width {
+                if !piece.is_empty() {
+                    row.push((std::mem::take(&mut piece), style));
+                }
+                rows.push(std::mem::take(&mut row));
+                row_width = 0;
+            }
+            piece.push(c);
+            row_width += char_width;
+        }
+        if !piece.is_empty() {
+            row.push((piece, style));
+        }
+    }
+    if !row.is_empty() || rows.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+pub fn draw_row(ctx: &mut DrawContext, row: &StyledRow, position: Point2D) {
+    let mut x = position.x;
+    for (text, style) in row {
+        ctx.draw_unicode_line(text, Point2D::new(x, position.y), *style);
+        x += text.chars().map(|c| c.width().unwrap_or(0) as i16).sum::<i16>();
+    }
+}
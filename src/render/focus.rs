@@ -0,0 +1,324 @@
+/*
+ * focus.rs
+ * module of constructs used in routing the keyboard
+ *
+ * mounts that want the keyboard ask to be focusable during relayout the same way they ask to capture input
+ * key presses go to the focused mount first and then up through its ancestors until one of them handles it
+ * tab walks focusables in the order they were laid out unless the focused mount wants it and arrows nobody handled move focus to the nearest focusable that way
+ * escape nobody handled drops focus, the screen runner quits on escape when nothing is focused
+ */
+
+use super::{space::*, mount::*};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/* struct that stores the id of a mount that can take focus and the space it is drawn in */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Focusable {
+    id: MountId,
+    space: AbsoluteSpace
+}
+
+#[derive(Debug, Default)]
+pub struct FocusService {
+    focusables: Vec<Focusable>,
+    focused: Option<MountId>
+}
+
+impl FocusService {
+    /* focusables are redefined every relayout for the same reason inputs are */
+    pub fn invalidate_all_focusables(&mut self) {
+        self.focusables.clear();
+    }
+
+    pub fn register(&mut self, id: MountId, space: AbsoluteSpace) {
+        self.focusables.push(Focusable { id, space });
+    }
+
+    pub fn focus(&mut self, id: MountId) {
+        self.focused = Some(id);
+    }
+
+    pub fn blur(&mut self) {
+        self.focused = None;
+    }
+
+    pub fn get_focused(&self) -> Option<MountId> {
+        self.focused
+    }
+
+    pub fn is_focused(&self, id: MountId) -> bool {
+        self.focused == Some(id)
+    }
+
+    /* focus the top-most focusable at the point, clicking anything else takes focus away */
+    pub fn focus_at(&mut self, point: Point2D) -> bool {
+        let focused = self.focusables
+            .iter()
+            .rev()
+            .find(|focusable| focusable.space.is_interior_point(point))
+            .map(|focusable| focusable.id);
+        let did_change = focused != self.focused;
+        self.focused = focused;
+        did_change
+    }
+
+    /* move focus forward or backward through the focusables in layout order, wrapping around */
+    fn cycle(&mut self, forward: bool) -> bool {
+        let count = self.focusables.len();
+        if count == 0 {
+            return false
+        }
+
+        let next = match self.focusables.iter().position(|focusable| Some(focusable.id) == self.focused) {
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+            None if forward => 0,
+            None => count - 1
+        };
+        self.focused = Some(self.focusables[next].id);
+        true
+    }
+
+    /* move focus to the closest focusable whose middle is in the given direction of the focused one */
+    fn move_toward(&mut self, direction: Point2D) -> bool {
+        let from = match self.focusables.iter().find(|focusable| Some(focusable.id) == self.focused) {
+            Some(focusable) => get_middle(focusable.space),
+            None => return self.cycle(true)
+        };
+
+        let next = self.focusables
+            .iter()
+            .filter(|focusable| Some(focusable.id) != self.focused)
+            .filter_map(|focusable| get_walk_score(from, get_middle(focusable.space), direction).map(|score| (score, focusable.id)))
+            .min_by_key(|&(score, _)| score)
+            .map(|(_, id)| id);
+
+        if next.is_some() {
+            self.focused = next;
+        }
+        next.is_some()
+    }
+
+    /*
+     * handles a crossterm key event, returns a boolean indicating if anything handled it
+     * keys go to the focused mount and bubble up to its ancestors, the root gets whatever nobody focused handled
+     */
+    pub fn handle_key_input(&mut self, key: KeyEvent, root: &mut dyn MountableLayout) -> bool {
+        /* the focused mount gets first pick of tab (like for completing what is typed) before it moves focus */
+        if let KeyCode::Tab | KeyCode::BackTab = key.code {
+            let is_handled = self.focused
+                .and_then(|id| root.find_descendant_mut(MountFinder::new(id)))
+                .is_some_and(|mountable| mountable.on_key_input(key));
+            return is_handled || self.cycle(key.code == KeyCode::Tab)
+        }
+
+        let mut finder = MountFinder::new(self.focused.unwrap_or(0));
+        loop {
+            if let Some(mountable) = root.find_descendant_mut(finder) {
+                if mountable.on_key_input(key) {
+                    return true
+                }
+            }
+
+            if finder.depth() == 0 {
+                break
+            }
+            finder = finder.parent();
+        }
+
+        /* escape nobody wanted lets go of focus so the next one can quit */
+        if key.code == KeyCode::Esc && self.focused.is_some() {
+            self.focused = None;
+            return true
+        }
+
+        if key.modifiers.contains(KeyModifiers::SHIFT) {
+            return false
+        }
+
+        match key.code {
+            KeyCode::Up => self.move_toward(Point2D::new(0, -1)),
+            KeyCode::Down => self.move_toward(Point2D::new(0, 1)),
+            KeyCode::Left => self.move_toward(Point2D::new(-1, 0)),
+            KeyCode::Right => self.move_toward(Point2D::new(1, 0)),
+            _ => false
+        }
+    }
+}
+
+fn get_middle(space: AbsoluteSpace) -> Point2D {
+    space.position + Point2D::new(space.size.x as i16/2, space.size.y as i16/2)
+}
+
+/*
+ * how far away a point is when walking in a direction, None if it isn't that way at all
+ * straying from the direction counts double and rows count double since cells are about twice as tall as they are wide
+ */
+pub fn get_walk_score(from: Point2D, to: Point2D, direction: Point2D) -> Option<i32> {
+    let dx = (to.x - from.x) as i32;
+    let dy = 2*(to.y - from.y) as i32;
+    let along = dx*direction.x as i32 + dy*direction.y as i32;
+    let across = (dx*direction.y as i32 - dy*direction.x as i32).abs();
+    if along <= 0 {
+        return None
+    }
+    Some(along + 2*across)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::prelude::*;
+
+    /* a mountable that handles some keys and remembers hearing them */
+    #[derive(Debug, Default)]
+    struct Pane {
+        keys: Vec<KeyCode>,
+        heard: Vec<KeyCode>,
+        children: Vec<Pane>,
+        layout: DrawLayout,
+        mount: Mount
+    }
+
+    impl Layoutable for Pane {
+        fn layout_ref(&self) -> &DrawLayout { &self.layout }
+        fn layout_mut(&mut self) -> &mut DrawLayout { &mut self.layout }
+    }
+
+    impl MountableLayout for Pane {
+        fn mount_ref(&self) -> &Mount { &self.mount }
+        fn mount_mut(&mut self) -> &mut Mount { &mut self.mount }
+        fn child_ref(&self, i: usize) -> Option<&dyn MountableLayout> { self.children.get(i).map(|child| child as &dyn MountableLayout) }
+        fn child_mut(&mut self, i: usize) -> Option<&mut dyn MountableLayout> { self.children.get_mut(i).map(|child| child as &mut dyn MountableLayout) }
+
+        fn on_key_input(&mut self, key: KeyEvent) -> bool {
+            let is_handled = self.keys.contains(&key.code);
+            if is_handled {
+                self.heard.push(key.code);
+            }
+            is_handled
+        }
+    }
+
+    fn pane(keys: &[KeyCode], children: Vec<Pane>) -> Pane {
+        Pane { keys: keys.to_vec(), children, ..Pane::default() }
+    }
+
+    /* 
+     * three panes in an L laid out in the order they were registered, ids 1, 2 and 3
+     *   1 2
+     *   3
+     */
+    fn get_panes(root: Pane) -> (Pane, FocusService) {
+        let mut root = root;
+        root.mount(Mount::default());
+        let mut focus = FocusService::default();
+        focus.register(1, AbsoluteSpace::new(0, 0, 10, 4));
+        focus.register(2, AbsoluteSpace::new(20, 0, 10, 4));
+        focus.register(3, AbsoluteSpace::new(0, 10, 10, 4));
+        (root, focus)
+    }
+
+    fn press(focus: &mut FocusService, root: &mut Pane, code: KeyCode) -> bool {
+        focus.handle_key_input(KeyEvent::new(code, KeyModifiers::NONE), root)
+    }
+
+    #[test]
+    fn tab_cycles_in_layout_order() {
+        let (mut root, mut focus) = get_panes(pane(&[], vec![pane(&[], vec![]), pane(&[], vec![]), pane(&[], vec![])]));
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            assert!(press(&mut focus, &mut root, KeyCode::Tab));
+            order.push(focus.get_focused().unwrap());
+        }
+        assert_eq!(order, vec![1, 2, 3, 1]);
+        assert!(press(&mut focus, &mut root, KeyCode::BackTab));
+        assert_eq!(focus.get_focused(), Some(3));
+
+        focus.blur();
+        assert!(press(&mut focus, &mut root, KeyCode::BackTab));
+        assert_eq!(focus.get_focused(), Some(3));
+    }
+
+    /* the focused mount can keep tab for itself */
+    #[test]
+    fn focused_mounts_get_tab_first() {
+        let (mut root, mut focus) = get_panes(pane(&[], vec![pane(&[KeyCode::Tab], vec![]), pane(&[], vec![])]));
+        focus.focus(1);
+        assert!(press(&mut focus, &mut root, KeyCode::Tab));
+        assert_eq!(focus.get_focused(), Some(1));
+        assert_eq!(root.children[0].heard, vec![KeyCode::Tab]);
+    }
+
+    #[test]
+    fn arrows_move_to_the_nearest_focusable_that_way() {
+        let (mut root, mut focus) = get_panes(pane(&[], vec![pane(&[], vec![]), pane(&[], vec![]), pane(&[], vec![])]));
+
+        /* nothing focused picks the first one */
+        assert!(press(&mut focus, &mut root, KeyCode::Right));
+        assert_eq!(focus.get_focused(), Some(1));
+
+        for (code, to) in [(KeyCode::Right, 2), (KeyCode::Down, 3), (KeyCode::Up, 1), (KeyCode::Down, 3)] {
+            assert!(press(&mut focus, &mut root, code));
+            assert_eq!(focus.get_focused(), Some(to));
+        }
+        assert!(!press(&mut focus, &mut root, KeyCode::Left));
+        assert!(!press(&mut focus, &mut root, KeyCode::Down));
+        assert_eq!(focus.get_focused(), Some(3));
+
+        /* shifted arrows are left for whoever wants them */
+        assert!(!focus.handle_key_input(KeyEvent::new(KeyCode::Up, KeyModifiers::SHIFT), &mut root));
+        assert_eq!(focus.get_focused(), Some(3));
+    }
+
+    /* keys go to the focused mount then its ancestors, escape nobody handled lets go of focus */
+    #[test]
+    fn keys_bubble_up() {
+        let child = pane(&[KeyCode::Char('a')], vec![pane(&[KeyCode::Char('b')], vec![])]);
+        let (mut root, mut focus) = get_panes(pane(&[KeyCode::Char('r')], vec![child]));
+        let grandchild = root.children[0].children[0].mount.id;
+        focus.focus(grandchild);
+
+        for code in [KeyCode::Char('b'), KeyCode::Char('a'), KeyCode::Char('r')] {
+            assert!(press(&mut focus, &mut root, code));
+        }
+        assert!(!press(&mut focus, &mut root, KeyCode::Char('x')));
+        assert_eq!(root.children[0].children[0].heard, vec![KeyCode::Char('b')]);
+        assert_eq!(root.children[0].heard, vec![KeyCode::Char('a')]);
+        assert_eq!(root.heard, vec![KeyCode::Char('r')]);
+
+        assert!(press(&mut focus, &mut root, KeyCode::Esc));
+        assert_eq!(focus.get_focused(), None);
+        assert!(!press(&mut focus, &mut root, KeyCode::Esc));
+
+        /* the root still hears keys with nothing focused */
+        assert!(press(&mut focus, &mut root, KeyCode::Char('r')));
+        assert!(!press(&mut focus, &mut root, KeyCode::Char('a')));
+    }
+
+    #[test]
+    fn clicking_focuses_the_top_most() {
+        let (_, mut focus) = get_panes(pane(&[], vec![]));
+        focus.register(4, AbsoluteSpace::new(5, 2, 10, 4));
+        assert!(focus.focus_at(Point2D::new(6, 3)));
+        assert_eq!(focus.get_focused(), Some(4));
+        assert!(!focus.focus_at(Point2D::new(6, 3)));
+        assert!(focus.focus_at(Point2D::new(1, 1)));
+        assert_eq!(focus.get_focused(), Some(1));
+        assert!(focus.focus_at(Point2D::new(15, 8)));
+        assert_eq!(focus.get_focused(), None);
+    }
+
+    /* straight ahead beats the same distance off to the side and rows count double */
+    #[test]
+    fn walk_scores() {
+        let from = Point2D::new(0, 0);
+        let right = Point2D::new(1, 0);
+        assert_eq!(get_walk_score(from, Point2D::new(4, 0), right), Some(4));
+        assert_eq!(get_walk_score(from, Point2D::new(4, 1), right), Some(8));
+        assert_eq!(get_walk_score(from, Point2D::new(-4, 0), right), None);
+        assert_eq!(get_walk_score(from, Point2D::new(0, 4), right), None);
+        assert_eq!(get_walk_score(from, Point2D::new(0, 2), Point2D::new(0, 1)), Some(4));
+    }
+}
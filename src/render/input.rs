@@ -38,7 +38,9 @@ pub enum InputEventKind {
     Drag(Point2D),
     Move(Point2D),
     Down(Point2D),
-    Up(Point2D)
+    Up(Point2D),
+    /* mouse wheel, negative rows scroll up */
+    Scroll(Point2D, i16)
 }
 
 impl InputService {
@@ -162,7 +164,15 @@ impl InputService {
                 /* up means we are no longer the capturing mount */
                 self.capturing_mount_id = None;
             }
-            _ => () // otherwise do nothing
+            /* the wheel goes to whatever is under the mouse */
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                if let Some(id) = maybe_id {
+                    self.input_event_queue.push(InputEvent {
+                        mount_id: id,
+                        kind: InputEventKind::Scroll(point, if event.kind == MouseEventKind::ScrollUp { -1 } else { 1 })
+                    });
+                }
+            }
         }
 
         let mut should_rerender = false;
@@ -3,14 +3,17 @@ pub mod draw;
 pub mod drawing;
 pub mod screen;
 pub mod shape;
+pub mod snapshot;
 
 mod run;
-pub use run::run;
+pub use run::{run_demo, run_screen, setup_terminal, restore_terminal, AppTerminal, RunStep, RunExit, RunEvent, EventLoop, EventSender, DEFAULT_FPS};
 
 mod mount;
 mod iter;
 mod anim;
 mod input;
+mod focus;
+mod damage;
 
 pub mod prelude {
     pub use super::draw::*;
@@ -20,4 +23,6 @@ pub mod prelude {
     pub use super::anim::*;
     pub use super::shape::*;
     pub use super::input::*;
+    pub use super::focus::*;
+    pub use super::damage::*;
 }
\ No newline at end of file
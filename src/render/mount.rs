@@ -8,6 +8,8 @@
 use super::prelude::*;
 use super::iter::*;
 
+use crossterm::event::KeyEvent;
+
 /* mount identifier */
 pub type MountId = u64;
 
@@ -58,6 +60,13 @@ impl MountFinder {
     pub fn push(self, index: u8) -> MountFinder {
         MountFinder { id: self.id | (index as MountId) << u8::BITS*self.depth() }
     }
+    /* the finder of the mount this one was forked from, the root is its own parent */
+    pub fn parent(self) -> MountFinder {
+        match self.depth() {
+            0 => self,
+            depth => MountFinder { id: self.id & !((u8::MAX as MountId) << (u8::BITS*(depth - 1))) }
+        }
+    }
     pub fn depth(&self) -> u32 {
         MountFinder::MAX_DEPTH - self.id.leading_zeros() / u8::BITS
     }
@@ -77,6 +86,10 @@ pub trait MountableLayout: Layoutable + std::fmt::Debug + AsMountableLayout + 's
 
     #[allow(unused_variables)]
     fn on_mouse_input(&mut self, event: InputEvent) -> bool { false }
+
+    /* key presses while this or a descendant is focused, returning false passes it on to the parent */
+    #[allow(unused_variables)]
+    fn on_key_input(&mut self, key: KeyEvent) -> bool { false }
     
     // required / utility
 
@@ -1,25 +1,31 @@
 /*
  * run.rs
- * render loop runner + test code hodgepodge :)
+ * the render loop runner, and the demo that shows it off
+ *
+ * the loop sleeps on a channel of events until something happens: terminal input, a timer going off or a message sent in by the app
+ * (network messages, game actions...), nothing wakes it up while idle and while drawings animate it draws frames at a steady fps
+ * the screen belongs to the loop, other threads only ever talk to it through an EventSender
  */
 
 use super::{
     screen::Screen,
-    drawing::{
-        map::{self, Map, Tile, Port},
-        game::Game
-    }
+    draw::StatefulDrawable,
+    mount::MountableLayout,
+    space::Point2D,
+    drawing::{map, game::Game}
 };
 
 use crate::enums;
+use crate::logic::{board::Board, game::Piece, model::{GameModel, PlayerModel}};
+use crate::keymap::{Command, Keymap};
 
 use crossterm::{
-    event::{poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseEvent},
+    event::{read, DisableMouseCapture, EnableMouseCapture, Event, KeyEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use tui_logger::{TuiLoggerWidget, TuiLoggerLevelOutput};
-use std::{io, time::{Duration, Instant}, sync::{Arc, Mutex}, thread, ops::DerefMut, fmt::Write};
+use std::{io::{self, Stdout}, time::{Duration, Instant}, sync::mpsc::{self, Receiver, RecvTimeoutError, Sender}, thread, fmt::Write};
 use tui::{
     backend::{CrosstermBackend},
     Terminal, widgets::*, 
@@ -27,206 +33,325 @@ use tui::{
     style::{Style, Color},
 };
 
-use rand::{prelude::Distribution, distributions::Uniform, Rng};
+use rand::{prelude::Distribution, Rng};
 
-const DEFAULT_REDRAW_DELAY_MS: u64 = 4;
+pub const DEFAULT_FPS: u32 = 60;
 
-/* this the render loop with some test code for now */
-pub fn run(enable_logger: bool, mut map: Option<Map>) -> Result<(), io::Error> {
-    let mut rng = rand::thread_rng();
+const DEMO_PLAYERS: [(&str, Color); 3] = [("Andre", Color::Red), ("Unbreakable Patches", Color::Green), ("Seigward", Color::Blue)];
 
-    let map = map.unwrap_or_else(|| {
-        let mut tiles: Vec<Tile> = Uniform::from(2..12) // from a uniform distribution from [2, 12)
-            .sample_iter(&mut rng) // create an iterator that samples from it
-            .take(*map::MAP_TILE_CAPACITY - 1) // sample as many times equal to the map capacity for tiles (minus 1 for desert tile)
-            .map(|roll| Tile::new(
-                if roll == 7 { 12 } else { roll }, // 7 becomes 12 because 7 is not on a tile its a robber round
-                enums::TileResource::Of(rand::random::<enums::Resource>())
-            )) 
-            .collect();
+pub type AppTerminal = Terminal<CrosstermBackend<Stdout>>;
 
-        // insert desert tile at a random location in the tiles vector
-        let desert_tile_index = rng.gen_range(0..=tiles.len());
-        tiles.insert(desert_tile_index, Tile::new(7, enums::TileResource::OfDesert));
+/* what the update hook passed to run_screen wants the loop to do next */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunStep {
+    Continue,
+    Rerender,
+    Finish,
+    Quit
+}
 
-        let ports: Vec<Port> = enums::PortResource::OfAnyKind // sample_iter takes a self type so I need to do PortResource::OfAnyKind instead of just PortResource
-            .sample_iter(&mut rng) // create an iterator that takes samples of PortResource
-            .take(*map::MAP_PORT_CAPACITY) // sample as many times equal to the map capacity for ports
-            .enumerate()
-            .map(|(i, resource)| Port::new(i, resource)) // we want ports containing these resources
-            .collect();
-        
-        Map::new(tiles, ports)
-    });
+/* why run_screen returned */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunExit {
+    Quit,
+    Finished
+}
 
-    
-    let game_screen_resource = Arc::new(Mutex::new(Screen::new(Game::new(map))));
-
-    // { // test robber animation
-    //     let game_screen_mutex = Arc::clone(&game_screen_resource);
-    //     thread::spawn(move || {
-    //         let mut rng = rand::thread_rng();
-    //         let mut last_num = desert_tile_index;
-    //         loop {
-    //             thread::sleep(Duration::from_secs(1));
-    //             let mut guard = game_screen_mutex.lock().unwrap();
-    //             let game_screen = guard.deref_mut();
-                
-    //             let mut num = rng.gen_range(0..*map::MAP_TILE_CAPACITY - 1);
-    //             if num == last_num {
-    //                 num = *map::MAP_TILE_CAPACITY - 1;
-    //             }
-
-    //             game_screen.root.map_dragger.drawing.move_robber(num, &mut game_screen.service.animation);
-    //             last_num = num;
-    //         }
-    //     });
-    // }
-
-    { // test road + building placement animations
-        let game_screen_mutex = Arc::clone(&game_screen_resource);
-        thread::spawn(move || {
-            let mut rng = rand::thread_rng();
-            let road_plots = map::MAP_GRAPH.plot_edges
-                .iter()
-                .enumerate()
-                .map(|(from_plot, edges)| {
-                    edges
-                        .iter()
-                        .filter_map(move |&to_plot| if from_plot < to_plot { Some((from_plot, to_plot)) } else { None })
-                })
-                .flatten();
-            
-            for tile in 0..map::MAP_GRAPH.tile_anchor_points.len() {
-                thread::sleep(Duration::from_millis(250));
-                let mut guard = game_screen_mutex.lock().unwrap();
-                let game_screen = guard.deref_mut();
-
-                game_screen.root.map_dragger.drawing.place_tile(
-                    tile,
-                    &mut game_screen.service.animation
-                );
-            }
+/* what the update hook passed to run_screen gets woken up with */
+#[derive(Debug)]
+pub enum RunEvent<M> {
+    /* a key press nothing on the screen handled */
+    Key(KeyEvent),
+    /* the screen handled input or drew a frame, drawings might have something for the app (like messages to send) */
+    Changed,
+    /* a timer set with EventSender::set_timer went off */
+    Tick,
+    /* something sent in with EventSender::send */
+    Message(M)
+}
 
-            {
-                let game_screen_mutex = Arc::clone(&game_screen_mutex);
-                thread::spawn(move || {
-                    for port in 0..map::MAP_GRAPH.port_points.len() {
-                        thread::sleep(Duration::from_millis(250));
-                        let mut guard = game_screen_mutex.lock().unwrap();
-                        let game_screen = guard.deref_mut();
-        
-                        game_screen.root.map_dragger.drawing.show_port(port, &mut game_screen.service.animation);
-                    }
-                });
-            }
+/* what goes through the channel of an event loop */
+#[derive(Debug)]
+enum LoopEvent<M> {
+    Input(Event),
+    Timer(Instant),
+    Message(M)
+}
 
-            for (plot_a, plot_b) in road_plots {
-                thread::sleep(Duration::from_millis(100));
-                let mut guard = game_screen_mutex.lock().unwrap();
-                let game_screen = guard.deref_mut();
-
-                game_screen.root.map_dragger.drawing.place_road(
-                    plot_a, plot_b, 
-                    Style::default().bg(Color::Rgb(
-                        rng.gen_range(0..=225), 
-                        rng.gen_range(0..=225), 
-                        rng.gen_range(0..=225)
-                    )), 
-                    &mut game_screen.service.animation
-                );
-            }
+/* sends events into an event loop from any thread, every thread that needs one gets its own clone */
+#[derive(Debug)]
+pub struct EventSender<M> {
+    sender: Sender<LoopEvent<M>>
+}
+
+impl<M> Clone for EventSender<M> {
+    fn clone(&self) -> Self {
+        EventSender { sender: self.sender.clone() }
+    }
+}
 
-            for plot in 0..map::MAP_GRAPH.plot_points.len() {
-                thread::sleep(Duration::from_millis(100));
-                let mut guard = game_screen_mutex.lock().unwrap();
-                let game_screen = guard.deref_mut();
-
-                game_screen.root.map_dragger.drawing.place_building(
-                    plot,
-                    enums::Building::Settlement.sample(&mut rng),
-                    Style::default().bg(Color::Rgb(
-                        rng.gen_range(0..=225), 
-                        rng.gen_range(0..=225), 
-                        rng.gen_range(0..=225)
-                    )), 
-                    &mut game_screen.service.animation
-                );
+impl<M> EventSender<M> {
+    /* send a message to the update hook, returns false once the loop is gone */
+    pub fn send(&self, message: M) -> bool {
+        self.sender.send(LoopEvent::Message(message)).is_ok()
+    }
+
+    /* wake the update hook up with a tick at the given time */
+    pub fn set_timer(&self, at: Instant) {
+        self.sender.send(LoopEvent::Timer(at)).ok();
+    }
+}
+
+/*
+ * the channel run_screen sleeps on, it outlives any one screen so the app can go from screen to screen (lobby to game)
+ * without losing anything sent in between, terminal input is read on its own thread from the first time a screen runs
+ */
+#[derive(Debug)]
+pub struct EventLoop<M> {
+    sender: EventSender<M>,
+    receiver: Receiver<LoopEvent<M>>,
+    timers: Vec<Instant>,
+    frame_interval: Duration,
+    is_reading_input: bool
+}
+
+impl<M: Send + 'static> EventLoop<M> {
+    pub fn new(fps: u32) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        EventLoop {
+            sender: EventSender { sender },
+            receiver,
+            timers: Vec::new(),
+            frame_interval: Duration::from_secs(1)/fps.max(1),
+            is_reading_input: false
+        }
+    }
+
+    pub fn get_sender(&self) -> EventSender<M> {
+        self.sender.clone()
+    }
+
+    /* the thread reading the terminal blocks until there is input so it never wakes anything up for nothing */
+    fn read_input(&mut self) {
+        if std::mem::replace(&mut self.is_reading_input, true) {
+            return
+        }
+
+        let sender = self.sender.sender.clone();
+        thread::spawn(move || {
+            while let Ok(event) = read() {
+                if sender.send(LoopEvent::Input(event)).is_err() {
+                    break
+                }
             }
         });
     }
 
-    let game_screen_mutex = Arc::clone(&game_screen_resource);
+    /* wait for the next event until the deadline, forever without one */
+    fn wait(&self, deadline: Option<Instant>) -> Option<LoopEvent<M>> {
+        match deadline {
+            Some(deadline) => match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None
+            },
+            /* the loop holds a sender itself so the channel can't disconnect */
+            None => self.receiver.recv().ok()
+        }
+    }
+
+    fn get_next_timer(&self) -> Option<Instant> {
+        self.timers.iter().min().copied()
+    }
+
+    /* take a timer that went off if there is one */
+    fn take_due_timer(&mut self) -> bool {
+        let now = Instant::now();
+        match self.timers.iter().position(|&at| at <= now) {
+            Some(i) => {
+                self.timers.swap_remove(i);
+                true
+            },
+            None => false
+        }
+    }
+}
 
-    // setup terminal
+
+pub fn setup_terminal() -> Result<AppTerminal, io::Error> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    //let mut frame_number: u128 = 0;
-    let mut delay_ms = 0;
+    Terminal::new(backend)
+}
+
+pub fn restore_terminal(terminal: &mut AppTerminal) -> Result<(), io::Error> {
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()
+}
+
+/* 
+ * the demo started with "catarminal demo": made up players watch every road and then a building on every plot
+ * get placed on a random board, for showing off the map without a game to play
+ */
+pub fn run_demo(enable_logger: bool, fps: u32, keymap: Keymap) -> Result<(), io::Error> {
+    let board = Board::generate(&mut rand::thread_rng());
+    let mut model = GameModel::new(board, DEMO_PLAYERS.len());
+    model.players = vec![PlayerModel::default(); DEMO_PLAYERS.len()];
+    let mut game = Game::new(model, DEMO_PLAYERS.iter().map(|&(name, color)| (String::from(name), color)).collect());
+    game.set_keymap(keymap.clone());
+
+    let mut game_screen = Screen::new(game);
+    game_screen.root.map_dragger.drawing.reveal(&mut game_screen.service.animation);
+    let tile_reveal_duration = Duration::from_millis(250*(*map::MAP_TILE_CAPACITY) as u64);
+    let mut events = EventLoop::new(fps);
+
+    let sender = events.get_sender();
+    thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        let roads = map::MAP_GRAPH.plot_edges
+            .iter()
+            .enumerate()
+            .flat_map(|(a, edges)| edges.iter().filter(move |&&b| a < b).map(move |&b| Piece::Road(a, b)));
+        let buildings: Vec<Piece> = (0..map::MAP_GRAPH.plot_points.len())
+            .map(|plot| match enums::Building::Settlement.sample(&mut rng) {
+                enums::Building::Settlement => Piece::Settlement(plot),
+                enums::Building::City => Piece::City(plot)
+            })
+            .collect();
+
+        /* nothing gets built until the tiles are down */
+        thread::sleep(tile_reveal_duration);
+        for piece in roads.chain(buildings) {
+            thread::sleep(Duration::from_millis(100));
+            if !sender.send((rng.gen_range(0..DEMO_PLAYERS.len()), piece)) {
+                return
+            }
+        }
+    });
+
+    let mut terminal = setup_terminal()?;
+    let result = run_screen(&mut terminal, enable_logger, &mut events, &mut game_screen, |screen, event| {
+        match event {
+            RunEvent::Key(key) if keymap.is_bound_to(key, Command::Quit) => return RunStep::Quit,
+            RunEvent::Message((player, piece)) => {
+                screen.root.model.pieces.place(player, piece);
+                screen.root.sync(&mut screen.service.animation);
+            },
+            _ => ()
+        }
+        RunStep::Continue
+    });
+    restore_terminal(&mut terminal)?;
+
+    result.map(|_| ())
+}
+
+/* 
+ * runs the loop for the given screen until the update hook asks to finish or quit
+ * the update hook is called with every event meant for the app (see RunEvent), everything that came in is handled before a frame is drawn
+ * and frames are drawn at most once every frame interval
+ */
+pub fn run_screen<T, M, F>(terminal: &mut AppTerminal, enable_logger: bool, events: &mut EventLoop<M>, screen: &mut Screen<T>, mut update: F) -> Result<RunExit, io::Error> 
+    where 
+        T: MountableLayout + StatefulDrawable<State = ()>,
+        M: Send + 'static,
+        F: FnMut(&mut Screen<T>, RunEvent<M>) -> RunStep
+{
+    events.read_input();
+
     let mut times_str = String::new();
     let mut flush_time = 0;
+    let mut last_frame_time: Option<Instant> = None;
+    /* set when something changed outside of a relayout, the next frame lays the screen out and redraws what changed in it (see damage.rs) */
+    let mut should_render = true;
 
     loop {
-        let mut run_step_start = Instant::now();
-        let mut should_render = false;
-        let mut maybe_mouse_event: Option<MouseEvent> = None;
-        let mut input_poll_time = 0;
-
-        if poll(Duration::from_millis(delay_ms))? {
-            input_poll_time = run_step_start.elapsed().as_millis();
-            run_step_start = Instant::now();
-            match read()? {
-                Event::Resize(_, _) => should_render = true,
-                Event::Key(key) => {
-                    match key.code {
-                        KeyCode::Esc => {
-                            // restore terminal
-                            disable_raw_mode()?;
-                            execute!(
-                                terminal.backend_mut(),
-                                LeaveAlternateScreen,
-                                DisableMouseCapture
-                            )?;
-                            terminal.show_cursor()?;
-
-                            break
-                        },
-                        _ => ()
+        /* a frame is due when something changed or drawings are animating, otherwise the loop sleeps until an event comes in */
+        let frame_at = if should_render || screen.service.animation.count() > 0 {
+            Some(last_frame_time.map_or_else(Instant::now, |time| time + events.frame_interval))
+        } else {
+            None
+        };
+        let deadline = [frame_at, events.get_next_timer()].into_iter().flatten().min();
+
+        let idle_start = Instant::now();
+        let mut maybe_event = events.wait(deadline);
+        let idle_time = idle_start.elapsed().as_millis();
+        let run_step_start = Instant::now();
+
+        /* handle everything that came in before drawing */
+        let mut did_change = false;
+        while let Some(event) = maybe_event {
+            let step = match event {
+                /* everything moves around on a resize so it is the one thing that redraws the whole screen */
+                LoopEvent::Input(Event::Resize(_, _)) => {
+                    screen.service.damage.invalidate_all();
+                    RunStep::Rerender
+                },
+                /* keys go through focus first and the update hook gets whatever nothing on the screen wanted */
+                LoopEvent::Input(Event::Key(key)) => if screen.service.focus.handle_key_input(key, &mut screen.root) {
+                    did_change = true;
+                    RunStep::Rerender
+                } else {
+                    update(screen, RunEvent::Key(key))
+                },
+                LoopEvent::Input(Event::Mouse(mouse_event)) => {
+                    log::info!("mouse event: {:?}", mouse_event);
+                    /* pressing the mouse moves focus to whatever is under it */
+                    let mut event_requires_rerender = false;
+                    if let MouseEventKind::Down(_) = mouse_event.kind {
+                        let point = Point2D::new(mouse_event.column as i16, mouse_event.row as i16);
+                        event_requires_rerender = screen.service.focus.focus_at(point);
+                    }
+                    // call on separate line because we dont want short-circuiting to prevent mouse input handler from running
+                    let input_requires_rerender = screen.service.input.handle_mouse_input(mouse_event, &mut screen.root);
+                    if event_requires_rerender || input_requires_rerender {
+                        did_change = true;
+                        RunStep::Rerender
+                    } else {
+                        RunStep::Continue
                     }
                 },
-                Event::Mouse(event) => maybe_mouse_event = Some(event)
+                LoopEvent::Timer(at) => {
+                    events.timers.push(at);
+                    RunStep::Continue
+                },
+                LoopEvent::Message(message) => update(screen, RunEvent::Message(message))
+            };
+
+            if let Some(exit) = take_step(step, &mut should_render) {
+                return Ok(exit)
             }
+            maybe_event = events.receiver.try_recv().ok();
         }
 
-        // game a lock of the game_screen here
-        //let mut game_screen = game_screen_mutex.lock().unwrap();
-        let mut guard = game_screen_mutex.lock().unwrap();
-        let game_screen = guard.deref_mut();
-
-        delay_ms = if game_screen.service.animation.count() > 0 { 0 } else { DEFAULT_REDRAW_DELAY_MS };
-        should_render = should_render || game_screen.service.animation.count() > 0;
+        while events.take_due_timer() {
+            if let Some(exit) = take_step(update(screen, RunEvent::Tick), &mut should_render) {
+                return Ok(exit)
+            }
+        }
 
-        if let Some(mouse_event) = maybe_mouse_event {
-            log::info!("mouse event: {:?}", mouse_event);
-            // call on separate line because we dont want short-circuiting to prevent mouse input handler from running
-            let event_requires_rerender = game_screen.service.input.handle_mouse_input(mouse_event, &mut game_screen.root); 
-            should_render = should_render || event_requires_rerender;
-        } 
+        if did_change {
+            if let Some(exit) = take_step(update(screen, RunEvent::Changed), &mut should_render) {
+                return Ok(exit)
+            }
+        }
 
-        if !should_render {
+        let is_frame_due = (should_render || screen.service.animation.count() > 0)
+            && last_frame_time.is_none_or(|time| time.elapsed() >= events.frame_interval);
+        if !is_frame_due {
             continue
         }
 
-        // log::info!("anims to render: {}", game_screen.service.animation.count());
-        // if game_screen.service.animation.count() == 1 {
-        //     log::info!("alpha: {:?}", game_screen.service.animation.animations.values().next().unwrap().duration)
-        // }
+        should_render = false;
+        last_frame_time = Some(Instant::now());
 
-        //log::info!("drawing frame #{:?}", frame_number);
-        //frame_number += 1;
         let mut frame_draw_time = 0;
         let total_start = Instant::now();
         let mut flush_start = Instant::now();
@@ -239,16 +364,18 @@ pub fn run(enable_logger: bool, mut map: Option<Map>) -> Result<(), io::Error> {
                 (areas[0], areas[1])
             };
             
+            let mut screen_area = area;
             if enable_logger {
                 /* divide screen for the logger and game */
                 let rects = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
                     .split(area);
+                screen_area = rects[0];
                 
                 /* draw the game screen */
                 let frame_start = Instant::now();
-                f.render_stateful_widget(game_screen.as_stateful_widget(), rects[0], &mut ());
+                f.render_stateful_widget(screen.as_stateful_widget(), rects[0], &mut ());
                 frame_draw_time = frame_start.elapsed().as_millis();
                 
                 let tui_w = TuiLoggerWidget::default()
@@ -275,25 +402,43 @@ pub fn run(enable_logger: bool, mut map: Option<Map>) -> Result<(), io::Error> {
             } else {
                 /* draw the game */
                 let frame_start = Instant::now();
-                f.render_stateful_widget(game_screen.as_stateful_widget(), area, &mut ());
+                f.render_stateful_widget(screen.as_stateful_widget(), area, &mut ());
                 frame_draw_time = frame_start.elapsed().as_millis();
             }
 
             let total_draw_time = total_start.elapsed().as_millis();
             let run_step_time = run_step_start.elapsed().as_millis();
+            let screen_area = (screen_area.width as u32*screen_area.height as u32).max(1);
+            let redrawn_percent = 100*screen.service.damage.get_last_redraw_area()/screen_area;
             times_str.clear();
             write!(times_str, 
-                "frame draw time: {:02} ms | total draw time: {:02} ms | run step time: {:02} ms | last flush time: {:02} ms | input poll time: {:02} ms", 
-                frame_draw_time, total_draw_time, run_step_time, flush_time, input_poll_time
+                "frame draw time: {:02} ms | redrawn: {:3}% | total draw time: {:02} ms | run step time: {:02} ms | last flush time: {:02} ms | idle time: {:02} ms", 
+                frame_draw_time, redrawn_percent, total_draw_time, run_step_time, flush_time, idle_time
             ).unwrap();
             /* draw the frame times */
             f.render_stateful_widget(StringLineWidget, times_area, &mut times_str);
             flush_start = Instant::now();
         })?;
         flush_time = flush_start.elapsed().as_millis();
+
+        /* a relayout can leave something for the app too (like the answer to a dialog) */
+        if let Some(exit) = take_step(update(screen, RunEvent::Changed), &mut should_render) {
+            return Ok(exit)
+        }
     }
+}
 
-    Ok(())
+/* fold a step the update hook took into whether to rerender, returns how the loop exits if it does */
+fn take_step(step: RunStep, should_render: &mut bool) -> Option<RunExit> {
+    match step {
+        RunStep::Continue => None,
+        RunStep::Rerender => {
+            *should_render = true;
+            None
+        },
+        RunStep::Finish => Some(RunExit::Finished),
+        RunStep::Quit => Some(RunExit::Quit)
+    }
 }
 
 struct StringLineWidget;
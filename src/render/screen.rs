@@ -8,7 +8,7 @@ use super::iter::CustomIterator;
 
 use tui::{
     layout::Rect,
-    buffer::Buffer,
+    buffer::{Buffer, Cell},
     widgets::StatefulWidget
 };
 
@@ -28,11 +28,15 @@ use tui::{
  * NOTE:
  * a behavior that optimizes some drawing is "clipping descendants" which means that child layouts cannot draw themselves 
  * or capture input in an area outside their parent area
+ * 
+ * the screen keeps what it drew last frame in a canvas and only redraws the regions that were damaged since (see damage.rs)
+ * the whole canvas is still copied out every frame because tui starts every frame from an empty buffer
  */
 #[derive(Debug)]
 pub struct Screen<T: MountableLayout + StatefulDrawable> {
     pub root: T,
-    pub service: ScreenService
+    pub service: ScreenService,
+    canvas: Buffer
 }
 
 /*
@@ -42,7 +46,9 @@ pub struct Screen<T: MountableLayout + StatefulDrawable> {
 #[derive(Debug, Default)]
 pub struct ScreenService {
     pub input: InputService,
-    pub animation: AnimationService
+    pub focus: FocusService,
+    pub animation: AnimationService,
+    pub damage: DamageService
 }
 
 impl<T: MountableLayout + StatefulDrawable> Screen<T> {
@@ -51,7 +57,8 @@ impl<T: MountableLayout + StatefulDrawable> Screen<T> {
 
         Screen {
             root,
-            service: ScreenService::default()
+            service: ScreenService::default(),
+            canvas: Buffer::default()
         }
     }
 
@@ -76,23 +83,74 @@ impl<T: MountableLayout + StatefulDrawable> Screen<T> {
         }
     }
 
+    /* relayout and draw the screen into a buffer of the given size instead of a terminal, see the snapshot module for reading it back */
+    pub fn render_to_buffer(&mut self, size: Size2D, state: &mut T::State) -> Buffer
+        where T::State: Sized
+    {
+        let mut buf = Buffer::empty(Rect::new(0, 0, size.x, size.y));
+        self.service.damage.invalidate_all();
+        self.as_stateful_widget().render(buf.area, &mut buf, state);
+        buf
+    }
+
     /* execute a relayout on the root struct */
     fn relayout_root(&mut self, absolute_screen_space: AbsoluteSpace) {
         LayoutContext::relayout(&mut self.root, absolute_screen_space, absolute_screen_space, &mut self.service);
+        self.service.damage.finish_relayout();
         self.service.animation.cleanup();
     }
 
-    /* draw the root struct with the given state inside the given buffer */
-    fn draw_root(&self, absolute_screen_space: AbsoluteSpace, buf: &mut Buffer, state: &<T as StatefulDrawable>::State) {
-        DrawContext::draw_stateful_child(
-            &mut DrawContext {
-                absolute_draw_space: absolute_screen_space,
-                absolute_layout_space: absolute_screen_space,
-                buf
-            }, 
-            &self.root, 
-            state
-        );
+    /* 
+     * redraw the damaged regions of the canvas with the given state and copy the canvas into the given buffer
+     * a region is redrawn by clearing it and drawing the root clipped to it, so drawings outside of it are skipped entirely
+     */
+    fn draw_root(&mut self, absolute_screen_space: AbsoluteSpace, buf: &mut Buffer, state: &<T as StatefulDrawable>::State) {
+        if self.canvas.area != absolute_screen_space.to_rect() {
+            self.canvas = Buffer::empty(absolute_screen_space.to_rect());
+            self.service.damage.invalidate_all();
+        }
+
+        for absolute_region_space in self.service.damage.take_regions(absolute_screen_space) {
+            for point in absolute_region_space {
+                self.canvas.get_mut(point.x as u16, point.y as u16).reset();
+            }
+
+            /* 
+             * a wide symbol on the right edge of a region needs the cell after it to be drawn at all
+             * so regions are drawn a column wider and that column is put back after since it wasn't damaged
+             */
+            let absolute_draw_space = AbsoluteSpace {
+                size: Size2D::new(absolute_region_space.size.x + 1, absolute_region_space.size.y),
+                ..absolute_region_space
+            }.intersection(absolute_screen_space);
+            let absolute_column_space = AbsoluteSpace::new(absolute_region_space.right(), absolute_region_space.top(), 1, absolute_region_space.size.y)
+                .try_intersection(absolute_screen_space);
+            let column: Vec<Cell> = absolute_column_space
+                .map(|space| space.into_iter().map(|point| self.canvas.get(point.x as u16, point.y as u16).clone()).collect())
+                .unwrap_or_default();
+
+            DrawContext::draw_stateful_child(
+                &mut DrawContext {
+                    absolute_draw_space,
+                    absolute_visible_space: absolute_screen_space,
+                    absolute_layout_space: absolute_screen_space,
+                    buf: &mut self.canvas
+                }, 
+                &self.root, 
+                state
+            );
+
+            if let Some(absolute_column_space) = absolute_column_space {
+                for (point, cell) in absolute_column_space.into_iter().zip(column) {
+                    *self.canvas.get_mut(point.x as u16, point.y as u16) = cell;
+                }
+            }
+        }
+
+        for point in absolute_screen_space {
+            let (x, y) = (point.x as u16, point.y as u16);
+            *buf.get_mut(x, y) = self.canvas.get(x, y).clone();
+        }
     }
 }
 
@@ -116,9 +174,10 @@ impl<'a, T: MountableLayout + StatefulDrawable> StatefulWidget for ScreenWidget<
          * drawings can update any relevant state before being drawn
          */
         self.screen.service.input.invalidate_all_inputs();
+        self.screen.service.focus.invalidate_all_focusables();
         self.screen.relayout_root(absolute_screen_space);
 
-        /* draw the screen */
+        /* redraw what was damaged and draw the screen */
         self.screen.draw_root(absolute_screen_space, buf, &state);
     }
 }
@@ -146,6 +205,7 @@ impl<'a> LayoutContext<'a> {
     ) {
         let id = mountable.mount_ref().id;
         let layout = mountable.layout_mut();
+        let is_animating = layout.anim.is_some();
         if let Some(anim) = layout.anim.as_deref_mut() {
             anim.update(&mut layout.space);
             if anim.state.playback != PlaybackState::Playing {
@@ -153,12 +213,18 @@ impl<'a> LayoutContext<'a> {
             }
         }
 
-        mountable.relayout(&mut LayoutContext {
+        let mut ctx = LayoutContext {
             id,
             parent_absolute_draw_space,
             parent_absolute_layout_space,
             service
-        });
+        };
+        mountable.relayout(&mut ctx);
+
+        /* tracked after the relayout since mounts can change their own layout in it */
+        let absolute_layout_space = ctx.get_absolute_layout_space_of(mountable);
+        let absolute_draw_space = ctx.get_visible_draw_space_of(mountable);
+        ctx.service.damage.track(id, absolute_layout_space, absolute_draw_space, is_animating);
     }
 
     /*
@@ -169,6 +235,27 @@ impl<'a> LayoutContext<'a> {
         self.parent_absolute_draw_space.try_intersection(subarea_absolute_layout_space)
     }
 
+    /* where the layoutable is drawn, if it is drawn at all */
+    fn get_visible_draw_space_of(&self, layoutable: &dyn Layoutable) -> Option<AbsoluteSpace> {
+        if layoutable.layout_ref().is_visible {
+            self.get_absolute_draw_space_of(self.get_absolute_layout_space_of(layoutable))
+        } else {
+            None
+        }
+    }
+
+    /* redraw wherever the mountable is drawn next frame, for mountables that change how they look without changing their layout */
+    pub fn invalidate(&mut self, mountable: &dyn MountableLayout) {
+        let absolute_draw_space = self.get_visible_draw_space_of(mountable);
+        self.service.damage.invalidate_drawn(absolute_draw_space);
+    }
+
+    /* redraw the part of the space inside where the mountable is drawn next frame, for things moving around inside of a mountable */
+    pub fn invalidate_space_in(&mut self, mountable: &dyn MountableLayout, absolute_space: AbsoluteSpace) {
+        let absolute_draw_space = self.get_visible_draw_space_of(mountable).and_then(|space| space.try_intersection(absolute_space));
+        self.service.damage.invalidate_drawn(absolute_draw_space);
+    }
+
     pub fn get_absolute_layout_space_of(&self, layoutable: &dyn Layoutable) -> AbsoluteSpace {
         layoutable.to_absolute_layout_space(self.parent_absolute_layout_space)
     }
@@ -209,6 +296,19 @@ impl<'a> LayoutContext<'a> {
         //}
     }
 
+    /* let the mountable take keyboard focus where it is drawn, returns whether it has focus right now */
+    pub fn relayout_focus_of(&mut self, mountable: &mut dyn MountableLayout) -> bool {
+        if !mountable.layout_ref().is_visible {
+            return false
+        }
+
+        let absolute_layout_space = mountable.to_absolute_layout_space(self.parent_absolute_layout_space);
+        if let Some(absolute_draw_space) = self.get_absolute_draw_space_of(absolute_layout_space) {
+            self.service.focus.register(self.id, absolute_draw_space);
+        }
+        self.service.focus.is_focused(self.id)
+    }
+
     /* reserve a subregion of the layout space for capturing input (reads as relayout.relayout_input_space_of(mountable)) */
     pub fn relayout_input_space_of(&mut self, mountable: &mut dyn MountableLayout, input_space: Space) {
         if !mountable.layout_ref().is_visible {
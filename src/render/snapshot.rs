@@ -0,0 +1,124 @@
+/*
+ * snapshot.rs
+ * reading a drawn buffer back out as text, plain for golden files and diffs or with ansi colors for looking at in a terminal
+ *
+ * a symbol wider than a cell (like the emoji on tiles and ports) covers the cells after it, those cells are skipped
+ * so every line comes out as wide as it looks on screen
+ */
+
+use tui::{buffer::{Buffer, Cell}, style::{Color, Modifier}};
+use unicode_width::UnicodeWidthStr;
+
+const RESET: &str = "\x1b[0m";
+
+const MODIFIER_CODES: [(Modifier, u8); 9] = [
+    (Modifier::BOLD, 1),
+    (Modifier::DIM, 2),
+    (Modifier::ITALIC, 3),
+    (Modifier::UNDERLINED, 4),
+    (Modifier::SLOW_BLINK, 5),
+    (Modifier::RAPID_BLINK, 6),
+    (Modifier::REVERSED, 7),
+    (Modifier::HIDDEN, 8),
+    (Modifier::CROSSED_OUT, 9)
+];
+
+/* the symbols of the buffer line by line, spaces at the end of a line are dropped */
+pub fn to_text(buf: &Buffer) -> String {
+    get_lines(buf)
+        .iter()
+        .map(|line| line.iter().map(|cell| cell.symbol.as_str()).collect::<String>().trim_end().to_owned() + "\n")
+        .collect()
+}
+
+/* the symbols of the buffer with escapes for their colors and modifiers, every line ends reset */
+pub fn to_ansi(buf: &Buffer) -> String {
+    let mut out = String::new();
+    for line in get_lines(buf) {
+        let mut last: Option<&Cell> = None;
+        for cell in line {
+            if last.is_none_or(|last| (last.fg, last.bg, last.modifier) != (cell.fg, cell.bg, cell.modifier)) {
+                out.push_str(&get_escape(cell));
+            }
+            out.push_str(&cell.symbol);
+            last = Some(cell);
+        }
+        out.push_str(RESET);
+        out.push('\n');
+    }
+    out
+}
+
+fn get_lines(buf: &Buffer) -> Vec<Vec<&Cell>> {
+    let width = buf.area.width.max(1) as usize;
+    buf.content
+        .chunks(width)
+        .map(|row| {
+            let mut covered = 0;
+            row.iter()
+                .filter(|cell| {
+                    if covered > 0 {
+                        covered -= 1;
+                        return false
+                    }
+                    covered = cell.symbol.width().saturating_sub(1);
+                    true
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/* every escape starts from a reset so it doesn't matter what came before it */
+fn get_escape(cell: &Cell) -> String {
+    let mut codes = vec![String::from("0")];
+    codes.extend(MODIFIER_CODES.iter().filter(|(modifier, _)| cell.modifier.contains(*modifier)).map(|(_, code)| code.to_string()));
+    codes.extend(get_color_code(cell.fg, 30));
+    codes.extend(get_color_code(cell.bg, 40));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/* base is 30 for the foreground and 40 for the background */
+fn get_color_code(color: Color, base: u8) -> Option<String> {
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(i) => return Some(format!("{};5;{}", base + 8, i))
+    };
+    Some(code.to_string())
+}
+
+/* 
+ * compare the buffer with the golden files named after it in the directory (relative to src), both as text and with colors
+ * running the tests with UPDATE_SNAPSHOTS set writes the files instead
+ */
+#[cfg(test)]
+pub fn assert_snapshot(dir: &str, name: &str, buf: &Buffer) {
+    for (extension, drawn) in [("txt", to_text(buf)), ("ansi", to_ansi(buf))] {
+        let path = format!("{}/src/{}/{}.{}", env!("CARGO_MANIFEST_DIR"), dir, name, extension);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &drawn).unwrap();
+            continue
+        }
+
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("no snapshot at {}, run with UPDATE_SNAPSHOTS=1 to write it", path));
+        assert!(expected == drawn, "{} doesn't match the drawing:\n{}", path, drawn);
+    }
+}
+
@@ -366,6 +366,20 @@ impl AbsoluteSpace {
             && self.bottom() > other.top()
     }
 
+    /* the smallest space containing both spaces */
+    pub fn union(self, other: AbsoluteSpace) -> AbsoluteSpace {
+        let x1 = min(self.left(), other.left());
+        let y1 = min(self.top(), other.top());
+        let x2 = max(self.right(), other.right());
+        let y2 = max(self.bottom(), other.bottom());
+
+        AbsoluteSpace::new(
+            x1, y1, 
+            u16::try_from(x2 as i32 - x1 as i32).unwrap(), 
+            u16::try_from(y2 as i32 - y1 as i32).unwrap()
+        )
+    }
+
     pub fn absolute_position_of(self, relative_position: Point2D) -> Point2D {
         Point2D {
             x: relative_position.x.checked_add(self.position.x).unwrap(),
@@ -0,0 +1,215 @@
+/*
+ * replay.rs
+ * watching a recorded game (see save.rs for how games get recorded)
+ *
+ * the replay is shown on the game screen the way a god view spectator sees the game, every state along the way is rebuilt up front
+ * going from one state to another syncs the map and player frames so pieces drop in like they did in the game and vanish going back
+ * a number typed before go-to-turn picks the turn, without one it goes back to the first turn
+ * stepping forward counts the stats like the game did, any other move counts them up again from the start of the recording
+ */
+
+use crate::render::{self, RunStep, RunEvent, EventLoop, screen::Screen, drawing::game::Game};
+use crate::net::save;
+use crate::logic::{lobby::Occupant, session::Presence, game::Viewer, history::History, model::GameModel, stats::GameStats};
+use crate::keymap::{Command, Keymap};
+use crate::app;
+
+use crossterm::event::KeyCode;
+
+use std::{io, path::Path, time::{Duration, Instant}};
+
+/* how long every action is shown for when playing at 1x */
+const STEP_INTERVAL: Duration = Duration::from_millis(1000);
+
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 16.0;
+
+#[derive(Debug)]
+struct Replay {
+    history: History,
+    /* how many records have been played */
+    position: usize,
+    speed: f32,
+    /* when the next record plays, None while paused */
+    next_step: Option<Instant>,
+    /* the turn typed in for go-to-turn */
+    count: Option<usize>
+}
+
+impl Replay {
+    /* show the game as it was at the position, stepping one forward plays out the record's events like the game did */
+    fn go_to(&mut self, position: usize, screen: &mut Screen<Game>) {
+        let position = position.min(self.history.get_record_count());
+        let from = std::mem::replace(&mut self.position, position);
+        let view = self.history.get_state(position).view_for(Viewer::God);
+
+        if position == from + 1 {
+            let events = &self.history.get_records(from, position)[0].events;
+            screen.root.model.apply_update(events, &view);
+            app::apply_events(screen, events);
+            for event in events.iter() {
+                screen.root.push_event(event);
+            }
+        } else {
+            screen.root.model.stats = self.get_stats(position);
+            screen.root.model.apply_view(&view);
+
+            /* the feed shows the turn so far */
+            screen.root.events.clear();
+            let turn_start = self.history.get_turn_start(self.history.get_turn_at(position));
+            for record in self.history.get_records(turn_start, position) {
+                for event in record.events.iter() {
+                    screen.root.push_event(event);
+                }
+            }
+        }
+
+        screen.root.sync(&mut screen.service.animation);
+        if self.position == self.history.get_record_count() {
+            self.next_step = None;
+        }
+    }
+
+    /* the stats as they stood at the position, counted up from the start of the recording */
+    fn get_stats(&self, position: usize) -> GameStats {
+        let start = self.history.get_state(0);
+        let mut model = GameModel::new(start.board.clone(), start.players.len());
+        model.apply_view(&start.view_for(Viewer::God));
+        for (i, record) in self.history.get_records(0, position).iter().enumerate() {
+            model.apply_update(&record.events, &self.history.get_state(i + 1).view_for(Viewer::God));
+        }
+        model.stats
+    }
+
+    /* returns false if the command isn't one for replays */
+    fn run_command(&mut self, command: Command, screen: &mut Screen<Game>) -> bool {
+        let turn = self.history.get_turn_at(self.position);
+        match command {
+            Command::StepForward => self.go_to(self.position + 1, screen),
+            Command::StepBack => self.go_to(self.position.saturating_sub(1), screen),
+            Command::NextTurn => self.go_to(self.history.get_turn_start(turn + 1), screen),
+            /* partway through a turn going back goes to its start first */
+            Command::PreviousTurn => {
+                let start = self.history.get_turn_start(turn);
+                self.go_to(if start < self.position { start } else { self.history.get_turn_start(turn.saturating_sub(1)) }, screen)
+            },
+            Command::GoToTurn => {
+                let turn = self.count.take().unwrap_or(1).max(1) - 1;
+                self.go_to(self.history.get_turn_start(turn), screen)
+            },
+            Command::TogglePlayback => {
+                self.next_step = match self.next_step {
+                    Some(_) => None,
+                    /* playing from the end starts over */
+                    None => {
+                        if self.position == self.history.get_record_count() {
+                            self.go_to(0, screen);
+                        }
+                        Some(Instant::now() + self.get_step_interval())
+                    }
+                };
+            },
+            Command::PlayFaster => self.speed = (self.speed*2.0).min(MAX_SPEED),
+            Command::PlaySlower => self.speed = (self.speed/2.0).max(MIN_SPEED),
+            _ => return false
+        }
+        true
+    }
+
+    fn get_step_interval(&self) -> Duration {
+        STEP_INTERVAL.div_f32(self.speed)
+    }
+
+    fn get_banner(&self, keymap: &Keymap) -> String {
+        let hint = |command: Command| keymap.get_chord(command).map_or_else(|| String::from("unbound"), |chord| chord.to_string());
+        let count = match self.count {
+            Some(count) => format!("go to turn {}..., ", count),
+            None => String::new()
+        };
+        format!(
+            "{}turn {}/{}, action {}/{}, {} at {}x | {} play, {} {} step, {} {} turn, N{} go to turn, {} {} speed",
+            count,
+            self.history.get_turn_at(self.position) + 1,
+            self.history.get_turn_count(),
+            self.position,
+            self.history.get_record_count(),
+            if self.next_step.is_some() { "playing" } else { "paused" },
+            self.speed,
+            hint(Command::TogglePlayback),
+            hint(Command::StepBack),
+            hint(Command::StepForward),
+            hint(Command::PreviousTurn),
+            hint(Command::NextTurn),
+            hint(Command::GoToTurn),
+            hint(Command::PlaySlower),
+            hint(Command::PlayFaster)
+        )
+    }
+}
+
+pub fn run(enable_logger: bool, fps: u32, keymap: &Keymap, path: &Path, speed: f32) -> Result<(), io::Error> {
+    let (start, records) = save::load_replay(path)?;
+    let setup = start.setup;
+    let history = History::new(start.game, records)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{} can't be replayed: {}", path.display(), err)))?;
+
+    let mut model = GameModel::new(history.get_state(0).board.clone(), setup.players.len());
+    for (player, seat) in setup.players.iter().enumerate() {
+        if seat.occupant == Occupant::Bot {
+            model.set_presence(player, Presence::Bot);
+        }
+    }
+
+    let mut screen = Screen::new(Game::new(
+        model,
+        setup.players
+            .iter()
+            .map(|seat| (seat.name.clone(), seat.color.get_color()))
+            .collect()
+    ));
+    screen.root.set_keymap(keymap.clone());
+
+    /* the recording can start partway through a game so the board shows up as it stands */
+    let mut replay = Replay { history, position: 0, speed: speed.clamp(MIN_SPEED, MAX_SPEED), next_step: None, count: None };
+    replay.go_to(0, &mut screen);
+    screen.root.map_dragger.drawing.settle(&mut screen.service.animation);
+    screen.root.banner = Some(replay.get_banner(keymap));
+
+    let mut events: EventLoop<()> = EventLoop::new(fps);
+    let sender = events.get_sender();
+    let mut terminal = render::setup_terminal()?;
+    let result = render::run_screen(&mut terminal, enable_logger, &mut events, &mut screen, |screen, event| {
+        match event {
+            RunEvent::Key(key) if keymap.is_bound_to(key, Command::Quit) => return RunStep::Quit,
+            RunEvent::Key(key) => match key.code {
+                KeyCode::Char(digit) if digit.is_ascii_digit() => {
+                    let digit = digit.to_digit(10).unwrap() as usize;
+                    replay.count = Some(replay.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                },
+                _ => {
+                    let commands: Vec<Command> = keymap.get_commands(key).collect();
+                    if !commands.into_iter().any(|command| replay.run_command(command, screen)) {
+                        return RunStep::Continue
+                    }
+                }
+            },
+            RunEvent::Tick => match replay.next_step {
+                Some(at) if Instant::now() >= at => {
+                    replay.go_to(replay.position + 1, screen);
+                    replay.next_step = replay.next_step.map(|_| Instant::now() + replay.get_step_interval());
+                },
+                _ => return RunStep::Continue
+            },
+            RunEvent::Changed | RunEvent::Message(()) => return RunStep::Continue
+        }
+
+        if let Some(at) = replay.next_step {
+            sender.set_timer(at);
+        }
+        screen.root.banner = Some(replay.get_banner(keymap));
+        RunStep::Rerender
+    });
+    render::restore_terminal(&mut terminal)?;
+
+    result.map(|_| ())
+}